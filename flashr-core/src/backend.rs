@@ -0,0 +1,585 @@
+//! The ways an image can be written to a device.
+//!
+//! `flash::flash_image_with_progress` checks the image and device, then hands
+//! the write to a `FlashBackend` and drives it through the same steps every
+//! time: `prepare`, `write`, `verify`, `finalize`. The phases, the sync, the
+//! verdict, and the post-flash hook stay with the flash, so a new backend (or
+//! a test fake) only implements the steps themselves.
+//!
+//! - `dd` (the default) - Runs `dd`, elevated through `pkexec` or `sudo` when
+//!   not running as root. A `dd` without `status=progress` (busybox, older
+//!   BSDs) is fed the image on its standard input instead (see `DdSupport`)
+//! - `native` - Copies the image in-process; needs flashr-tui itself to run
+//!   as root
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::device::format_lsblk_size;
+use crate::error::FlashError;
+use crate::flash::{
+    acquire_elevator, clear_device, describe_command, elevated_command, is_root, label_base,
+    label_device_post_flash, parse_dd_bytes, terminate, verify_image, FlashOptions, COMMAND_PREFIX,
+    ELEVATION_PREFIX,
+};
+use crate::journal::JournalFile;
+
+/// How often the native backend reports progress, like `dd status=progress`.
+pub(crate) const NATIVE_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Backends selectable with `--backend` or the config's `backend` key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Dd,
+    Native,
+}
+
+impl BackendKind {
+    const ALL: [BackendKind; 2] = [BackendKind::Dd, BackendKind::Native];
+
+    /// Lowercase name, as given to `--backend`.
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Dd => "dd",
+            BackendKind::Native => "native",
+        }
+    }
+
+    /// Parse a name in any case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// A fresh backend of this kind, for one flash.
+    pub fn create(self) -> Box<dyn FlashBackend> {
+        match self {
+            BackendKind::Dd => Box::new(DdBackend::default()),
+            BackendKind::Native => Box::new(NativeBackend),
+        }
+    }
+}
+
+/// What a backend writes: the image, the resolved device (e.g. "/dev/sdb"),
+/// and the options of the flash.
+///
+/// A resumed write starts at `resume_from` (a whole number of blocks) in
+/// both the image and the device. The backend tells `journal` the absolute
+/// offset it has written up to as it goes.
+#[derive(Debug, Clone, Copy)]
+pub struct Target<'a> {
+    pub image: &'a Path,
+    pub device: &'a str,
+    pub options: &'a FlashOptions,
+    pub resume_from: u64,
+    pub journal: Option<&'a JournalFile>,
+}
+
+impl Target<'_> {
+    /// Pass "`written` bytes since the start of this write" on to the journal.
+    fn record_written(&self, written: u64) {
+        if let Some(journal) = self.journal {
+            journal.record(self.resume_from + written);
+        }
+    }
+}
+
+/// One way of writing an image to a device.
+///
+/// Each step reports through `progress` like the rest of the flash, and
+/// should stop early once `target.options.cancel` is set.
+pub trait FlashBackend: Send {
+    /// Short name for the summary, e.g. "dd".
+    fn name(&self) -> &'static str;
+
+    /// Get ready to write: acquire privileges and clear old partitions.
+    /// Sends the `ELEVATION_PREFIX` line.
+    fn prepare(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError>;
+
+    /// Write the image. Sends the `COMMAND_PREFIX` line, then dd-style
+    /// progress lines (see `flash::parse_dd_bytes`).
+    fn write(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError>;
+
+    /// Read the device back and compare it with the image.
+    ///
+    /// # Returns
+    ///
+    /// The image's hex digest and whether the device content matches it. An
+    /// error means the verification could not run.
+    fn verify(&mut self, target: &Target, progress: &Sender<String>) -> Result<(String, bool)>;
+
+    /// Post-flash steps such as labeling. Failures here do not fail the
+    /// flash.
+    ///
+    /// # Returns
+    ///
+    /// A message for the user, if any; one starting with `LABEL_PREFIX`
+    /// means the label was set.
+    fn finalize(&mut self, target: &Target, progress: &Sender<String>) -> Result<Option<String>>;
+}
+
+/// Which optional operands the installed `dd` accepts.
+///
+/// GNU `dd` takes them all. Busybox and older BSD builds lack some and
+/// refuse to run when given one. Without `status=progress` the image is fed
+/// to `dd` on its standard input and flashr-tui counts the bytes itself, like
+/// the native backend. Asking such a `dd` for its statistics with SIGUSR1 or
+/// SIGINFO is not an option: a busybox built without signal handling dies of
+/// it, and an elevated `dd` can only be signalled through another elevation.
+///
+/// # Fields
+///
+/// * `progress` - `status=progress`
+/// * `quiet` - `status=none`
+/// * `sync_output` - `oflag=sync`; without it the write ends with `conv=fsync`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DdSupport {
+    pub progress: bool,
+    pub quiet: bool,
+    pub sync_output: bool,
+}
+
+impl DdSupport {
+    /// What the `dd` on the `PATH` supports, probed once per process.
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<DdSupport> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            let support = Self {
+                progress: dd_accepts("status=progress"),
+                quiet: dd_accepts("status=none"),
+                sync_output: dd_accepts("oflag=sync"),
+            };
+            debug!("dd supports {support:?}");
+            support
+        })
+    }
+}
+
+/// Whether `dd` runs with `operand`, copying nothing.
+fn dd_accepts(operand: &str) -> bool {
+    Command::new("dd")
+        .args(["if=/dev/zero", "of=/dev/null", "count=0", operand])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Writes with `dd`, elevated when not running as root.
+///
+/// # Fields
+///
+/// * `elevator` - Elevation tool found by `prepare`, if not running as root
+/// * `support` - Operands `dd` accepts; detected on first use when unset
+#[derive(Debug, Default)]
+pub struct DdBackend {
+    elevator: Option<&'static str>,
+    support: Option<DdSupport>,
+}
+
+impl DdBackend {
+    fn support(&mut self) -> DdSupport {
+        *self.support.get_or_insert_with(DdSupport::detect)
+    }
+}
+
+impl FlashBackend for DdBackend {
+    fn name(&self) -> &'static str {
+        "dd"
+    }
+
+    fn prepare(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError> {
+        self.elevator = acquire_elevator(progress)?;
+        let _ = progress.send(format!(
+            "{ELEVATION_PREFIX}{}",
+            self.elevator.unwrap_or("none (running as root)")
+        ));
+        clear_device(target, self.elevator, progress)?;
+        Ok(())
+    }
+
+    fn write(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError> {
+        let cancel = &target.options.cancel;
+        let block_size = target.options.block_size();
+        let support = self.support();
+        let mut cmd = elevated_command("dd", self.elevator);
+        if support.progress {
+            cmd.arg(format!("if={}", target.image.display()));
+        }
+        cmd.arg(format!("of={}", target.device))
+            .arg(format!("bs={}", dd_block_size(block_size)));
+        if target.resume_from > 0 {
+            let blocks = target.resume_from / block_size;
+            // A fed `dd` reads the image from where `feed_dd` starts it.
+            if support.progress {
+                cmd.arg(format!("skip={blocks}"));
+            }
+            cmd.arg(format!("seek={blocks}"));
+        }
+        if support.progress {
+            cmd.arg("status=progress");
+        }
+        cmd.arg(if support.sync_output {
+            "oflag=sync"
+        } else {
+            "conv=fsync"
+        });
+        let command = describe_command(&cmd);
+        debug!("running {command}");
+        let _ = progress.send(format!("{COMMAND_PREFIX}{command}"));
+
+        let mut child = cmd
+            .stdin(if support.progress {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .stderr(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .context("run dd (do you have permission?)")?;
+
+        if !support.progress {
+            if let Err(err) = feed_dd(&mut child, target, progress) {
+                terminate(&mut child, self.elevator);
+                let _ = child.wait();
+                return Err(err.into());
+            }
+        }
+        relay_dd_output(&mut child, target, progress, self.elevator)?;
+
+        let status = child.wait().context("wait for dd")?;
+        if cancel.is_cancelled() {
+            // Leave the device quiet, with what was written so far on it.
+            Command::new("sync").status().ok();
+            return Err(FlashError::Cancelled);
+        }
+        if !status.success() {
+            warn!("dd to {} exited with {status}", target.device);
+            return Err(FlashError::DdFailed(status));
+        }
+        Ok(())
+    }
+
+    fn verify(&mut self, target: &Target, progress: &Sender<String>) -> Result<(String, bool)> {
+        let iso_size = std::fs::metadata(target.image)
+            .context("read ISO metadata")?
+            .len();
+        let blocks = iso_size.div_ceil(1024 * 1024);
+        let mut cmd = elevated_command("dd", self.elevator);
+        cmd.arg(format!("if={}", target.device))
+            .arg("bs=1M")
+            .arg(format!("count={}", blocks));
+        if self.support().quiet {
+            cmd.arg("status=none");
+        }
+        let mut child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("spawn dd for verification read")?;
+
+        let verified = match child.stdout.take() {
+            Some(mut stdout) => verify_image(target.image, &mut stdout, progress, target.options),
+            None => Err(anyhow::anyhow!("dd for verification read has no output")),
+        };
+        if target.options.cancel.is_cancelled() {
+            terminate(&mut child, self.elevator);
+        }
+        let _ = child.wait();
+        verified
+    }
+
+    fn finalize(&mut self, target: &Target, _progress: &Sender<String>) -> Result<Option<String>> {
+        let label = label_base(target.image, target.options.label.as_deref());
+        label_device_post_flash(&label, target.device, self.elevator)
+    }
+}
+
+/// Copies the image in-process, without `dd` or an elevation tool.
+#[derive(Debug, Default)]
+pub struct NativeBackend;
+
+impl FlashBackend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn prepare(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError> {
+        if !is_root() {
+            return Err(FlashError::Other(anyhow::anyhow!(
+                "The native backend writes the device itself and needs root. \
+                 Run with sudo, or use --backend dd."
+            )));
+        }
+        let _ = progress.send(format!("{ELEVATION_PREFIX}none (running as root)"));
+        clear_device(target, None, progress)?;
+        Ok(())
+    }
+
+    fn write(&mut self, target: &Target, progress: &Sender<String>) -> Result<(), FlashError> {
+        let cancel = &target.options.cancel;
+        let block_size = target.options.block_size();
+        let _ = progress.send(format!(
+            "{COMMAND_PREFIX}native copy of {} to {} ({} chunks, synced)",
+            target.image.display(),
+            target.device,
+            format_lsblk_size(block_size)
+        ));
+
+        let mut image = File::open(target.image)
+            .with_context(|| format!("open image: {}", target.image.display()))?;
+        let mut device = OpenOptions::new()
+            .write(true)
+            .open(target.device)
+            .with_context(|| format!("open {} for writing", target.device))?;
+        if target.resume_from > 0 {
+            let start = SeekFrom::Start(target.resume_from);
+            image.seek(start).context("seek image")?;
+            device
+                .seek(start)
+                .with_context(|| format!("seek {}", target.device))?;
+        }
+
+        let mut buf = vec![0u8; block_size as usize];
+        let mut done = 0u64;
+        let start = Instant::now();
+        let mut last_report = start;
+        loop {
+            let read = image.read(&mut buf).context("read image")?;
+            if read == 0 {
+                break;
+            }
+            device
+                .write_all(&buf[..read])
+                .and_then(|()| device.sync_data())
+                .with_context(|| format!("write {}", target.device))?;
+            done += read as u64;
+            target.record_written(done);
+            if cancel.is_cancelled() {
+                return Err(FlashError::Cancelled);
+            }
+            if last_report.elapsed() >= NATIVE_REPORT_INTERVAL {
+                last_report = Instant::now();
+                let _ = progress.send(copied_line(done, start.elapsed()));
+            }
+        }
+        device
+            .sync_all()
+            .with_context(|| format!("sync {}", target.device))?;
+        let _ = progress.send(copied_line(done, start.elapsed()));
+        debug!(
+            "copied {done} bytes to {} in {:?}",
+            target.device,
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    fn verify(&mut self, target: &Target, progress: &Sender<String>) -> Result<(String, bool)> {
+        let mut device = File::open(target.device)
+            .with_context(|| format!("open {} for verification read", target.device))?;
+        verify_image(target.image, &mut device, progress, target.options)
+    }
+
+    fn finalize(&mut self, target: &Target, _progress: &Sender<String>) -> Result<Option<String>> {
+        let label = label_base(target.image, target.options.label.as_deref());
+        label_device_post_flash(&label, target.device, None)
+    }
+}
+
+/// Pass the lines `dd` writes to its standard error on as progress until it
+/// closes it, stopping `dd` when the flash is cancelled.
+fn relay_dd_output(
+    child: &mut Child,
+    target: &Target,
+    progress: &Sender<String>,
+    elevator: Option<&str>,
+) -> Result<()> {
+    let Some(mut stderr) = child.stderr.take() else {
+        return Ok(());
+    };
+    let mut buf = [0u8; 4096];
+    let mut pending = String::new();
+    loop {
+        let read = stderr.read(&mut buf).context("read dd output")?;
+        if read == 0 {
+            break;
+        }
+        if target.options.cancel.is_cancelled() {
+            terminate(child, elevator);
+            break;
+        }
+        let chunk = String::from_utf8_lossy(&buf[..read]);
+        for ch in chunk.chars() {
+            if ch == '\n' || ch == '\r' {
+                let line = pending.trim();
+                if !line.is_empty() {
+                    record_dd_line(target, line);
+                    let _ = progress.send(line.to_string());
+                }
+                pending.clear();
+            } else {
+                pending.push(ch);
+            }
+        }
+    }
+
+    let line = pending.trim();
+    if !line.is_empty() {
+        record_dd_line(target, line);
+        let _ = progress.send(line.to_string());
+    }
+    Ok(())
+}
+
+/// Write the image into the standard input of a `dd` without
+/// `status=progress`, sending dd-style progress lines for the bytes handed
+/// over, and close it so `dd` finishes. A cancelled flash stops feeding early.
+///
+/// The journal is not told about these bytes: `dd` may not have written
+/// them yet, so only its own final byte count is journaled.
+fn feed_dd(child: &mut Child, target: &Target, progress: &Sender<String>) -> Result<()> {
+    let mut input = child.stdin.take().context("dd has no input")?;
+    let mut image = File::open(target.image)
+        .with_context(|| format!("open image: {}", target.image.display()))?;
+    if target.resume_from > 0 {
+        image
+            .seek(SeekFrom::Start(target.resume_from))
+            .context("seek image")?;
+    }
+
+    let mut buf = vec![0u8; target.options.block_size() as usize];
+    let mut done = 0u64;
+    let start = Instant::now();
+    let mut last_report = start;
+    while !target.options.cancel.is_cancelled() {
+        let read = image.read(&mut buf).context("read image")?;
+        if read == 0 {
+            break;
+        }
+        if let Err(err) = input.write_all(&buf[..read]) {
+            // `dd` stopped reading; its exit status says why.
+            debug!("dd input closed: {err}");
+            break;
+        }
+        done += read as u64;
+        if last_report.elapsed() >= NATIVE_REPORT_INTERVAL {
+            last_report = Instant::now();
+            let _ = progress.send(copied_line(done, start.elapsed()));
+        }
+    }
+    let _ = progress.send(copied_line(done, start.elapsed()));
+    Ok(())
+}
+
+/// Journal the byte count of a `dd` progress line, if it has one.
+fn record_dd_line(target: &Target, line: &str) {
+    if let Some(bytes) = parse_dd_bytes(line) {
+        target.record_written(bytes);
+    }
+}
+
+/// A progress line in the shape `dd status=progress` prints, so the same
+/// parsers read both backends.
+pub(crate) fn copied_line(bytes: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0.0 {
+        bytes as f64 / secs / 1_000_000.0
+    } else {
+        0.0
+    };
+    format!(
+        "{bytes} bytes ({:.1} MB) copied, {secs:.0} s, {rate:.1} MB/s",
+        bytes as f64 / 1_000_000.0
+    )
+}
+
+/// `bytes` as a `dd` size operand: whole mebibytes as "4M", anything else
+/// as a plain byte count.
+pub(crate) fn dd_block_size(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB && bytes.is_multiple_of(MIB) {
+        format!("{}M", bytes / MIB)
+    } else {
+        bytes.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::parse_dd_rate;
+
+    #[test]
+    fn native_progress_reads_like_dd() {
+        let line = copied_line(50_000_000, Duration::from_secs(2));
+        assert_eq!(line, "50000000 bytes (50.0 MB) copied, 2 s, 25.0 MB/s");
+        assert_eq!(parse_dd_bytes(&line), Some(50_000_000));
+        assert_eq!(parse_dd_rate(&line).as_deref(), Some("25.0 MB/s"));
+
+        assert_eq!(BackendKind::parse("Native"), Some(BackendKind::Native));
+        assert_eq!(BackendKind::parse("udisks2"), None);
+        assert_eq!(BackendKind::default().create().name(), "dd");
+
+        assert_eq!(dd_block_size(crate::flash::DEFAULT_BLOCK_SIZE), "4M");
+        assert_eq!(dd_block_size(512 * 1024), "524288");
+    }
+
+    #[test]
+    fn dd_without_progress_is_fed_the_image() {
+        let dir = std::env::temp_dir().join(format!("flashr-dd-feed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("image.iso");
+        let device = dir.join("device");
+        let data: Vec<u8> = (0..3 * 4096).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&image, &data).unwrap();
+        std::fs::write(&device, b"").unwrap();
+
+        let options = FlashOptions {
+            block_size: Some(4096),
+            ..FlashOptions::default()
+        };
+        let device_path = device.display().to_string();
+        let target = Target {
+            image: &image,
+            device: &device_path,
+            options: &options,
+            resume_from: 0,
+            journal: None,
+        };
+        let mut backend = DdBackend {
+            elevator: None,
+            support: Some(DdSupport {
+                progress: false,
+                quiet: false,
+                sync_output: false,
+            }),
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        backend.write(&target, &tx).unwrap();
+
+        assert_eq!(std::fs::read(&device).unwrap(), data);
+        let lines: Vec<String> = rx.try_iter().collect();
+        let command = lines[0].strip_prefix(COMMAND_PREFIX).unwrap();
+        assert!(!command.contains("status=progress") && !command.contains("if="));
+        assert!(command.ends_with("conv=fsync"));
+        assert!(lines
+            .iter()
+            .any(|line| parse_dd_bytes(line) == Some(data.len() as u64)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,364 @@
+//! Hashing images, and checking them against a published checksum before
+//! flashing.
+//!
+//! The expected digest is given either directly (`sha256:<hex>`) or as a
+//! checksum list such as `SHA256SUMS`, in which case the line naming the
+//! image's file is used. Both the GNU (`<hex>  <name>`) and BSD
+//! (`SHA256 (<name>) = <hex>`) list formats are understood.
+//!
+//! SHA-256 and SHA-512 are computed in-process. BLAKE3 digests come from the
+//! `b3sum` tool, which is several times faster on multi-gigabyte images.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::error::FlashError;
+
+/// Hash algorithms accepted for published checksums and used to verify
+/// flashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl Algorithm {
+    const ALL: [Algorithm; 3] = [Algorithm::Sha256, Algorithm::Sha512, Algorithm::Blake3];
+
+    /// Lowercase name, as used in `sha256:<hex>`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Name for messages, e.g. "SHA-256".
+    pub fn label(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "SHA-256",
+            Algorithm::Sha512 => "SHA-512",
+            Algorithm::Blake3 => "BLAKE3",
+        }
+    }
+
+    /// Parse a name in any case (e.g., "sha256" or the BSD tag "SHA256").
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Number of hex digits in a digest.
+    fn hex_len(self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Blake3 => 64,
+            Algorithm::Sha512 => 128,
+        }
+    }
+
+    /// Whether `hex` has the digits of one of this algorithm's digests.
+    fn fits(self, hex: &str) -> bool {
+        hex.len() == self.hex_len() && hex.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// The algorithm whose digests are `hex`, judged by length and digits;
+    /// `short` is the one 64-digit digests are taken for.
+    fn for_digest(hex: &str, short: Algorithm) -> Option<Self> {
+        [short, Algorithm::Sha512]
+            .into_iter()
+            .find(|algorithm| algorithm.fits(hex))
+    }
+
+    /// The algorithm a checksum list named like `list` holds 64-digit digests
+    /// of: BLAKE3 for `b3sum` output such as "B3SUMS", SHA-256 otherwise.
+    fn for_list(list: &Path) -> Self {
+        let name = list
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if name.starts_with("b3") || name.contains("blake3") {
+            Algorithm::Blake3
+        } else {
+            Algorithm::Sha256
+        }
+    }
+
+    /// Fail unless this algorithm can be used here: BLAKE3 needs `b3sum`.
+    pub fn ensure_available(self) -> Result<()> {
+        if self == Algorithm::Blake3 && which::which("b3sum").is_err() {
+            anyhow::bail!("BLAKE3 needs the b3sum tool, which is not installed");
+        }
+        Ok(())
+    }
+
+    /// Start hashing data fed in with `Hasher::update`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if BLAKE3 is asked for and `b3sum` cannot be started.
+    pub fn hasher(self) -> Result<Hasher> {
+        self.ensure_available()?;
+        Ok(match self {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Box::new(Sha512::new())),
+            Algorithm::Blake3 => {
+                let mut child = Command::new("b3sum")
+                    .arg("--no-names")
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .context("run b3sum")?;
+                let stdin = child.stdin.take().context("b3sum input")?;
+                Hasher::Blake3 { child, stdin }
+            }
+        })
+    }
+
+    /// Hash the file at `path`, returning the lowercase hex digest.
+    pub fn hash_file(self, path: &Path) -> Result<String> {
+        let mut file =
+            std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let mut hasher = self.hasher()?;
+        let mut buf = vec![0u8; 1024 * 1024];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .with_context(|| format!("read {}", path.display()))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n])?;
+        }
+        hasher.finish()
+    }
+}
+
+/// A digest being computed, started with `Algorithm::hasher`.
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha512(Box<Sha512>),
+    /// `b3sum` reading the data from its standard input
+    Blake3 {
+        child: Child,
+        stdin: ChildStdin,
+    },
+}
+
+impl Hasher {
+    /// Feed in the next chunk of data.
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+            Hasher::Blake3 { stdin, .. } => stdin.write_all(data).context("write to b3sum")?,
+        }
+        Ok(())
+    }
+
+    /// The lowercase hex digest of everything fed in.
+    pub fn finish(self) -> Result<String> {
+        match self {
+            Hasher::Sha256(hasher) => Ok(hex(&hasher.finalize())),
+            Hasher::Sha512(hasher) => Ok(hex(&hasher.finalize())),
+            Hasher::Blake3 { child, stdin } => {
+                // Closing its input tells b3sum the data is complete.
+                drop(stdin);
+                let output = child.wait_with_output().context("wait for b3sum")?;
+                let text = String::from_utf8_lossy(&output.stdout);
+                match text.split_whitespace().next() {
+                    Some(digest) if output.status.success() && Algorithm::Blake3.fits(digest) => {
+                        Ok(digest.to_string())
+                    }
+                    _ => anyhow::bail!("b3sum failed ({})", output.status),
+                }
+            }
+        }
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Where the expected digest of an image comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// A digest given directly (e.g., "sha256:ab12...")
+    Digest { algorithm: Algorithm, hex: String },
+    /// A checksum list, searched for the image's file name
+    List(PathBuf),
+}
+
+impl Checksum {
+    /// Parse `<algorithm>:<hex>` or the path of a checksum list.
+    pub fn parse(spec: &str) -> Result<Self> {
+        if let Some((name, hex)) = spec.split_once(':') {
+            if let Some(algorithm) = Algorithm::parse(name) {
+                if !algorithm.fits(hex) {
+                    anyhow::bail!(
+                        "'{hex}' is not a {} digest ({} hex digits)",
+                        algorithm.name(),
+                        algorithm.hex_len()
+                    );
+                }
+                return Ok(Checksum::Digest {
+                    algorithm,
+                    hex: hex.to_ascii_lowercase(),
+                });
+            }
+        }
+        let path = Path::new(spec);
+        if path.is_file() {
+            return Ok(Checksum::List(path.to_path_buf()));
+        }
+        anyhow::bail!(
+            "checksum must be sha256:<hex>, sha512:<hex>, blake3:<hex>, or a checksum file: '{spec}'"
+        )
+    }
+
+    /// The digest `image` is expected to have.
+    pub fn expected_for(&self, image: &Path) -> Result<(Algorithm, String)> {
+        match self {
+            Checksum::Digest { algorithm, hex } => Ok((*algorithm, hex.clone())),
+            Checksum::List(list) => {
+                let text = std::fs::read_to_string(list)
+                    .with_context(|| format!("read {}", list.display()))?;
+                let name = image
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default();
+                find_in_list(&text, &name, Algorithm::for_list(list))
+                    .with_context(|| format!("{} has no checksum for {name}", list.display()))
+            }
+        }
+    }
+
+    /// Hash `image` and fail with `FlashError::ChecksumMismatch` unless it
+    /// matches the expected digest.
+    pub fn verify(&self, image: &Path) -> Result<(), FlashError> {
+        let (algorithm, expected) = self.expected_for(image)?;
+        let actual = algorithm.hash_file(image)?;
+        if actual != expected {
+            return Err(FlashError::ChecksumMismatch {
+                image: image.to_path_buf(),
+                algorithm,
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Find the digest for `file_name` in the text of a checksum list, taking
+/// 64-digit digests in GNU lines for `short` (SHA-256 or BLAKE3).
+///
+/// Lines that are neither GNU nor BSD style (comments, PGP armor) are skipped.
+pub fn find_in_list(text: &str, file_name: &str, short: Algorithm) -> Option<(Algorithm, String)> {
+    text.lines().find_map(|line| {
+        let line = line.trim();
+        let (algorithm, hex, name) =
+            parse_bsd_line(line).or_else(|| parse_gnu_line(line, short))?;
+        let name = name.strip_prefix("./").unwrap_or(name);
+        (name == file_name && algorithm.fits(hex)).then(|| (algorithm, hex.to_ascii_lowercase()))
+    })
+}
+
+/// Split a BSD-style line, "SHA256 (name) = hex".
+fn parse_bsd_line(line: &str) -> Option<(Algorithm, &str, &str)> {
+    let (tag, rest) = line.split_once(" (")?;
+    let (name, hex) = rest.rsplit_once(") = ")?;
+    Some((Algorithm::parse(tag)?, hex, name))
+}
+
+/// Split a GNU-style line, "hex  name" (or "hex *name" in binary mode).
+fn parse_gnu_line(line: &str, short: Algorithm) -> Option<(Algorithm, &str, &str)> {
+    let (hex, name) = line.split_once(' ')?;
+    let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+    Some((Algorithm::for_digest(hex, short)?, hex, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn parse_accepts_digests_and_rejects_bad_ones() {
+        assert_eq!(
+            Checksum::parse(&format!("SHA256:{}", EMPTY_SHA256.to_uppercase())).unwrap(),
+            Checksum::Digest {
+                algorithm: Algorithm::Sha256,
+                hex: EMPTY_SHA256.to_string(),
+            }
+        );
+        assert_eq!(
+            Checksum::parse(&format!("blake3:{EMPTY_SHA256}")).unwrap(),
+            Checksum::Digest {
+                algorithm: Algorithm::Blake3,
+                hex: EMPTY_SHA256.to_string(),
+            }
+        );
+        assert!(Checksum::parse("sha256:abcd").is_err());
+        assert!(Checksum::parse(&format!("sha512:{EMPTY_SHA256}")).is_err());
+        assert!(Checksum::parse("/nonexistent/SHA256SUMS").is_err());
+    }
+
+    #[test]
+    fn find_in_list_reads_gnu_and_bsd_lines() {
+        let other = "a".repeat(64);
+        let text = format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\n\
+             {other}  other.iso\n\
+             {EMPTY_SHA256} *./arch.iso\n\
+             SHA256 (debian.iso) = {other}\n"
+        );
+        assert_eq!(
+            find_in_list(&text, "arch.iso", Algorithm::Sha256),
+            Some((Algorithm::Sha256, EMPTY_SHA256.to_string()))
+        );
+        assert_eq!(
+            find_in_list(&text, "debian.iso", Algorithm::Blake3),
+            Some((Algorithm::Sha256, other))
+        );
+        assert_eq!(find_in_list(&text, "missing.iso", Algorithm::Sha256), None);
+        assert_eq!(
+            find_in_list(
+                &text,
+                "arch.iso",
+                Algorithm::for_list(Path::new("/isos/B3SUMS"))
+            ),
+            Some((Algorithm::Blake3, EMPTY_SHA256.to_string()))
+        );
+    }
+
+    #[test]
+    fn verify_compares_the_image_digest() {
+        let path = std::env::temp_dir().join(format!(
+            "flashr_tui_checksum_test_{}.iso",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"").unwrap();
+        let matching = Checksum::parse(&format!("sha256:{EMPTY_SHA256}")).unwrap();
+        let other = Checksum::parse(&format!("sha256:{}", "0".repeat(64))).unwrap();
+        let results = (matching.verify(&path), other.verify(&path));
+        std::fs::remove_file(&path).ok();
+        assert!(results.0.is_ok());
+        assert!(matches!(
+            results.1,
+            Err(FlashError::ChecksumMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,962 @@
+//! Device detection and listing using `lsblk`.
+//!
+//! This module queries the Linux block device (lsblk) command to enumerate
+//! USB and removable storage devices, then presents them as a list of `Disk` structs.
+//! When `lsblk` is missing or its JSON output is unusable, devices are enumerated
+//! from `/proc/partitions` and sysfs attributes instead. Every path and tool
+//! it uses comes from `platform::current()`.
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::error::DeviceError;
+use crate::platform;
+use crate::task::{no_wake, Wake};
+
+/// Represents a block storage device (USB drive, hard disk, etc.).
+///
+/// # Fields
+///
+/// * `name` - Device name without path prefix (e.g., "sdb", "sdc1")
+/// * `vendor` - Vendor string reported by the device (e.g., "SanDisk"), empty if unknown
+/// * `model` - Human-readable model string (e.g., "SanDisk Cruzer")
+/// * `size` - Human-readable size string (e.g., "57.3G", "1.8M")
+/// * `serial` - Hardware serial number reported by the device, if any
+/// * `by_id` - Stable `/dev/disk/by-id/...` link for this disk, if udev created one
+/// * `removable` - Whether the kernel reports the disk as removable media
+#[derive(Debug, Clone)]
+pub struct Disk {
+    pub name: String,
+    pub vendor: String,
+    pub model: String,
+    pub size: String,
+    pub serial: Option<String>,
+    pub by_id: Option<String>,
+    pub removable: bool,
+}
+
+impl Disk {
+    /// Get the full device path for this disk.
+    ///
+    /// # Returns
+    ///
+    /// Full path like "/dev/sdb"
+    pub fn device_path(&self) -> String {
+        platform::current().device_path(&self.name)
+    }
+
+    /// Get the path that should be used to address this disk across re-enumeration.
+    ///
+    /// Prefers the `/dev/disk/by-id` link (tied to the hardware identity), falling
+    /// back to the kernel name when no such link exists.
+    pub fn stable_path(&self) -> String {
+        self.by_id.clone().unwrap_or_else(|| self.device_path())
+    }
+
+    /// Machine-readable description, as printed by `flashr-tui list --json`.
+    ///
+    /// `size_bytes` is read from sysfs and is `null` when unavailable.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "path": self.device_path(),
+            "by_id": self.by_id,
+            "vendor": self.vendor,
+            "model": self.model,
+            "size": self.size,
+            "size_bytes": size_bytes(&self.name),
+            "serial": self.serial,
+            "removable": self.removable,
+        })
+    }
+
+    /// Whether `path` refers to this disk, by kernel path or by-id link.
+    pub fn matches_path(&self, path: &str) -> bool {
+        self.device_path() == path || self.by_id.as_deref() == Some(path)
+    }
+
+    /// Whether the device list filter `query` matches this disk's name,
+    /// vendor, model, serial, or by-id link (case-insensitive substring).
+    pub fn matches_filter(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        [
+            Some(self.name.as_str()),
+            Some(self.vendor.as_str()),
+            Some(self.model.as_str()),
+            self.serial.as_deref(),
+            self.by_id.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+/// Find the stable by-id link for a whole-disk kernel name (e.g., "sdb").
+///
+/// Scans `/dev/disk/by-id` for links that resolve to `/dev/<name>`. Returns
+/// `None` when the directory is missing (no udev) or no link points at the disk.
+pub fn by_id_link(name: &str) -> Option<String> {
+    let platform = platform::current();
+    let by_id_dir = platform.stable_link_dir()?;
+    let target = std::path::PathBuf::from(platform.device_path(name));
+    let candidates = std::fs::read_dir(by_id_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| std::fs::canonicalize(entry.path()).ok().as_ref() == Some(&target))
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    pick_by_id(candidates).map(|link| format!("{by_id_dir}/{link}"))
+}
+
+/// Choose the most descriptive by-id link name for a whole disk.
+///
+/// Partition links (`-partN`) are ignored. Transport-based names such as
+/// `usb-...` or `ata-...` are preferred over opaque `wwn-`/`nvme-eui.` ones;
+/// ties are broken alphabetically so the choice is deterministic.
+fn pick_by_id(mut candidates: Vec<String>) -> Option<String> {
+    candidates.retain(|name| !name.contains("-part"));
+    candidates.sort_by_key(|name| {
+        let opaque = name.starts_with("wwn-") || name.starts_with("nvme-eui.");
+        (opaque, name.clone())
+    });
+    candidates.into_iter().next()
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LsblkOutput {
+    pub blockdevices: Vec<LsblkDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LsblkDevice {
+    pub name: String,
+    #[serde(default)]
+    pub vendor: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub size: Option<String>,
+    #[serde(default)]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub rm: Option<bool>,
+    #[serde(default)]
+    pub r#type: String,
+    #[serde(default)]
+    pub fstype: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub fsused: Option<String>,
+    #[serde(default)]
+    pub mountpoint: Option<String>,
+    #[serde(default)]
+    pub mountpoints: Option<Vec<Option<String>>>,
+    #[serde(default)]
+    pub children: Vec<LsblkDevice>,
+}
+
+/// Find `device` among the listed disks for a flash without the TUI.
+///
+/// Hidden devices are not listed, so they are not found. Internal disks are
+/// refused outright, since there is no way to type the stronger confirmation
+/// they need.
+pub fn find_unattended_target<'a>(devices: &'a [Disk], device: &str) -> Result<&'a Disk> {
+    let disk = devices
+        .iter()
+        .find(|d| d.matches_path(device))
+        .ok_or_else(|| anyhow::anyhow!("device not found (or hidden by the config): {device}"))?;
+    if !disk.removable {
+        anyhow::bail!(
+            "{} is an internal (non-removable) disk; flash it from the TUI",
+            disk.device_path()
+        );
+    }
+    Ok(disk)
+}
+
+/// Value of `--device` that picks the single attached removable device.
+pub const AUTO: &str = "auto";
+
+/// The only removable disk in `disks`, for `--device auto`.
+///
+/// # Errors
+///
+/// Returns an error when there is no removable disk, or more than one; the
+/// latter lists the candidates so one can be named instead.
+pub fn single_removable(disks: &[Disk]) -> Result<&Disk> {
+    let candidates: Vec<&Disk> = disks.iter().filter(|d| d.removable).collect();
+    match candidates.as_slice() {
+        [disk] => Ok(disk),
+        [] => Err(anyhow::anyhow!(
+            "--device auto: no removable device is attached"
+        )),
+        _ => {
+            let list = candidates
+                .iter()
+                .map(|d| {
+                    format!(
+                        "  {}  {} {}  {}",
+                        d.device_path(),
+                        d.vendor,
+                        d.model,
+                        d.size
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Err(anyhow::anyhow!(
+                "--device auto: {} removable devices are attached; name one with --device:\n{list}",
+                candidates.len()
+            ))
+        }
+    }
+}
+
+/// List available block devices on the system.
+///
+/// Runs `lsblk --json` and filters for block devices (`type == "disk"`).
+/// If `show_all` is false, further filters to only removable devices (`rm == 1`);
+/// otherwise removable devices are listed before internal ones.
+/// Falls back to `/proc/partitions` + sysfs when `lsblk` is unavailable or its
+/// output cannot be parsed (e.g. old util-linux without JSON support).
+///
+/// # Arguments
+///
+/// * `show_all` - If `true`, list all disk devices; if `false`, list only removable devices
+///
+/// # Returns
+///
+/// `Ok(Vec<Disk>)` with the list of devices, or an error if both `lsblk` and the fallback fail.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - `lsblk` fails or its output cannot be parsed, and
+/// - `/proc/partitions` cannot be read
+pub fn list(show_all: bool) -> Result<Vec<Disk>> {
+    let mut disks = match list_lsblk(show_all) {
+        Ok(disks) => disks,
+        Err(err) => {
+            warn!("lsblk listing failed, reading sysfs instead: {err:#}");
+            list_sysfs(show_all).map_err(|fallback| {
+                anyhow::anyhow!("{err:#}; sysfs fallback failed: {fallback:#}")
+            })?
+        }
+    };
+    // Removable disks first, so the list can be drawn as two sections.
+    disks.sort_by_key(|disk| !disk.removable);
+    debug!(
+        "listed {} disk(s) (show_all: {show_all}): {}",
+        disks.len(),
+        disks
+            .iter()
+            .map(|disk| format!("{} {}", disk.name, disk.size))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    Ok(disks)
+}
+
+/// Enumerate disks with `lsblk --json`.
+fn list_lsblk(show_all: bool) -> Result<Vec<Disk>> {
+    let output = platform::current()
+        .lsblk()
+        .args(["--json", "-o", "NAME,VENDOR,MODEL,SIZE,SERIAL,RM,TYPE"])
+        .output()
+        .context("run lsblk")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            return Err(anyhow::anyhow!("lsblk failed"));
+        }
+        return Err(anyhow::anyhow!("lsblk failed: {stderr}"));
+    }
+
+    let parsed: LsblkOutput =
+        serde_json::from_slice(&output.stdout).context("parse lsblk output")?;
+
+    let disks = parsed
+        .blockdevices
+        .into_iter()
+        .filter(|dev| dev.r#type == "disk")
+        .filter(|dev| show_all || dev.rm.unwrap_or(false))
+        .map(|dev| Disk {
+            by_id: by_id_link(&dev.name),
+            removable: dev.rm.unwrap_or(false),
+            name: dev.name,
+            vendor: dev
+                .vendor
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default(),
+            model: dev.model.unwrap_or_default(),
+            size: dev.size.unwrap_or_default(),
+            serial: dev
+                .serial
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        })
+        .collect();
+
+    Ok(disks)
+}
+
+/// Enumerate disks from `/proc/partitions` and `/sys/block/<name>` attributes.
+fn list_sysfs(show_all: bool) -> Result<Vec<Disk>> {
+    let disks = platform::current()
+        .disk_names()?
+        .into_iter()
+        .filter(|name| !is_virtual_disk(name))
+        .filter(|name| show_all || read_sys_attr(name, "removable").as_deref() == Some("1"))
+        .map(|name| {
+            let size = read_sys_attr(&name, "size")
+                .and_then(|sectors| sectors.parse::<u64>().ok())
+                .map(|sectors| format_lsblk_size(sectors * 512))
+                .unwrap_or_default();
+            Disk {
+                vendor: read_sys_attr(&name, "device/vendor").unwrap_or_default(),
+                model: read_sys_attr(&name, "device/model").unwrap_or_default(),
+                serial: read_sys_attr(&name, "device/serial"),
+                by_id: by_id_link(&name),
+                removable: read_sys_attr(&name, "removable").as_deref() == Some("1"),
+                size,
+                name,
+            }
+        })
+        .collect();
+
+    Ok(disks)
+}
+
+/// Capacity of a disk in bytes, from sysfs (which counts 512-byte sectors).
+pub fn size_bytes(name: &str) -> Option<u64> {
+    read_sys_attr(name, "size")?
+        .parse::<u64>()
+        .ok()
+        .map(|sectors| sectors * 512)
+}
+
+/// Read a trimmed sysfs attribute for a block device, ignoring empty values.
+fn read_sys_attr(name: &str, attr: &str) -> Option<String> {
+    platform::current().block_attribute(name, attr)
+}
+
+/// Whether a block device name belongs to a virtual device that lsblk wouldn't report as a disk.
+fn is_virtual_disk(name: &str) -> bool {
+    ["loop", "ram", "zram", "sr", "dm-", "md", "nbd"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Format a byte count the way `lsblk` does (binary units, one decimal, e.g. "57.3G").
+pub fn format_lsblk_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let formatted = format!("{value:.1}");
+    let formatted = formatted.strip_suffix(".0").unwrap_or(&formatted);
+    format!("{formatted}{}", UNITS[unit])
+}
+
+/// Existing content found on a partition of a target device.
+///
+/// # Fields
+///
+/// * `name` - Partition path (e.g., "/dev/sdb1")
+/// * `fstype` - Filesystem type, if one was detected
+/// * `label` - Filesystem label, if set
+/// * `size` - Human-readable partition size
+/// * `used` - Human-readable used space (only known while the filesystem is mounted)
+/// * `mountpoint` - Where the partition is mounted, if it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionContent {
+    pub name: String,
+    pub fstype: Option<String>,
+    pub label: Option<String>,
+    pub size: String,
+    pub used: Option<String>,
+    pub mountpoint: Option<String>,
+}
+
+impl PartitionContent {
+    /// Describe the partition for the Confirm screen, e.g.
+    /// "ext4 partition 'Backups' on /dev/sdb1 (50G, 12G used)".
+    pub fn describe(&self) -> String {
+        let kind = match &self.fstype {
+            Some(fstype) => format!("{fstype} partition"),
+            None => "Unformatted partition".to_string(),
+        };
+        let label = match &self.label {
+            Some(label) => format!(" '{label}'"),
+            None => String::new(),
+        };
+        let usage = match &self.used {
+            Some(used) => format!("{used} used"),
+            None if self.fstype.is_some() => "usage unknown, not mounted".to_string(),
+            None => "no filesystem".to_string(),
+        };
+        format!("{kind}{label} on {} ({}, {usage})", self.name, self.size)
+    }
+}
+
+/// Summarize the partitions currently present on a device.
+///
+/// Runs an unprivileged `lsblk` for the device. Used space is only reported
+/// for mounted filesystems.
+///
+/// # Errors
+///
+/// Returns an error if `lsblk` cannot be run or its output cannot be parsed.
+pub fn content_summary(device: &str) -> Result<Vec<PartitionContent>> {
+    let output = platform::current()
+        .lsblk()
+        .args([
+            "--json",
+            "-o",
+            "NAME,TYPE,FSTYPE,LABEL,SIZE,FSUSED,MOUNTPOINT",
+            "-p",
+            device,
+        ])
+        .output()
+        .context("run lsblk for content summary")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("lsblk failed for {device}"));
+    }
+
+    let parsed: LsblkOutput =
+        serde_json::from_slice(&output.stdout).context("parse lsblk content summary")?;
+    Ok(contents_from_lsblk(parsed))
+}
+
+/// Extract partition contents from parsed `lsblk` output.
+fn contents_from_lsblk(parsed: LsblkOutput) -> Vec<PartitionContent> {
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    parsed
+        .blockdevices
+        .into_iter()
+        .flat_map(|dev| dev.children)
+        .filter(|child| child.r#type == "part")
+        .map(|child| PartitionContent {
+            name: child.name,
+            fstype: non_empty(child.fstype),
+            label: non_empty(child.label),
+            size: child.size.unwrap_or_else(|| "?".to_string()),
+            used: non_empty(child.fsused),
+            mountpoint: non_empty(child.mountpoint),
+        })
+        .collect()
+}
+
+/// Details of one device for the Device step's details pane.
+///
+/// # Fields
+///
+/// * `device` - Kernel device path the details were read for (e.g., "/dev/sdb")
+/// * `partitions` - Partitions on the device, or why they could not be listed
+/// * `first_sector` - What the start of the device holds (see `describe_first_sectors`)
+#[derive(Debug, Clone)]
+pub struct DeviceDetails {
+    pub device: String,
+    pub partitions: Result<Vec<PartitionContent>, String>,
+    pub first_sector: String,
+}
+
+impl DeviceDetails {
+    /// Gather details for a device. Never fails; problems are reported in the fields.
+    ///
+    /// Reading the first sectors needs read access to the device node, which
+    /// unprivileged users usually lack; the partition table type reported by
+    /// `lsblk` (from udev) is shown instead in that case.
+    pub fn load(device: &str) -> Self {
+        let first_sector = match read_first_sectors(device) {
+            Ok(bytes) => describe_first_sectors(&bytes),
+            Err(_) => match partition_table_type(device) {
+                Some(pttype) => format!("{pttype} partition table (per udev)"),
+                None => "Unknown (no read permission)".to_string(),
+            },
+        };
+        Self {
+            device: device.to_string(),
+            partitions: content_summary(device).map_err(|err| format!("{err:#}")),
+            first_sector,
+        }
+    }
+}
+
+/// Bytes read from the start of a device for `describe_first_sectors`.
+const FIRST_SECTORS_BYTES: usize = 1024;
+
+/// Read the first two 512-byte sectors of a device.
+fn read_first_sectors(device: &str) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(device).with_context(|| format!("open {device}"))?;
+    let mut bytes = vec![0u8; FIRST_SECTORS_BYTES];
+    let read = file
+        .read(&mut bytes)
+        .with_context(|| format!("read {device}"))?;
+    bytes.truncate(read);
+    Ok(bytes)
+}
+
+/// Partition table type as reported by `lsblk` ("gpt", "dos"), if any.
+fn partition_table_type(device: &str) -> Option<String> {
+    let output = platform::current()
+        .lsblk()
+        .args(["-dno", "PTTYPE", device])
+        .output()
+        .ok()?;
+    let pttype = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match pttype.as_str() {
+        "" => None,
+        "dos" => Some("MBR".to_string()),
+        other => Some(other.to_uppercase()),
+    }
+}
+
+/// Summarize the first sectors of a device: partition table type, boot
+/// sector, blank, or unrecognized.
+pub fn describe_first_sectors(bytes: &[u8]) -> String {
+    if bytes.len() < 512 {
+        return "Unreadable (device too small)".to_string();
+    }
+    if bytes[..512].iter().all(|b| *b == 0) {
+        return "Blank (first sector is zeroed)".to_string();
+    }
+    if bytes.get(512..520) == Some(b"EFI PART".as_slice()) {
+        return "GPT partition table".to_string();
+    }
+    if bytes[510..512] == [0x55, 0xAA] {
+        return if bytes[446..510].iter().any(|b| *b != 0) {
+            "MBR partition table".to_string()
+        } else {
+            "Boot sector without partition table".to_string()
+        };
+    }
+    "Unrecognized data".to_string()
+}
+
+/// A process that has a target device or one of its partitions open.
+///
+/// # Fields
+///
+/// * `pid` - Process ID
+/// * `command` - Short command name from `/proc/<pid>/comm` (e.g., "gnome-disks")
+/// * `path` - The device node it holds open (e.g., "/dev/sdb1")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceHolder {
+    pub pid: u32,
+    pub command: String,
+    pub path: String,
+}
+
+impl DeviceHolder {
+    /// Describe the holder for warnings, e.g. "gnome-disks (pid 4242) on /dev/sdb1".
+    pub fn describe(&self) -> String {
+        format!("{} (pid {}) on {}", self.command, self.pid, self.path)
+    }
+}
+
+/// Find processes that have a device or any of its partitions open.
+///
+/// Scans `/proc/<pid>/fd` for links to the device nodes. When running
+/// unprivileged only the user's own processes can be inspected, so an empty
+/// result is a best-effort answer rather than a guarantee.
+///
+/// # Arguments
+///
+/// * `device` - Kernel device path (e.g., "/dev/sdb")
+pub fn holders(device: &str) -> Vec<DeviceHolder> {
+    let platform = platform::current();
+    let nodes = device_nodes(device);
+    let own_pid = std::process::id();
+
+    let mut found = Vec::new();
+    for pid in platform.process_ids() {
+        if pid == own_pid {
+            continue;
+        }
+        let held = platform.open_files(pid).into_iter().find_map(|target| {
+            let target = target.to_string_lossy();
+            nodes.iter().find(|node| **node == target).cloned()
+        });
+        if let Some(path) = held {
+            let command = platform
+                .process_name(pid)
+                .unwrap_or_else(|| "?".to_string());
+            found.push(DeviceHolder { pid, command, path });
+        }
+    }
+    found.sort_by_key(|holder| holder.pid);
+    found
+}
+
+/// The device node plus the nodes of its partitions, as listed in sysfs.
+fn device_nodes(device: &str) -> Vec<String> {
+    let platform = platform::current();
+    let mut nodes = vec![device.to_string()];
+    nodes.extend(
+        platform
+            .partition_names(platform.device_name(device))
+            .iter()
+            .map(|name| platform.device_path(name)),
+    );
+    nodes
+}
+
+/// How often the polling fallback re-reads `/proc/partitions`.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How device hotplug changes are being detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchMode {
+    /// Events from `udevadm monitor`
+    Udev,
+    /// Periodic comparison of `/proc/partitions` (containers, non-systemd hosts)
+    Polling,
+}
+
+/// Background watcher that signals when the set of block devices may have changed.
+///
+/// Uses `udevadm monitor` when a udev daemon is reachable, otherwise polls
+/// `/proc/partitions` every two seconds. Each change sends `()` on the channel;
+/// callers are expected to debounce bursts before re-enumerating.
+pub struct DeviceWatcher {
+    pub mode: WatchMode,
+    pub changes: mpsc::Receiver<()>,
+    monitor: Option<Child>,
+}
+
+impl DeviceWatcher {
+    /// Start watching for device changes in a background thread.
+    pub fn start() -> Self {
+        Self::start_waking(no_wake())
+    }
+
+    /// Like `start`, calling `wake` after each change is sent.
+    pub fn start_waking(wake: Wake) -> Self {
+        let (tx, changes) = mpsc::channel();
+        let platform = platform::current();
+        if let Some(command) = platform.device_monitor() {
+            if let Some(monitor) = spawn_udev_monitor(command, tx.clone(), wake.clone()) {
+                info!("watching for device changes with udevadm monitor");
+                return Self {
+                    mode: WatchMode::Udev,
+                    changes,
+                    monitor: Some(monitor),
+                };
+            }
+        }
+
+        info!("udev unavailable; polling /proc/partitions for device changes");
+        std::thread::spawn(move || {
+            let mut last = platform.block_devices_fingerprint();
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+                let current = platform.block_devices_fingerprint();
+                if current != last {
+                    last = current;
+                    if tx.send(()).is_err() {
+                        break;
+                    }
+                    wake();
+                }
+            }
+        });
+        Self {
+            mode: WatchMode::Polling,
+            changes,
+            monitor: None,
+        }
+    }
+
+    /// Drain pending change notifications.
+    ///
+    /// # Returns
+    ///
+    /// `true` if at least one change was signalled since the last call.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        if let Some(monitor) = &mut self.monitor {
+            let _ = monitor.kill();
+            let _ = monitor.wait();
+        }
+    }
+}
+
+/// Spawn `udevadm monitor` for block devices (`command`, from
+/// `Platform::device_monitor`) and forward add/remove events.
+///
+/// Returns `None` if `udevadm` cannot be started.
+fn spawn_udev_monitor(mut command: Command, tx: mpsc::Sender<()>, wake: Wake) -> Option<Child> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if line.contains(" add ") || line.contains(" remove ") || line.contains(" change ") {
+                if tx.send(()).is_err() {
+                    break;
+                }
+                wake();
+            }
+        }
+    });
+    Some(child)
+}
+
+/// A validated device path guaranteed to be a real block device (not a symlink).
+///
+/// Construct via `DevicePath::validate()` which checks that the path exists,
+/// is not a symlink, and points to a block device.
+#[derive(Debug, Clone)]
+pub struct DevicePath(String);
+
+impl DevicePath {
+    /// Validate a device path string.
+    ///
+    /// Rejects symlinks (tells the user the real target) and non-block-device paths.
+    pub fn validate(path: &str) -> Result<Self, DeviceError> {
+        let not_found = |source| DeviceError::NotFound {
+            path: path.to_string(),
+            source,
+        };
+        let meta = std::fs::symlink_metadata(path).map_err(not_found)?;
+        if meta.file_type().is_symlink() {
+            let target = std::fs::canonicalize(path).map_err(not_found)?;
+            return Err(DeviceError::Symlink {
+                path: path.to_string(),
+                target,
+            });
+        }
+        let meta = std::fs::metadata(path).map_err(not_found)?;
+        if !meta.file_type().is_block_device() {
+            return Err(DeviceError::NotBlockDevice {
+                path: path.to_string(),
+            });
+        }
+        Ok(Self(path.to_string()))
+    }
+
+    /// Resolve a stable `/dev/disk/by-id` link to its current kernel device and validate it.
+    ///
+    /// Any other path is passed to `validate()` unchanged, so symlinks outside
+    /// `/dev/disk/by-id` are still rejected.
+    pub fn resolve(path: &str) -> Result<Self, DeviceError> {
+        let by_id_dir = platform::current().stable_link_dir();
+        if !by_id_dir.is_some_and(|dir| path.starts_with(dir)) {
+            return Self::validate(path);
+        }
+        let real = std::fs::canonicalize(path).map_err(|_| DeviceError::Detached {
+            path: path.to_string(),
+        })?;
+        debug!("{path} resolves to {}", real.display());
+        Self::validate(&real.to_string_lossy())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for DevicePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::ops::Deref for DevicePath {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_by_id_skips_partitions_and_prefers_transport_names() {
+        let candidates = vec![
+            "wwn-0x5000c500a1b2c3d4".to_string(),
+            "usb-SanDisk_Cruzer_1234-0:0-part1".to_string(),
+            "usb-SanDisk_Cruzer_1234-0:0".to_string(),
+        ];
+        assert_eq!(
+            pick_by_id(candidates).as_deref(),
+            Some("usb-SanDisk_Cruzer_1234-0:0")
+        );
+    }
+
+    #[test]
+    fn matches_filter_checks_identity_fields_case_insensitively() {
+        let disk = Disk {
+            name: "sdc".to_string(),
+            vendor: "Samsung".to_string(),
+            model: "Flash Drive FIT".to_string(),
+            size: "119.5G".to_string(),
+            serial: Some("0374622110004471".to_string()),
+            by_id: None,
+            removable: true,
+        };
+        assert!(disk.matches_filter("SDC"));
+        assert!(disk.matches_filter("samsung"));
+        assert!(disk.matches_filter("drive fit"));
+        assert!(disk.matches_filter("04471"));
+        assert!(disk.matches_filter(""));
+        assert!(!disk.matches_filter("sandisk"));
+    }
+
+    #[test]
+    fn single_removable_needs_exactly_one_candidate() {
+        let disk = |name: &str, removable: bool| Disk {
+            name: name.to_string(),
+            vendor: "SanDisk".to_string(),
+            model: "Cruzer".to_string(),
+            size: "8G".to_string(),
+            serial: None,
+            by_id: None,
+            removable,
+        };
+        let one = [disk("sda", false), disk("sdb", true)];
+        assert_eq!(single_removable(&one).unwrap().name, "sdb");
+        assert!(single_removable(&one[..1]).is_err());
+        let two = [disk("sdb", true), disk("sdc", true)];
+        assert_eq!(
+            single_removable(&two).unwrap_err().to_string(),
+            "--device auto: 2 removable devices are attached; name one with --device:\n  \
+             /dev/sdb  SanDisk Cruzer  8G\n  /dev/sdc  SanDisk Cruzer  8G"
+        );
+    }
+
+    #[test]
+    fn to_json_reports_path_and_missing_size_as_null() {
+        let disk = Disk {
+            name: "flashr-no-such-disk".to_string(),
+            vendor: "SanDisk".to_string(),
+            model: "Cruzer".to_string(),
+            size: "57.3G".to_string(),
+            serial: None,
+            by_id: Some("/dev/disk/by-id/usb-SanDisk_Cruzer-0:0".to_string()),
+            removable: true,
+        };
+        let json = disk.to_json();
+        assert_eq!(json["path"], "/dev/flashr-no-such-disk");
+        assert_eq!(json["size"], "57.3G");
+        assert!(json["size_bytes"].is_null());
+        assert!(json["serial"].is_null());
+        assert_eq!(json["removable"], true);
+    }
+
+    #[test]
+    fn pick_by_id_returns_none_for_partition_only_links() {
+        assert_eq!(pick_by_id(vec!["ata-Disk-part2".to_string()]), None);
+    }
+
+    #[test]
+    fn contents_from_lsblk_reads_partitions() {
+        let json = r#"{"blockdevices": [{"name": "/dev/sdb", "type": "disk", "children": [
+            {"name": "/dev/sdb1", "type": "part", "fstype": "ext4", "label": "Backups",
+             "size": "50G", "fsused": "12G"},
+            {"name": "/dev/sdb2", "type": "part", "fstype": null, "label": null,
+             "size": "1G", "fsused": null}
+        ]}]}"#;
+        let parsed: LsblkOutput = serde_json::from_str(json).expect("valid lsblk json");
+        let contents = contents_from_lsblk(parsed);
+        assert_eq!(contents.len(), 2);
+        assert_eq!(
+            contents[0].describe(),
+            "ext4 partition 'Backups' on /dev/sdb1 (50G, 12G used)"
+        );
+        assert_eq!(
+            contents[1].describe(),
+            "Unformatted partition on /dev/sdb2 (1G, no filesystem)"
+        );
+    }
+
+    #[test]
+    fn describe_first_sectors_recognizes_tables() {
+        let mut bytes = vec![0u8; 1024];
+        assert_eq!(describe_first_sectors(&bytes), "Blank (first sector is zeroed)");
+        bytes[510] = 0x55;
+        bytes[511] = 0xAA;
+        assert_eq!(
+            describe_first_sectors(&bytes),
+            "Boot sector without partition table"
+        );
+        bytes[450] = 0xEE;
+        assert_eq!(describe_first_sectors(&bytes), "MBR partition table");
+        bytes[512..520].copy_from_slice(b"EFI PART");
+        assert_eq!(describe_first_sectors(&bytes), "GPT partition table");
+        assert_eq!(
+            describe_first_sectors(&bytes[..100]),
+            "Unreadable (device too small)"
+        );
+    }
+
+    #[test]
+    fn holder_description_names_process_and_node() {
+        let holder = DeviceHolder {
+            pid: 4242,
+            command: "gnome-disks".to_string(),
+            path: "/dev/sdb1".to_string(),
+        };
+        assert_eq!(holder.describe(), "gnome-disks (pid 4242) on /dev/sdb1");
+    }
+
+    #[test]
+    fn is_virtual_disk_filters_loop_and_zram() {
+        assert!(is_virtual_disk("loop0"));
+        assert!(is_virtual_disk("zram0"));
+        assert!(!is_virtual_disk("sdb"));
+        assert!(!is_virtual_disk("nvme0n1"));
+    }
+
+    #[test]
+    fn format_lsblk_size_matches_lsblk_style() {
+        assert_eq!(format_lsblk_size(8 * 1024 * 1024 * 1024), "8G");
+        assert_eq!(format_lsblk_size(61_530_439_680), "57.3G");
+        assert_eq!(format_lsblk_size(512), "512B");
+    }
+
+    #[test]
+    fn matches_path_accepts_kernel_and_stable_paths() {
+        let disk = Disk {
+            name: "sdb".to_string(),
+            vendor: String::new(),
+            model: String::new(),
+            size: String::new(),
+            serial: None,
+            by_id: Some("/dev/disk/by-id/usb-Stick".to_string()),
+            removable: true,
+        };
+        assert!(disk.matches_path("/dev/sdb"));
+        assert!(disk.matches_path("/dev/disk/by-id/usb-Stick"));
+        assert!(!disk.matches_path("/dev/sdc"));
+        assert_eq!(disk.stable_path(), "/dev/disk/by-id/usb-Stick");
+    }
+}
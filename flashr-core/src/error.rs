@@ -0,0 +1,210 @@
+//! Why a flash failed, as types a frontend can match on.
+//!
+//! `FlashError` covers the flash as a whole and `DeviceError` the checks on
+//! the target device. Failures without a kind of their own (a missing tool,
+//! an I/O error) are kept as `FlashError::Other`. Both types display the same
+//! messages the flash has always given, so printing them is unchanged.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::checksum::Algorithm;
+
+/// A target device that cannot or must not be written.
+#[derive(Debug)]
+pub enum DeviceError {
+    /// The path does not exist
+    NotFound {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A `/dev/disk/by-id` link whose disk was unplugged
+    Detached { path: String },
+    /// A symlink outside `/dev/disk/by-id`, with the device it points to
+    Symlink { path: String, target: PathBuf },
+    /// The path is not a block device
+    NotBlockDevice { path: String },
+    /// The device holds the running system's root filesystem
+    HoldsRoot,
+    /// Partitions are mounted and the wipe was not confirmed
+    Mounted { mountpoints: Vec<String> },
+    /// Other processes have the device open, described by name and pid
+    Busy { holders: Vec<String> },
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceError::NotFound { path, .. } => write!(f, "target device not found: {path}"),
+            DeviceError::Detached { path } => write!(f, "device {path} is no longer attached"),
+            DeviceError::Symlink { path, target } => write!(
+                f,
+                "target path {path} is a symlink to {}. Use the real device path instead.",
+                target.display()
+            ),
+            DeviceError::NotBlockDevice { path } => {
+                write!(f, "target is not a block device: {path}")
+            }
+            DeviceError::HoldsRoot => {
+                f.write_str("Refusing to flash device containing the root filesystem (/).")
+            }
+            DeviceError::Mounted { mountpoints } => {
+                let preview = mountpoints
+                    .iter()
+                    .take(4)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "Target device has mounted filesystems ({preview}). Unmount all partitions before flashing."
+                )
+            }
+            DeviceError::Busy { holders } => write!(
+                f,
+                "Target device is in use by {}. Close these programs before flashing.",
+                holders.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeviceError::NotFound { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A failed flash.
+#[derive(Debug)]
+pub enum FlashError {
+    /// The flash was cancelled or the program was told to stop
+    Cancelled,
+    /// The image has no partition table, so raw-writing it would not boot
+    NotHybrid,
+    /// The image could not be classified
+    UnknownImageType,
+    /// The image does not match the published checksum
+    ChecksumMismatch {
+        image: PathBuf,
+        algorithm: Algorithm,
+        expected: String,
+        actual: String,
+    },
+    /// The target device failed its checks
+    Device(DeviceError),
+    /// Not root, and neither `pkexec` nor `sudo` is installed
+    NoElevator,
+    /// `sudo` did not accept the password
+    AuthenticationFailed,
+    /// `dd` exited unsuccessfully
+    DdFailed(ExitStatus),
+    /// The device reads back differently from the image
+    VerificationMismatch,
+    /// Verification was required (`--verify`) but could not run
+    VerificationUnavailable(anyhow::Error),
+    /// The pre-flash hook refused the flash, or the post-flash hook could not
+    /// run or exited non-zero
+    HookFailed(anyhow::Error),
+    /// Any other failure
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for FlashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlashError::Cancelled => f.write_str("Flash cancelled"),
+            FlashError::NotHybrid => f.write_str("ISO has no partition table; hybrid ISO required"),
+            FlashError::UnknownImageType => f.write_str("Unable to determine ISO type"),
+            FlashError::ChecksumMismatch {
+                image,
+                algorithm,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {}: expected {} {expected}, got {actual}",
+                image.display(),
+                algorithm.name()
+            ),
+            FlashError::Device(err) => fmt::Display::fmt(err, f),
+            FlashError::NoElevator => f.write_str(
+                "Root privileges required for flashing. \
+                 Install pkexec or sudo, or run with: sudo flashr-tui --execute",
+            ),
+            FlashError::AuthenticationFailed => f.write_str("sudo authentication failed"),
+            FlashError::DdFailed(status) => write!(f, "dd failed ({status})"),
+            FlashError::VerificationMismatch => {
+                f.write_str("Verification failed: device content does not match source image")
+            }
+            FlashError::VerificationUnavailable(err) => {
+                write!(f, "Verification required but could not run: {err:#}")
+            }
+            FlashError::HookFailed(err) | FlashError::Other(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for FlashError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlashError::Device(err) => err.source(),
+            FlashError::HookFailed(err) | FlashError::Other(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<DeviceError> for FlashError {
+    fn from(err: DeviceError) -> Self {
+        FlashError::Device(err)
+    }
+}
+
+/// Keeps the kind of a `FlashError` or `DeviceError` that passed through
+/// `anyhow`; anything else becomes `Other`.
+impl From<anyhow::Error> for FlashError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<FlashError>() {
+            Ok(flash) => return flash,
+            Err(err) => err,
+        };
+        match err.downcast::<DeviceError>() {
+            Ok(device) => FlashError::Device(device),
+            Err(err) => FlashError::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anyhow_errors_keep_their_kind() {
+        let cancelled = anyhow::Error::new(FlashError::Cancelled);
+        assert!(matches!(FlashError::from(cancelled), FlashError::Cancelled));
+
+        let mounted = anyhow::Error::new(DeviceError::Mounted {
+            mountpoints: vec!["/media/a".to_string(), "/media/b".to_string()],
+        });
+        let err = FlashError::from(mounted);
+        assert!(matches!(
+            err,
+            FlashError::Device(DeviceError::Mounted { .. })
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Target device has mounted filesystems (/media/a, /media/b). \
+             Unmount all partitions before flashing."
+        );
+
+        let other = FlashError::from(anyhow::anyhow!("lsblk failed"));
+        assert!(matches!(other, FlashError::Other(_)));
+        assert_eq!(other.to_string(), "lsblk failed");
+    }
+}
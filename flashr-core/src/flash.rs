@@ -0,0 +1,1543 @@
+//! Flash ISO image to USB device with progress tracking.
+//!
+//! This module handles the actual flashing operation using `dd`, streams progress updates
+//! through an mpsc channel, and optionally labels the USB drive based on the ISO filename.
+//!
+//! When not running as root, privileged commands (`dd`, `partprobe`, labeling tools)
+//! are automatically wrapped with `pkexec` or `sudo` for privilege elevation.
+
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::backend::{BackendKind, FlashBackend, Target};
+use crate::checksum::{Algorithm, Checksum};
+use crate::device::{DevicePath, LsblkOutput};
+use crate::error::{DeviceError, FlashError};
+use crate::hook::{self, HookContext};
+use crate::inhibit::SleepInhibitor;
+use crate::iso::IsoKind;
+use crate::journal::{Journal, JournalFile};
+use crate::platform;
+use crate::signals;
+use crate::stage::StagedImage;
+
+/// Check if the current process is running as root (euid == 0).
+pub fn is_root() -> bool {
+    platform::current().is_root()
+}
+
+/// Prefix of the progress message naming the elevation tool in use.
+pub const ELEVATION_PREFIX: &str = "Elevation: ";
+
+/// Prefix of the progress message carrying the exact write command line.
+pub const COMMAND_PREFIX: &str = "Command: ";
+
+/// Prefix of the progress message announcing the next `FlashPhase`.
+pub const PHASE_PREFIX: &str = "Phase: ";
+
+/// Prefix of verification progress messages, followed by "done/total" bytes.
+pub const VERIFY_PREFIX: &str = "Verified: ";
+
+/// Prefix of the progress message carrying the image's digest, written like
+/// a `--checksum` spec ("sha256:<hex>").
+pub const IMAGE_HASH_PREFIX: &str = "Image hash: ";
+
+/// Prefix of the progress messages reporting the verification verdict.
+pub const VERDICT_PREFIX: &str = "Verification ";
+
+/// Prefix of the progress message naming the volume label that was applied.
+pub const LABEL_PREFIX: &str = "Label set to ";
+
+/// Prefix of the progress message sent before a resumed write, followed by
+/// the byte offset it starts at. The write's own byte counts start from
+/// there, not from the start of the image.
+pub const RESUME_PREFIX: &str = "Resuming at byte ";
+
+/// How often verification reports progress.
+pub(crate) const VERIFY_REPORT_INTERVAL: std::time::Duration =
+    std::time::Duration::from_millis(500);
+
+/// Phases of flashing one device, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FlashPhase {
+    /// Copying the image into `FlashOptions::stage_dir` (only when staging)
+    Stage,
+    /// The backend is copying the image to the device
+    Write,
+    /// Flushing buffered writes to the device
+    Sync,
+    /// Comparing checksums of the image and the device
+    Verify,
+    /// Re-reading the partition table and setting the volume label
+    PostFlash,
+}
+
+impl FlashPhase {
+    /// All phases, in the order they run.
+    pub const ALL: [FlashPhase; 5] = [
+        FlashPhase::Stage,
+        FlashPhase::Write,
+        FlashPhase::Sync,
+        FlashPhase::Verify,
+        FlashPhase::PostFlash,
+    ];
+
+    /// Short name, used in progress messages and on the progress bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            FlashPhase::Stage => "stage",
+            FlashPhase::Write => "write",
+            FlashPhase::Sync => "sync",
+            FlashPhase::Verify => "verify",
+            FlashPhase::PostFlash => "post-flash",
+        }
+    }
+
+    /// Parse a name produced by `name`.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|phase| phase.name() == name)
+    }
+}
+
+/// Announce the start of a flash phase.
+pub(crate) fn send_phase(progress: &mpsc::Sender<String>, phase: FlashPhase) {
+    debug!("phase: {}", phase.name());
+    let _ = progress.send(format!("{PHASE_PREFIX}{}", phase.name()));
+}
+
+/// Block size written when `FlashOptions::block_size` is unset.
+pub const DEFAULT_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Choices for a flash that come from the user rather than the image or device.
+///
+/// # Fields
+///
+/// * `confirmed_wipe` - The user agreed to overwrite existing partitions
+/// * `require_verification` - Fail when the read-back verification cannot run,
+///   instead of reporting it as skipped (`--verify`)
+/// * `checksum` - Published checksum the image must match before writing (`--checksum`)
+/// * `label` - Volume label to apply instead of one derived from the image name (`--label`)
+/// * `post_hook` - Script to run after a successful flash (`--post-hook`, see `hook`)
+/// * `pre_hook` - Script that may refuse the flash right before writing (see `hook`)
+/// * `label_hook` - Script that picks the volume label when `label` is unset (see `hook`)
+/// * `hash` - Algorithm the read-back verification compares digests with (`--hash`)
+/// * `backend` - How the image is written (`--backend`, see `backend`)
+/// * `block_size` - Bytes per `dd` block or native write; `DEFAULT_BLOCK_SIZE` when unset
+/// * `journal_dir` - Directory to journal the write offset in, for resuming
+///   after a crash (see `journal`)
+/// * `resume` - Continue from the journaled offset when the journal in
+///   `journal_dir` is for this image and device (`--resume`)
+/// * `stage_dir` - Directory (normally a tmpfs) to copy the image into before
+///   writing, so a slow source does not stall the write (`--stage`, see `stage`)
+/// * `cancel` - Set to stop the flash early
+#[derive(Debug, Clone, Default)]
+pub struct FlashOptions {
+    pub confirmed_wipe: bool,
+    pub require_verification: bool,
+    pub checksum: Option<Checksum>,
+    pub label: Option<String>,
+    pub post_hook: Option<PathBuf>,
+    pub pre_hook: Option<PathBuf>,
+    pub label_hook: Option<PathBuf>,
+    pub hash: Algorithm,
+    pub backend: BackendKind,
+    pub block_size: Option<u64>,
+    pub journal_dir: Option<PathBuf>,
+    pub resume: bool,
+    pub stage_dir: Option<PathBuf>,
+    pub cancel: CancelFlag,
+}
+
+impl FlashOptions {
+    /// Bytes written per block: `block_size`, or `DEFAULT_BLOCK_SIZE`.
+    pub fn block_size(&self) -> u64 {
+        self.block_size.unwrap_or(DEFAULT_BLOCK_SIZE)
+    }
+}
+
+/// Request to stop a running flash, shared between its clones.
+///
+/// The flash checks it between phases and while copying or verifying; the
+/// backend then stops, the device is synced, and the flash fails with "Flash
+/// cancelled". A cancelled flash leaves the device partly written. Every flag
+/// also reads as cancelled once SIGINT, SIGTERM, or SIGHUP has arrived (see
+/// `signals`).
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    /// Ask the flash to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called or the program was told to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed) || signals::received().is_some()
+    }
+
+    pub(crate) fn check(&self) -> Result<(), FlashError> {
+        if self.is_cancelled() {
+            return Err(FlashError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a verification progress message into (done, total) bytes.
+pub fn parse_verify_progress(line: &str) -> Option<(u64, u64)> {
+    let (done, total) = line.strip_prefix(VERIFY_PREFIX)?.split_once('/')?;
+    Some((done.parse().ok()?, total.parse().ok()?))
+}
+
+/// Find an available privilege elevation tool.
+///
+/// Checks for `sudo` first (terminal prompt with credential caching),
+/// then falls back to `pkexec` (graphical prompt, no caching).
+/// `sudo` is preferred because its credential cache (default 15 minutes)
+/// avoids repeated password prompts across multiple commands.
+///
+/// # Returns
+///
+/// `Some("sudo")` or `Some("pkexec")` if found, `None` if neither is available.
+pub fn find_elevator() -> Option<&'static str> {
+    ["sudo", "pkexec"]
+        .into_iter()
+        .find(|tool| which::which(tool).is_ok())
+}
+
+/// Check if the `isohybrid` tool (from syslinux) is available.
+pub fn has_isohybrid() -> bool {
+    which::which("isohybrid").is_ok()
+}
+
+/// Convert an ISO to hybrid format using the `isohybrid` tool.
+///
+/// This modifies the ISO file in-place by adding an MBR partition table,
+/// making it suitable for raw-writing to USB drives.
+pub fn convert_isohybrid(image: &Path) -> Result<()> {
+    let status = Command::new("isohybrid")
+        .arg(image)
+        .status()
+        .context("run isohybrid")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("isohybrid conversion failed"));
+    }
+    Ok(())
+}
+
+/// Resolve the privilege elevation tool for a privileged device operation.
+///
+/// Returns `None` when already running as root. Otherwise finds `sudo` or
+/// `pkexec`, reports the choice through `progress`, and primes the sudo
+/// credential cache so the user is prompted at most once per operation.
+///
+/// # Errors
+///
+/// Returns `FlashError::NoElevator` if no elevator is installed, or
+/// `FlashError::AuthenticationFailed` if sudo authentication fails.
+pub fn acquire_elevator(
+    progress: &mpsc::Sender<String>,
+) -> Result<Option<&'static str>, FlashError> {
+    if is_root() {
+        debug!("running as root; no elevation needed");
+        return Ok(None);
+    }
+    let elev = find_elevator().ok_or(FlashError::NoElevator)?;
+    info!("elevating with {elev}");
+    let _ = progress.send(format!(
+        "Not running as root; using '{}' for privilege elevation",
+        elev
+    ));
+    // Prime the credential cache so the user only enters their password
+    // once. `sudo -v` validates credentials without running a command;
+    // subsequent sudo calls within the timeout window (default 15 min)
+    // won't re-prompt.
+    if elev == "sudo" {
+        let _ = progress.send("Requesting sudo access...".to_string());
+        let prime = Command::new("sudo")
+            .arg("-v")
+            .status()
+            .context("failed to obtain sudo credentials")?;
+        if !prime.success() {
+            warn!("sudo -v failed ({prime})");
+            return Err(FlashError::AuthenticationFailed);
+        }
+    }
+    Ok(Some(elev))
+}
+
+/// Build a `Command` that runs a program with privilege elevation if needed.
+///
+/// If already root, returns `Command::new(program)` directly.
+/// If not root, wraps the command with the given elevator (e.g., `pkexec` or `sudo`).
+///
+/// # Arguments
+///
+/// * `program` - The program to run (e.g., "dd", "partprobe")
+/// * `elevator` - Optional elevator tool name (e.g., "pkexec", "sudo")
+///
+/// # Returns
+///
+/// A `Command` ready for argument addition and execution.
+pub fn elevated_command(program: &str, elevator: Option<&str>) -> Command {
+    match elevator {
+        Some(elev) if !is_root() => {
+            let mut cmd = Command::new(elev);
+            cmd.arg(program);
+            cmd
+        }
+        _ => Command::new(program),
+    }
+}
+
+/// Stop a running `child` started through `elevated_command`.
+///
+/// SIGTERM rather than SIGKILL, so sudo passes it on to the command it runs.
+/// An elevated process may refuse signals from an unprivileged one; `kill`
+/// is then run elevated, as a last resort `Child::kill`.
+pub(crate) fn terminate(child: &mut std::process::Child, elevator: Option<&str>) {
+    let pid = nix::unistd::Pid::from_raw(child.id() as i32);
+    if nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGTERM).is_ok() {
+        return;
+    }
+    let killed = elevated_command("kill", elevator)
+        .arg(child.id().to_string())
+        .stdin(std::process::Stdio::null())
+        .status();
+    if !killed.is_ok_and(|status| status.success()) {
+        let _ = child.kill();
+    }
+}
+
+/// Flash an ISO image to a USB device with live progress streaming.
+///
+/// This function:
+/// 1. Validates that the ISO is hybrid (safe to raw-write)
+/// 2. Keeps the system from sleeping (see `inhibit`) and spawns a `dd`
+///    process to copy the image to the device
+/// 3. Reads progress lines from `dd` stderr and sends them via the progress channel
+/// 4. Waits for `dd` to complete and validates success
+/// 5. Refreshes the kernel's partition table with `partprobe`
+/// 6. Attempts to label the device based on ISO filename
+///
+/// When not running as root, `dd` and post-flash commands are automatically
+/// elevated via `pkexec` or `sudo`.
+///
+/// # Arguments
+///
+/// * `image` - Path to the ISO file
+/// * `device` - Device path (e.g., "/dev/sdb") or stable `/dev/disk/by-id/...` link
+/// * `progress` - Channel to send progress messages to
+/// * `options` - Wipe confirmation and verification requirements
+///
+/// # Returns
+///
+/// `Ok(())` if flash succeeded, `Err` if any step failed.
+///
+/// # Errors
+///
+/// Returns a `FlashError` saying which step failed, e.g. when:
+/// - ISO is NonHybrid or type cannot be determined
+/// - The image does not match `options.checksum`
+/// - The device is missing, mounted, or in use (`FlashError::Device`)
+/// - No privilege elevation tool is available when not running as root
+/// - `dd` command fails to execute or returns non-zero
+/// - Reading progress from `dd` fails
+/// - The read-back verification finds a mismatch
+/// - The flash is cancelled through `options.cancel`
+/// - The post-flash hook fails
+///
+/// # Note
+///
+/// Labeling failures are non-fatal and reported in progress messages but don't cause the function to fail.
+/// So is an image that cannot be staged in `options.stage_dir`; it is then read from where it is.
+pub fn flash_image_with_progress(
+    image: &Path,
+    device: &str,
+    progress: mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<(), FlashError> {
+    info!(
+        "flashing {} to {device} with the {} backend",
+        image.display(),
+        options.backend.name()
+    );
+    let result = flash_checked(image, device, &progress, options);
+    match &result {
+        Ok(()) => info!("flash of {} to {device} finished", image.display()),
+        Err(err) => error!("flash of {} to {device} failed: {err:#}", image.display()),
+    }
+    result
+}
+
+/// The checks, write, and post-flash hook of `flash_image_with_progress`.
+fn flash_checked(
+    image: &Path,
+    device: &str,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<(), FlashError> {
+    match crate::iso::detect(image)? {
+        IsoKind::Hybrid => {}
+        IsoKind::NonHybrid => return Err(FlashError::NotHybrid),
+        IsoKind::Unknown => return Err(FlashError::UnknownImageType),
+    }
+
+    // Resolve the stable by-id link (if any) to the disk's current kernel name
+    // and validate it (symlink, block device), then check mount safety separately.
+    let device_path = DevicePath::resolve(device)?;
+    ensure_device_safe(device_path.as_str(), options.confirmed_wipe)?;
+    ensure_device_not_held(device_path.as_str())?;
+    options.cancel.check()?;
+
+    // Held until the flash returns; a suspend mid-write corrupts the stick.
+    let _awake = stay_awake(&format!("Flashing {}", image.display()), progress);
+
+    // Everything that reads the image reads the staged copy; the journal and
+    // the hooks still name the image the user picked.
+    let staged = options
+        .stage_dir
+        .as_deref()
+        .and_then(|dir| stage_image(image, dir, progress, options));
+    options.cancel.check()?;
+    let source = staged.as_ref().map_or(image, StagedImage::path);
+
+    if let Some(checksum) = &options.checksum {
+        let _ = progress.send("Checking image checksum...".to_string());
+        checksum.verify(source)?;
+        let _ = progress.send("Image checksum matches.".to_string());
+    }
+
+    let labeled;
+    let options = match (&options.label_hook, &options.label) {
+        (Some(script), None) => {
+            let derived = label_base(image, None);
+            match hook::compute_label(script, image, device_path.as_str(), &derived, progress) {
+                Ok(Some(label)) => {
+                    labeled = FlashOptions {
+                        label: Some(label),
+                        ..options.clone()
+                    };
+                    &labeled
+                }
+                Ok(None) => options,
+                // Like labeling itself, a failing label hook does not fail the flash.
+                Err(err) => {
+                    warn!("label hook: {err:#}");
+                    let _ = progress.send(format!("{err:#}; keeping the label {derived}"));
+                    options
+                }
+            }
+        }
+        _ => options,
+    };
+    if let Some(script) = &options.pre_hook {
+        hook::run_pre_hook(script, image, device_path.as_str(), progress)
+            .map_err(FlashError::HookFailed)?;
+    }
+
+    let mut backend = options.backend.create();
+
+    let journal = options
+        .journal_dir
+        .as_deref()
+        .and_then(|dir| open_journal(dir, image, device, options));
+    let target = Target {
+        image: source,
+        device: device_path.as_str(),
+        options,
+        resume_from: journal.as_ref().map_or(0, |(_, offset)| *offset),
+        journal: journal.as_ref().map(|(journal, _)| journal),
+    };
+    let flashed = run_backend(backend.as_mut(), &target, progress)?;
+
+    if let Some(script) = &options.post_hook {
+        let sha256 = match flashed.image_hash {
+            Some(hash) if options.hash == Algorithm::Sha256 => hash,
+            _ => Algorithm::Sha256.hash_file(source)?,
+        };
+        let context = HookContext {
+            image,
+            device: target.device,
+            label: flashed.label.as_deref(),
+            sha256: &sha256,
+        };
+        hook::run_post_hook(script, &context, progress).map_err(FlashError::HookFailed)?;
+    }
+
+    Ok(())
+}
+
+/// Keep the machine from sleeping for as long as the returned lock is held,
+/// or say why it cannot.
+pub(crate) fn stay_awake(why: &str, progress: &mpsc::Sender<String>) -> Option<SleepInhibitor> {
+    match SleepInhibitor::acquire(why) {
+        Ok(lock) => Some(lock),
+        Err(err) => {
+            let _ = progress.send(format!("Could not prevent sleep while flashing: {err:#}"));
+            None
+        }
+    }
+}
+
+/// Copy `image` into `dir` as the `FlashPhase::Stage` of a flash, or say why
+/// not and return `None` to read it from where it is.
+fn stage_image(
+    image: &Path,
+    dir: &Path,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Option<StagedImage> {
+    send_phase(progress, FlashPhase::Stage);
+    let _ = progress.send(format!("Copying the image to {}...", dir.display()));
+    match StagedImage::create(image, dir, progress, &options.cancel) {
+        Ok(staged) => Some(staged),
+        Err(err) => {
+            warn!("not staging {}: {err:#}", image.display());
+            let _ = progress.send(format!(
+                "Not staging the image ({err:#}); reading it from {}",
+                image.display()
+            ));
+            None
+        }
+    }
+}
+
+/// The journal for writing `image` to `device` (as given, so a by-id link
+/// keeps naming the same stick), and the offset to start at.
+///
+/// A journal that cannot be written is logged and the flash goes ahead
+/// without one.
+fn open_journal(
+    dir: &Path,
+    image: &Path,
+    device: &str,
+    options: &FlashOptions,
+) -> Option<(JournalFile, u64)> {
+    let path = Journal::path_for(dir, device);
+    let resume_from = match Journal::load(&path) {
+        Ok(Some(journal)) if options.resume => journal
+            .resume_offset(image, device, options.block_size())
+            .unwrap_or(0),
+        Ok(_) => 0,
+        Err(err) => {
+            warn!("ignoring journal: {err:#}");
+            0
+        }
+    };
+    let opened = Journal::new(image, device, resume_from)
+        .and_then(|journal| JournalFile::create(path, journal));
+    match opened {
+        Ok(journal) => Some((journal, resume_from)),
+        Err(err) => {
+            warn!("flashing without a journal: {err:#}");
+            None
+        }
+    }
+}
+
+/// What a successful `run_backend` learned along the way.
+///
+/// # Fields
+///
+/// * `image_hash` - Image digest from the verification, if it ran
+/// * `label` - Volume label that was applied, if any
+#[derive(Debug, Default)]
+struct Flashed {
+    image_hash: Option<String>,
+    label: Option<String>,
+}
+
+/// Drive `backend` through its steps for one device, announcing each
+/// `FlashPhase` and syncing between the write and the verification.
+fn run_backend(
+    backend: &mut dyn FlashBackend,
+    target: &Target,
+    progress: &mpsc::Sender<String>,
+) -> Result<Flashed, FlashError> {
+    let options = target.options;
+    backend.prepare(target, progress)?;
+    options.cancel.check()?;
+
+    send_phase(progress, FlashPhase::Write);
+    if target.resume_from > 0 {
+        info!("{}: resuming at byte {}", target.device, target.resume_from);
+        let _ = progress.send(format!("{RESUME_PREFIX}{}", target.resume_from));
+    }
+    let written = backend.write(target, progress);
+    if let Some(journal) = target.journal {
+        // A finished write has nothing left to resume; anything else keeps
+        // the last offset for `--resume`.
+        match written {
+            Ok(()) => journal.remove(),
+            Err(_) => journal.save(),
+        }
+    }
+    written?;
+
+    send_phase(progress, FlashPhase::Sync);
+    let _ = progress.send("Syncing...".to_string());
+    Command::new("sync").status().ok();
+
+    // Verify flash integrity before labeling (labeling modifies the device).
+    send_phase(progress, FlashPhase::Verify);
+    let _ = progress.send("Verifying flash integrity...".to_string());
+    let mut flashed = Flashed::default();
+    let verified = backend.verify(target, progress);
+    options.cancel.check()?;
+    match verified {
+        Ok((hash, true)) => {
+            info!("{}: verification passed ({hash})", target.device);
+            flashed.image_hash = Some(hash);
+            let _ = progress.send(format!(
+                "{VERDICT_PREFIX}passed: {} checksums match.",
+                options.hash.label()
+            ));
+        }
+        Ok((_, false)) => return Err(FlashError::VerificationMismatch),
+        Err(e) if options.require_verification => {
+            return Err(FlashError::VerificationUnavailable(e));
+        }
+        Err(e) => {
+            warn!("{}: verification skipped: {e:#}", target.device);
+            let _ = progress.send(format!("{VERDICT_PREFIX}skipped: {e}"));
+        }
+    }
+
+    // Labeling failures are reported but do not fail the flash.
+    send_phase(progress, FlashPhase::PostFlash);
+    if let Ok(Some(message)) = backend.finalize(target, progress) {
+        flashed.label = message.strip_prefix(LABEL_PREFIX).map(str::to_string);
+        let _ = progress.send(message);
+    }
+    Ok(flashed)
+}
+
+/// Render a command as a shell-style line for display, quoting arguments
+/// that contain spaces or quotes.
+pub fn describe_command(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|part| {
+            let part = part.to_string_lossy();
+            if part.contains([' ', '\'', '"']) {
+                format!("'{}'", part.replace('\'', "'\\''"))
+            } else {
+                part.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Characters locales put between groups of three digits.
+const DIGIT_GROUP_SEPARATORS: [char; 6] = [',', '.', '\'', ' ', '\u{a0}', '\u{202f}'];
+
+/// Parse byte count from a dd progress line.
+///
+/// GNU `dd` prints the byte count first, followed by a word that depends on
+/// the locale, in both its progress and its final format:
+///
+/// - `"1234567890 bytes (1.2 GB, 1.1 GiB) copied, 5 s, 247 MB/s"`
+/// - `"1234567890 bytes (1.2 GB) copied, 5.00123 s, 247 MB/s"` (before coreutils 8.24)
+/// - `"1.234.567.890 Bytes (1,2 GB, 1,1 GiB) kopiert, 5 s, 247 MB/s"` (digits
+///   grouped by the locale)
+///
+/// A line that starts with a human-readable size (`"1.2 GiB copied, ..."`)
+/// gives that size in bytes, rounded by the unit. The "N+M records in/out"
+/// lines of the final summary are not byte counts.
+///
+/// # Arguments
+///
+/// * `line` - A line from dd's progress output
+///
+/// # Returns
+///
+/// `Some(bytes)` if line starts with a byte count or a size, `None` otherwise.
+pub fn parse_dd_bytes(line: &str) -> Option<u64> {
+    let line = line.trim_start();
+    let (number, rest) = split_leading_number(line)?;
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace());
+    // "2+0 records in"
+    if rest.starts_with('+') {
+        return None;
+    }
+    let word: String = rest.chars().take_while(|c| c.is_alphabetic()).collect();
+    if let Some(unit) = unit_bytes(&word) {
+        let value: f64 = number.replace(',', ".").parse().ok()?;
+        return Some((value * unit as f64).round() as u64);
+    }
+    if word.is_empty() {
+        return None;
+    }
+    // Any other word is "bytes" in the locale's language; a byte count is
+    // whole, so separators in it only group digits.
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Split off the number a line starts with: digits, optionally grouped by one
+/// of `DIGIT_GROUP_SEPARATORS`, or with a decimal point or comma.
+///
+/// The number is returned as written, for the caller to read as a count or
+/// a size once it knows which one follows.
+fn split_leading_number(line: &str) -> Option<(&str, &str)> {
+    let digits_at = |start: usize| line[start..].bytes().take_while(u8::is_ascii_digit).count();
+    let mut end = digits_at(0);
+    if end == 0 {
+        return None;
+    }
+    while let Some(sep) = line[end..]
+        .chars()
+        .next()
+        .filter(|c| DIGIT_GROUP_SEPARATORS.contains(c))
+    {
+        let after = end + sep.len_utf8();
+        let run = digits_at(after);
+        // A group has exactly three digits; a shorter or longer run after
+        // '.' or ',' is a decimal part and ends the number.
+        if run == 3 {
+            end = after + run;
+        } else if run > 0 && (sep == '.' || sep == ',') {
+            end = after + run;
+            break;
+        } else {
+            break;
+        }
+    }
+    Some(line.split_at(end))
+}
+
+/// Bytes per unit of a human-readable size, as `dd` writes them (SI units
+/// for "kB", "MB", ..., binary ones for "KiB", "MiB", ... and the single letters).
+fn unit_bytes(unit: &str) -> Option<u64> {
+    let exponent = match unit.chars().next()? {
+        'B' if unit.len() == 1 => return Some(1),
+        'k' | 'K' => 1,
+        'M' => 2,
+        'G' => 3,
+        'T' => 4,
+        'P' => 5,
+        _ => return None,
+    };
+    let base: u64 = match &unit[1..] {
+        "B" => 1000,
+        "iB" | "" => 1024,
+        _ => return None,
+    };
+    Some(base.pow(exponent))
+}
+
+/// Parse the transfer rate from a dd progress line.
+///
+/// `dd status=progress` ends each line with the current rate, e.g.
+/// `"... copied, 5 s, 24.7 MB/s"`.
+///
+/// # Returns
+///
+/// `Some("24.7 MB/s")` if the line ends with a rate, `None` otherwise.
+pub fn parse_dd_rate(line: &str) -> Option<String> {
+    let rate = line.rsplit(", ").next()?.trim();
+    if rate.ends_with("/s") && rate.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(rate.to_string())
+    } else {
+        None
+    }
+}
+
+/// Perform post-flash privileged operations using direct command invocations.
+///
+/// Runs `partprobe` to refresh the kernel partition table, then attempts to
+/// label the USB partition with `label_base` (see `label_base()`). Each tool is
+/// invoked directly via `Command` (no shell interpretation) to avoid injection risks.
+pub(crate) fn label_device_post_flash(
+    label_base: &str,
+    device: &str,
+    elevator: Option<&str>,
+) -> Result<Option<String>> {
+    // Run partprobe directly (no shell).
+    let _ = platform::current()
+        .reread_partitions(device, elevator)
+        .status()
+        .context("run partprobe")?;
+
+    // Try to discover partitions and apply a label.
+    if let Some((label, tool, args)) = resolve_label_command(label_base, device) {
+        let status = elevated_command(&tool, elevator)
+            .args(&args)
+            .status();
+        return match status {
+            Ok(s) if s.success() => Ok(Some(format!("{LABEL_PREFIX}{label}"))),
+            Ok(_) => Ok(Some("Labeling failed".to_string())),
+            Err(_) => Ok(Some("Labeling tool not available".to_string())),
+        };
+    }
+
+    // If lsblk didn't show partitions yet, retry now that partprobe has run.
+    if !label_base.is_empty() {
+        if let Some((label, tool, args)) = resolve_label_command(label_base, device) {
+            let status = elevated_command(&tool, elevator)
+                .args(&args)
+                .status();
+            return match status {
+                Ok(s) if s.success() => Ok(Some(format!("{LABEL_PREFIX}{label}"))),
+                Ok(_) => Ok(Some("Labeling failed".to_string())),
+                Err(_) => Ok(Some("Labeling tool not available".to_string())),
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+/// The label to apply after flashing, before per-filesystem truncation:
+/// the explicit `label` if one was given, otherwise the image's file stem,
+/// sanitized either way.
+pub(crate) fn label_base(image: &Path, label: Option<&str>) -> String {
+    label
+        .or_else(|| image.file_stem().and_then(|s| s.to_str()))
+        .map(sanitize_label)
+        .unwrap_or_default()
+}
+
+/// Resolve the label tool, args, and label string for a device partition.
+///
+/// Runs an unprivileged `lsblk` to discover the first partition with a
+/// supported filesystem and returns the label command to apply.
+fn resolve_label_command(label_base: &str, device: &str) -> Option<(String, String, Vec<String>)> {
+    if label_base.is_empty() {
+        return None;
+    }
+
+    let output = platform::current()
+        .lsblk()
+        .args(["--json", "-o", "NAME,FSTYPE,TYPE", "-p", device])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let parsed: LsblkOutput = serde_json::from_slice(&output.stdout).ok()?;
+
+    for dev in parsed.blockdevices {
+        if dev.r#type == "disk" {
+            for child in dev.children {
+                if let Some(fstype) = child.fstype.clone() {
+                    if is_supported_fstype(&fstype) {
+                        let (label, tool, args) = label_command(&child.name, &fstype, label_base);
+                        return Some((label, tool.to_string(), args));
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Information about existing partitions on a device.
+///
+/// Used to warn the user before wiping a device that already has partitions,
+/// filesystems, or a bootable operating system.
+#[derive(Debug, Clone, Default)]
+pub struct DevicePartitionInfo {
+    /// Whether the device has any partitions
+    pub has_partitions: bool,
+    /// Human-readable list of partition details (e.g., "/dev/sdb1 ext4 50G")
+    pub partition_details: Vec<String>,
+    /// Whether any partitions are currently mounted
+    pub has_mounted: bool,
+    /// Mountpoints that are currently in use
+    pub mounted_paths: Vec<String>,
+}
+
+impl DevicePartitionInfo {
+    /// Fold another device's partition info into this one (used for multi-device targets).
+    pub fn merge(&mut self, other: DevicePartitionInfo) {
+        self.has_partitions |= other.has_partitions;
+        self.has_mounted |= other.has_mounted;
+        self.partition_details.extend(other.partition_details);
+        self.mounted_paths.extend(other.mounted_paths);
+    }
+}
+
+/// Check whether a device has existing partitions, filesystems, or mounted volumes.
+///
+/// This is used before flashing to warn the user that the target device already
+/// contains data (e.g., a bootable OS) and give them a chance to confirm or cancel.
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb")
+///
+/// # Returns
+///
+/// `Ok(DevicePartitionInfo)` with details about what was found, or an error if
+/// `lsblk` cannot be run.
+pub fn check_device_partitions(device: &str) -> Result<DevicePartitionInfo> {
+    let output = platform::current()
+        .lsblk()
+        .args([
+            "--json",
+            "-o",
+            "NAME,TYPE,FSTYPE,SIZE,MOUNTPOINT,MOUNTPOINTS",
+            "-p",
+            device,
+        ])
+        .output()
+        .context("run lsblk to check partitions")?;
+
+    if !output.status.success() {
+        return Ok(DevicePartitionInfo {
+            has_partitions: false,
+            partition_details: Vec::new(),
+            has_mounted: false,
+            mounted_paths: Vec::new(),
+        });
+    }
+
+    let parsed: LsblkOutput =
+        serde_json::from_slice(&output.stdout).context("parse lsblk partition info")?;
+
+    let mut partition_details = Vec::new();
+    let mut mounted_paths = Vec::new();
+
+    for dev in &parsed.blockdevices {
+        for child in &dev.children {
+            if child.r#type == "part" {
+                let fstype = child.fstype.as_deref().unwrap_or("unknown");
+                let size = child.size.as_deref().unwrap_or("?");
+                partition_details.push(format!("{} ({}, {})", child.name, fstype, size));
+
+                // Collect mountpoints
+                if let Some(mp) = &child.mountpoint {
+                    if !mp.is_empty() && mp != "[SWAP]" {
+                        mounted_paths.push(mp.clone());
+                    }
+                }
+                if let Some(list) = &child.mountpoints {
+                    for item in list.iter().flatten() {
+                        if !item.is_empty() && item != "[SWAP]" && !mounted_paths.contains(item) {
+                            mounted_paths.push(item.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let has_partitions = !partition_details.is_empty();
+    let has_mounted = !mounted_paths.is_empty();
+
+    Ok(DevicePartitionInfo {
+        has_partitions,
+        partition_details,
+        has_mounted,
+        mounted_paths,
+    })
+}
+
+/// Check mount safety for a device whose path has already been validated
+/// by `DevicePath::validate()` (symlink + block device checks).
+///
+/// # Errors
+///
+/// Returns `DeviceError::HoldsRoot` for the system disk and
+/// `DeviceError::Mounted` for mounted partitions without `user_confirmed_wipe`,
+/// or `FlashError::Other` if `lsblk` fails.
+pub fn ensure_device_safe(device: &str, user_confirmed_wipe: bool) -> Result<(), FlashError> {
+    let output = platform::current()
+        .lsblk()
+        .args([
+            "--json",
+            "-o",
+            "NAME,TYPE,MOUNTPOINT,MOUNTPOINTS,FSTYPE",
+            "-p",
+            device,
+        ])
+        .output()
+        .context("run lsblk for mount safety checks")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let message = if stderr.is_empty() {
+            "lsblk failed for mount safety checks".to_string()
+        } else {
+            format!("lsblk failed for mount safety checks: {stderr}")
+        };
+        return Err(FlashError::Other(anyhow::anyhow!(message)));
+    }
+
+    let parsed: LsblkOutput =
+        serde_json::from_slice(&output.stdout).context("parse lsblk mount safety output")?;
+
+    let mut mounts = Vec::new();
+    for dev in &parsed.blockdevices {
+        collect_mountpoints(dev, &mut mounts);
+    }
+
+    if mounts.iter().any(|m| m == "/") {
+        return Err(DeviceError::HoldsRoot.into());
+    }
+
+    // If the user confirmed the wipe, mounted partitions are OK -- they will
+    // be unmounted by wipe_device_if_needed(). Otherwise, block and ask the
+    // user to unmount manually.
+    if !user_confirmed_wipe && !mounts.is_empty() {
+        return Err(DeviceError::Mounted {
+            mountpoints: mounts,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Refuse to write a device that another process has open.
+///
+/// A concurrent writer (a stuck `dd`, GNOME Disks, a VM) would silently
+/// corrupt the image, so the offenders are named in the error.
+pub(crate) fn ensure_device_not_held(device: &str) -> Result<(), DeviceError> {
+    let holders = crate::device::holders(device);
+    if holders.is_empty() {
+        return Ok(());
+    }
+    Err(DeviceError::Busy {
+        holders: holders.iter().map(|h| h.describe()).collect(),
+    })
+}
+
+/// Get `target`'s device ready for the write: wipe its old partitions, or,
+/// when resuming, only unmount the ones the partly written image brought up.
+pub(crate) fn clear_device(
+    target: &Target,
+    elevator: Option<&str>,
+    progress: &mpsc::Sender<String>,
+) -> Result<()> {
+    if target.resume_from == 0 {
+        return wipe_device_if_needed(target.device, elevator, progress);
+    }
+    unmount_partitions(&list_partitions(target.device)?, elevator);
+    Ok(())
+}
+
+pub(crate) fn wipe_device_if_needed(
+    device: &str,
+    elevator: Option<&str>,
+    progress: &mpsc::Sender<String>,
+) -> Result<()> {
+    let partitions = list_partitions(device)?;
+    if partitions.is_empty() {
+        return Ok(());
+    }
+
+    let _ = progress.send("Device has existing partitions, wiping...".to_string());
+
+    // Unmount each partition, then wipefs and partprobe, each as a direct
+    // command invocation with no shell interpretation.
+    unmount_partitions(&partitions, elevator);
+
+    let platform = platform::current();
+    platform
+        .wipe_signatures(device, elevator)
+        .status()
+        .context("wipefs failed")?;
+
+    platform
+        .reread_partitions(device, elevator)
+        .status()
+        .context("partprobe failed after wipe")?;
+
+    let _ = progress.send("Device wiped successfully.".to_string());
+
+    Ok(())
+}
+
+/// List the full paths of a device's partitions (e.g., "/dev/sdb1").
+///
+/// Returns an empty list if `lsblk` fails for the device.
+pub fn list_partitions(device: &str) -> Result<Vec<String>> {
+    let output = platform::current()
+        .lsblk()
+        .args(["--json", "-o", "NAME,TYPE", "-p", device])
+        .output()
+        .context("run lsblk to check partitions")?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let parsed: LsblkOutput =
+        serde_json::from_slice(&output.stdout).context("parse lsblk partition output")?;
+
+    // `-p` makes lsblk report full paths, so names are used as-is.
+    Ok(parsed
+        .blockdevices
+        .into_iter()
+        .flat_map(|dev| dev.children)
+        .filter(|c| c.r#type == "part")
+        .map(|c| c.name)
+        .collect())
+}
+
+/// Unmount each partition (failures are OK — a partition may not be mounted).
+///
+/// When running unprivileged, UDisks2 is tried first so the session's
+/// automounter sees the unmount and no extra auth prompt appears; the elevated
+/// `umount` is the fallback.
+pub fn unmount_partitions(partitions: &[String], elevator: Option<&str>) {
+    let use_udisks = crate::udisks::available();
+    for partition in partitions {
+        if use_udisks && crate::udisks::unmount(partition).is_ok() {
+            continue;
+        }
+        let _ = elevated_command("umount", elevator)
+            .arg(partition)
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// Verify flash integrity by comparing digests (with `options.hash`) of the
+/// source image and the bytes read back from `device`.
+///
+/// Reads as many bytes from `device` as the image has, and stops early when
+/// the flash is cancelled; stopping whatever feeds `device` is up to the
+/// caller.
+///
+/// # Returns
+///
+/// The image's hex digest and whether the device content matches it.
+pub(crate) fn verify_image(
+    image: &Path,
+    device: &mut dyn Read,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<(String, bool)> {
+    let cancel = &options.cancel;
+    let iso_size = std::fs::metadata(image)
+        .with_context(|| format!("read image size: {}", image.display()))?
+        .len();
+
+    let _ = progress.send("Verifying: hashing source image...".to_string());
+
+    // Both the image and the device are read in full, so progress counts each
+    // byte twice.
+    let total = iso_size * 2;
+    let mut done = 0;
+    let mut last_report = std::time::Instant::now();
+    let mut report = |done: u64| {
+        if last_report.elapsed() >= VERIFY_REPORT_INTERVAL {
+            last_report = std::time::Instant::now();
+            let _ = progress.send(format!("{VERIFY_PREFIX}{done}/{total}"));
+        }
+    };
+
+    // Hash the source ISO file.
+    let mut hasher = options.hash.hasher()?;
+    let mut file =
+        std::fs::File::open(image).with_context(|| format!("open ISO: {}", image.display()))?;
+    let mut buf = [0u8; 1024 * 1024]; // 1 MB chunks
+    let mut remaining = iso_size;
+    while remaining > 0 {
+        let to_read = std::cmp::min(remaining as usize, buf.len());
+        let n = file.read(&mut buf[..to_read]).context("read ISO for hash")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n])?;
+        remaining -= n as u64;
+        done += n as u64;
+        report(done);
+        cancel.check()?;
+    }
+    let source_hex = hasher.finish()?;
+    let _ = progress.send(format!(
+        "{IMAGE_HASH_PREFIX}{}:{source_hex}",
+        options.hash.name()
+    ));
+
+    let _ = progress.send("Verifying: reading back from device...".to_string());
+
+    // Read the same number of bytes back from the device and hash them.
+    let mut hasher = options.hash.hasher()?;
+    let mut remaining = iso_size;
+    while remaining > 0 {
+        let to_read = std::cmp::min(remaining as usize, buf.len());
+        let n = device
+            .read(&mut buf[..to_read])
+            .context("read device for hash")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n])?;
+        remaining -= n as u64;
+        done += n as u64;
+        report(done);
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+    let device_hex = hasher.finish()?;
+
+    let matches = source_hex == device_hex;
+    Ok((source_hex, matches))
+}
+
+fn collect_mountpoints(dev: &crate::device::LsblkDevice, mounts: &mut Vec<String>) {
+    if let Some(mp) = &dev.mountpoint {
+        if !mp.is_empty() && mp != "[SWAP]" {
+            mounts.push(mp.clone());
+        }
+    }
+
+    if let Some(list) = &dev.mountpoints {
+        for item in list.iter().flatten() {
+            if !item.is_empty() && item != "[SWAP]" {
+                mounts.push(item.clone());
+            }
+        }
+    }
+
+    for child in &dev.children {
+        collect_mountpoints(child, mounts);
+    }
+}
+
+/// Sanitize a string for use as a filesystem label.
+///
+/// Keeps alphanumeric characters, hyphens, and underscores.
+/// Replaces spaces with underscores.
+/// Removes all other characters.
+///
+/// # Arguments
+///
+/// * `input` - String to sanitize (typically an ISO filename)
+///
+/// # Returns
+///
+/// Sanitized label string safe for filesystem labels.
+pub fn sanitize_label(input: &str) -> String {
+    let mut out = String::new();
+    for ch in input.chars() {
+        if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+            out.push(ch);
+        } else if ch.is_ascii_whitespace() {
+            out.push('_');
+        }
+    }
+    out
+}
+
+/// Check if a filesystem type supports labeling.
+///
+/// Returns `true` for FAT, NTFS, and EXT filesystems which can be labeled
+/// with `fatlabel`, `ntfslabel`, or `e2label` respectively.
+fn is_supported_fstype(fstype: &str) -> bool {
+    matches!(
+        fstype,
+        "vfat" | "fat" | "fat16" | "fat32" | "ntfs" | "ext2" | "ext3" | "ext4"
+    )
+}
+
+/// Get the appropriate label command and arguments for a filesystem type.
+///
+/// Returns the tool name and arguments to execute for labeling the partition.
+/// Different filesystems have different tools and max label lengths:
+/// - FAT: `fatlabel`, max 11 characters
+/// - NTFS: `ntfslabel`, max 32 characters
+/// - EXT: `e2label`, max 16 characters
+fn label_command<'a>(
+    partition: &'a str,
+    fstype: &'a str,
+    base: &'a str,
+) -> (String, &'a str, Vec<String>) {
+    match fstype {
+        "vfat" | "fat" | "fat16" | "fat32" => {
+            let label = truncate_label(base, 11);
+            (
+                label.clone(),
+                "fatlabel",
+                vec![partition.to_string(), label],
+            )
+        }
+        "ntfs" => {
+            let label = truncate_label(base, 32);
+            (
+                label.clone(),
+                "ntfslabel",
+                vec![partition.to_string(), label],
+            )
+        }
+        "ext2" | "ext3" | "ext4" => {
+            let label = truncate_label(base, 16);
+            (label.clone(), "e2label", vec![partition.to_string(), label])
+        }
+        _ => (base.to_string(), "false", Vec::new()),
+    }
+}
+
+/// Truncate a label to a maximum length.
+///
+/// Safely truncates UTF-8 strings by grapheme count (not byte count), so a
+/// character with combining marks is kept or dropped as a whole.
+pub fn truncate_label(input: &str, max_len: usize) -> String {
+    input.graphemes(true).take(max_len).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_and_verify_messages_round_trip() {
+        for phase in FlashPhase::ALL {
+            assert_eq!(FlashPhase::parse(phase.name()), Some(phase));
+        }
+        assert_eq!(parse_verify_progress("Verified: 512/2048"), Some((512, 2048)));
+        assert_eq!(parse_verify_progress("Verifying flash integrity..."), None);
+    }
+
+    #[test]
+    fn parse_dd_bytes_reads_leading_number() {
+        let line = "123456789 bytes (123 MB) copied, 1 s, 123 MB/s";
+        assert_eq!(parse_dd_bytes(line), Some(123_456_789));
+    }
+
+    #[test]
+    fn describe_command_quotes_arguments_with_spaces() {
+        let mut cmd = Command::new("sudo");
+        cmd.args(["dd", "if=/home/me/my image.iso", "of=/dev/sdb"]);
+        assert_eq!(
+            describe_command(&cmd),
+            "sudo dd 'if=/home/me/my image.iso' of=/dev/sdb"
+        );
+    }
+
+    #[test]
+    fn parse_dd_rate_reads_trailing_rate() {
+        let line = "123456789 bytes (123 MB, 118 MiB) copied, 5 s, 24.7 MB/s";
+        assert_eq!(parse_dd_rate(line).as_deref(), Some("24.7 MB/s"));
+        assert_eq!(parse_dd_rate("Verifying flash integrity..."), None);
+    }
+
+    #[test]
+    fn parse_dd_bytes_returns_none_without_leading_digits() {
+        assert_eq!(parse_dd_bytes("dd: failed to open"), None);
+    }
+
+    #[test]
+    fn parse_dd_bytes_reads_gnu_formats_and_locales() {
+        let samples = [
+            // coreutils 8.24 and later, progress and final summary
+            (
+                "1048576000 bytes (1.0 GB, 1000 MiB) copied, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "2147483648 bytes (2.1 GB, 2.0 GiB) copied, 8.04291 s, 267 MB/s",
+                2_147_483_648,
+            ),
+            ("1 byte copied, 0.000123 s, 8.1 kB/s", 1),
+            // before 8.24
+            (
+                "524288000 bytes (524 MB) copied, 2.01 s, 261 MB/s",
+                524_288_000,
+            ),
+            // de_DE, fr_FR, ru_RU, pl_PL wording
+            (
+                "1048576000 Bytes (1,0 GB, 1000 MiB) kopiert, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1048576000 octets (1,0 GB, 1000 MiB) copiés, 4,01 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1048576000 байт (1,0 GB, 1000 MiB) скопирован, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1048576000 bajtów (1,0 GB, 1000 MiB) skopiowane, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            // digits grouped by the locale
+            (
+                "1.048.576.000 Bytes (1,0 GB, 1000 MiB) kopiert, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1,048,576,000 bytes (1.0 GB, 1000 MiB) copied, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1\u{202f}048\u{202f}576\u{202f}000 octets (1,0 GB) copiés, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            (
+                "1'048'576'000 Bytes (1.0 GB) kopiert, 4 s, 262 MB/s",
+                1_048_576_000,
+            ),
+            // macOS and uutils pad the count
+            (
+                "  1048576000 bytes (1049 MB, 1000 MiB) transferred 4.002s, 262 MB/s",
+                1_048_576_000,
+            ),
+            // a human-readable size first
+            ("1.5 GiB copied, 6 s, 268 MB/s", 1_610_612_736),
+            ("512 MB copied, 2 s, 256 MB/s", 512_000_000),
+            ("2,5 GB kopiert, 10 s, 250 MB/s", 2_500_000_000),
+        ];
+        for (line, bytes) in samples {
+            assert_eq!(parse_dd_bytes(line), Some(bytes), "{line}");
+        }
+        assert_eq!(parse_dd_bytes("2000+0 records in"), None);
+        assert_eq!(parse_dd_bytes("2000+0 Datensätze ein"), None);
+        assert_eq!(parse_dd_bytes("250+1 records out"), None);
+        assert_eq!(
+            parse_dd_rate("1048576000 Bytes (1,0 GB, 1000 MiB) kopiert, 4,01 s, 26,2 MB/s")
+                .as_deref(),
+            Some("26,2 MB/s")
+        );
+    }
+
+    #[test]
+    fn sanitize_label_keeps_supported_chars() {
+        let input = "Fedora Linux 40 (Beta)!";
+        assert_eq!(sanitize_label(input), "Fedora_Linux_40_Beta");
+    }
+
+    #[test]
+    fn label_base_prefers_the_explicit_label() {
+        let image = Path::new("/isos/Fedora 40.iso");
+        assert_eq!(label_base(image, None), "Fedora_40");
+        assert_eq!(label_base(image, Some("RESCUE stick!")), "RESCUE_stick");
+    }
+
+    #[test]
+    fn truncate_label_uses_char_boundaries() {
+        assert_eq!(truncate_label("abcdef", 3), "abc");
+        assert_eq!(truncate_label("åäö", 2), "åä");
+    }
+
+    #[test]
+    fn label_command_uses_partition_path_as_is() {
+        let (label, tool, args) = label_command("/dev/sdb1", "ext4", "ubuntu_live");
+        assert_eq!(label, "ubuntu_live");
+        assert_eq!(tool, "e2label");
+        assert_eq!(args, vec!["/dev/sdb1", "ubuntu_live"]);
+    }
+
+    #[test]
+    fn collect_mountpoints_reads_nested_entries() {
+        let tree = crate::device::LsblkDevice {
+            name: "/dev/sdb".to_string(),
+            vendor: None,
+            model: None,
+            size: None,
+            serial: None,
+            rm: None,
+            r#type: "disk".to_string(),
+            fstype: None,
+            label: None,
+            fsused: None,
+            mountpoint: None,
+            mountpoints: None,
+            children: vec![crate::device::LsblkDevice {
+                name: "/dev/sdb1".to_string(),
+                vendor: None,
+                model: None,
+                size: None,
+                serial: None,
+                rm: None,
+                r#type: "part".to_string(),
+                fstype: Some("ext4".to_string()),
+                label: None,
+                fsused: None,
+                mountpoint: Some("/media/usb".to_string()),
+                mountpoints: Some(vec![Some("/media/usb".to_string()), Some("".to_string())]),
+                children: Vec::new(),
+            }],
+        };
+
+        let mut mounts = Vec::new();
+        collect_mountpoints(&tree, &mut mounts);
+        assert!(mounts.iter().any(|m| m == "/media/usb"));
+    }
+
+    /// Records the steps it is driven through; verification reports `matches`.
+    struct FakeBackend {
+        steps: Vec<&'static str>,
+        matches: bool,
+    }
+
+    impl FlashBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn prepare(&mut self, _: &Target, _: &mpsc::Sender<String>) -> Result<(), FlashError> {
+            self.steps.push("prepare");
+            Ok(())
+        }
+
+        fn write(&mut self, _: &Target, _: &mpsc::Sender<String>) -> Result<(), FlashError> {
+            self.steps.push("write");
+            Ok(())
+        }
+
+        fn verify(&mut self, _: &Target, _: &mpsc::Sender<String>) -> Result<(String, bool)> {
+            self.steps.push("verify");
+            Ok(("abc".to_string(), self.matches))
+        }
+
+        fn finalize(&mut self, _: &Target, _: &mpsc::Sender<String>) -> Result<Option<String>> {
+            self.steps.push("finalize");
+            Ok(Some(format!("{LABEL_PREFIX}FAKE")))
+        }
+    }
+
+    #[test]
+    fn run_backend_drives_the_steps_in_order() {
+        let options = FlashOptions::default();
+        let target = Target {
+            image: Path::new("/isos/fake.iso"),
+            device: "/dev/fake",
+            options: &options,
+            resume_from: 0,
+            journal: None,
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut backend = FakeBackend {
+            steps: Vec::new(),
+            matches: true,
+        };
+        let flashed = run_backend(&mut backend, &target, &tx).unwrap();
+        assert_eq!(backend.steps, ["prepare", "write", "verify", "finalize"]);
+        assert_eq!(flashed.image_hash.as_deref(), Some("abc"));
+        assert_eq!(flashed.label.as_deref(), Some("FAKE"));
+        let phases: Vec<_> = rx
+            .try_iter()
+            .filter_map(|line| FlashPhase::parse(line.strip_prefix(PHASE_PREFIX)?))
+            .collect();
+        // Staging happens before the backend gets the image.
+        assert_eq!(phases, &FlashPhase::ALL[1..]);
+
+        let mut backend = FakeBackend {
+            steps: Vec::new(),
+            matches: false,
+        };
+        let err = run_backend(&mut backend, &target, &tx).unwrap_err();
+        assert!(matches!(err, FlashError::VerificationMismatch));
+        assert_eq!(backend.steps, ["prepare", "write", "verify"]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Hook scripts run at fixed points of a flash.
+//!
+//! Power users extend flashr-tui with their own executables instead of
+//! patching it. Each hook gets the details in its environment and answers
+//! through its exit status or output:
+//!
+//! - `device_filter` - Run for every listed device, with `FLASHR_DEVICE`,
+//!   `FLASHR_SERIAL`, `FLASHR_BY_ID`, `FLASHR_VENDOR`, `FLASHR_MODEL`,
+//!   `FLASHR_SIZE`, and `FLASHR_REMOVABLE` ("1" or "0"). Exit 0 keeps the
+//!   device; anything else hides it, as does a filter that cannot run.
+//! - `label_hook` - Run before writing, when no `--label` was given, with
+//!   `FLASHR_IMAGE`, `FLASHR_DEVICE`, and `FLASHR_LABEL` (the label derived
+//!   from the image name). The first line it prints becomes the label;
+//!   printing nothing keeps the derived one.
+//! - `pre_hook` - Run after the safety checks and right before writing, with
+//!   `FLASHR_IMAGE` and `FLASHR_DEVICE`. A non-zero exit refuses the flash.
+//! - `post_hook` (`--post-hook`) - Run after a successful flash, for asset
+//!   tagging, inventory updates, or notifications, with `FLASHR_IMAGE`,
+//!   `FLASHR_DEVICE`, `FLASHR_LABEL` (empty if none), and `FLASHR_SHA256`
+//!   (hex SHA-256 of the image). A non-zero exit fails the flash.
+//!
+//! `FLASHR_DEVICE` is the kernel device path (e.g. "/dev/sdb"). Hooks run
+//! with flashr-tui's own privileges, not elevated. The output of the
+//! pre-flash, label, and post-flash hooks (stderr, for the label hook) is
+//! passed on as progress messages.
+
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::mpsc;
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+
+use crate::device::Disk;
+
+/// Prefix of the progress messages carrying the hook's output.
+pub const HOOK_PREFIX: &str = "Hook: ";
+
+/// What a finished flash tells its hook.
+///
+/// # Fields
+///
+/// * `image` - Image that was written
+/// * `device` - Kernel device path it was written to
+/// * `label` - Volume label that was applied, if any
+/// * `sha256` - Hex SHA-256 of the image
+#[derive(Debug, Clone)]
+pub struct HookContext<'a> {
+    pub image: &'a Path,
+    pub device: &'a str,
+    pub label: Option<&'a str>,
+    pub sha256: &'a str,
+}
+
+impl HookContext<'_> {
+    /// The environment variables the hook is run with.
+    fn env(&self) -> [(&'static str, String); 4] {
+        [
+            ("FLASHR_IMAGE", self.image.display().to_string()),
+            ("FLASHR_DEVICE", self.device.to_string()),
+            ("FLASHR_LABEL", self.label.unwrap_or_default().to_string()),
+            ("FLASHR_SHA256", self.sha256.to_string()),
+        ]
+    }
+}
+
+/// The environment variables the device filter sees for `disk`.
+fn disk_env(disk: &Disk) -> [(&'static str, String); 7] {
+    let removable = if disk.removable { "1" } else { "0" };
+    [
+        ("FLASHR_DEVICE", disk.device_path()),
+        ("FLASHR_SERIAL", disk.serial.clone().unwrap_or_default()),
+        ("FLASHR_BY_ID", disk.by_id.clone().unwrap_or_default()),
+        ("FLASHR_VENDOR", disk.vendor.clone()),
+        ("FLASHR_MODEL", disk.model.clone()),
+        ("FLASHR_SIZE", disk.size.clone()),
+        ("FLASHR_REMOVABLE", removable.to_string()),
+    ]
+}
+
+/// Run `script` with `env`, without input, and collect its output.
+fn run_script(script: &Path, env: &[(&'static str, String)], what: &str) -> Result<Output> {
+    Command::new(script)
+        .envs(env.iter().map(|(key, value)| (*key, value)))
+        .stdin(std::process::Stdio::null())
+        .output()
+        .with_context(|| format!("run {what} {}", script.display()))
+}
+
+/// Send the non-empty lines of `stream` through `progress` as hook output.
+fn forward_lines(stream: &[u8], progress: &mpsc::Sender<String>) {
+    for line in String::from_utf8_lossy(stream).lines() {
+        let line = line.trim();
+        if !line.is_empty() {
+            let _ = progress.send(format!("{HOOK_PREFIX}{line}"));
+        }
+    }
+}
+
+/// Ask the device filter `script` whether `disk` may be offered.
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be started; callers hide the
+/// device then, since the filter could not approve it.
+pub fn device_allowed(script: &Path, disk: &Disk) -> Result<bool> {
+    let output = run_script(script, &disk_env(disk), "device filter")?;
+    let allowed = output.status.success();
+    if !allowed {
+        debug!(
+            "device filter {} hid {} ({})",
+            script.display(),
+            disk.device_path(),
+            output.status
+        );
+    }
+    Ok(allowed)
+}
+
+/// Ask the label hook `script` for the volume label of `image` on `device`.
+///
+/// # Returns
+///
+/// The first line the script printed, or `None` if it printed nothing, in
+/// which case `default_label` stays.
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be started or exits non-zero.
+pub fn compute_label(
+    script: &Path,
+    image: &Path,
+    device: &str,
+    default_label: &str,
+    progress: &mpsc::Sender<String>,
+) -> Result<Option<String>> {
+    let env = [
+        ("FLASHR_IMAGE", image.display().to_string()),
+        ("FLASHR_DEVICE", device.to_string()),
+        ("FLASHR_LABEL", default_label.to_string()),
+    ];
+    let output = run_script(script, &env, "label hook")?;
+    forward_lines(&output.stderr, progress);
+    if !output.status.success() {
+        anyhow::bail!("Label hook {} failed ({})", script.display(), output.status);
+    }
+    let label = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string);
+    debug!("label hook {} chose {label:?}", script.display());
+    Ok(label)
+}
+
+/// Run `script` before `image` is written to `device`, sending its output
+/// lines through `progress`.
+///
+/// # Errors
+///
+/// Returns an error, which refuses the flash, if the script cannot be
+/// started or exits non-zero.
+pub fn run_pre_hook(
+    script: &Path,
+    image: &Path,
+    device: &str,
+    progress: &mpsc::Sender<String>,
+) -> Result<()> {
+    info!("running pre-flash hook {}", script.display());
+    let _ = progress.send(format!("Running pre-flash hook {}...", script.display()));
+    let env = [
+        ("FLASHR_IMAGE", image.display().to_string()),
+        ("FLASHR_DEVICE", device.to_string()),
+    ];
+    let output = run_script(script, &env, "pre-flash hook")?;
+    forward_lines(&output.stdout, progress);
+    forward_lines(&output.stderr, progress);
+    if !output.status.success() {
+        warn!(
+            "pre-flash hook {} refused the flash ({})",
+            script.display(),
+            output.status
+        );
+        anyhow::bail!(
+            "Pre-flash hook {} refused the flash ({})",
+            script.display(),
+            output.status
+        );
+    }
+    Ok(())
+}
+
+/// Run `script` after a flash, sending its output lines through `progress`.
+///
+/// # Errors
+///
+/// Returns an error if the script cannot be started or exits non-zero.
+pub fn run_post_hook(
+    script: &Path,
+    context: &HookContext,
+    progress: &mpsc::Sender<String>,
+) -> Result<()> {
+    info!("running post-flash hook {}", script.display());
+    let _ = progress.send(format!("Running post-flash hook {}...", script.display()));
+    let output = run_script(script, &context.env(), "post-flash hook")?;
+    forward_lines(&output.stdout, progress);
+    forward_lines(&output.stderr, progress);
+    if !output.status.success() {
+        warn!(
+            "post-flash hook {} exited with {}",
+            script.display(),
+            output.status
+        );
+        anyhow::bail!(
+            "Post-flash hook {} failed ({})",
+            script.display(),
+            output.status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_sees_the_flash_in_its_environment() {
+        let context = HookContext {
+            image: Path::new("/isos/arch.iso"),
+            device: "/dev/sdb",
+            label: None,
+            sha256: "ab12",
+        };
+        let (tx, rx) = mpsc::channel();
+        run_post_hook(Path::new("/bin/true"), &context, &tx).unwrap();
+        drop(tx);
+        assert_eq!(rx.iter().count(), 1);
+        assert_eq!(
+            context.env(),
+            [
+                ("FLASHR_IMAGE", "/isos/arch.iso".to_string()),
+                ("FLASHR_DEVICE", "/dev/sdb".to_string()),
+                ("FLASHR_LABEL", String::new()),
+                ("FLASHR_SHA256", "ab12".to_string()),
+            ]
+        );
+        assert!(run_post_hook(Path::new("/bin/false"), &context, &mpsc::channel().0).is_err());
+    }
+
+    #[test]
+    fn hooks_filter_devices_label_and_refuse() {
+        let dir = std::env::temp_dir().join(format!("flashr-hook-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str, body: &str| {
+            use std::os::unix::fs::PermissionsExt;
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let filter = script("filter", r#"[ "$FLASHR_SERIAL" = "APPROVED1" ]"#);
+        let label = script("label", r#"echo "LAB-${FLASHR_LABEL}"; echo chosen >&2"#);
+        let disk = |serial: &str| Disk {
+            name: "sdb".to_string(),
+            vendor: "SanDisk".to_string(),
+            model: "Cruzer".to_string(),
+            size: "16G".to_string(),
+            serial: Some(serial.to_string()),
+            by_id: None,
+            removable: true,
+        };
+        assert!(device_allowed(&filter, &disk("APPROVED1")).unwrap());
+        assert!(!device_allowed(&filter, &disk("OTHER")).unwrap());
+        assert!(device_allowed(&dir.join("missing"), &disk("APPROVED1")).is_err());
+
+        let (tx, rx) = mpsc::channel();
+        let image = Path::new("/isos/arch.iso");
+        assert_eq!(
+            compute_label(&label, image, "/dev/sdb", "arch", &tx).unwrap(),
+            Some("LAB-arch".to_string())
+        );
+        assert_eq!(rx.try_recv().unwrap(), format!("{HOOK_PREFIX}chosen"));
+        let silent = Path::new("/bin/true");
+        assert_eq!(
+            compute_label(silent, image, "/dev/sdb", "arch", &tx).unwrap(),
+            None
+        );
+        assert!(run_pre_hook(silent, image, "/dev/sdb", &tx).is_ok());
+        assert!(run_pre_hook(Path::new("/bin/false"), image, "/dev/sdb", &tx).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,81 @@
+//! Keeping the machine awake while flashing.
+//!
+//! A suspend halfway through the write leaves a corrupt stick, so a flash
+//! holds a systemd-logind "block" inhibitor lock on sleep, idle, and the lid
+//! switch for as long as it runs. The lock is held by a `systemd-inhibit`
+//! process wrapped around `sleep infinity`, which is killed to release it.
+
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+/// Operations blocked while the lock is held.
+const WHAT: &str = "sleep:idle:handle-lid-switch";
+
+/// How long to wait for `systemd-inhibit` to fail (e.g. without logind)
+/// before assuming it holds the lock.
+const STARTUP_GRACE: Duration = Duration::from_millis(200);
+
+/// A held inhibitor lock, released when dropped.
+pub struct SleepInhibitor {
+    child: Child,
+}
+
+impl SleepInhibitor {
+    /// Take the lock, giving `why` as the reason (shown by
+    /// `systemd-inhibit --list` and desktop power dialogs).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `systemd-inhibit` is missing or exits at once,
+    /// e.g. because logind is not running or refuses the lock.
+    pub fn acquire(why: &str) -> Result<Self> {
+        if which::which("systemd-inhibit").is_err() {
+            anyhow::bail!("systemd-inhibit not found");
+        }
+        let mut child = command(why)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("run systemd-inhibit")?;
+        std::thread::sleep(STARTUP_GRACE);
+        if let Some(status) = child.try_wait().context("wait for systemd-inhibit")? {
+            anyhow::bail!("systemd-inhibit exited ({status})");
+        }
+        Ok(Self { child })
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The `systemd-inhibit` command that holds the lock until killed.
+fn command(why: &str) -> Command {
+    let mut cmd = Command::new("systemd-inhibit");
+    cmd.arg(format!("--what={WHAT}"))
+        .arg("--who=flashr-tui")
+        .arg(format!("--why={why}"))
+        .arg("--mode=block")
+        .args(["sleep", "infinity"]);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_blocks_sleep_until_killed() {
+        assert_eq!(
+            crate::flash::describe_command(&command("Flashing arch.iso")),
+            "systemd-inhibit --what=sleep:idle:handle-lid-switch --who=flashr-tui \
+             '--why=Flashing arch.iso' --mode=block sleep infinity"
+        );
+    }
+}
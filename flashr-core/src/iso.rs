@@ -9,6 +9,7 @@
 //! privileges — only read access to the ISO file.
 
 use anyhow::{Context, Result};
+use log::debug;
 use std::io::Read;
 use std::path::Path;
 
@@ -43,6 +44,10 @@ const MBR_SIGNATURE_OFFSET: usize = 510;
 /// GPT header magic string at byte offset 512.
 const GPT_MAGIC: &[u8; 8] = b"EFI PART";
 
+/// Bytes of an image's start that detection looks at: the MBR and the GPT
+/// magic after it.
+pub const HEADER_LEN: usize = 520;
+
 /// Detect the type of an ISO image file.
 ///
 /// Works by reading the first 520 bytes and checking for:
@@ -71,12 +76,23 @@ pub fn detect(image: &Path) -> Result<IsoKind> {
         .with_context(|| format!("open ISO image: {}", image.display()))?;
 
     // Read enough for MBR (512 bytes) + potential GPT header (8 more bytes)
-    let mut buf = [0u8; 520];
+    let mut buf = [0u8; HEADER_LEN];
     let bytes_read = file.read(&mut buf).context("read ISO header")?;
+    let kind = detect_header(&buf[..bytes_read]);
+    debug!("{}: {kind:?}", image.display());
+    Ok(kind)
+}
+
+/// Detect the type of an image from its first `HEADER_LEN` bytes (all of
+/// it, when shorter), for an image that is not a file, such as a download
+/// streamed to the device (see `stream`).
+pub fn detect_header(buf: &[u8]) -> IsoKind {
+    let bytes_read = buf.len().min(HEADER_LEN);
 
     // Need at least 512 bytes to inspect MBR
     if bytes_read < 512 {
-        return Ok(IsoKind::Unknown);
+        debug!("only {bytes_read} bytes, type unknown");
+        return IsoKind::Unknown;
     }
 
     // Check MBR boot signature at bytes 510-511
@@ -93,11 +109,43 @@ pub fn detect(image: &Path) -> Result<IsoKind> {
     // Check for GPT header at byte 512 (present in some hybrid ISOs)
     let has_gpt = bytes_read >= 520 && buf[512..520] == *GPT_MAGIC;
 
-    if (has_mbr_signature && has_partition_entry) || has_gpt {
-        Ok(IsoKind::Hybrid)
+    let kind = if (has_mbr_signature && has_partition_entry) || has_gpt {
+        IsoKind::Hybrid
     } else {
-        Ok(IsoKind::NonHybrid)
-    }
+        IsoKind::NonHybrid
+    };
+    debug!(
+        "{kind:?} (MBR signature: {has_mbr_signature}, partition entry: \
+         {has_partition_entry}, GPT: {has_gpt})"
+    );
+    kind
+}
+
+/// Extensions of detached signature and checksum files published next to images.
+const SIGNATURE_EXTENSIONS: [&str; 7] =
+    ["sig", "asc", "gpg", "sha256", "sha256sum", "sha512", "b3"];
+
+/// Checksum lists that cover every image in a download directory.
+const CHECKSUM_LISTS: [&str; 5] = [
+    "SHA256SUMS",
+    "SHA512SUMS",
+    "B3SUMS",
+    "sha256sum.txt",
+    "CHECKSUM",
+];
+
+/// Find a signature or checksum file that lets the user verify `image`.
+///
+/// Looks for `<image>.sig`-style files beside the image, then for a checksum
+/// list in the same directory. Only presence is checked; nothing is verified.
+pub fn find_signature(image: &Path) -> Option<std::path::PathBuf> {
+    let file_name = image.file_name()?.to_string_lossy();
+    let dir = image.parent().unwrap_or(Path::new(""));
+    SIGNATURE_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{file_name}.{ext}")))
+        .chain(CHECKSUM_LISTS.iter().map(|name| dir.join(name)))
+        .find(|path| path.is_file())
 }
 
 #[cfg(test)]
@@ -123,6 +171,27 @@ mod tests {
         path
     }
 
+    #[test]
+    fn find_signature_checks_sidecar_files_and_checksum_lists() {
+        // Its own directory, since any checksum list in a shared one counts.
+        let dir = std::env::temp_dir().join(format!("flashr_tui_iso_sig_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.iso");
+        std::fs::write(&path, [0u8; 16]).unwrap();
+        assert_eq!(find_signature(&path), None);
+
+        let sums = dir.join("SHA256SUMS");
+        std::fs::write(&sums, b"").unwrap();
+        assert_eq!(find_signature(&path), Some(sums));
+
+        let sidecar = dir.join("image.iso.asc");
+        std::fs::write(&sidecar, b"").unwrap();
+        let found = find_signature(&path);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(found, Some(sidecar));
+    }
+
     #[test]
     fn detect_returns_unknown_for_small_file() {
         let path = write_temp_file(&[0u8; 128]);
@@ -0,0 +1,335 @@
+//! Crash-safe record of how far a write got, for resuming it.
+//!
+//! When `FlashOptions::journal_dir` is set, the write step keeps a small JSON
+//! file per device in that directory, saying which image is being written and
+//! how many bytes of it are already on the device. Both backends write synced
+//! (`dd oflag=sync`, `sync_data` after each native chunk), so every offset
+//! the backend reports is on the device. The file is rewritten at most every
+//! `JOURNAL_INTERVAL` and removed once the write completes.
+//!
+//! After a crash or power loss the file is still there. A flash with
+//! `FlashOptions::resume` set then continues from that offset, rounded down
+//! to a whole block, instead of from the start, but only if the image still
+//! has the same path, size, and modification time, and the device is given
+//! by the same path and still has the stable identity recorded with the
+//! journal: its first link in `/dev/disk/by-id`, which names the hardware.
+//! A kernel name like `/dev/sdb` alone could by then be another stick, so a
+//! journal without that identity is never resumed.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// How often a running write saves its offset.
+pub const JOURNAL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Directory of the links naming block devices by their hardware identity.
+const BY_ID_DIR: &str = "/dev/disk/by-id";
+
+/// How far a write of `image` to `device` got.
+///
+/// # Fields
+///
+/// * `image` - Image being written
+/// * `image_size` - Its size in bytes when the write started
+/// * `image_modified` - Its modification time then, in seconds since the Unix epoch
+/// * `device` - Device as given to the flash (a by-id link where one exists)
+/// * `identity` - The device's first `/dev/disk/by-id` link when the write
+///   started, if it had one
+/// * `offset` - Bytes of the image known to be on the device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal {
+    pub image: PathBuf,
+    pub image_size: u64,
+    pub image_modified: u64,
+    pub device: String,
+    #[serde(default)]
+    pub identity: Option<String>,
+    pub offset: u64,
+}
+
+impl Journal {
+    /// A journal for a write of `image` to `device` starting at `offset`.
+    pub fn new(image: &Path, device: &str, offset: u64) -> Result<Self> {
+        let (image_size, image_modified) = image_stamp(image)?;
+        Ok(Self {
+            image: image.to_path_buf(),
+            image_size,
+            image_modified,
+            device: device.to_string(),
+            identity: identity_in(Path::new(BY_ID_DIR), device),
+            offset,
+        })
+    }
+
+    /// The journal file for `device` in `dir`, named after the device's
+    /// last path component (e.g. "usb-SanDisk_Cruzer_4C53-0:0.json").
+    pub fn path_for(dir: &Path, device: &str) -> PathBuf {
+        let name = Path::new(device).file_name().map_or_else(
+            || device.replace('/', "_"),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        dir.join(format!("{name}.json"))
+    }
+
+    /// The journal at `path`, or `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("read journal: {}", path.display()));
+            }
+        };
+        serde_json::from_str(&data)
+            .map(Some)
+            .with_context(|| format!("parse journal: {}", path.display()))
+    }
+
+    /// Every journal in `dir` that parses, with its file; none if `dir` does
+    /// not exist.
+    pub fn list(dir: &Path) -> Vec<(PathBuf, Self)> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut journals: Vec<(PathBuf, Self)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| Some((path.clone(), Self::load(&path).ok()??)))
+            .collect();
+        journals.sort_by(|a, b| a.0.cmp(&b.0));
+        journals
+    }
+
+    /// Write the journal to `path` so that a crash leaves either the old or
+    /// the new content: a temporary file is synced, then renamed over it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("create journal directory: {}", dir.display()))?;
+        let tmp = path.with_extension("json.tmp");
+        let mut file =
+            File::create(&tmp).with_context(|| format!("write journal: {}", tmp.display()))?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("write journal: {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("replace journal: {}", path.display()))?;
+        // Make the rename itself survive a power loss.
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+
+    /// Where a write of `image` to `device` in `block_size` blocks can pick
+    /// up, or `None` if this journal is for another write or another device
+    /// behind the same path, the image changed since, or nothing (or
+    /// everything) was written.
+    pub fn resume_offset(&self, image: &Path, device: &str, block_size: u64) -> Option<u64> {
+        self.resume_offset_in(Path::new(BY_ID_DIR), image, device, block_size)
+    }
+
+    /// `resume_offset` with the device's identity looked up in `by_id`.
+    fn resume_offset_in(
+        &self,
+        by_id: &Path,
+        image: &Path,
+        device: &str,
+        block_size: u64,
+    ) -> Option<u64> {
+        if self.image != image || self.device != device {
+            return None;
+        }
+        if self.identity.is_none() || self.identity != identity_in(by_id, device) {
+            return None;
+        }
+        if image_stamp(image).ok()? != (self.image_size, self.image_modified) {
+            return None;
+        }
+        let offset = self.offset.min(self.image_size);
+        let offset = offset - offset % block_size.max(1);
+        (offset > 0 && offset < self.image_size).then_some(offset)
+    }
+
+    /// Share of the image already written, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f64 {
+        if self.image_size == 0 {
+            0.0
+        } else {
+            self.offset.min(self.image_size) as f64 / self.image_size as f64
+        }
+    }
+}
+
+/// The first link in `by_id` (by name) that resolves to the same device node
+/// as `device`, or `None` if there is none or `device` does not exist.
+fn identity_in(by_id: &Path, device: &str) -> Option<String> {
+    let node = std::fs::canonicalize(device).ok()?;
+    let mut links: Vec<PathBuf> = std::fs::read_dir(by_id)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|link| std::fs::canonicalize(link).is_ok_and(|target| target == node))
+        .collect();
+    links.sort();
+    links.first().map(|link| link.display().to_string())
+}
+
+/// Size and modification time (in seconds) of `image`.
+fn image_stamp(image: &Path) -> Result<(u64, u64)> {
+    let meta =
+        std::fs::metadata(image).with_context(|| format!("read image: {}", image.display()))?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+    Ok((meta.len(), modified))
+}
+
+/// The journal of a running write, saved as the backend reports progress.
+///
+/// Saving is best effort: a journal that cannot be written is logged, and
+/// never fails the flash.
+#[derive(Debug)]
+pub struct JournalFile {
+    path: PathBuf,
+    state: RefCell<(Journal, Instant)>,
+}
+
+impl JournalFile {
+    /// Save `journal` to `path` and keep it up to date from here on.
+    pub fn create(path: PathBuf, journal: Journal) -> Result<Self> {
+        journal.save(&path)?;
+        debug!("journaling to {}", path.display());
+        Ok(Self {
+            path,
+            state: RefCell::new((journal, Instant::now())),
+        })
+    }
+
+    /// Record that `offset` bytes of the image are on the device, saving the
+    /// journal if the last save is `JOURNAL_INTERVAL` old.
+    pub fn record(&self, offset: u64) {
+        let due = {
+            let mut state = self.state.borrow_mut();
+            state.0.offset = offset;
+            state.1.elapsed() >= JOURNAL_INTERVAL
+        };
+        if due {
+            self.save();
+        }
+    }
+
+    /// Save the latest offset now.
+    pub fn save(&self) {
+        let mut state = self.state.borrow_mut();
+        if let Err(err) = state.0.save(&self.path) {
+            warn!("journal not saved: {err:#}");
+        }
+        state.1 = Instant::now();
+    }
+
+    /// Remove the journal: the write completed and there is nothing to
+    /// resume.
+    pub fn remove(&self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!("journal not removed: {err:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_resumes_only_the_same_write() {
+        let dir = std::env::temp_dir().join(format!("flashr-journal-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let by_id = dir.join("by-id");
+        std::fs::create_dir_all(&by_id).unwrap();
+        let image = dir.join("image.iso");
+        std::fs::write(&image, vec![0u8; 10_000]).unwrap();
+        std::fs::write(dir.join("sdb"), b"").unwrap();
+        let link = by_id.join("usb-Fake_Stick_123-0:0");
+        std::os::unix::fs::symlink(dir.join("sdb"), &link).unwrap();
+        let device = link.to_str().unwrap();
+        let path = Journal::path_for(&dir, device);
+        assert_eq!(path, dir.join("usb-Fake_Stick_123-0:0.json"));
+        assert_eq!(Journal::load(&path).unwrap(), None);
+
+        let mut started = Journal::new(&image, device, 0).unwrap();
+        started.identity = identity_in(&by_id, device);
+        let journal = JournalFile::create(path.clone(), started).unwrap();
+        journal.record(6_500);
+        journal.save();
+        let saved = Journal::load(&path).unwrap().unwrap();
+        assert_eq!(saved.offset, 6_500);
+        let resume = |journal: &Journal, device: &str, block_size: u64| {
+            journal.resume_offset_in(&by_id, &image, device, block_size)
+        };
+        assert_eq!(resume(&saved, device, 1_000), Some(6_000));
+        assert_eq!(resume(&saved, "/dev/sdc", 1_000), None);
+        assert_eq!(resume(&saved, device, 8_000), None);
+        assert_eq!(Journal::list(&dir), [(path.clone(), saved)]);
+
+        std::fs::write(&image, vec![0u8; 12_000]).unwrap();
+        let changed = Journal::load(&path).unwrap().unwrap();
+        assert_eq!(resume(&changed, device, 1_000), None);
+
+        journal.remove();
+        assert_eq!(Journal::load(&path).unwrap(), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn journal_resumes_only_on_the_same_hardware() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("flashr-journal-id-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let by_id = dir.join("by-id");
+        std::fs::create_dir_all(&by_id).unwrap();
+        let image = dir.join("image.iso");
+        std::fs::write(&image, vec![0u8; 10_000]).unwrap();
+        let node = dir.join("sdb");
+        std::fs::write(&node, b"").unwrap();
+        let device = node.to_str().unwrap();
+        symlink(&node, by_id.join("usb-Fake_Stick_123-0:0")).unwrap();
+        symlink(dir.join("sdc"), by_id.join("usb-Other_Stick_456-0:0")).unwrap();
+
+        let mut journal = Journal::new(&image, device, 6_500).unwrap();
+        let resume = |journal: &Journal| journal.resume_offset_in(&by_id, &image, device, 1_000);
+        assert_eq!(resume(&journal), None);
+
+        journal.identity = identity_in(&by_id, device);
+        assert_eq!(
+            journal.identity.as_deref(),
+            by_id.join("usb-Fake_Stick_123-0:0").to_str()
+        );
+        assert_eq!(resume(&journal), Some(6_000));
+
+        // Another stick now sits behind the same kernel name.
+        std::fs::remove_file(by_id.join("usb-Fake_Stick_123-0:0")).unwrap();
+        std::fs::remove_file(by_id.join("usb-Other_Stick_456-0:0")).unwrap();
+        symlink(&node, by_id.join("usb-Other_Stick_456-0:0")).unwrap();
+        assert_eq!(resume(&journal), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,81 @@
+//! The flashing logic behind flashr-tui, without a user interface.
+//!
+//! This crate finds removable disks, checks that an image can be raw-written,
+//! and writes it with `dd`, elevating through `pkexec` or `sudo` when not
+//! running as root. Frontends (the flashr-tui TUI, its daemon, or other
+//! tools and GUIs) drive it the same way:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use flashr_core::{device, flash, iso, Task};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let image = Path::new("/isos/arch.iso");
+//! assert_eq!(iso::detect(image)?, iso::IsoKind::Hybrid);
+//! let disks = device::list(false)?;
+//! let disk = device::single_removable(&disks)?;
+//!
+//! let target = disk.stable_path();
+//! let task = Task::spawn("flash", move |progress_tx| {
+//!     let options = flash::FlashOptions::default();
+//!     flash::flash_image_with_progress(image, &target, progress_tx, &options)
+//! });
+//! task.wait(|line| println!("{line}"))??;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `Task::wait` blocks until the flash finishes; an event loop calls
+//! `Task::poll` instead. Progress arrives as text lines. Lines starting with the `flash::*_PREFIX`
+//! constants carry structured data, such as the phase, the image hash, the
+//! verification verdict, or the label. The helpers in `flash` parse `dd`'s
+//! own lines.
+//!
+//! Diagnostics go through the `log` crate; install any `log` backend to see
+//! device scans, elevation, and each step of a flash.
+//!
+//! Modules:
+//!
+//! - `device` - Listing disks with `lsblk`, stable by-id paths, hotplug watching
+//! - `iso` - Telling hybrid ISOs (safe to raw-write) from others
+//! - `provider` - `DeviceProvider`, the device list behind the TUI, with a mock for tests
+//! - `error` - `FlashError` and `DeviceError`, for matching on why a flash failed
+//! - `flash` - The write, sync, verify, and label pipeline, and privilege elevation
+//! - `backend` - `FlashBackend` and the `dd` and native backends that do the writing
+//! - `checksum` - Image hashing and published-checksum checks
+//! - `hook` - Post-flash hook scripts
+//! - `journal` - Journaling the write offset, for resuming after a crash
+//! - `inhibit` - Keeping the machine awake while flashing
+//! - `stage` - Copying the image into RAM before flashing it
+//! - `stream` - Flashing an image as it downloads, without a local copy
+//! - `signals` - Stopping flashes cleanly on SIGINT, SIGTERM, and SIGHUP
+//! - `task` - Running an operation on a worker thread and collecting its progress
+//! - `udisks` - Unmounting and powering off through UDisks2
+//! - `platform` - `Platform`, the OS-specific calls, and its Linux implementation
+
+pub mod backend;
+pub mod checksum;
+pub mod device;
+pub mod error;
+pub mod flash;
+pub mod hook;
+pub mod inhibit;
+pub mod iso;
+pub mod journal;
+pub mod platform;
+pub mod provider;
+pub mod signals;
+pub mod stage;
+pub mod stream;
+pub mod task;
+pub mod udisks;
+
+pub use backend::{BackendKind, FlashBackend};
+pub use device::Disk;
+pub use error::{DeviceError, FlashError};
+pub use flash::{CancelFlag, FlashOptions, FlashPhase};
+pub use iso::IsoKind;
+pub use platform::Platform;
+pub use provider::DeviceProvider;
+pub use task::Task;
@@ -0,0 +1,339 @@
+//! What differs between operating systems, behind the `Platform` trait.
+//!
+//! Everything in flashr-core that only works on Linux goes through
+//! `platform::current()`: the root check (`geteuid`), device paths under
+//! `/dev` and the by-id links udev makes there, the disk attributes in sysfs,
+//! `/proc/partitions`, the mount table, and the open files in `/proc`, the
+//! RAM-backed `/dev/shm`, and the `lsblk`, `partprobe`, `wipefs`, and
+//! `udevadm` tools. `Linux` implements it.
+//!
+//! Supporting another OS means a new implementation gated on its
+//! `target_os`, returned by `current()` there. Until one exists, building
+//! for any other target stops with a compile error naming this module.
+//! Filesystem tools (`mkfs.*`, `fatlabel`, `e2label`), `dd`, and the
+//! elevation tools are still called by name, as every supported OS has them.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result};
+
+use crate::flash::elevated_command;
+
+/// The operating system services the flash and device code rely on.
+pub trait Platform: Send + Sync {
+    /// Short name of the OS, for logs (e.g. "linux").
+    fn name(&self) -> &'static str;
+
+    /// Whether the process runs with full privileges (root).
+    fn is_root(&self) -> bool;
+
+    /// Path of the device node with kernel name `name` (e.g. "sdb" to
+    /// "/dev/sdb").
+    fn device_path(&self, name: &str) -> String;
+
+    /// Kernel name of the device node at `path`, the inverse of
+    /// `device_path`; other paths are returned unchanged.
+    fn device_name<'a>(&self, path: &'a str) -> &'a str;
+
+    /// Path of partition `number` of the disk at `device` (e.g.
+    /// "/dev/sdb1", "/dev/nvme0n1p1").
+    fn partition_path(&self, device: &str, number: u32) -> String;
+
+    /// Directory of stable links named after the hardware, if the OS keeps
+    /// one.
+    fn stable_link_dir(&self) -> Option<&'static str>;
+
+    /// Kernel names of the block devices that are whole disks.
+    fn disk_names(&self) -> Result<Vec<String>>;
+
+    /// Kernel names of the partitions on disk `name`, sorted.
+    fn partition_names(&self, name: &str) -> Vec<String>;
+
+    /// A trimmed, non-empty attribute of block device `name`, such as
+    /// "size" (in 512-byte sectors), "removable", or "device/model".
+    fn block_attribute(&self, name: &str, attr: &str) -> Option<String>;
+
+    /// Text that changes whenever a block device appears or goes away, for
+    /// detecting hotplug by polling.
+    fn block_devices_fingerprint(&self) -> String;
+
+    /// A command printing block device changes as they happen, one event
+    /// per line, or `None` if the OS has no such monitor running.
+    fn device_monitor(&self) -> Option<Command>;
+
+    /// PIDs of the running processes.
+    fn process_ids(&self) -> Vec<u32>;
+
+    /// Command name of process `pid`, if it can be read.
+    fn process_name(&self, pid: u32) -> Option<String>;
+
+    /// The files process `pid` has open; empty if they cannot be read.
+    fn open_files(&self, pid: u32) -> Vec<PathBuf>;
+
+    /// Type of the filesystem `path` is on (e.g. "ext4", "nfs4"), if known.
+    fn filesystem_type(&self, path: &Path) -> Option<String>;
+
+    /// Bytes an unprivileged user can still write to the filesystem of `path`.
+    fn free_space(&self, path: &Path) -> Option<u64>;
+
+    /// A directory kept in RAM that every user can write to, if any.
+    fn memory_dir(&self) -> Option<PathBuf>;
+
+    /// An `lsblk` command, for callers to add the columns and device to.
+    /// Its JSON output is parsed into `device::LsblkOutput`.
+    fn lsblk(&self) -> Command;
+
+    /// A command, elevated through `elevator`, that has the kernel re-read
+    /// the partition table of `device`.
+    fn reread_partitions(&self, device: &str, elevator: Option<&str>) -> Command;
+
+    /// A command, elevated through `elevator`, that erases the filesystem
+    /// and partition table signatures on `device`.
+    fn wipe_signatures(&self, device: &str, elevator: Option<&str>) -> Command;
+}
+
+/// The platform flashr-core was built for.
+#[cfg(target_os = "linux")]
+pub fn current() -> &'static dyn Platform {
+    &Linux
+}
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("flashr-core has no `Platform` for this OS yet; see flashr-core/src/platform.rs");
+
+/// Linux: udev, sysfs, procfs, and util-linux.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Linux;
+
+/// Directory of device nodes.
+#[cfg(target_os = "linux")]
+const DEV_DIR: &str = "/dev/";
+
+/// Directory holding udev's stable per-device symlinks.
+#[cfg(target_os = "linux")]
+const BY_ID_DIR: &str = "/dev/disk/by-id";
+
+/// Root of the sysfs tree of whole disks.
+#[cfg(target_os = "linux")]
+const SYS_BLOCK: &str = "/sys/block";
+
+/// Root of the sysfs tree of all block devices, partitions included.
+#[cfg(target_os = "linux")]
+const SYS_CLASS_BLOCK: &str = "/sys/class/block";
+
+/// Kernel partition table listing.
+#[cfg(target_os = "linux")]
+const PROC_PARTITIONS: &str = "/proc/partitions";
+
+/// Control socket that exists only when a udev daemon is running.
+#[cfg(target_os = "linux")]
+const UDEV_CONTROL: &str = "/run/udev/control";
+
+/// The mount table of this process.
+#[cfg(target_os = "linux")]
+const PROC_MOUNTS: &str = "/proc/self/mounts";
+
+/// The tmpfs for shared memory, writable by everyone.
+#[cfg(target_os = "linux")]
+const DEV_SHM: &str = "/dev/shm";
+
+#[cfg(target_os = "linux")]
+impl Platform for Linux {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn is_root(&self) -> bool {
+        nix::unistd::geteuid().is_root()
+    }
+
+    fn device_path(&self, name: &str) -> String {
+        format!("{DEV_DIR}{name}")
+    }
+
+    fn device_name<'a>(&self, path: &'a str) -> &'a str {
+        path.strip_prefix(DEV_DIR).unwrap_or(path)
+    }
+
+    fn partition_path(&self, device: &str, number: u32) -> String {
+        // Disks whose name ends in a digit (nvme0n1, mmcblk0) use a `p` separator.
+        if device.ends_with(|c: char| c.is_ascii_digit()) {
+            format!("{device}p{number}")
+        } else {
+            format!("{device}{number}")
+        }
+    }
+
+    fn stable_link_dir(&self) -> Option<&'static str> {
+        Some(BY_ID_DIR)
+    }
+
+    fn disk_names(&self) -> Result<Vec<String>> {
+        let partitions =
+            std::fs::read_to_string(PROC_PARTITIONS).context("read /proc/partitions")?;
+        Ok(parse_proc_partitions(&partitions)
+            .into_iter()
+            // Whole disks have a directory directly under /sys/block; partitions don't.
+            .filter(|name| Path::new(SYS_BLOCK).join(name).is_dir())
+            .collect())
+    }
+
+    fn partition_names(&self, name: &str) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Path::new(SYS_BLOCK).join(name)) else {
+            return Vec::new();
+        };
+        let mut parts: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.path().join("partition").exists())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .collect();
+        parts.sort();
+        parts
+    }
+
+    fn block_attribute(&self, name: &str, attr: &str) -> Option<String> {
+        let path = Path::new(SYS_CLASS_BLOCK).join(name).join(attr);
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn block_devices_fingerprint(&self) -> String {
+        std::fs::read_to_string(PROC_PARTITIONS).unwrap_or_default()
+    }
+
+    fn device_monitor(&self) -> Option<Command> {
+        if !Path::new(UDEV_CONTROL).exists() {
+            return None;
+        }
+        let mut cmd = Command::new("udevadm");
+        cmd.args(["monitor", "--udev", "--subsystem-match=block"]);
+        Some(cmd)
+    }
+
+    fn process_ids(&self) -> Vec<u32> {
+        let Ok(procs) = std::fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        procs
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .collect()
+    }
+
+    fn process_name(&self, pid: u32) -> Option<String> {
+        std::fs::read_to_string(format!("/proc/{pid}/comm"))
+            .ok()
+            .map(|comm| comm.trim().to_string())
+    }
+
+    fn open_files(&self, pid: u32) -> Vec<PathBuf> {
+        let Ok(fds) = std::fs::read_dir(format!("/proc/{pid}/fd")) else {
+            return Vec::new();
+        };
+        fds.flatten()
+            .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+            .collect()
+    }
+
+    fn filesystem_type(&self, path: &Path) -> Option<String> {
+        let path = std::fs::canonicalize(path).ok()?;
+        let mounts = std::fs::read_to_string(PROC_MOUNTS).ok()?;
+        mount_containing(&mounts, &path)
+    }
+
+    fn free_space(&self, path: &Path) -> Option<u64> {
+        let stat = nix::sys::statvfs::statvfs(path).ok()?;
+        // The counts are 32-bit on some targets.
+        #[allow(clippy::useless_conversion)]
+        Some(u64::from(stat.blocks_available()) * u64::from(stat.fragment_size()))
+    }
+
+    fn memory_dir(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(DEV_SHM)).filter(|dir| dir.is_dir())
+    }
+
+    fn lsblk(&self) -> Command {
+        Command::new("lsblk")
+    }
+
+    fn reread_partitions(&self, device: &str, elevator: Option<&str>) -> Command {
+        let mut cmd = elevated_command("partprobe", elevator);
+        cmd.arg(device);
+        cmd
+    }
+
+    fn wipe_signatures(&self, device: &str, elevator: Option<&str>) -> Command {
+        let mut cmd = elevated_command("wipefs", elevator);
+        cmd.args(["-a", device]);
+        cmd
+    }
+}
+
+/// Extract device names from the contents of `/proc/partitions`.
+///
+/// Skips the header line and any line that doesn't have the
+/// `major minor #blocks name` shape.
+#[cfg(target_os = "linux")]
+fn parse_proc_partitions(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [major, _, _, name] if major.parse::<u32>().is_ok() => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The filesystem type of the innermost mount in `mounts` (in the format of
+/// `/proc/self/mounts`) that contains the absolute `path`.
+#[cfg(target_os = "linux")]
+fn mount_containing(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mountpoint = fields.nth(1)?.replace("\\040", " ");
+            let fstype = fields.next()?;
+            Some((PathBuf::from(mountpoint), fstype))
+        })
+        .filter(|(mountpoint, _)| path.starts_with(mountpoint))
+        // Later mounts over the same point hide earlier ones.
+        .max_by_key(|(mountpoint, _)| mountpoint.components().count())
+        .map(|(_, fstype)| fstype.to_string())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_partitions_skips_header() {
+        let contents = "major minor  #blocks  name\n\n   8        0  60063744 sda\n   8        1     524288 sda1\n";
+        assert_eq!(parse_proc_partitions(contents), vec!["sda", "sda1"]);
+    }
+
+    #[test]
+    fn linux_paths_round_trip() {
+        let linux = Linux;
+        assert_eq!(linux.device_path("sdb"), "/dev/sdb");
+        assert_eq!(linux.device_name("/dev/sdb"), "sdb");
+        assert_eq!(linux.device_name("sdb"), "sdb");
+        assert_eq!(linux.partition_path("/dev/sdb", 1), "/dev/sdb1");
+        assert_eq!(linux.partition_path("/dev/nvme0n1", 1), "/dev/nvme0n1p1");
+        assert_eq!(linux.partition_path("/dev/mmcblk0", 2), "/dev/mmcblk0p2");
+
+        let mounts = "/dev/sda2 / ext4 rw 0 0\n\
+                      nas:/isos /mnt/my\\040isos nfs4 rw 0 0\n";
+        let isos = Path::new("/mnt/my isos/arch.iso");
+        assert_eq!(mount_containing(mounts, isos).as_deref(), Some("nfs4"));
+        let home = Path::new("/home/a/arch.iso");
+        assert_eq!(mount_containing(mounts, home).as_deref(), Some("ext4"));
+    }
+}
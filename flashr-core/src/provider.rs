@@ -0,0 +1,232 @@
+//! Where the device list and per-device details come from.
+//!
+//! The TUI asks a `DeviceProvider` for the disks it offers and for what it
+//! shows about them (size, partitions, holders, first sectors) instead of
+//! calling `device` and `flash` directly. `SystemDevices` answers from `lsblk`, sysfs, and
+//! `/proc`; `MockDevices` answers from fixed in-memory data, so the Device and
+//! Confirm steps can be tested without real disks. Another OS would add its
+//! own provider.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::device::{self, DeviceDetails, DeviceHolder, DevicePath, Disk, PartitionContent};
+use crate::error::DeviceError;
+use crate::flash::{self, DevicePartitionInfo};
+
+/// A source of disks and their details.
+pub trait DeviceProvider: Send {
+    /// Disks to offer, removable ones first; only removable disks unless
+    /// `show_all`. See `device::list`.
+    fn list(&self, show_all: bool) -> Result<Vec<Disk>>;
+
+    /// Capacity in bytes of the disk with kernel name `name` (e.g., "sdb").
+    fn size_bytes(&self, name: &str) -> Option<u64>;
+
+    /// Partitions on `device` (e.g., "/dev/sdb") and what they hold.
+    fn content_summary(&self, device: &str) -> Result<Vec<PartitionContent>>;
+
+    /// Processes that have `device` or one of its partitions open.
+    fn holders(&self, device: &str) -> Vec<DeviceHolder>;
+
+    /// Everything the details pane shows for `device`.
+    fn details(&self, device: &str) -> DeviceDetails;
+
+    /// Fail unless `path` (a kernel path or by-id link) still names an
+    /// attached block device.
+    fn ensure_attached(&self, path: &str) -> Result<(), DeviceError>;
+
+    /// Existing partitions on `device` for the wipe confirmation. See
+    /// `flash::check_device_partitions`.
+    fn partition_info(&self, device: &str) -> Result<DevicePartitionInfo>;
+}
+
+/// The real devices of this machine, through the functions in `device`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemDevices;
+
+impl DeviceProvider for SystemDevices {
+    fn list(&self, show_all: bool) -> Result<Vec<Disk>> {
+        device::list(show_all)
+    }
+
+    fn size_bytes(&self, name: &str) -> Option<u64> {
+        device::size_bytes(name)
+    }
+
+    fn content_summary(&self, device: &str) -> Result<Vec<PartitionContent>> {
+        device::content_summary(device)
+    }
+
+    fn holders(&self, device: &str) -> Vec<DeviceHolder> {
+        device::holders(device)
+    }
+
+    fn details(&self, device: &str) -> DeviceDetails {
+        DeviceDetails::load(device)
+    }
+
+    fn ensure_attached(&self, path: &str) -> Result<(), DeviceError> {
+        DevicePath::resolve(path).map(drop)
+    }
+
+    fn partition_info(&self, device: &str) -> Result<DevicePartitionInfo> {
+        flash::check_device_partitions(device)
+    }
+}
+
+/// Fixed devices for tests, built up with the `with_*` methods.
+///
+/// # Fields
+///
+/// * `disks` - Disks `list` returns (non-removable ones only with `show_all`)
+/// * `sizes` - Capacity in bytes by kernel name; unknown when missing
+/// * `contents` - Partitions by device path; none when missing
+/// * `holders` - Processes holding each device path open; none when missing
+#[derive(Debug, Clone, Default)]
+pub struct MockDevices {
+    pub disks: Vec<Disk>,
+    pub sizes: HashMap<String, u64>,
+    pub contents: HashMap<String, Vec<PartitionContent>>,
+    pub holders: HashMap<String, Vec<DeviceHolder>>,
+}
+
+impl MockDevices {
+    /// A disk named `name` (e.g., "sdb") with a model and capacity, listed
+    /// as removable or internal.
+    pub fn with_disk(mut self, name: &str, model: &str, bytes: u64, removable: bool) -> Self {
+        self.disks.push(Disk {
+            name: name.to_string(),
+            vendor: String::new(),
+            model: model.to_string(),
+            size: device::format_lsblk_size(bytes),
+            serial: None,
+            by_id: None,
+            removable,
+        });
+        self.sizes.insert(name.to_string(), bytes);
+        self
+    }
+
+    /// A partition on `device` (e.g., "/dev/sdb"), mounted at `mountpoint`
+    /// if given.
+    pub fn with_partition(
+        mut self,
+        device: &str,
+        partition: &str,
+        fstype: &str,
+        mountpoint: Option<&str>,
+    ) -> Self {
+        self.contents
+            .entry(device.to_string())
+            .or_default()
+            .push(PartitionContent {
+                name: partition.to_string(),
+                fstype: Some(fstype.to_string()),
+                label: None,
+                size: String::new(),
+                used: None,
+                mountpoint: mountpoint.map(str::to_string),
+            });
+        self
+    }
+
+    /// A process `command` with `pid` holding `device` open.
+    pub fn with_holder(mut self, device: &str, command: &str, pid: u32) -> Self {
+        self.holders
+            .entry(device.to_string())
+            .or_default()
+            .push(DeviceHolder {
+                pid,
+                command: command.to_string(),
+                path: device.to_string(),
+            });
+        self
+    }
+}
+
+impl DeviceProvider for MockDevices {
+    fn list(&self, show_all: bool) -> Result<Vec<Disk>> {
+        let mut disks: Vec<Disk> = self
+            .disks
+            .iter()
+            .filter(|disk| show_all || disk.removable)
+            .cloned()
+            .collect();
+        disks.sort_by_key(|disk| !disk.removable);
+        Ok(disks)
+    }
+
+    fn size_bytes(&self, name: &str) -> Option<u64> {
+        self.sizes.get(name).copied()
+    }
+
+    fn content_summary(&self, device: &str) -> Result<Vec<PartitionContent>> {
+        Ok(self.contents.get(device).cloned().unwrap_or_default())
+    }
+
+    fn holders(&self, device: &str) -> Vec<DeviceHolder> {
+        self.holders.get(device).cloned().unwrap_or_default()
+    }
+
+    fn details(&self, device: &str) -> DeviceDetails {
+        DeviceDetails {
+            device: device.to_string(),
+            partitions: self
+                .content_summary(device)
+                .map_err(|err| format!("{err:#}")),
+            // Mock disks read as blank.
+            first_sector: device::describe_first_sectors(&[0; 1024]),
+        }
+    }
+
+    fn ensure_attached(&self, path: &str) -> Result<(), DeviceError> {
+        if self.disks.iter().any(|disk| disk.matches_path(path)) {
+            Ok(())
+        } else {
+            Err(DeviceError::Detached {
+                path: path.to_string(),
+            })
+        }
+    }
+
+    fn partition_info(&self, device: &str) -> Result<DevicePartitionInfo> {
+        let contents = self.content_summary(device)?;
+        let mounted_paths: Vec<String> = contents
+            .iter()
+            .filter_map(|content| content.mountpoint.clone())
+            .collect();
+        Ok(DevicePartitionInfo {
+            has_partitions: !contents.is_empty(),
+            partition_details: contents.iter().map(PartitionContent::describe).collect(),
+            has_mounted: !mounted_paths.is_empty(),
+            mounted_paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_lists_removable_disks_first() {
+        let mock = MockDevices::default()
+            .with_disk("sda", "Internal SSD", 500_000_000_000, false)
+            .with_disk("sdb", "Cruzer", 16_000_000_000, true)
+            .with_partition("/dev/sdb", "/dev/sdb1", "vfat", Some("/media/usb"));
+
+        let names = |disks: Vec<Disk>| disks.into_iter().map(|d| d.name).collect::<Vec<_>>();
+        assert_eq!(names(mock.list(false).unwrap()), ["sdb"]);
+        assert_eq!(names(mock.list(true).unwrap()), ["sdb", "sda"]);
+        assert_eq!(mock.size_bytes("sdb"), Some(16_000_000_000));
+        assert_eq!(mock.size_bytes("sdc"), None);
+        assert_eq!(mock.details("/dev/sdb").partitions.unwrap().len(), 1);
+        assert!(mock.holders("/dev/sdb").is_empty());
+        assert!(mock.ensure_attached("/dev/sdb").is_ok());
+        assert!(mock.ensure_attached("/dev/sdc").is_err());
+        let info = mock.partition_info("/dev/sdb").unwrap();
+        assert!(info.has_partitions && info.has_mounted);
+    }
+}
@@ -0,0 +1,73 @@
+//! Shutting down cleanly on SIGINT, SIGTERM, and SIGHUP.
+//!
+//! Dying on the spot would leave an elevated `dd` writing to the device with
+//! nobody watching, so the first signal is only recorded. A running flash
+//! sees it through its `CancelFlag`, stops `dd`, and syncs; the TUI then
+//! restores the terminal, and the process exits with 128 plus the signal
+//! number, the code a shell reports for a process killed by that signal.
+//! A second signal exits at once, for when the cleanup itself hangs.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+/// Signals that end the program.
+const SIGNALS: [i32; 3] = [SIGINT, SIGTERM, SIGHUP];
+
+/// Whether a signal has arrived, and which one last did.
+static RECEIVED: OnceLock<(Arc<AtomicBool>, Arc<AtomicUsize>)> = OnceLock::new();
+
+/// Start recording signals instead of dying from them.
+///
+/// # Errors
+///
+/// Returns an error if a handler cannot be registered.
+pub fn install() -> Result<()> {
+    let (seen, last) = RECEIVED.get_or_init(Default::default);
+    for signal in SIGNALS {
+        // Handlers run in order: the shutdown sees the flag set only from the
+        // second signal on, and the signal number is stored before the flag.
+        signal_hook::flag::register_conditional_shutdown(signal, exit_code(signal), seen.clone())
+            .and_then(|_| signal_hook::flag::register_usize(signal, last.clone(), signal as usize))
+            .and_then(|_| signal_hook::flag::register(signal, seen.clone()))
+            .with_context(|| format!("install {} handler", name(signal)))?;
+    }
+    Ok(())
+}
+
+/// The signal that arrived, if any since `install`.
+pub fn received() -> Option<i32> {
+    let (seen, last) = RECEIVED.get()?;
+    seen.load(Ordering::Relaxed)
+        .then(|| last.load(Ordering::Relaxed) as i32)
+}
+
+/// Exit code for a run ended by `signal`.
+pub fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
+/// Conventional name of one of the handled signals.
+pub fn name(signal: i32) -> &'static str {
+    match signal {
+        SIGINT => "SIGINT",
+        SIGTERM => "SIGTERM",
+        SIGHUP => "SIGHUP",
+        _ => "a signal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_map_to_shell_exit_codes() {
+        assert_eq!(exit_code(SIGINT), 130);
+        assert_eq!(exit_code(SIGTERM), 143);
+        assert_eq!(name(SIGHUP), "SIGHUP");
+        assert_eq!(received(), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Copying the image into RAM before flashing it.
+//!
+//! Reading an image from a slow network mount stalls the device write
+//! whenever the mount stalls. When `FlashOptions::stage_dir` is set, the flash
+//! first copies the image into that directory (normally a tmpfs such as
+//! `/dev/shm`, see `Platform::memory_dir`) and writes and verifies the copy.
+//! The copy is its own `FlashPhase::Stage` and reports "done/total" bytes
+//! with `STAGE_PREFIX`. It is removed once the flash returns.
+//!
+//! A copy that would not fit, with `STAGE_MARGIN` to spare, is skipped and
+//! the image is read from where it is, as is one that fails part way.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+use crate::flash::CancelFlag;
+use crate::platform;
+
+/// Prefix of staging progress messages, followed by "done/total" bytes.
+pub const STAGE_PREFIX: &str = "Staged: ";
+
+/// Room left over in the staging directory after the copy, so staging does
+/// not fill the machine's RAM to the brim.
+pub const STAGE_MARGIN: u64 = 256 * 1024 * 1024;
+
+/// How often the copy reports progress.
+const STAGE_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Filesystem types whose reads go over the network.
+const NETWORK_FILESYSTEMS: [&str; 12] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.glusterfs",
+];
+
+/// Staged copies made by this process so far, keeping their directories apart.
+static STAGED: AtomicUsize = AtomicUsize::new(0);
+
+/// The filesystem type of `image` when it is on a network mount.
+pub fn network_filesystem(image: &Path) -> Option<String> {
+    platform::current()
+        .filesystem_type(image)
+        .filter(|fstype| NETWORK_FILESYSTEMS.contains(&fstype.as_str()))
+}
+
+/// Parse a staging progress message into (done, total) bytes.
+pub fn parse_stage_progress(line: &str) -> Option<(u64, u64)> {
+    let (done, total) = line.strip_prefix(STAGE_PREFIX)?.split_once('/')?;
+    Some((done.parse().ok()?, total.parse().ok()?))
+}
+
+/// A copy of the image in the staging directory, removed when dropped.
+#[derive(Debug)]
+pub struct StagedImage {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl StagedImage {
+    /// Copy `image` into a fresh directory under `dir`.
+    ///
+    /// The copy keeps the image's file name, so labels derived from it do not
+    /// change. Progress goes to `progress`; a set `cancel` stops the copy.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the image does not fit in `dir` with `STAGE_MARGIN` to
+    /// spare, or when copying fails or is cancelled.
+    pub fn create(
+        image: &Path,
+        dir: &Path,
+        progress: &mpsc::Sender<String>,
+        cancel: &CancelFlag,
+    ) -> Result<Self> {
+        let total = std::fs::metadata(image)
+            .with_context(|| format!("read image size: {}", image.display()))?
+            .len();
+        let free = platform::current()
+            .free_space(dir)
+            .with_context(|| format!("read free space of {}", dir.display()))?;
+        if free < total.saturating_add(STAGE_MARGIN) {
+            bail!(
+                "{} has {free} bytes free, too little for a {total}-byte image",
+                dir.display()
+            );
+        }
+
+        let name = image.file_name().context("image has no file name")?;
+        let count = STAGED.fetch_add(1, Ordering::Relaxed);
+        let dir = dir.join(format!("flashr-tui-{}-{count}", std::process::id()));
+        std::fs::create_dir(&dir).with_context(|| format!("create {}", dir.display()))?;
+        // From here on, dropping `staged` removes what was copied so far.
+        let staged = Self {
+            path: dir.join(name),
+            dir,
+        };
+        debug!("staging {} at {}", image.display(), staged.path.display());
+
+        let mut source =
+            File::open(image).with_context(|| format!("open image: {}", image.display()))?;
+        let mut copy = File::create(&staged.path)
+            .with_context(|| format!("create {}", staged.path.display()))?;
+        let mut buf = vec![0u8; 4 * 1024 * 1024];
+        let mut done = 0;
+        let mut last_report = Instant::now();
+        let _ = progress.send(format!("{STAGE_PREFIX}0/{total}"));
+        loop {
+            let n = source.read(&mut buf).context("read image")?;
+            if n == 0 {
+                break;
+            }
+            copy.write_all(&buf[..n])
+                .with_context(|| format!("write {}", staged.path.display()))?;
+            done += n as u64;
+            if last_report.elapsed() >= STAGE_REPORT_INTERVAL {
+                last_report = Instant::now();
+                let _ = progress.send(format!("{STAGE_PREFIX}{done}/{total}"));
+            }
+            if cancel.is_cancelled() {
+                bail!("staging cancelled");
+            }
+        }
+        if done != total {
+            bail!("image changed size while staging ({done} of {total} bytes)");
+        }
+        let _ = progress.send(format!("{STAGE_PREFIX}{done}/{total}"));
+        Ok(staged)
+    }
+
+    /// Path of the copy.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for StagedImage {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.dir) {
+            warn!("remove staged image {}: {err}", self.dir.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_copy_matches_and_is_removed() {
+        let dir = std::env::temp_dir().join(format!("flashr-stage-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("arch.iso");
+        std::fs::write(&image, b"hybrid image").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let staged = StagedImage::create(&image, &dir, &tx, &CancelFlag::default()).unwrap();
+        assert_eq!(staged.path().file_name(), image.file_name());
+        assert_ne!(staged.path(), image);
+        assert_eq!(std::fs::read(staged.path()).unwrap(), b"hybrid image");
+        let last = rx.try_iter().last().unwrap();
+        assert_eq!(parse_stage_progress(&last), Some((12, 12)));
+
+        let copy = staged.path().to_path_buf();
+        drop(staged);
+        assert!(!copy.exists());
+        assert!(image.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,499 @@
+//! Writing an image to a device while it downloads.
+//!
+//! `flash_stream_with_progress` takes the image from a reader, normally an
+//! HTTP download, instead of a file, so flashing a 5 GB image needs no 5 GB
+//! of local disk. The bytes are hashed on their way to the device, with
+//! `FlashOptions::hash` and with the algorithm of `FlashOptions::checksum`
+//! when that differs. After the sync, the digests are compared with the
+//! published checksum and with the device read back.
+//!
+//! A stream can only be read once. A download that does not match its
+//! checksum is therefore caught after it is written, and the flash fails so
+//! the stick is not trusted. Whatever else needs the image file is left out:
+//! staging, the journal (and so resuming), and the hooks.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+
+use crate::backend::{copied_line, dd_block_size, BackendKind, DdSupport, NATIVE_REPORT_INTERVAL};
+use crate::checksum::{Algorithm, Hasher};
+use crate::device::{format_lsblk_size, DevicePath};
+use crate::error::FlashError;
+use crate::flash::{
+    acquire_elevator, describe_command, elevated_command, ensure_device_not_held,
+    ensure_device_safe, is_root, label_base, label_device_post_flash, send_phase, stay_awake,
+    terminate, wipe_device_if_needed, FlashOptions, FlashPhase, COMMAND_PREFIX, ELEVATION_PREFIX,
+    IMAGE_HASH_PREFIX, VERDICT_PREFIX, VERIFY_PREFIX, VERIFY_REPORT_INTERVAL,
+};
+use crate::iso::{self, IsoKind};
+
+/// Flash the image read from `source` to `device`, like
+/// `flash::flash_image_with_progress` flashes an image file.
+///
+/// # Arguments
+///
+/// * `source` - The image, read once from start to end
+/// * `name` - File name of the image (e.g., "debian-12.iso"), looked up in a
+///   checksum list and used for the volume label
+/// * `device` - Device path (e.g., "/dev/sdb") or stable `/dev/disk/by-id/...` link
+/// * `progress` - Channel to send progress messages to
+/// * `options` - As for an image file, except that the hooks, `journal_dir`,
+///   `resume`, and `stage_dir` are ignored
+///
+/// # Errors
+///
+/// Returns a `FlashError` like `flash_image_with_progress` does, and also
+/// when reading `source` fails part way. The device then holds part of the
+/// image. A mismatch with `options.checksum` is found after the write.
+pub fn flash_stream_with_progress(
+    source: &mut dyn Read,
+    name: &str,
+    device: &str,
+    progress: mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<(), FlashError> {
+    info!(
+        "streaming {name} to {device} with the {} backend",
+        options.backend.name()
+    );
+    let result = stream_checked(source, name, device, &progress, options);
+    match &result {
+        Ok(()) => info!("stream of {name} to {device} finished"),
+        Err(err) => error!("stream of {name} to {device} failed: {err:#}"),
+    }
+    result
+}
+
+/// The checks, write, verification, and labeling of `flash_stream_with_progress`.
+fn stream_checked(
+    source: &mut dyn Read,
+    name: &str,
+    device: &str,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<(), FlashError> {
+    // The start of the image decides whether it may be written at all, so it
+    // is read before the device is touched.
+    let mut header = Vec::with_capacity(iso::HEADER_LEN);
+    source
+        .take(iso::HEADER_LEN as u64)
+        .read_to_end(&mut header)
+        .context("read image")?;
+    match iso::detect_header(&header) {
+        IsoKind::Hybrid => {}
+        IsoKind::NonHybrid => return Err(FlashError::NotHybrid),
+        IsoKind::Unknown => return Err(FlashError::UnknownImageType),
+    }
+    let image = Path::new(name);
+    let expected = options
+        .checksum
+        .as_ref()
+        .map(|checksum| checksum.expected_for(image))
+        .transpose()?;
+
+    let device_path = DevicePath::resolve(device)?;
+    let device = device_path.as_str();
+    ensure_device_safe(device, options.confirmed_wipe)?;
+    ensure_device_not_held(device)?;
+    options.cancel.check()?;
+    let _awake = stay_awake(&format!("Flashing {name}"), progress);
+    if options.pre_hook.is_some() || options.label_hook.is_some() || options.post_hook.is_some() {
+        let _ = progress.send("Hooks are not run for a streamed image.".to_string());
+    }
+
+    let elevator = match options.backend {
+        BackendKind::Dd => acquire_elevator(progress)?,
+        BackendKind::Native if is_root() => None,
+        BackendKind::Native => {
+            return Err(FlashError::Other(anyhow::anyhow!(
+                "The native backend writes the device itself and needs root. \
+                 Run with sudo, or use --backend dd."
+            )))
+        }
+    };
+    let _ = progress.send(format!(
+        "{ELEVATION_PREFIX}{}",
+        elevator.unwrap_or("none (running as root)")
+    ));
+    wipe_device_if_needed(device, elevator, progress)?;
+    options.cancel.check()?;
+
+    let mut hashers = vec![(options.hash, options.hash.hasher()?)];
+    if let Some((algorithm, _)) = &expected {
+        if *algorithm != options.hash {
+            hashers.push((*algorithm, algorithm.hasher()?));
+        }
+    }
+    send_phase(progress, FlashPhase::Write);
+    let mut source = header.as_slice().chain(source);
+    let written = match options.backend {
+        BackendKind::Dd => write_with_dd(
+            &mut source,
+            name,
+            device,
+            elevator,
+            &mut hashers,
+            progress,
+            options,
+        )?,
+        BackendKind::Native => {
+            write_natively(&mut source, name, device, &mut hashers, progress, options)?
+        }
+    };
+    let mut digests = Vec::new();
+    for (algorithm, hasher) in hashers {
+        digests.push((algorithm, hasher.finish()?));
+    }
+    let digest = |algorithm: Algorithm| {
+        digests
+            .iter()
+            .find(|(a, _)| *a == algorithm)
+            .map(|(_, hex)| hex.clone())
+            .unwrap_or_default()
+    };
+
+    send_phase(progress, FlashPhase::Sync);
+    let _ = progress.send("Syncing...".to_string());
+    Command::new("sync").status().ok();
+
+    if let Some((algorithm, expected)) = expected {
+        let actual = digest(algorithm);
+        if actual != expected {
+            return Err(FlashError::ChecksumMismatch {
+                image: PathBuf::from(name),
+                algorithm,
+                expected,
+                actual,
+            });
+        }
+        let _ = progress.send("Image checksum matches.".to_string());
+    }
+
+    send_phase(progress, FlashPhase::Verify);
+    let _ = progress.send("Verifying flash integrity...".to_string());
+    let image_hex = digest(options.hash);
+    let _ = progress.send(format!(
+        "{IMAGE_HASH_PREFIX}{}:{image_hex}",
+        options.hash.name()
+    ));
+    let verified = read_back(device, written, elevator, progress, options);
+    options.cancel.check()?;
+    match verified {
+        Ok(device_hex) if device_hex == image_hex => {
+            info!("{device}: verification passed ({image_hex})");
+            let _ = progress.send(format!(
+                "{VERDICT_PREFIX}passed: {} checksums match.",
+                options.hash.label()
+            ));
+        }
+        Ok(_) => return Err(FlashError::VerificationMismatch),
+        Err(e) if options.require_verification => {
+            return Err(FlashError::VerificationUnavailable(e));
+        }
+        Err(e) => {
+            warn!("{device}: verification skipped: {e:#}");
+            let _ = progress.send(format!("{VERDICT_PREFIX}skipped: {e}"));
+        }
+    }
+
+    // Labeling failures are reported but do not fail the flash.
+    send_phase(progress, FlashPhase::PostFlash);
+    let label = label_base(image, options.label.as_deref());
+    if let Ok(Some(message)) = label_device_post_flash(&label, device, elevator) {
+        let _ = progress.send(message);
+    }
+    Ok(())
+}
+
+/// Feed `source` to an (elevated) `dd` writing `device`.
+///
+/// # Returns
+///
+/// The number of bytes written.
+fn write_with_dd(
+    source: &mut dyn Read,
+    name: &str,
+    device: &str,
+    elevator: Option<&str>,
+    hashers: &mut [(Algorithm, Hasher)],
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<u64, FlashError> {
+    let support = DdSupport::detect();
+    let mut cmd = elevated_command("dd", elevator);
+    cmd.arg(format!("of={device}"))
+        .arg(format!("bs={}", dd_block_size(options.block_size())));
+    if support.quiet {
+        cmd.arg("status=none");
+    }
+    cmd.arg(if support.sync_output {
+        "oflag=sync"
+    } else {
+        "conv=fsync"
+    });
+    let command = describe_command(&cmd);
+    debug!("running {command} < {name}");
+    let _ = progress.send(format!("{COMMAND_PREFIX}{command} < {name}"));
+
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("run dd (do you have permission?)")?;
+    let mut input = child.stdin.take().context("dd has no input")?;
+    let mut dd_stopped = false;
+    let copied = copy_hashing(
+        source,
+        &mut |chunk| input.write_all(chunk).inspect_err(|_| dd_stopped = true),
+        hashers,
+        progress,
+        options,
+    );
+    drop(input);
+    if copied.is_err() && !dd_stopped {
+        terminate(&mut child, elevator);
+    }
+    let status = child.wait().context("wait for dd")?;
+    if options.cancel.is_cancelled() {
+        // Leave the device quiet, with what was written so far on it.
+        Command::new("sync").status().ok();
+        return Err(FlashError::Cancelled);
+    }
+    if !status.success() {
+        let mut output = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut output);
+        }
+        for line in output.lines().filter(|line| !line.trim().is_empty()) {
+            let _ = progress.send(line.trim().to_string());
+        }
+        // A download that failed first says more than the dd it stopped.
+        if dd_stopped || copied.is_ok() {
+            warn!("dd to {device} exited with {status}");
+            return Err(FlashError::DdFailed(status));
+        }
+    }
+    copied
+}
+
+/// Write `source` to `device` in-process, syncing every block like the
+/// native backend.
+///
+/// # Returns
+///
+/// The number of bytes written.
+fn write_natively(
+    source: &mut dyn Read,
+    name: &str,
+    device: &str,
+    hashers: &mut [(Algorithm, Hasher)],
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<u64, FlashError> {
+    let _ = progress.send(format!(
+        "{COMMAND_PREFIX}native copy of {name} to {device} ({} chunks, synced)",
+        format_lsblk_size(options.block_size())
+    ));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(device)
+        .with_context(|| format!("open {device} for writing"))?;
+    let written = copy_hashing(
+        source,
+        &mut |chunk| file.write_all(chunk).and_then(|()| file.sync_data()),
+        hashers,
+        progress,
+        options,
+    )?;
+    file.sync_all().with_context(|| format!("sync {device}"))?;
+    Ok(written)
+}
+
+/// Copy `source` into `write` block by block, hashing each block with every
+/// one of `hashers` and sending dd-style progress lines.
+///
+/// # Returns
+///
+/// The number of bytes copied.
+fn copy_hashing(
+    source: &mut dyn Read,
+    write: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+    hashers: &mut [(Algorithm, Hasher)],
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<u64, FlashError> {
+    let mut buf = vec![0u8; options.block_size() as usize];
+    let mut done = 0u64;
+    let start = Instant::now();
+    let mut last_report = start;
+    loop {
+        options.cancel.check()?;
+        // A download arrives in small pieces; whole blocks keep the writes
+        // as large as with an image file.
+        let mut read = 0;
+        while read < buf.len() {
+            match source.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(anyhow::Error::new(err).context("read image").into()),
+            }
+        }
+        if read == 0 {
+            break;
+        }
+        for (_, hasher) in hashers.iter_mut() {
+            hasher.update(&buf[..read])?;
+        }
+        write(&buf[..read]).context("write device")?;
+        done += read as u64;
+        if last_report.elapsed() >= NATIVE_REPORT_INTERVAL {
+            last_report = Instant::now();
+            let _ = progress.send(copied_line(done, start.elapsed()));
+        }
+    }
+    let _ = progress.send(copied_line(done, start.elapsed()));
+    debug!("streamed {done} bytes in {:?}", start.elapsed());
+    Ok(done)
+}
+
+/// Read the first `len` bytes of `device` back and hash them with `options.hash`.
+fn read_back(
+    device: &str,
+    len: u64,
+    elevator: Option<&str>,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<String> {
+    let _ = progress.send("Verifying: reading back from device...".to_string());
+    if options.backend == BackendKind::Native {
+        let mut file =
+            File::open(device).with_context(|| format!("open {device} for verification read"))?;
+        return hash_prefix(&mut file, len, progress, options);
+    }
+    let mut cmd = elevated_command("dd", elevator);
+    cmd.arg(format!("if={device}"))
+        .arg("bs=1M")
+        .arg(format!("count={}", len.div_ceil(1024 * 1024)));
+    if DdSupport::detect().quiet {
+        cmd.arg("status=none");
+    }
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("spawn dd for verification read")?;
+    let hashed = match child.stdout.take() {
+        Some(mut stdout) => hash_prefix(&mut stdout, len, progress, options),
+        None => Err(anyhow::anyhow!("dd for verification read has no output")),
+    };
+    if options.cancel.is_cancelled() {
+        terminate(&mut child, elevator);
+    }
+    let _ = child.wait();
+    hashed
+}
+
+/// Hash the first `len` bytes of `reader` with `options.hash`, reporting
+/// "done/len" with `VERIFY_PREFIX`. Stops early when the flash is cancelled.
+fn hash_prefix(
+    reader: &mut dyn Read,
+    len: u64,
+    progress: &mpsc::Sender<String>,
+    options: &FlashOptions,
+) -> Result<String> {
+    let mut hasher = options.hash.hasher()?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut done = 0;
+    let mut last_report = Instant::now();
+    while done < len && !options.cancel.is_cancelled() {
+        let to_read = buf.len().min((len - done) as usize);
+        let n = reader
+            .read(&mut buf[..to_read])
+            .context("read device for hash")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n])?;
+        done += n as u64;
+        if last_report.elapsed() >= VERIFY_REPORT_INTERVAL {
+            last_report = Instant::now();
+            let _ = progress.send(format!("{VERIFY_PREFIX}{done}/{len}"));
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_bytes_are_written_whole_and_hashed_on_the_way() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let options = FlashOptions {
+            block_size: Some(4096),
+            ..FlashOptions::default()
+        };
+        let mut hashers = vec![
+            (Algorithm::Sha256, Algorithm::Sha256.hasher().unwrap()),
+            (Algorithm::Sha512, Algorithm::Sha512.hasher().unwrap()),
+        ];
+        // A source handing out a few bytes at a time, like a slow download.
+        let mut trickle = TrickleReader(&data);
+        let mut device = Vec::new();
+        let mut writes = Vec::new();
+        let (tx, rx) = mpsc::channel();
+        let written = copy_hashing(
+            &mut trickle,
+            &mut |chunk| {
+                writes.push(chunk.len());
+                device.extend_from_slice(chunk);
+                Ok(())
+            },
+            &mut hashers,
+            &tx,
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(device, data);
+        assert_eq!(writes, [4096, 4096, 1808]);
+        assert!(rx.try_iter().any(|line| line.starts_with("10000 bytes")));
+        let digests: Vec<String> = hashers
+            .into_iter()
+            .map(|(_, hasher)| hasher.finish().unwrap())
+            .collect();
+        let mut sha256 = Algorithm::Sha256.hasher().unwrap();
+        sha256.update(&data).unwrap();
+        assert_eq!(digests[0], sha256.finish().unwrap());
+
+        // The read-back hashes only as much as was written.
+        let mut longer = data.clone();
+        longer.extend_from_slice(&[0xff; 5000]);
+        let options = FlashOptions::default();
+        let hex = hash_prefix(&mut longer.as_slice(), data.len() as u64, &tx, &options).unwrap();
+        assert_eq!(hex, digests[0]);
+    }
+
+    /// Reads at most 700 bytes at a time.
+    struct TrickleReader<'a>(&'a [u8]);
+
+    impl Read for TrickleReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(700);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+}
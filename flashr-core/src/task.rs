@@ -0,0 +1,181 @@
+//! Long-running operations on a worker thread, with streamed progress.
+//!
+//! Flashing, wiping, formatting, and the device tests all run the same way:
+//! the work gets a `Sender<String>` for progress lines and returns one result.
+//! A `Task` owns both ends, so a frontend either polls it from its event loop
+//! without blocking (`poll`) or waits for it while printing progress (`wait`).
+//! Several tasks can run at once; each has its own channel.
+//!
+//! An event loop that sleeps until something happens starts its tasks with
+//! `spawn_waking`: the task then calls a `Wake` after every progress line
+//! and once its result is ready, so the loop never has to poll on a timer.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+
+/// Called from a background thread when there is something new to poll.
+pub type Wake = Arc<dyn Fn() + Send + Sync>;
+
+/// A `Wake` that does nothing, for frontends that poll on a timer or block.
+pub fn no_wake() -> Wake {
+    Arc::new(|| {})
+}
+
+/// A worker thread that streams progress lines and then yields a `T`.
+///
+/// A second thread relays the progress lines and then the result, calling
+/// the task's `Wake` for each, so the result arrives after the last line.
+#[derive(Debug)]
+pub struct Task<T> {
+    name: &'static str,
+    progress: Receiver<String>,
+    result: Receiver<std::thread::Result<T>>,
+}
+
+/// A task's worker thread panicked instead of returning a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Panicked(pub &'static str);
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} thread panicked", self.0)
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+impl<T: Send + 'static> Task<T> {
+    /// Run `work` on a new thread named `name` (e.g., "flash"), handing it
+    /// the sender for its progress lines.
+    pub fn spawn<F>(name: &'static str, work: F) -> Self
+    where
+        F: FnOnce(Sender<String>) -> T + Send + 'static,
+    {
+        Self::spawn_waking(name, no_wake(), work)
+    }
+
+    /// Like `spawn`, calling `wake` after each progress line and once the
+    /// result can be collected.
+    pub fn spawn_waking<F>(name: &'static str, wake: Wake, work: F) -> Self
+    where
+        F: FnOnce(Sender<String>) -> T + Send + 'static,
+    {
+        let (line_tx, lines) = mpsc::channel();
+        let (progress_tx, progress) = mpsc::channel();
+        let (result_tx, result) = mpsc::channel();
+        let worker = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || work(line_tx))
+            .expect("failed to spawn thread");
+        std::thread::Builder::new()
+            .name(format!("{name} progress"))
+            .spawn(move || {
+                // Ends once the worker has returned (or panicked) and dropped
+                // its sender.
+                for line in lines {
+                    let _ = progress_tx.send(line);
+                    wake();
+                }
+                let _ = result_tx.send(worker.join());
+                wake();
+            })
+            .expect("failed to spawn thread");
+        Self {
+            name,
+            progress,
+            result,
+        }
+    }
+}
+
+impl<T> Task<T> {
+    /// Progress lines sent since the last call, and the result once the work
+    /// has finished. Never blocks; the result is returned only once.
+    pub fn poll(&mut self) -> (Vec<String>, Option<Result<T, Panicked>>) {
+        // Check for the result before draining: every line is relayed before
+        // it, so none sent just before the work finished is left behind.
+        let result = match self.result.try_recv() {
+            Ok(result) => Some(self.finish(result)),
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        };
+        let lines = self.progress.try_iter().collect();
+        (lines, result)
+    }
+
+    /// Block until the work finishes, passing each progress line to `on_line`
+    /// as it arrives.
+    pub fn wait(self, mut on_line: impl FnMut(String)) -> Result<T, Panicked> {
+        for line in &self.progress {
+            on_line(line);
+        }
+        match self.result.recv() {
+            Ok(result) => self.finish(result),
+            Err(_) => Err(Panicked(self.name)),
+        }
+    }
+
+    fn finish(&self, result: std::thread::Result<T>) -> Result<T, Panicked> {
+        result.map_err(|_| Panicked(self.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_stream_progress_then_return_once() {
+        let task = Task::spawn("test", |progress| {
+            progress.send("one".to_string()).unwrap();
+            progress.send("two".to_string()).unwrap();
+            7
+        });
+        let mut lines = Vec::new();
+        assert_eq!(task.wait(|line| lines.push(line)), Ok(7));
+        assert_eq!(lines, ["one", "two"]);
+
+        let mut task = Task::spawn("test", |progress| {
+            progress.send("done".to_string()).unwrap();
+        });
+        let mut lines = Vec::new();
+        let result = loop {
+            let (mut new, result) = task.poll();
+            lines.append(&mut new);
+            if let Some(result) = result {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result, Ok(()));
+        assert_eq!(lines, ["done"]);
+        assert!(task.poll().1.is_none());
+
+        let task: Task<()> = Task::spawn("test", |_| panic!("boom"));
+        assert_eq!(
+            task.wait(|_| {}).unwrap_err().to_string(),
+            "test thread panicked"
+        );
+    }
+
+    #[test]
+    fn waking_tasks_wake_per_line_and_once_done() {
+        let (wakes_tx, wakes) = mpsc::channel();
+        let wakes_tx = std::sync::Mutex::new(wakes_tx);
+        let wake: Wake = Arc::new(move || wakes_tx.lock().unwrap().send(()).unwrap());
+        let mut task = Task::spawn_waking("test", wake, |progress| {
+            progress.send("one".to_string()).unwrap();
+            progress.send("two".to_string()).unwrap();
+            7
+        });
+        for _ in 0..3 {
+            wakes.recv().unwrap();
+        }
+        // The last wake comes with the result, after every line.
+        assert_eq!(
+            task.poll(),
+            (vec!["one".to_string(), "two".to_string()], Some(Ok(7)))
+        );
+        assert!(wakes.try_recv().is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Mount management through UDisks2.
+//!
+//! On a desktop session, unprivileged users may unmount, mount, and power off
+//! removable drives through the UDisks2 daemon without extra authentication.
+//! Going through UDisks2 (via its `udisksctl` D-Bus client) instead of
+//! elevating `umount`/`eject` avoids a password prompt per partition and keeps
+//! the session's automounter in sync with what happened to the device.
+
+use anyhow::{Context, Result};
+use std::process::Command;
+
+use crate::flash::is_root;
+
+/// Whether UDisks2 should be used for mount management.
+///
+/// True when running unprivileged and `udisksctl` is installed. Root sessions
+/// talk to the kernel directly instead.
+pub fn available() -> bool {
+    !is_root() && which::which("udisksctl").is_ok()
+}
+
+/// Unmount a filesystem by block device (e.g., "/dev/sdb1").
+///
+/// # Errors
+///
+/// Returns an error if `udisksctl` cannot be run or UDisks2 refuses the unmount.
+pub fn unmount(device: &str) -> Result<()> {
+    run_udisksctl(&["unmount", "--no-user-interaction", "-b", device]).map(|_| ())
+}
+
+/// Mount a filesystem by block device and return its mount point.
+///
+/// # Errors
+///
+/// Returns an error if `udisksctl` cannot be run or UDisks2 refuses the mount.
+pub fn mount(device: &str) -> Result<String> {
+    let stdout = run_udisksctl(&["mount", "--no-user-interaction", "-b", device])?;
+    parse_mount_point(&stdout)
+        .ok_or_else(|| anyhow::anyhow!("udisksctl did not report a mount point for {device}"))
+}
+
+/// Power off a whole drive so it can be unplugged safely.
+///
+/// UDisks2 unmounts any remaining filesystems on the drive first.
+///
+/// # Errors
+///
+/// Returns an error if `udisksctl` cannot be run or UDisks2 refuses the request.
+pub fn power_off(device: &str) -> Result<()> {
+    run_udisksctl(&["power-off", "--no-user-interaction", "-b", device]).map(|_| ())
+}
+
+/// Run `udisksctl` and return its stdout, turning failures into errors with stderr attached.
+fn run_udisksctl(args: &[&str]) -> Result<String> {
+    let output = Command::new("udisksctl")
+        .args(args)
+        .output()
+        .context("run udisksctl")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            return Err(anyhow::anyhow!("udisksctl {} failed", args[0]));
+        }
+        return Err(anyhow::anyhow!("udisksctl {} failed: {stderr}", args[0]));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Extract the mount point from `udisksctl mount` output.
+///
+/// The output looks like "Mounted /dev/sdb1 at /run/media/user/LABEL" (older
+/// releases append a trailing period).
+fn parse_mount_point(stdout: &str) -> Option<String> {
+    let line = stdout.lines().find(|line| line.starts_with("Mounted "))?;
+    let (_, path) = line.split_once(" at ")?;
+    let path = path.trim();
+    let path = path.strip_suffix('.').unwrap_or(path);
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mount_point_handles_both_output_styles() {
+        assert_eq!(
+            parse_mount_point("Mounted /dev/sdb1 at /run/media/me/NIXOS.\n").as_deref(),
+            Some("/run/media/me/NIXOS")
+        );
+        assert_eq!(
+            parse_mount_point("Mounted /dev/sdb1 at /media/me/DATA\n").as_deref(),
+            Some("/media/me/DATA")
+        );
+        assert_eq!(parse_mount_point("Error mounting /dev/sdb1"), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Bounded history of progress and status messages for the log pane.
+//!
+//! Progress lines and status messages normally overwrite each other on screen;
+//! the activity log keeps the most recent ones, each stamped with the time
+//! since startup, so what led up to an error can still be read afterwards.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Number of messages kept before the oldest are dropped.
+pub const LOG_CAPACITY: usize = 1000;
+
+/// Ring buffer of timestamped messages.
+///
+/// # Fields
+///
+/// * `started` - When the log was created; entries are stamped relative to it
+/// * `entries` - Stamped messages, oldest first
+/// * `last_status` - Last status message recorded by `note_status`
+/// * `total` - Number of messages recorded so far, including dropped ones
+#[derive(Debug)]
+pub struct ActivityLog {
+    started: Instant,
+    entries: VecDeque<String>,
+    last_status: String,
+    total: usize,
+}
+
+impl Default for ActivityLog {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            entries: VecDeque::new(),
+            last_status: String::new(),
+            total: 0,
+        }
+    }
+}
+
+impl ActivityLog {
+    /// Record a message, dropping the oldest once `LOG_CAPACITY` is reached.
+    pub fn push(&mut self, message: &str) {
+        let message = message.trim();
+        if message.is_empty() {
+            return;
+        }
+        if self.entries.len() == LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        let elapsed = self.started.elapsed().as_secs();
+        self.entries.push_back(format!(
+            "+{:02}:{:02} {message}",
+            elapsed / 60,
+            elapsed % 60
+        ));
+        self.total += 1;
+    }
+
+    /// Record the status message if it changed since the last call.
+    pub fn note_status(&mut self, status: &str) {
+        if status != self.last_status {
+            self.last_status = status.to_string();
+            self.push(status);
+        }
+    }
+
+    /// Recorded messages, oldest first.
+    pub fn entries(&self) -> &VecDeque<String> {
+        &self.entries
+    }
+
+    /// Number of messages recorded so far, including ones already dropped.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Messages recorded after the first `seen`, oldest first; ones already
+    /// dropped are skipped.
+    pub fn since(&self, seen: usize) -> impl Iterator<Item = &String> {
+        let new = self.total.saturating_sub(seen);
+        self.entries
+            .iter()
+            .skip(self.entries.len().saturating_sub(new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_drops_oldest_and_skips_blank_lines() {
+        let mut log = ActivityLog::default();
+        for i in 0..LOG_CAPACITY + 5 {
+            log.push(&format!("line {i}"));
+        }
+        log.push("   ");
+        assert_eq!(log.entries().len(), LOG_CAPACITY);
+        assert_eq!(log.entries().front().unwrap(), "+00:00 line 5");
+    }
+
+    #[test]
+    fn note_status_records_changes_only() {
+        let mut log = ActivityLog::default();
+        log.note_status("Rescanning");
+        log.note_status("Rescanning");
+        log.note_status("");
+        log.note_status("Device list updated.");
+        assert_eq!(log.entries().len(), 2);
+    }
+
+    #[test]
+    fn since_returns_only_newer_messages() {
+        let mut log = ActivityLog::default();
+        log.push("first");
+        let seen = log.total();
+        log.push("second");
+        log.push("third");
+        let newer: Vec<&String> = log.since(seen).collect();
+        assert_eq!(newer, ["+00:00 second", "+00:00 third"]);
+        assert_eq!(log.since(log.total()).count(), 0);
+    }
+}
@@ -0,0 +1,99 @@
+//! Audit trail of flashes in the system log.
+//!
+//! Each flash records who started it, which image went to which devices, and
+//! how it ended, so admins of shared machines can tell who wrote what to which
+//! disk. Messages go to the local syslog socket, which journald also serves;
+//! when there is no socket (containers, minimal systems) nothing is logged
+//! and the flash is unaffected.
+
+use std::fmt::Display;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+
+/// Socket served by syslog daemons and journald.
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Identifier the messages are tagged with.
+const TAG: &str = "flashr-tui";
+
+/// Syslog priorities: the "user" facility with "notice" or "err" severity.
+const PRIORITY_NOTICE: u8 = 8 + 5;
+const PRIORITY_ERR: u8 = 8 + 3;
+
+/// Record that a flash of `image` to `devices` is starting.
+pub fn flash_started(image: &Path, devices: &[String]) {
+    send(PRIORITY_NOTICE, &started_message(image, devices, &user()));
+}
+
+/// Record how a flash of `image` to `devices` ended.
+pub fn flash_finished<E: Display>(image: &Path, devices: &[String], result: &Result<(), E>) {
+    let priority = if result.is_ok() {
+        PRIORITY_NOTICE
+    } else {
+        PRIORITY_ERR
+    };
+    send(priority, &finished_message(image, devices, &user(), result));
+}
+
+/// The person behind the flash: the user who invoked sudo, if any, since the
+/// process itself then runs as root.
+fn user() -> String {
+    ["SUDO_USER", "USER"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("uid {}", nix::unistd::getuid()))
+}
+
+fn started_message(image: &Path, devices: &[String], user: &str) -> String {
+    format!(
+        "flash started: image={:?} devices={:?} user={user:?}",
+        image.display().to_string(),
+        devices.join(",")
+    )
+}
+
+fn finished_message<E: Display>(
+    image: &Path,
+    devices: &[String],
+    user: &str,
+    result: &Result<(), E>,
+) -> String {
+    let outcome = match result {
+        Ok(()) => "flash succeeded".to_string(),
+        Err(err) => format!("flash failed ({err})"),
+    };
+    format!(
+        "{outcome}: image={:?} devices={:?} user={user:?}",
+        image.display().to_string(),
+        devices.join(",")
+    )
+}
+
+/// Send one message to the syslog socket, ignoring failures.
+fn send(priority: u8, message: &str) {
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    let line = format!("<{priority}>{TAG}[{}]: {message}", std::process::id());
+    let _ = socket.send_to(line.as_bytes(), SYSLOG_SOCKET);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_name_image_devices_user_and_outcome() {
+        let image = Path::new("/isos/arch linux.iso");
+        let devices = ["/dev/sdb".to_string(), "/dev/sdc".to_string()];
+        assert_eq!(
+            started_message(image, &devices, "alice"),
+            r#"flash started: image="/isos/arch linux.iso" devices="/dev/sdb,/dev/sdc" user="alice""#
+        );
+        assert_eq!(
+            finished_message(image, &devices[..1], "alice", &Err("dd failed".to_string())),
+            r#"flash failed (dd failed): image="/isos/arch linux.iso" devices="/dev/sdb" user="alice""#
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Short read/write benchmark for a target device.
+//!
+//! Times a fixed-size sequential read (and optionally a destructive write) with
+//! `dd` using direct I/O, so users can spot slow, dying, or counterfeit sticks
+//! before trusting them with an installer. Privileged commands are elevated the
+//! same way as the flash engine.
+
+use anyhow::{Context, Result};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::device::DevicePath;
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, unmount_partitions,
+};
+
+/// Block size used for benchmark transfers, in MiB.
+const BENCH_BLOCK_MIB: u64 = 4;
+
+/// Number of blocks transferred per benchmark pass (64 MiB total).
+const BENCH_BLOCKS: u64 = 16;
+
+/// Outcome of a device benchmark.
+///
+/// # Fields
+///
+/// * `bytes` - Number of bytes transferred per pass
+/// * `read_mbps` - Sequential read throughput in MB/s
+/// * `write_mbps` - Sequential write throughput in MB/s (`None` for read-only runs)
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub bytes: u64,
+    pub read_mbps: f64,
+    pub write_mbps: Option<f64>,
+}
+
+impl BenchResult {
+    /// One-line human-readable summary, e.g. "read 31.2 MB/s, write 9.8 MB/s".
+    pub fn summary(&self) -> String {
+        match self.write_mbps {
+            Some(write) => format!("read {:.1} MB/s, write {:.1} MB/s", self.read_mbps, write),
+            None => format!("read {:.1} MB/s (write not tested)", self.read_mbps),
+        }
+    }
+}
+
+/// Run a benchmark against a device.
+///
+/// Always measures a 64 MiB sequential read from the start of the device.
+/// When `destructive` is true, first writes 64 MiB of zeros to the start of the
+/// device, which destroys its partition table and any data in that range; a
+/// device holding the root filesystem is refused and mounted partitions are
+/// unmounted before that write.
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `destructive` - Whether to include the write pass
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the device path is invalid or unsafe to write,
+/// elevation fails, or `dd` fails.
+pub fn run_benchmark(
+    device: &str,
+    destructive: bool,
+    progress: mpsc::Sender<String>,
+) -> Result<BenchResult> {
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    if destructive {
+        ensure_device_safe(dev, true)?;
+    }
+    let elevator = acquire_elevator(&progress)?;
+    let bytes = BENCH_BLOCKS * BENCH_BLOCK_MIB * 1024 * 1024;
+
+    let write_mbps = if destructive {
+        let partitions = list_partitions(dev)?;
+        if !partitions.is_empty() {
+            let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+            unmount_partitions(&partitions, elevator);
+        }
+        let _ = progress.send(format!("Writing {} MiB to {dev}...", bytes / (1024 * 1024)));
+        let elapsed = time_dd(
+            elevated_command("dd", elevator)
+                .arg("if=/dev/zero")
+                .arg(format!("of={dev}"))
+                .arg(format!("bs={BENCH_BLOCK_MIB}M"))
+                .arg(format!("count={BENCH_BLOCKS}"))
+                .arg("oflag=direct")
+                .arg("conv=fsync"),
+        )
+        .context("benchmark write pass")?;
+        Some(throughput_mbps(bytes, elapsed))
+    } else {
+        None
+    };
+
+    let _ = progress.send(format!(
+        "Reading {} MiB from {dev}...",
+        bytes / (1024 * 1024)
+    ));
+    let elapsed = time_dd(
+        elevated_command("dd", elevator)
+            .arg(format!("if={dev}"))
+            .arg("of=/dev/null")
+            .arg(format!("bs={BENCH_BLOCK_MIB}M"))
+            .arg(format!("count={BENCH_BLOCKS}"))
+            .arg("iflag=direct"),
+    )
+    .context("benchmark read pass")?;
+
+    Ok(BenchResult {
+        bytes,
+        read_mbps: throughput_mbps(bytes, elapsed),
+        write_mbps,
+    })
+}
+
+/// Split the optional "[done/total]" prefix used by long-running media tests
+/// off a progress message.
+///
+/// Returns the parsed fraction (if any) and the remaining human-readable text.
+pub fn split_progress(line: &str) -> (Option<(u64, u64)>, &str) {
+    let parsed = line.strip_prefix('[').and_then(|rest| {
+        let (inner, text) = rest.split_once(']')?;
+        let (done, total) = inner.split_once('/')?;
+        Some(((done.parse().ok()?, total.parse().ok()?), text.trim_start()))
+    });
+    match parsed {
+        Some((fraction, text)) => (Some(fraction), text),
+        None => (None, line),
+    }
+}
+
+/// Run a prepared `dd` command to completion and return how long it took.
+fn time_dd(cmd: &mut std::process::Command) -> Result<Duration> {
+    let start = Instant::now();
+    let output = cmd
+        .arg("status=none")
+        .stdout(std::process::Stdio::null())
+        .output()
+        .context("run dd")?;
+    let elapsed = start.elapsed();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            return Err(anyhow::anyhow!("dd failed"));
+        }
+        return Err(anyhow::anyhow!("dd failed: {stderr}"));
+    }
+    Ok(elapsed)
+}
+
+/// Convert a byte count and duration into decimal megabytes per second.
+pub(crate) fn throughput_mbps(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    bytes as f64 / 1_000_000.0 / secs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput_mbps_uses_decimal_megabytes() {
+        let mbps = throughput_mbps(50_000_000, Duration::from_secs(2));
+        assert!((mbps - 25.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn throughput_mbps_handles_zero_duration() {
+        assert_eq!(throughput_mbps(1024, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn split_progress_strips_fraction_prefix() {
+        assert_eq!(
+            split_progress("[3/128] Writing probe"),
+            (Some((3, 128)), "Writing probe")
+        );
+        assert_eq!(split_progress("Unmounting"), (None, "Unmounting"));
+        assert_eq!(split_progress("[sdb] failed"), (None, "[sdb] failed"));
+    }
+
+    #[test]
+    fn summary_mentions_untested_write() {
+        let result = BenchResult {
+            bytes: 1,
+            read_mbps: 12.34,
+            write_mbps: None,
+        };
+        assert_eq!(result.summary(), "read 12.3 MB/s (write not tested)");
+    }
+}
@@ -0,0 +1,258 @@
+//! Managing the download cache.
+//!
+//! Downloaded images stay in `download::cache_dir()`. Each one gets a
+//! `<image>.sha256` file beside it holding the SHA-256 it had when it was
+//! downloaded, in the GNU checksum-list format, so a cached image can be
+//! re-verified at any time. An image's modification time doubles as its
+//! last use: it is set when the image is downloaded, found in the cache
+//! again, or flashed. `evict` removes the least recently used images first
+//! once the cache outgrows the config's `cache_limit_gb`.
+//!
+//! 'd' in the Image step opens the cache screen (`Step::Cache`), listing the
+//! images with their size, age, and digest.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+use crate::checksum::{self, Algorithm, Checksum};
+use crate::download;
+
+/// Suffix of the file recording an image's SHA-256.
+pub const DIGEST_SUFFIX: &str = ".sha256";
+
+/// An image in the download cache.
+///
+/// # Fields
+///
+/// * `path` - The image file
+/// * `size` - Size in bytes
+/// * `used` - When it was last downloaded, found in the cache, or flashed
+/// * `sha256` - SHA-256 recorded when it was downloaded, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedImage {
+    pub path: PathBuf,
+    pub size: u64,
+    pub used: SystemTime,
+    pub sha256: Option<String>,
+}
+
+impl CachedImage {
+    /// File name of the image.
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// The images in `dir`, most recently used first. Unfinished downloads
+/// (`.part`) and the files next to the images are left out.
+pub fn list(dir: &Path) -> Result<Vec<CachedImage>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("read {}", dir.display())),
+    };
+    let mut images = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("read {}", dir.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".part") || name.ends_with(DIGEST_SUFFIX) {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        images.push(CachedImage {
+            sha256: recorded_digest(&path),
+            size: meta.len(),
+            used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            path,
+        });
+    }
+    images.sort_by_key(|image| std::cmp::Reverse(image.used));
+    Ok(images)
+}
+
+/// The file recording the digest of `image`.
+fn digest_path(image: &Path) -> PathBuf {
+    let mut path = image.as_os_str().to_owned();
+    path.push(DIGEST_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// The SHA-256 recorded for `image`, if there is one.
+fn recorded_digest(image: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(digest_path(image)).ok()?;
+    let name = image.file_name()?.to_string_lossy();
+    checksum::find_in_list(&text, &name, Algorithm::Sha256)
+        .filter(|(algorithm, _)| *algorithm == Algorithm::Sha256)
+        .map(|(_, hex)| hex)
+}
+
+/// Record `sha256` as the digest of `image`.
+pub fn record(image: &Path, sha256: &str) -> Result<()> {
+    let name = image
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let path = digest_path(image);
+    std::fs::write(&path, format!("{sha256}  {name}\n"))
+        .with_context(|| format!("write {}", path.display()))
+}
+
+/// Mark `image` as just used, if it is in the download cache, so eviction
+/// keeps it longest.
+pub fn note_use(image: &Path) {
+    let in_cache = download::cache_dir().is_some_and(|dir| image.parent() == Some(&dir));
+    if in_cache {
+        touch(image);
+    }
+}
+
+/// Set the modification time of `image` to now.
+pub fn touch(image: &Path) {
+    if let Ok(file) = File::options().write(true).open(image) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// Hash `image` again and compare it with its recorded digest. An image
+/// without one gets its digest recorded now.
+///
+/// # Returns
+///
+/// Whether there was a recorded digest to compare with.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be read or no longer matches.
+pub fn verify(image: &CachedImage) -> Result<bool> {
+    match &image.sha256 {
+        Some(hex) => {
+            let checksum = Checksum::Digest {
+                algorithm: Algorithm::Sha256,
+                hex: hex.clone(),
+            };
+            checksum.verify(&image.path)?;
+            Ok(true)
+        }
+        None => {
+            let digest = Algorithm::Sha256.hash_file(&image.path)?;
+            record(&image.path, &digest)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Delete `image` and its recorded digest.
+pub fn remove(image: &Path) -> Result<()> {
+    std::fs::remove_file(image).with_context(|| format!("delete {}", image.display()))?;
+    std::fs::remove_file(digest_path(image)).ok();
+    Ok(())
+}
+
+/// Delete the least recently used images in `dir` until the cache holds at
+/// most `limit` bytes, never deleting `keep` (the image just downloaded).
+///
+/// # Returns
+///
+/// The deleted images.
+pub fn evict(dir: &Path, limit: u64, keep: &Path) -> Result<Vec<CachedImage>> {
+    let images = list(dir)?;
+    let mut total: u64 = images.iter().map(|image| image.size).sum();
+    let mut evicted = Vec::new();
+    for image in images.into_iter().rev() {
+        if total <= limit {
+            break;
+        }
+        if image.path == keep {
+            continue;
+        }
+        remove(&image.path)?;
+        total -= image.size;
+        evicted.push(image);
+    }
+    Ok(evicted)
+}
+
+/// Keep the cache in `dir` within `limit` bytes, if there is a limit, now
+/// that `image` was downloaded into it. Deleted images, and a failure to
+/// delete them, are reported to `progress`.
+pub fn enforce_limit(
+    dir: &Path,
+    limit: Option<u64>,
+    image: &Path,
+    progress: &mpsc::Sender<String>,
+) {
+    let Some(limit) = limit else {
+        return;
+    };
+    match evict(dir, limit, image) {
+        Ok(evicted) => {
+            for image in evicted {
+                let _ = progress.send(format!(
+                    "Cache limit: deleted {} ({})",
+                    image.name(),
+                    crate::format_size(image.size)
+                ));
+            }
+        }
+        Err(err) => {
+            let _ = progress.send(format!("Cache limit not kept: {err:#}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn eviction_drops_the_least_recently_used_but_never_the_new_image() {
+        let dir = std::env::temp_dir().join(format!("flashr_tui_cache_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        let image = |name: &str, size: usize, age: u64| {
+            let path = dir.join(name);
+            std::fs::write(&path, vec![0u8; size]).unwrap();
+            let file = File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+            path
+        };
+        let new = image("new.iso", 40, 3_600);
+        let old = image("old.iso", 30, 1_800);
+        let recent = image("recent.iso", 20, 60);
+        std::fs::write(dir.join("half.iso.part"), [0u8; 10]).unwrap();
+        record(&recent, &"ab".repeat(32)).unwrap();
+
+        let images = list(&dir).unwrap();
+        let names: Vec<String> = images.iter().map(CachedImage::name).collect();
+        assert_eq!(names, ["recent.iso", "old.iso", "new.iso"]);
+        assert_eq!(images[0].sha256, Some("ab".repeat(32)));
+        assert_eq!(images[1].sha256, None);
+
+        // 90 bytes over a limit of 60: "new" is oldest but kept, so "old" goes.
+        let evicted = evict(&dir, 60, &new).unwrap();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].path, old);
+        assert!(!old.exists() && new.exists());
+
+        touch(&new);
+        assert_eq!(list(&dir).unwrap()[0].path, new);
+        remove(&recent).unwrap();
+        assert!(!digest_path(&recent).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
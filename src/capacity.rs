@@ -0,0 +1,322 @@
+//! Counterfeit-capacity detection.
+//!
+//! Fake USB sticks report a large capacity but only store a fraction of it:
+//! writes past the real size are dropped or wrap around onto earlier blocks.
+//! Such sticks accept an image without complaint and then fail to boot. This
+//! test writes a uniquely patterned 1 MiB probe at evenly spaced offsets across
+//! the advertised capacity, then reads every probe back with direct I/O. A probe
+//! that cannot be written, or comes back different, marks the point where real
+//! storage ends.
+//!
+//! The test is destructive: every probe overwrites data on the device.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::mpsc;
+
+use crate::device::{format_lsblk_size, DevicePath};
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, unmount_partitions,
+};
+use crate::wipe::device_sectors;
+
+/// Number of probes spread across the device.
+const PROBE_COUNT: u64 = 64;
+
+/// Size of each probe block.
+const PROBE_BYTES: u64 = 1024 * 1024;
+
+/// Outcome of a capacity test.
+///
+/// # Fields
+///
+/// * `advertised_bytes` - Capacity the device reports
+/// * `probes` - Number of probe blocks written and read back
+/// * `failed_offsets` - Byte offsets of probes that could not be written or
+///   did not read back intact
+#[derive(Debug, Clone)]
+pub struct CapacityReport {
+    pub advertised_bytes: u64,
+    pub probes: u64,
+    pub failed_offsets: Vec<u64>,
+}
+
+impl CapacityReport {
+    /// Whether every probe was written and read back intact.
+    pub fn genuine(&self) -> bool {
+        self.failed_offsets.is_empty()
+    }
+
+    /// Bytes that can be trusted to hold data: everything below the first failed probe.
+    pub fn usable_bytes(&self) -> u64 {
+        self.failed_offsets
+            .iter()
+            .min()
+            .copied()
+            .unwrap_or(self.advertised_bytes)
+    }
+
+    /// One-line human-readable summary.
+    pub fn summary(&self) -> String {
+        if self.genuine() {
+            format!(
+                "All {} probes verified across {}; capacity looks genuine.",
+                self.probes,
+                format_lsblk_size(self.advertised_bytes)
+            )
+        } else {
+            format!(
+                "{} of {} probes failed; only about {} of the advertised {} holds data. \
+                 This device is likely counterfeit.",
+                self.failed_offsets.len(),
+                self.probes,
+                format_lsblk_size(self.usable_bytes()),
+                format_lsblk_size(self.advertised_bytes)
+            )
+        }
+    }
+}
+
+/// Run the capacity test against a device.
+///
+/// Progress messages start with "[n/total]" so callers can drive a gauge
+/// (see `bench::split_progress`).
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the device is invalid or unsafe, elevation fails, or
+/// `dd` cannot be run. Probes that fail to write or read back are reported in
+/// the `CapacityReport`, not as errors.
+pub fn run_capacity_test(device: &str, progress: mpsc::Sender<String>) -> Result<CapacityReport> {
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    ensure_device_safe(dev, true)?;
+    let advertised_bytes = device_sectors(dev)? * 512;
+    if advertised_bytes < PROBE_BYTES * 2 {
+        return Err(anyhow::anyhow!("{dev} is too small to test"));
+    }
+    let elevator = acquire_elevator(&progress)?;
+
+    let partitions = list_partitions(dev)?;
+    if !partitions.is_empty() {
+        let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+        unmount_partitions(&partitions, elevator);
+    }
+
+    let offsets = probe_offsets(advertised_bytes, PROBE_COUNT);
+    let failed_offsets = run_probes(dev, elevator, &offsets, run_seed(), &progress)?;
+
+    Ok(CapacityReport {
+        advertised_bytes,
+        probes: offsets.len() as u64,
+        failed_offsets,
+    })
+}
+
+/// Write a probe at each of `offsets` on `dev`, then read them back.
+///
+/// # Returns
+///
+/// The offsets of the probes that could not be written or did not read back
+/// intact.
+///
+/// # Errors
+///
+/// Returns an error only if `dd` cannot be run at all.
+fn run_probes(
+    dev: &str,
+    elevator: Option<&str>,
+    offsets: &[u64],
+    seed: u64,
+    progress: &mpsc::Sender<String>,
+) -> Result<Vec<u64>> {
+    let total = offsets.len() * 2;
+    let mut failed_offsets = Vec::new();
+
+    // Write every probe before reading any back, so a stick that wraps high
+    // offsets onto low ones overwrites its earlier probes and gets caught.
+    for (i, offset) in offsets.iter().enumerate() {
+        let _ = progress.send(format!(
+            "[{}/{total}] Writing probe at {}",
+            i + 1,
+            format_lsblk_size(*offset)
+        ));
+        // Counterfeit sticks often refuse writes past their real capacity.
+        if let Some(err) = write_probe(dev, elevator, *offset, &probe_pattern(seed, *offset))? {
+            let _ = progress.send(format!(
+                "Probe at {} could not be written: {err}",
+                format_lsblk_size(*offset)
+            ));
+            failed_offsets.push(*offset);
+        }
+    }
+
+    for (i, offset) in offsets.iter().enumerate() {
+        let _ = progress.send(format!(
+            "[{}/{total}] Reading probe at {}",
+            offsets.len() + i + 1,
+            format_lsblk_size(*offset)
+        ));
+        if failed_offsets.contains(offset) {
+            continue;
+        }
+        // A read error past the real capacity is as much a failure as a mismatch.
+        let intact = read_probe(dev, elevator, *offset)
+            .map(|data| data == probe_pattern(seed, *offset))
+            .unwrap_or(false);
+        if !intact {
+            failed_offsets.push(*offset);
+        }
+    }
+    Ok(failed_offsets)
+}
+
+/// Evenly spaced, MiB-aligned probe offsets from the start to the last full MiB.
+fn probe_offsets(capacity: u64, count: u64) -> Vec<u64> {
+    let last_block = capacity / PROBE_BYTES - 1;
+    let count = count.min(last_block + 1).max(2);
+    let mut offsets: Vec<u64> = (0..count)
+        .map(|i| i * last_block / (count - 1) * PROBE_BYTES)
+        .collect();
+    offsets.dedup();
+    offsets
+}
+
+/// Probe contents: every 8-byte word encodes the run seed, the probe offset,
+/// and the word index, so stale data and aliased blocks never match.
+fn probe_pattern(seed: u64, offset: u64) -> Vec<u8> {
+    let base = seed ^ offset.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (0..PROBE_BYTES / 8)
+        .flat_map(|word| (base ^ word).to_le_bytes())
+        .collect()
+}
+
+/// Seed that differs between runs so a previous run's probes can't pass.
+fn run_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Write one probe block with direct I/O, feeding the pattern through `dd`'s stdin.
+///
+/// # Returns
+///
+/// `None` once the probe is written, or why it was not: `dd`'s message and
+/// exit status, or the failure to hand it the whole probe.
+///
+/// # Errors
+///
+/// Returns an error only if `dd` cannot be started or waited for.
+fn write_probe(
+    dev: &str,
+    elevator: Option<&str>,
+    offset: u64,
+    data: &[u8],
+) -> Result<Option<String>> {
+    let mut child = elevated_command("dd", elevator)
+        .arg(format!("of={dev}"))
+        .arg(format!("bs={PROBE_BYTES}"))
+        .arg(format!("seek={}", offset / PROBE_BYTES))
+        .arg("count=1")
+        .arg("iflag=fullblock")
+        .arg("oflag=direct")
+        .arg("conv=notrunc,fsync")
+        .arg("status=none")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("run dd for capacity probe")?;
+    // dd exiting early breaks the pipe; its own status and message say more,
+    // so the write error only counts when dd claims success.
+    let sent = child.stdin.take().map(|mut stdin| stdin.write_all(data));
+    let output = child.wait_with_output().context("wait for dd")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Ok(Some(format!("{stderr} ({})", output.status)));
+    }
+    if let Some(Err(err)) = sent {
+        return Ok(Some(format!("sending the probe to dd failed: {err}")));
+    }
+    Ok(None)
+}
+
+/// Read one probe block back with direct I/O, bypassing the page cache.
+fn read_probe(dev: &str, elevator: Option<&str>, offset: u64) -> Result<Vec<u8>> {
+    let output = elevated_command("dd", elevator)
+        .arg(format!("if={dev}"))
+        .arg(format!("bs={PROBE_BYTES}"))
+        .arg(format!("skip={}", offset / PROBE_BYTES))
+        .arg("count=1")
+        .arg("iflag=direct")
+        .arg("status=none")
+        .stdin(Stdio::null())
+        .output()
+        .context("run dd for capacity probe")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("reading probe at byte {offset} failed"));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_offsets_cover_start_and_end() {
+        let capacity = 64 * PROBE_BYTES;
+        let offsets = probe_offsets(capacity, 4);
+        assert_eq!(offsets.first(), Some(&0));
+        assert_eq!(offsets.last(), Some(&(63 * PROBE_BYTES)));
+        assert_eq!(offsets.len(), 4);
+        assert!(offsets.iter().all(|o| o % PROBE_BYTES == 0));
+    }
+
+    #[test]
+    fn probe_patterns_differ_between_offsets() {
+        assert_ne!(probe_pattern(1, 0), probe_pattern(1, PROBE_BYTES));
+        assert_eq!(probe_pattern(1, 0).len() as u64, PROBE_BYTES);
+    }
+
+    #[test]
+    fn usable_bytes_stops_at_first_failure() {
+        let report = CapacityReport {
+            advertised_bytes: 64 * PROBE_BYTES,
+            probes: 4,
+            failed_offsets: vec![42 * PROBE_BYTES, 21 * PROBE_BYTES],
+        };
+        assert!(!report.genuine());
+        assert_eq!(report.usable_bytes(), 21 * PROBE_BYTES);
+    }
+
+    #[test]
+    fn probes_that_fail_to_write_count_as_failed() {
+        // dd cannot open this, so every write fails the way a dying stick's would.
+        let dev = "/nonexistent/flashr-capacity-probe";
+        let offsets = [0, PROBE_BYTES];
+        let (tx, rx) = mpsc::channel();
+        let failed_offsets = run_probes(dev, None, &offsets, 1, &tx).unwrap();
+        assert_eq!(failed_offsets, offsets);
+        assert!(rx.try_iter().any(|line| {
+            line.starts_with("Probe at 0B could not be written: dd: failed to open")
+                && line.ends_with("(exit status: 1)")
+        }));
+
+        let report = CapacityReport {
+            advertised_bytes: 2 * PROBE_BYTES,
+            probes: 2,
+            failed_offsets,
+        };
+        assert!(!report.genuine());
+        assert_eq!(report.usable_bytes(), 0);
+    }
+}
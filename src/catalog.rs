@@ -0,0 +1,429 @@
+//! The built-in catalog of distributions to download.
+//!
+//! 'c' in the Image step opens the catalog: a distribution, then one of its
+//! releases, then an architecture, much like Fedora Media Writer. The chosen
+//! image is downloaded into the cache (see `download`) and the TUI moves on
+//! to the Device step with it. The index is `catalog.json`, bundled into the
+//! binary, so browsing works offline; only the download needs the network.
+//!
+//! An image can list mirrors besides its main URL. The user then picks one,
+//! or lets `download::fastest_mirror` time a short download from each. An
+//! image can also name a torrent, offered as one more way to download it.
+//!
+//! Images that name the distribution's checksum list (and its signature,
+//! where one is published) are verified against it before the TUI moves on
+//! to the Device step; see `download::PublishedSums`.
+//!
+//! With the config's `catalog_url` set, the TUI fetches a newer index from
+//! there at startup, in the background (see `Catalog::fetch_update`). The
+//! index must carry a good detached OpenPGP signature by the key pinned with
+//! the config's `catalog_key` fingerprint, which must be in the user's GnuPG
+//! keyring; no other key will do. A verified index newer than the bundled
+//! one (by its `updated` date) replaces it and is kept in the cache with its
+//! signature, so new releases stay on offer offline and across restarts. The
+//! saved index is verified again each time it is loaded.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::download::{self, Proxy};
+
+/// The bundled index.
+const BUNDLED: &str = include_str!("catalog.json");
+
+/// All distributions in the catalog, in the order they are offered.
+///
+/// # Fields
+///
+/// * `updated` - When the index was last changed ("YYYY-MM-DD"); of two
+///   catalogs, the later one is used
+/// * `distros` - The distributions
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Catalog {
+    #[serde(default)]
+    pub updated: String,
+    pub distros: Vec<Distro>,
+}
+
+/// Where `Catalog::fetch_update` gets a newer index.
+///
+/// # Fields
+///
+/// * `url` - URL of the index, in the format of the bundled `catalog.json`
+/// * `signature` - URL of the index's detached OpenPGP signature
+/// * `key` - Fingerprint of the key the index must be signed by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub url: String,
+    pub signature: String,
+    pub key: Option<String>,
+}
+
+/// A newer catalog found by `Catalog::fetch_update`.
+///
+/// # Fields
+///
+/// * `catalog` - The new catalog
+/// * `signer` - Who signed it, as GnuPG names the key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub catalog: Catalog,
+    pub signer: String,
+}
+
+/// One distribution, or one flavour of it.
+///
+/// # Fields
+///
+/// * `name` - Name shown in the menu (e.g., "Fedora Workstation")
+/// * `summary` - One line about what it is
+/// * `releases` - Releases on offer, newest first
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Distro {
+    pub name: String,
+    #[serde(default)]
+    pub summary: String,
+    pub releases: Vec<Release>,
+}
+
+/// One release of a distribution.
+///
+/// # Fields
+///
+/// * `version` - Version shown in the menu (e.g., "24.04.1 LTS")
+/// * `images` - One image per architecture
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Release {
+    pub version: String,
+    pub images: Vec<CatalogImage>,
+}
+
+/// A downloadable image of a release.
+///
+/// # Fields
+///
+/// * `arch` - Architecture as the distribution names it ("x86_64", "amd64", ...)
+/// * `url` - Where the image is downloaded from
+/// * `mirrors` - Other URLs serving the same file, e.g. on other continents
+/// * `torrent` - Magnet link or `.torrent` URL for the same file
+/// * `checksums` - URL of the checksum list naming the file (e.g., SHA256SUMS)
+/// * `signature` - URL of the detached signature of `checksums`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CatalogImage {
+    pub arch: String,
+    pub url: String,
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    #[serde(default)]
+    pub torrent: Option<String>,
+    #[serde(default)]
+    pub checksums: Option<String>,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl Catalog {
+    /// The catalog bundled into the binary.
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("bundled catalog.json is valid")
+    }
+
+    /// The bundled catalog, or the last update fetched when that is newer and
+    /// its saved signature is still a good one by `key` (the config's
+    /// `catalog_key`).
+    pub fn load(key: Option<&str>) -> Self {
+        let bundled = Self::bundled();
+        let Some(path) = update_path() else {
+            return bundled;
+        };
+        if !path.exists() {
+            return bundled;
+        }
+        let saved = (|| {
+            let key = key.context("catalog_key is not set")?;
+            download::require_signature(&path, &signature_path(&path), key)
+                .context("catalog signature")?;
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("read {}", path.display()))?;
+            Self::parse(&json)
+        })();
+        match saved {
+            Ok(saved) if saved.is_newer_than(&bundled) => saved,
+            Ok(_) => bundled,
+            Err(err) => {
+                log::warn!("ignoring the saved catalog {}: {err:#}", path.display());
+                bundled
+            }
+        }
+    }
+
+    /// Whether this catalog was updated after `other`.
+    pub fn is_newer_than(&self, other: &Self) -> bool {
+        self.updated > other.updated
+    }
+
+    /// Fetch the index at `remote` and check its signature. An index newer
+    /// than `current` is saved with its signature, so `load` starts with it
+    /// from now on.
+    ///
+    /// # Returns
+    ///
+    /// The new catalog and who signed it, or `None` when it is not newer
+    /// than `current`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when no key is pinned, the index or its signature cannot be
+    /// fetched, the signature is not a good one by the pinned key (see
+    /// `download::require_signature`), or the index is invalid.
+    pub fn fetch_update(remote: &Remote, current: &Self, proxy: &Proxy) -> Result<Option<Update>> {
+        let key = remote
+            .key
+            .as_deref()
+            .context("set catalog_key to the fingerprint of the key that signs the catalog")?;
+        let path = update_path().context("no cache location (HOME is not set)")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        }
+        let index = path.with_extension("json.new");
+        let signature = path.with_extension("json.new.sig");
+        let fetched = (|| {
+            download::fetch_small(&remote.url, &index, proxy)?;
+            download::fetch_small(&remote.signature, &signature, proxy)?;
+            let signer = download::require_signature(&index, &signature, key)
+                .context("catalog signature")?;
+            let json = std::fs::read_to_string(&index)
+                .with_context(|| format!("read {}", index.display()))?;
+            anyhow::Ok((Self::parse(&json)?, signer))
+        })();
+        match fetched {
+            Ok((catalog, signer)) if catalog.is_newer_than(current) => {
+                let saved_signature = signature_path(&path);
+                std::fs::rename(&signature, &saved_signature)
+                    .with_context(|| format!("save {}", saved_signature.display()))?;
+                std::fs::rename(&index, &path)
+                    .with_context(|| format!("save {}", path.display()))?;
+                Ok(Some(Update { catalog, signer }))
+            }
+            fetched => {
+                std::fs::remove_file(&index).ok();
+                std::fs::remove_file(&signature).ok();
+                fetched.map(|_| None)
+            }
+        }
+    }
+
+    /// Parse a catalog index.
+    ///
+    /// # Errors
+    ///
+    /// Fails on malformed JSON, on an `updated` date not written
+    /// "YYYY-MM-DD", on a distribution without releases or a
+    /// release without images, on a URL with no file name to save it as, on
+    /// a torrent that is not a magnet link or `.torrent` URL, and on a
+    /// signature without a checksum list.
+    pub fn parse(json: &str) -> Result<Self> {
+        let catalog: Self = serde_json::from_str(json).context("parse catalog")?;
+        if !catalog.updated.is_empty() && !is_date(&catalog.updated) {
+            anyhow::bail!("catalog date is not YYYY-MM-DD: {}", catalog.updated);
+        }
+        for distro in &catalog.distros {
+            if distro.releases.is_empty() {
+                anyhow::bail!("catalog entry {} has no releases", distro.name);
+            }
+            for release in &distro.releases {
+                if release.images.is_empty() {
+                    anyhow::bail!(
+                        "catalog entry {} {} has no images",
+                        distro.name,
+                        release.version
+                    );
+                }
+                for url in release.images.iter().flat_map(CatalogImage::urls) {
+                    download::file_name_for(url)?;
+                }
+                for torrent in release.images.iter().filter_map(|i| i.torrent.as_ref()) {
+                    if !download::is_torrent(torrent) {
+                        anyhow::bail!("catalog torrent is not a torrent: {torrent}");
+                    }
+                    download::file_name_for(torrent)?;
+                }
+                for image in &release.images {
+                    if image.signature.is_some() && image.checksums.is_none() {
+                        anyhow::bail!(
+                            "catalog image {} has a signature but no checksums",
+                            image.url
+                        );
+                    }
+                    for url in image.checksums.iter().chain(&image.signature) {
+                        download::file_name_for(url)?;
+                    }
+                }
+            }
+        }
+        Ok(catalog)
+    }
+}
+
+/// Where the last catalog update is saved.
+fn update_path() -> Option<PathBuf> {
+    let images = download::cache_dir()?;
+    Some(images.parent()?.join("catalog.json"))
+}
+
+/// Where the signature of the catalog saved at `path` is kept.
+fn signature_path(path: &Path) -> PathBuf {
+    path.with_extension("json.sig")
+}
+
+/// Whether `text` is a date written "YYYY-MM-DD", so dates compare as text.
+fn is_date(text: &str) -> bool {
+    text.len() == 10
+        && text.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+impl Release {
+    /// Index of the image for the machine flashr-tui runs on, or 0 when
+    /// there is none, so the menu starts on the likeliest choice.
+    pub fn host_image(&self) -> usize {
+        self.images
+            .iter()
+            .position(CatalogImage::matches_host)
+            .unwrap_or(0)
+    }
+}
+
+impl CatalogImage {
+    /// The main URL followed by the mirrors.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url.as_str()).chain(self.mirrors.iter().map(String::as_str))
+    }
+
+    /// Ways to download the image, as a menu label and the URLs to fetch
+    /// it from: the fastest mirror (when there are mirrors), each URL by
+    /// its host, then the torrent.
+    pub fn download_choices(&self) -> Vec<(String, Vec<String>)> {
+        let mut choices = Vec::new();
+        if !self.mirrors.is_empty() {
+            let urls = self.urls().map(String::from).collect();
+            choices.push(("Fastest (time each mirror briefly)".to_string(), urls));
+        }
+        for url in self.urls() {
+            choices.push((download::host_of(url).to_string(), vec![url.to_string()]));
+        }
+        if let Some(torrent) = &self.torrent {
+            choices.push((
+                "BitTorrent (needs aria2c)".to_string(),
+                vec![torrent.clone()],
+            ));
+        }
+        choices
+    }
+
+    /// The checksum list to verify the image with, if the catalog names one.
+    pub fn published_sums(&self) -> Option<download::PublishedSums> {
+        Some(download::PublishedSums {
+            url: self.checksums.clone()?,
+            signature: self.signature.clone(),
+        })
+    }
+
+    /// Whether the image is built for the machine flashr-tui runs on,
+    /// allowing for the Debian names of the common architectures.
+    pub fn matches_host(&self) -> bool {
+        let alias = match std::env::consts::ARCH {
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+        self.arch == std::env::consts::ARCH || self.arch == alias
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_catalog_parses_and_bad_entries_are_rejected() {
+        let catalog = Catalog::bundled();
+        assert!(catalog
+            .distros
+            .iter()
+            .any(|d| d.name == "Fedora Workstation"));
+
+        assert!(is_date(&catalog.updated));
+        let dated = |updated: &str| Catalog {
+            updated: updated.to_string(),
+            distros: Vec::new(),
+        };
+        assert!(dated("2031-01-05").is_newer_than(&catalog));
+        assert!(!dated("").is_newer_than(&catalog));
+        assert!(Catalog::parse(r#"{"updated": "5 Jan 2031", "distros": []}"#).is_err());
+
+        let no_images = r#"{"distros": [{"name": "Arch Linux", "releases": [
+            {"version": "2024.10.01", "images": []}]}]}"#;
+        assert!(Catalog::parse(no_images).is_err());
+        let no_file_name = r#"{"distros": [{"name": "Arch Linux", "releases": [
+            {"version": "2024.10.01", "images": [
+                {"arch": "x86_64", "url": "https://example.org/arch.iso",
+                 "mirrors": ["https://example.net/"]}]}]}]}"#;
+        assert!(Catalog::parse(no_file_name).is_err());
+
+        let release = Release {
+            version: "12.7".to_string(),
+            images: vec![
+                CatalogImage {
+                    arch: "s390x-nowhere".to_string(),
+                    url: "https://example.org/a.iso".to_string(),
+                    mirrors: Vec::new(),
+                    torrent: Some("magnet:?xt=urn:btih:00&dn=a.iso".to_string()),
+                    checksums: None,
+                    signature: None,
+                },
+                CatalogImage {
+                    arch: std::env::consts::ARCH.to_string(),
+                    url: "https://example.org/b.iso".to_string(),
+                    mirrors: vec!["https://example.net/b.iso".to_string()],
+                    torrent: None,
+                    checksums: Some("https://example.org/SHA256SUMS".to_string()),
+                    signature: None,
+                },
+            ],
+        };
+        assert_eq!(release.host_image(), 1);
+        let urls: Vec<&str> = release.images[1].urls().collect();
+        assert_eq!(
+            urls,
+            ["https://example.org/b.iso", "https://example.net/b.iso"]
+        );
+        let labels: Vec<String> = release.images[0]
+            .download_choices()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, ["example.org", "BitTorrent (needs aria2c)"]);
+        assert_eq!(release.images[1].download_choices()[0].1, urls);
+
+        let not_a_torrent = r#"{"distros": [{"name": "Arch Linux", "releases": [
+            {"version": "2024.10.01", "images": [
+                {"arch": "x86_64", "url": "https://example.org/arch.iso",
+                 "torrent": "https://example.org/arch.iso"}]}]}]}"#;
+        assert!(Catalog::parse(not_a_torrent).is_err());
+        let unsigned_signature = r#"{"distros": [{"name": "Arch Linux", "releases": [
+            {"version": "2024.10.01", "images": [
+                {"arch": "x86_64", "url": "https://example.org/arch.iso",
+                 "signature": "https://example.org/SHA256SUMS.gpg"}]}]}]}"#;
+        assert!(Catalog::parse(unsigned_signature).is_err());
+        assert!(release.images[0].published_sums().is_none());
+        assert_eq!(
+            release.images[1].published_sums().unwrap().url,
+            "https://example.org/SHA256SUMS"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Reading the system clipboard and cleaning up pasted paths.
+//!
+//! Terminals deliver pastes as bracketed-paste events on their own; the
+//! clipboard is only read directly for the explicit paste key, through
+//! whichever of `wl-paste`, `xclip`, or `xsel` is installed.
+
+use anyhow::{Context, Result};
+use std::process::{Command, Stdio};
+
+/// Clipboard readers tried in order: Wayland first, then X11.
+const CLIPBOARD_TOOLS: [(&str, &[&str]); 3] = [
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+];
+
+/// Read text from the system clipboard.
+///
+/// # Errors
+///
+/// Returns an error if no clipboard tool is installed or the tool fails (for
+/// example when there is no graphical session).
+pub fn read() -> Result<String> {
+    let (tool, args) = CLIPBOARD_TOOLS
+        .iter()
+        .find(|(tool, _)| which::which(tool).is_ok())
+        .ok_or_else(|| anyhow::anyhow!("no clipboard tool found; install wl-clipboard or xclip"))?;
+    let output = Command::new(tool)
+        .args(*args)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("run {tool}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(anyhow::anyhow!("{tool} failed: {stderr}"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Turn pasted text into a single path.
+///
+/// Keeps only the first non-empty line, and strips surrounding quotes and a
+/// `file://` prefix, which file managers commonly add when copying a file.
+pub fn clean_path(text: &str) -> String {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    let line = ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            line.strip_prefix(*quote)
+                .and_then(|rest| rest.strip_suffix(*quote))
+        })
+        .unwrap_or(line);
+    line.strip_prefix("file://").unwrap_or(line).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_path_strips_quotes_uris_and_extra_lines() {
+        assert_eq!(clean_path("'/tmp/my image.iso'\n"), "/tmp/my image.iso");
+        assert_eq!(
+            clean_path("\nfile:///home/me/nixos.iso\r\nsecond"),
+            "/home/me/nixos.iso"
+        );
+        assert_eq!(clean_path("  plain.iso  "), "plain.iso");
+    }
+}
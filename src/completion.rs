@@ -0,0 +1,131 @@
+//! Shell-style Tab completion for the file picker's path input.
+//!
+//! The first Tab completes a unique match, or extends the input to the longest
+//! prefix shared by every match. When that adds nothing, further Tabs cycle
+//! through the matches one at a time, like a shell's menu completion.
+
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::expand_home;
+
+/// Matches being cycled through with repeated Tab presses.
+///
+/// # Fields
+///
+/// * `candidates` - Completed inputs, sorted; directories end with "/"
+/// * `index` - Candidate currently shown in the input
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub candidates: Vec<String>,
+    pub index: usize,
+}
+
+impl Completion {
+    /// The candidate currently shown in the input.
+    pub fn current(&self) -> &str {
+        &self.candidates[self.index]
+    }
+
+    /// Move to the next candidate, wrapping around, and return it.
+    pub fn advance(&mut self) -> &str {
+        self.index = (self.index + 1) % self.candidates.len();
+        self.current()
+    }
+}
+
+/// Completed inputs for a partially typed path.
+///
+/// The last path component of `input` is matched as a prefix against the
+/// entries of its directory; relative inputs are resolved against `cwd`, and
+/// a leading "~/" means home.
+/// Hidden entries are offered when `show_hidden` is set or the typed prefix
+/// itself starts with '.'. Unreadable directories yield no matches.
+pub fn candidates(input: &str, cwd: &Path, show_hidden: bool) -> Vec<String> {
+    let (dir_part, prefix) = match input.rfind('/') {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    let dir = if dir_part.is_empty() {
+        cwd.to_path_buf()
+    } else {
+        cwd.join(expand_home(dir_part))
+    };
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<String> = read_dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_str()?.to_string();
+            if !name.starts_with(prefix) {
+                return None;
+            }
+            if name.starts_with('.') && !show_hidden && !prefix.starts_with('.') {
+                return None;
+            }
+            // Follow symlinks so a link to a directory completes like one.
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{dir_part}{name}{suffix}"))
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Longest prefix shared by every candidate (empty when there are none).
+pub fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    // Compare whole graphemes so a shared base letter isn't split from its accent.
+    let mut len = first.len();
+    for candidate in rest {
+        len = first
+            .grapheme_indices(true)
+            .zip(candidate.graphemes(true))
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((index, grapheme), _)| index + grapheme.len())
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_complete_files_and_directories() {
+        let dir = std::env::temp_dir().join(format!("flashr_tui_complete_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("isos")).unwrap();
+        std::fs::write(dir.join("ubuntu-22.04.iso"), b"").unwrap();
+        std::fs::write(dir.join("ubuntu-24.04.iso"), b"").unwrap();
+        std::fs::write(dir.join(".hidden.iso"), b"").unwrap();
+
+        let ubuntu = candidates("ub", &dir, false);
+        let isos = candidates("is", &dir, false);
+        let hidden = candidates("", &dir, false).len();
+        let dotted = candidates(".h", &dir, false);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(ubuntu, ["ubuntu-22.04.iso", "ubuntu-24.04.iso"]);
+        assert_eq!(common_prefix(&ubuntu), "ubuntu-2");
+        assert_eq!(isos, ["isos/"]);
+        assert_eq!(hidden, 3);
+        assert_eq!(dotted, [".hidden.iso"]);
+    }
+
+    #[test]
+    fn completion_cycles_and_wraps() {
+        let mut completion = Completion {
+            candidates: vec!["a.iso".to_string(), "b.iso".to_string()],
+            index: 0,
+        };
+        assert_eq!(completion.advance(), "b.iso");
+        assert_eq!(completion.advance(), "a.iso");
+        assert_eq!(common_prefix(&completion.candidates), "");
+    }
+}
@@ -0,0 +1,587 @@
+//! User configuration loaded from a JSON file.
+//!
+//! The config lives at `$XDG_CONFIG_HOME/flashr-tui/config.json` (falling back to
+//! `~/.config/flashr-tui/config.json`) and may be overridden with `--config`.
+//! A missing file is not an error; every field has a default. The TUI reloads
+//! the file when it changes (or on Ctrl+R), re-applying the command-line
+//! `Overrides` on top.
+//!
+//! Example:
+//!
+//! ```json
+//! {
+//!   "hidden_devices": ["WD-WCC4E1234567", "usb-Seagate_Backup_Plus_NA8XYZ-0:0"],
+//!   "trusted_devices": ["4C530001230915117445"],
+//!   "bookmarks": ["~/Downloads", "/mnt/nas/isos"],
+//!   "compact": true,
+//!   "auto_advance": true,
+//!   "size_limit_gb": 64,
+//!   "post_hook": "~/bin/tag-stick.sh",
+//!   "pre_hook": "~/bin/check-inventory.sh",
+//!   "label_hook": "~/bin/asset-label.sh",
+//!   "device_filter": "~/bin/approved-serials.sh",
+//!   "hash_algorithm": "blake3",
+//!   "backend": "native",
+//!   "stage_dir": "/tmp/flashr",
+//!   "proxy": "http://proxy.example.com:3128",
+//!   "no_proxy": "localhost,.example.com",
+//!   "cache_limit_gb": 20,
+//!   "catalog_url": "https://example.com/flashr/catalog.json",
+//!   "catalog_key": "0123456789ABCDEF0123456789ABCDEF01234567",
+//!   "theme": "light",
+//!   "theme_colors": { "highlight_bg": "#005f87" }
+//! }
+//! ```
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::BackendKind;
+use crate::checksum::Algorithm;
+use crate::device::{self, Disk};
+use crate::hook;
+
+/// User configuration.
+///
+/// # Fields
+///
+/// * `hidden_devices` - Devices never shown in the device list (serial, by-id link, or by-id name)
+/// * `trusted_devices` - When non-empty, the only devices that may be written in execute mode
+/// * `theme` - Name of the built-in theme to start with (see `theme::BUILTIN_THEMES`)
+/// * `theme_colors` - Per-role color overrides applied on top of `theme`
+/// * `bookmarks` - Directories the file picker can jump to with 1-9 (a leading "~" means home)
+/// * `compact` - Start without the logo banner (toggle at runtime with Ctrl+O)
+/// * `auto_advance` - Skip from the image straight to Confirm when exactly one
+///   removable, trusted device is present
+/// * `size_limit_gb` - Refuse to write to devices larger than this many GB (10^9 bytes)
+/// * `post_hook` - Script to run after each successful flash (a leading "~" means home)
+/// * `pre_hook` - Script run right before writing; a non-zero exit refuses the flash
+/// * `label_hook` - Script printing the volume label to use when `--label` is not given
+/// * `device_filter` - Script run for each listed device; a non-zero exit hides it
+/// * `hash_algorithm` - Algorithm verification compares digests with: "sha256"
+///   (default), "sha512", or "blake3"
+/// * `backend` - How images are written: "dd" (default) or "native" (needs root)
+/// * `stage_dir` - Directory images are copied into before flashing when staging
+///   (a leading "~" means home); default the RAM-backed `/dev/shm`
+/// * `proxy` - HTTP(S) proxy for downloads, replacing the `https_proxy` environment variable
+/// * `no_proxy` - Comma-separated hosts downloaded from without the proxy,
+///   replacing the `no_proxy` environment variable
+/// * `cache_limit_gb` - Most GB (10^9 bytes) the download cache may hold; the
+///   least recently used images are deleted to stay below it
+/// * `catalog_url` - Where to fetch a newer download catalog at startup (see `catalog`)
+/// * `catalog_signature_url` - Detached signature of the catalog at
+///   `catalog_url`; default `catalog_url` with ".sig" appended
+/// * `catalog_key` - Fingerprint of the OpenPGP key the catalog must be
+///   signed by; without it no catalog update is used
+/// * `path` - File the config was loaded from, where bookmark changes are saved
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub hidden_devices: Vec<String>,
+    pub trusted_devices: Vec<String>,
+    pub theme: Option<String>,
+    pub theme_colors: ThemeColors,
+    pub bookmarks: Vec<String>,
+    pub compact: bool,
+    pub auto_advance: bool,
+    pub size_limit_gb: Option<u64>,
+    pub post_hook: Option<String>,
+    pub pre_hook: Option<String>,
+    pub label_hook: Option<String>,
+    pub device_filter: Option<String>,
+    pub hash_algorithm: Algorithm,
+    pub backend: BackendKind,
+    pub stage_dir: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub cache_limit_gb: Option<u64>,
+    pub catalog_url: Option<String>,
+    pub catalog_signature_url: Option<String>,
+    pub catalog_key: Option<String>,
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// Color overrides for the selected theme.
+///
+/// Each value is a color name (e.g. "red", "lightblue") or a hex value
+/// (e.g. "#ff8800"); unset roles keep the theme's color.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ThemeColors {
+    pub logo: Option<String>,
+    pub success: Option<String>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+    pub muted: Option<String>,
+    pub progress: Option<String>,
+    pub highlight_fg: Option<String>,
+    pub highlight_bg: Option<String>,
+}
+
+impl Config {
+    /// Default location of the config file, if a home or config directory is known.
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.json"))
+    }
+
+    /// Load the config from the default location.
+    ///
+    /// Returns the default config when the file does not exist.
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the config from a specific file.
+    ///
+    /// Returns the default config when the file does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let mut config: Self = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data)
+                .with_context(|| format!("parse config: {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("read config: {}", path.display()));
+            }
+        };
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// When the file the config was loaded from was last modified, or `None`
+    /// if it does not exist (or there is no file).
+    pub fn modified(&self) -> Option<SystemTime> {
+        let path = self.path.as_deref()?;
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+
+    /// Load the config again from the file it came from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no file, or it cannot be read or parsed.
+    pub fn reload(&self) -> Result<Self> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("no config location (HOME is not set)"))?;
+        Self::load_from(path)
+    }
+
+    /// Bookmarked directories, with a leading "~" expanded to the home directory.
+    pub fn bookmark_dirs(&self) -> Vec<PathBuf> {
+        self.bookmarks.iter().map(|b| expand_home(b)).collect()
+    }
+
+    /// The post-flash hook script, with a leading "~" expanded to the home directory.
+    pub fn post_hook_path(&self) -> Option<PathBuf> {
+        self.post_hook.as_deref().map(expand_home)
+    }
+
+    /// The pre-flash hook script, with a leading "~" expanded.
+    pub fn pre_hook_path(&self) -> Option<PathBuf> {
+        self.pre_hook.as_deref().map(expand_home)
+    }
+
+    /// The label hook script, with a leading "~" expanded.
+    pub fn label_hook_path(&self) -> Option<PathBuf> {
+        self.label_hook.as_deref().map(expand_home)
+    }
+
+    /// Directory to stage images in: `stage_dir` with a leading "~" expanded,
+    /// or the platform's RAM-backed directory.
+    pub fn stage_dir_path(&self) -> Option<PathBuf> {
+        match &self.stage_dir {
+            Some(dir) => Some(expand_home(dir)),
+            None => crate::platform::current().memory_dir(),
+        }
+    }
+
+    /// Proxy settings for downloads; unset ones fall back to the environment.
+    pub fn download_proxy(&self) -> crate::download::Proxy {
+        crate::download::Proxy {
+            url: self.proxy.clone().filter(|url| !url.is_empty()),
+            no_proxy: self.no_proxy.clone(),
+        }
+    }
+
+    /// Most bytes the download cache may hold, if limited.
+    pub fn cache_limit(&self) -> Option<u64> {
+        self.cache_limit_gb
+            .map(|gb| gb.saturating_mul(1_000_000_000))
+    }
+
+    /// Where to fetch catalog updates from, if anywhere.
+    pub fn catalog_remote(&self) -> Option<crate::catalog::Remote> {
+        let url = self.catalog_url.clone().filter(|url| !url.is_empty())?;
+        let signature = self
+            .catalog_signature_url
+            .clone()
+            .unwrap_or_else(|| format!("{url}.sig"));
+        Some(crate::catalog::Remote {
+            url,
+            signature,
+            key: self.catalog_key.clone(),
+        })
+    }
+
+    /// The device filter script, with a leading "~" expanded.
+    pub fn device_filter_path(&self) -> Option<PathBuf> {
+        self.device_filter.as_deref().map(expand_home)
+    }
+
+    /// Write `bookmarks` back to the config file.
+    ///
+    /// Only the `bookmarks` key is replaced; every other key in the file is kept
+    /// as written. The file and its directory are created if missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config location is known, or the file cannot be
+    /// parsed or written.
+    pub fn save_bookmarks(&self) -> Result<()> {
+        let path = self
+            .path
+            .clone()
+            .or_else(Self::default_path)
+            .ok_or_else(|| anyhow::anyhow!("no config location (HOME is not set)"))?;
+        let mut value = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data)
+                .with_context(|| format!("parse config: {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => serde_json::json!({}),
+            Err(err) => {
+                return Err(err).with_context(|| format!("read config: {}", path.display()));
+            }
+        };
+        let object = value
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("config is not a JSON object: {}", path.display()))?;
+        object.insert("bookmarks".to_string(), serde_json::json!(self.bookmarks));
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("create config dir: {}", dir.display()))?;
+        }
+        let data = serde_json::to_string_pretty(&value)?;
+        std::fs::write(&path, data + "\n")
+            .with_context(|| format!("write config: {}", path.display()))
+    }
+
+    /// Whether a device is hidden by the `hidden_devices` list.
+    pub fn is_hidden(&self, disk: &Disk) -> bool {
+        self.hidden_devices
+            .iter()
+            .any(|entry| disk_matches(disk, entry))
+    }
+
+    /// Whether a device may be written in execute mode.
+    ///
+    /// Every device is trusted when `trusted_devices` is empty.
+    pub fn is_trusted(&self, disk: &Disk) -> bool {
+        self.trusted_devices.is_empty()
+            || self
+                .trusted_devices
+                .iter()
+                .any(|entry| disk_matches(disk, entry))
+    }
+
+    /// Refuse devices outside `trusted_devices` or over the size limit.
+    pub fn ensure_writable(&self, disk: &Disk) -> Result<()> {
+        if !self.is_trusted(disk) {
+            anyhow::bail!(
+                "{} is not in the configured trusted_devices list",
+                disk.device_path()
+            );
+        }
+        if let Some(message) = self.size_limit_error(disk) {
+            anyhow::bail!(message);
+        }
+        Ok(())
+    }
+
+    /// Fail early if a configured hook or the tool behind the hash algorithm
+    /// is missing, rather than after the image has been written.
+    pub fn ensure_flash_prerequisites(&self) -> Result<()> {
+        let hooks = [
+            ("pre-flash hook", self.pre_hook_path()),
+            ("label hook", self.label_hook_path()),
+            ("post-flash hook", self.post_hook_path()),
+        ];
+        for (what, script) in hooks {
+            if let Some(script) = script.filter(|script| !script.is_file()) {
+                anyhow::bail!("{what} not found: {}", script.display());
+            }
+        }
+        self.hash_algorithm.ensure_available()
+    }
+
+    /// Why `disk` may not be written under `size_limit_gb`, or `None` if it may.
+    ///
+    /// A disk whose size cannot be read is refused while a limit is set.
+    pub fn size_limit_error(&self, disk: &Disk) -> Option<String> {
+        self.size_limit_error_sized(disk, device::size_bytes(&disk.name))
+    }
+
+    /// `size_limit_error` for a disk of `size` bytes, as reported by a
+    /// `DeviceProvider`.
+    pub fn size_limit_error_sized(&self, disk: &Disk, size: Option<u64>) -> Option<String> {
+        let limit_gb = self.size_limit_gb?;
+        size_limit_message(&disk.device_path(), size, limit_gb)
+    }
+
+    /// Remove hidden devices, and those the `device_filter` script rejects,
+    /// from a freshly enumerated device list.
+    ///
+    /// A filter that cannot run rejects every device: it is there to keep
+    /// devices out, so it fails closed.
+    pub fn filter_devices(&self, devices: Vec<Disk>) -> Vec<Disk> {
+        let filter = self.device_filter_path();
+        devices
+            .into_iter()
+            .filter(|d| !self.is_hidden(d))
+            .filter(|d| match &filter {
+                None => true,
+                Some(script) => hook::device_allowed(script, d).unwrap_or_else(|err| {
+                    log::warn!("device filter: {err:#}; hiding {}", d.device_path());
+                    false
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Refusal message for a device of `size` bytes under a limit of `limit_gb` GB.
+fn size_limit_message(device: &str, size: Option<u64>, limit_gb: u64) -> Option<String> {
+    match size {
+        Some(size) if size <= limit_gb.saturating_mul(1_000_000_000) => None,
+        Some(size) => Some(format!(
+            "{device} is {:.1} GB, over the {limit_gb} GB size limit.",
+            size as f64 / 1e9
+        )),
+        None => Some(format!(
+            "Cannot read the size of {device}; refusing under the {limit_gb} GB size limit."
+        )),
+    }
+}
+
+/// Settings given on the command line, which take precedence over the config
+/// file and are applied again after every reload.
+///
+/// # Fields
+///
+/// * `size_limit_gb` - `--size-limit`; only tightens the config's own limit
+/// * `post_hook` - `--post-hook`
+/// * `hash_algorithm` - `--hash`
+/// * `backend` - `--backend`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    pub size_limit_gb: Option<u64>,
+    pub post_hook: Option<String>,
+    pub hash_algorithm: Option<Algorithm>,
+    pub backend: Option<BackendKind>,
+}
+
+impl Overrides {
+    /// Apply these settings to `config`.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(limit) = self.size_limit_gb {
+            config.size_limit_gb = Some(config.size_limit_gb.map_or(limit, |own| own.min(limit)));
+        }
+        if let Some(hook) = &self.post_hook {
+            config.post_hook = Some(hook.clone());
+        }
+        if let Some(hash) = self.hash_algorithm {
+            config.hash_algorithm = hash;
+        }
+        if let Some(backend) = self.backend {
+            config.backend = backend;
+        }
+    }
+}
+
+/// Directory holding flashr-tui's config files.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("flashr-tui"))
+}
+
+/// Directory holding what flashr-tui writes for itself between runs (the
+/// log and the remembered preferences), as opposed to what the user edits.
+pub fn state_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|dir| dir.join("flashr-tui"))
+}
+
+/// Directory the write offset of each flash is journaled in, for resuming
+/// after a crash (see `flashr_core::journal`).
+pub fn journal_dir() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("journal"))
+}
+
+/// Expand a leading "~" (alone or followed by "/") to `$HOME`.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Resolve a path typed by the user: a leading "~" means home, a relative
+/// path is taken from `base`, and "." and ".." components are folded away.
+///
+/// Symlinks are kept as given rather than canonicalized, since an image's
+/// file name picks its volume label and its line in a checksum list.
+pub fn resolve_path(path: &str, base: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in base.join(expand_home(path)).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Match a config entry against a disk's serial or by-id link (full path or file name).
+fn disk_matches(disk: &Disk, entry: &str) -> bool {
+    let entry = entry.trim();
+    if entry.is_empty() {
+        return false;
+    }
+    if disk.serial.as_deref() == Some(entry) {
+        return true;
+    }
+    match disk.by_id.as_deref() {
+        Some(by_id) => by_id == entry || by_id.rsplit('/').next() == Some(entry),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disk(serial: Option<&str>, by_id: Option<&str>) -> Disk {
+        Disk {
+            name: "sdb".to_string(),
+            vendor: String::new(),
+            model: String::new(),
+            size: String::new(),
+            serial: serial.map(str::to_string),
+            by_id: by_id.map(str::to_string),
+            removable: true,
+        }
+    }
+
+    #[test]
+    fn hidden_devices_match_serial_and_by_id_name() {
+        let config = Config {
+            hidden_devices: vec!["SER123".to_string(), "usb-Backup-0:0".to_string()],
+            ..Config::default()
+        };
+        assert!(config.is_hidden(&disk(Some("SER123"), None)));
+        assert!(config.is_hidden(&disk(None, Some("/dev/disk/by-id/usb-Backup-0:0"))));
+        assert!(!config.is_hidden(&disk(Some("OTHER"), None)));
+
+        let filtered = |script: &str| Config {
+            device_filter: Some(script.to_string()),
+            ..config.clone()
+        };
+        let listed = vec![disk(Some("SER123"), None), disk(Some("OTHER"), None)];
+        let kept = |script: &str| filtered(script).filter_devices(listed.clone()).len();
+        assert_eq!(kept("/bin/true"), 1);
+        assert_eq!(kept("/bin/false"), 0);
+        assert_eq!(kept("/nonexistent/filter"), 0);
+    }
+
+    #[test]
+    fn resolve_path_joins_relative_paths_and_folds_dots() {
+        let base = Path::new("/home/me/Downloads");
+        assert_eq!(
+            resolve_path("../isos/./arch.iso", base),
+            PathBuf::from("/home/me/isos/arch.iso")
+        );
+        assert_eq!(
+            resolve_path("/srv/isos/../arch.iso", base),
+            PathBuf::from("/srv/arch.iso")
+        );
+        assert_eq!(
+            resolve_path("arch.iso", base),
+            PathBuf::from("/home/me/Downloads/arch.iso")
+        );
+    }
+
+    #[test]
+    fn empty_trusted_list_trusts_everything() {
+        let config = Config::default();
+        assert!(config.is_trusted(&disk(None, None)));
+
+        let config = Config {
+            trusted_devices: vec!["SER123".to_string()],
+            ..Config::default()
+        };
+        assert!(config.is_trusted(&disk(Some("SER123"), None)));
+        assert!(!config.is_trusted(&disk(Some("SER999"), None)));
+    }
+
+    #[test]
+    fn size_limit_refuses_larger_and_unknown_sizes() {
+        assert_eq!(
+            size_limit_message("/dev/sdb", Some(32_000_000_000), 32),
+            None
+        );
+        assert_eq!(
+            size_limit_message("/dev/sdb", Some(64_000_000_000), 32).as_deref(),
+            Some("/dev/sdb is 64.0 GB, over the 32 GB size limit.")
+        );
+        assert!(size_limit_message("/dev/sdb", None, 32).is_some());
+        assert_eq!(Config::default().size_limit_error(&disk(None, None)), None);
+    }
+
+    #[test]
+    fn load_from_missing_file_returns_default() {
+        let path = std::env::temp_dir().join("flashr_tui_missing_config.json");
+        let config = Config::load_from(&path).expect("missing config is not an error");
+        assert!(config.hidden_devices.is_empty());
+    }
+
+    #[test]
+    fn save_bookmarks_keeps_other_keys() {
+        let path = std::env::temp_dir().join(format!(
+            "flashr_tui_bookmarks_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, r#"{"theme": "light"}"#).unwrap();
+
+        let mut config = Config::load_from(&path).unwrap();
+        config.bookmarks.push("/mnt/nas".to_string());
+        config.save_bookmarks().unwrap();
+
+        let reloaded = Config::load_from(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(reloaded.theme.as_deref(), Some("light"));
+        assert_eq!(reloaded.bookmarks, vec!["/mnt/nas".to_string()]);
+    }
+}
@@ -0,0 +1,529 @@
+//! Daemon mode: flashing driven over a local socket.
+//!
+//! `flashr-tui daemon` listens on a Unix socket for JSON-RPC 2.0 requests,
+//! one per line, and answers each with a single line, so desktop frontends or
+//! web dashboards can drive flashing while this crate keeps the privileged
+//! logic. Methods:
+//!
+//! - `list_devices` - `{ "all": bool }`; devices as printed by `list --json`
+//! - `start_flash` - `{ "image", "device", "verify", "stage", "checksum", "label" }`;
+//!   starts a flash in the background and returns its `{ "id" }`
+//! - `progress` - `{ "id" }`; the flash's current state (see `FlashStatus`)
+//! - `cancel` - `{ "id" }`; asks the flash to stop, returns `true`
+//!
+//! Flashes follow the same rules as `--no-tui`: the device must be listed,
+//! removable, trusted, and within the size limit, and nothing is written
+//! unless the daemon was started with `--execute`. The socket is created
+//! readable and writable by the daemon's user only. A signal cancels running
+//! flashes and stops the daemon once they have ended.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use nix::sys::stat::{self, Mode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::checksum::Checksum;
+use crate::flash::{self, CancelFlag, FlashOptions, FlashPhase};
+use crate::report::FlashReport;
+use crate::{
+    audit, device, history, signals, stage, Config, FlashError, FlashFailure, FlashResult, Task,
+};
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Application errors: refused devices, unknown flash ids, and the like.
+const REQUEST_FAILED: i64 = -32000;
+
+/// How often the daemon checks for new clients, a signal, or finished flashes.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Socket the daemon listens on when `--socket` is not given:
+/// `$XDG_RUNTIME_DIR/flashr-tui.sock`, `/run/flashr-tui.sock` for root, and
+/// a per-user file in the temp directory otherwise.
+pub fn default_socket() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("flashr-tui.sock");
+    }
+    if flash::is_root() {
+        return PathBuf::from("/run/flashr-tui.sock");
+    }
+    std::env::temp_dir().join(format!("flashr-tui-{}.sock", nix::unistd::getuid()))
+}
+
+/// Listen on `socket` and serve requests until SIGINT, SIGTERM, or SIGHUP.
+///
+/// A leftover socket file from an earlier daemon is replaced, but not one
+/// another daemon is still listening on, nor a file that is not a socket.
+/// The socket is created readable and writable by its owner only. On a
+/// signal, running flashes are cancelled and waited for, and the socket is
+/// removed.
+///
+/// # Errors
+///
+/// Returns an error if `socket` is taken or the socket cannot be created.
+pub fn serve(socket: &Path, config: Config, execute: bool) -> Result<()> {
+    if let Ok(meta) = std::fs::symlink_metadata(socket) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", socket.display());
+        }
+        if UnixStream::connect(socket).is_ok() {
+            anyhow::bail!("another daemon is listening on {}", socket.display());
+        }
+        std::fs::remove_file(socket)
+            .with_context(|| format!("remove stale socket {}", socket.display()))?;
+    }
+    // Created under a umask that leaves only the owner's bits, so there is no
+    // moment in which others may connect.
+    let umask = stat::umask(Mode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(socket);
+    stat::umask(umask);
+    let listener = listener.with_context(|| format!("listen on {}", socket.display()))?;
+    eprintln!(
+        "Listening on {}{}",
+        socket.display(),
+        if execute {
+            ""
+        } else {
+            " (dry run; pass --execute to flash)"
+        }
+    );
+
+    // Polled rather than blocking, so a signal is noticed between clients.
+    listener
+        .set_nonblocking(true)
+        .context("make socket non-blocking")?;
+    let daemon = Arc::new(Daemon::new(config, execute));
+    while signals::received().is_none() {
+        let Ok((stream, _)) = listener.accept() else {
+            std::thread::sleep(ACCEPT_INTERVAL);
+            continue;
+        };
+        let daemon = Arc::clone(&daemon);
+        std::thread::spawn(move || daemon.serve_connection(stream));
+    }
+    // Running flashes see the signal too; let them stop `dd` and sync.
+    daemon.wait_for_flashes();
+    std::fs::remove_file(socket).ok();
+    Ok(())
+}
+
+/// An error reply to a request.
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::new(REQUEST_FAILED, format!("{err:#}"))
+    }
+}
+
+/// Parameters of `start_flash`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StartParams {
+    image: PathBuf,
+    device: String,
+    #[serde(default)]
+    verify: bool,
+    #[serde(default)]
+    stage: bool,
+    checksum: Option<String>,
+    label: Option<String>,
+}
+
+/// Parameters naming a flash, for `progress` and `cancel`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdParams {
+    id: usize,
+}
+
+/// Parameters of `list_devices`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListParams {
+    #[serde(default)]
+    all: bool,
+}
+
+/// State shared by every connection.
+struct Daemon {
+    config: Config,
+    execute: bool,
+    /// Flashes started so far; a flash's id is its index plus one.
+    flashes: Mutex<Vec<Arc<Mutex<FlashStatus>>>>,
+}
+
+impl Daemon {
+    fn new(config: Config, execute: bool) -> Self {
+        Self {
+            config,
+            execute,
+            flashes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Block until every flash has ended.
+    fn wait_for_flashes(&self) {
+        let running = || {
+            let flashes = self.flashes.lock().unwrap();
+            flashes
+                .iter()
+                .any(|status| status.lock().unwrap().result.is_none())
+        };
+        while running() {
+            std::thread::sleep(ACCEPT_INTERVAL);
+        }
+    }
+
+    /// Answer requests on one connection until the client hangs up.
+    fn serve_connection(&self, stream: UnixStream) {
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = self.handle_line(&line);
+            if writeln!(writer, "{reply}").is_err() {
+                return;
+            }
+        }
+    }
+
+    /// The reply to one request line.
+    fn handle_line(&self, line: &str) -> Value {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                return error_reply(Value::Null, RpcError::new(PARSE_ERROR, err.to_string()))
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return error_reply(id, RpcError::new(INVALID_PARAMS, "missing method"));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        match self.call(method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => error_reply(id, err),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "list_devices" => {
+                let params: ListParams = if params.is_null() {
+                    ListParams::default()
+                } else {
+                    parse_params(params)?
+                };
+                let devices = self.config.filter_devices(device::list(params.all)?);
+                Ok(devices.iter().map(|d| d.to_json()).collect())
+            }
+            "start_flash" => self.start_flash(parse_params(params)?),
+            "progress" => {
+                let IdParams { id } = parse_params(params)?;
+                let status = self.flash(id)?;
+                let json = status.lock().unwrap().to_json(id);
+                Ok(json)
+            }
+            "cancel" => {
+                let IdParams { id } = parse_params(params)?;
+                self.flash(id)?.lock().unwrap().cancel.cancel();
+                Ok(Value::Bool(true))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method: {method}"),
+            )),
+        }
+    }
+
+    /// The flash with id `id`.
+    fn flash(&self, id: usize) -> Result<Arc<Mutex<FlashStatus>>, RpcError> {
+        let flashes = self.flashes.lock().unwrap();
+        id.checked_sub(1)
+            .and_then(|index| flashes.get(index))
+            .cloned()
+            .ok_or_else(|| RpcError::new(REQUEST_FAILED, format!("no flash with id {id}")))
+    }
+
+    /// Check a `start_flash` request and start the flash on a worker thread.
+    fn start_flash(&self, params: StartParams) -> Result<Value, RpcError> {
+        if !params.image.is_absolute() || !params.image.is_file() {
+            return Err(RpcError::new(
+                REQUEST_FAILED,
+                format!(
+                    "image not found (give an absolute path): {}",
+                    params.image.display()
+                ),
+            ));
+        }
+        let devices = self.config.filter_devices(device::list(true)?);
+        let disk = device::find_unattended_target(&devices, &params.device)?;
+        if !self.execute {
+            return Err(RpcError::new(
+                REQUEST_FAILED,
+                format!(
+                    "dry run: would flash {} to {} (start the daemon with --execute to flash)",
+                    params.image.display(),
+                    disk.device_path()
+                ),
+            ));
+        }
+        self.config.ensure_writable(disk)?;
+        self.config.ensure_flash_prerequisites()?;
+        let options = FlashOptions {
+            confirmed_wipe: true,
+            require_verification: params.verify,
+            checksum: params
+                .checksum
+                .as_deref()
+                .map(Checksum::parse)
+                .transpose()?,
+            label: params.label,
+            post_hook: self.config.post_hook_path(),
+            pre_hook: self.config.pre_hook_path(),
+            label_hook: self.config.label_hook_path(),
+            hash: self.config.hash_algorithm,
+            backend: self.config.backend,
+            block_size: None,
+            journal_dir: crate::config::journal_dir(),
+            resume: false,
+            stage_dir: params.stage.then(|| self.config.stage_dir_path()).flatten(),
+            cancel: CancelFlag::default(),
+        };
+
+        let target = disk.stable_path();
+        let mut flashes = self.flashes.lock().unwrap();
+        let busy = flashes.iter().any(|status| {
+            let status = status.lock().unwrap();
+            status.result.is_none() && status.device == target
+        });
+        if busy {
+            return Err(RpcError::new(
+                REQUEST_FAILED,
+                format!("{} is already being flashed", disk.device_path()),
+            ));
+        }
+        let status = Arc::new(Mutex::new(FlashStatus::new(
+            params.image.clone(),
+            target.clone(),
+            options.cancel.clone(),
+        )));
+        flashes.push(Arc::clone(&status));
+        let id = flashes.len();
+
+        let image = params.image;
+        let mut report = FlashReport::new(image.clone(), std::slice::from_ref(disk));
+        std::thread::spawn(move || {
+            let devices = [target.clone()];
+            audit::flash_started(&image, &devices);
+            let started = Instant::now();
+            let task = {
+                let image = image.clone();
+                Task::spawn("flash", move |progress_tx| {
+                    flash::flash_image_with_progress(&image, &target, progress_tx, &options)
+                })
+            };
+            let result = task
+                .wait(|line| {
+                    report.apply(&line);
+                    status.lock().unwrap().apply(&line);
+                })
+                .unwrap_or_else(|panicked| Err(FlashError::Other(panicked.into())))
+                .map_err(FlashFailure::from);
+            audit::flash_finished(&image, &devices, &result);
+            let total = status.lock().unwrap().total;
+            report.outcome = Some(FlashResult::finished(
+                &result,
+                Some(started.elapsed()),
+                total,
+            ));
+            if let Err(err) = history::record(&report) {
+                log::warn!("flash not added to the history: {err:#}");
+            }
+            status.lock().unwrap().result = Some(result);
+        });
+        Ok(json!({ "id": id }))
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn error_reply(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+/// What a client can learn about one flash through `progress`.
+///
+/// # Fields
+///
+/// * `image` - Image being written
+/// * `device` - Stable path of the target device
+/// * `phase` - Phase announced last
+/// * `written` - Bytes `dd` has written so far
+/// * `total` - Image size in bytes
+/// * `speed` - Latest transfer rate reported by `dd`
+/// * `staged` - Progress of staging the image in RAM as (done, total) bytes, once reported
+/// * `verified` - Verification progress as (done, total) bytes, once reported
+/// * `message` - Latest other progress message
+/// * `result` - How the flash ended, once it has
+/// * `cancel` - Flag that stops the flash
+#[derive(Debug)]
+struct FlashStatus {
+    image: PathBuf,
+    device: String,
+    phase: Option<FlashPhase>,
+    written: u64,
+    total: u64,
+    speed: Option<String>,
+    staged: Option<(u64, u64)>,
+    verified: Option<(u64, u64)>,
+    message: Option<String>,
+    result: Option<Result<(), FlashFailure>>,
+    cancel: CancelFlag,
+}
+
+impl FlashStatus {
+    fn new(image: PathBuf, device: String, cancel: CancelFlag) -> Self {
+        let total = std::fs::metadata(&image).map(|m| m.len()).unwrap_or(0);
+        Self {
+            image,
+            device,
+            phase: None,
+            written: 0,
+            total,
+            speed: None,
+            staged: None,
+            verified: None,
+            message: None,
+            result: None,
+            cancel,
+        }
+    }
+
+    /// Take in one progress message from the flash thread.
+    fn apply(&mut self, line: &str) {
+        if let Some(name) = line.strip_prefix(flash::PHASE_PREFIX) {
+            self.phase = FlashPhase::parse(name);
+        } else if let Some(staged) = stage::parse_stage_progress(line) {
+            self.staged = Some(staged);
+        } else if let Some(verified) = flash::parse_verify_progress(line) {
+            self.verified = Some(verified);
+        } else if let (Some(FlashPhase::Write), Some(bytes)) =
+            (self.phase, flash::parse_dd_bytes(line))
+        {
+            self.written = bytes;
+            self.speed = flash::parse_dd_rate(line);
+        } else {
+            self.message = Some(line.to_string());
+        }
+    }
+
+    fn to_json(&self, id: usize) -> Value {
+        let state = match &self.result {
+            None => "running",
+            Some(Ok(())) => "succeeded",
+            Some(Err(failure)) if matches!(*failure.error, FlashError::Cancelled) => "cancelled",
+            Some(Err(_)) => "failed",
+        };
+        json!({
+            "id": id,
+            "image": self.image.display().to_string(),
+            "device": self.device,
+            "state": state,
+            "phase": self.phase.map(FlashPhase::name),
+            "written": self.written,
+            "total": self.total,
+            "speed": self.speed,
+            "staged": self.staged,
+            "verified": self.verified,
+            "message": self.message,
+            "error": self
+                .result
+                .as_ref()
+                .and_then(|result| result.as_ref().err())
+                .map(ToString::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_answered_with_results_or_errors() {
+        let daemon = Daemon::new(Config::default(), false);
+        let reply = daemon.handle_line("{not json");
+        assert_eq!(reply["error"]["code"], PARSE_ERROR);
+
+        let reply = daemon.handle_line(r#"{"jsonrpc": "2.0", "id": 7, "method": "format"}"#);
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+
+        let reply = daemon.handle_line(
+            r#"{"jsonrpc": "2.0", "id": 8, "method": "start_flash", "params": {"image": "/a.iso"}}"#,
+        );
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+
+        let reply = daemon.handle_line(
+            r#"{"jsonrpc": "2.0", "id": 9, "method": "progress", "params": {"id": 1}}"#,
+        );
+        assert_eq!(reply["error"]["message"], "no flash with id 1");
+    }
+
+    #[test]
+    fn status_follows_progress_messages() {
+        let mut status = FlashStatus::new(
+            PathBuf::from("/isos/a.iso"),
+            "/dev/sdb".to_string(),
+            CancelFlag::default(),
+        );
+        status.apply("Phase: write");
+        status.apply("2048 bytes (2.0 kB, 2.0 KiB) copied, 1 s, 2.0 kB/s");
+        status.apply("Phase: verify");
+        status.apply("Verified: 512/8192");
+        status.result = Some(Err(FlashError::Cancelled.into()));
+        let json = status.to_json(3);
+        assert_eq!(json["state"], "cancelled");
+        assert_eq!(json["phase"], "verify");
+        assert_eq!(json["written"], 2048);
+        assert_eq!(json["speed"], "2.0 kB/s");
+        assert_eq!(json["verified"], json!([512, 8192]));
+        assert_eq!(json["error"], "Flash cancelled");
+    }
+}
@@ -0,0 +1,1108 @@
+//! Downloading images into the local cache.
+//!
+//! Images are fetched with `curl` (or `wget` when curl is missing) into
+//! `$XDG_CACHE_HOME/flashr-tui/images`, falling back to
+//! `~/.cache/flashr-tui/images`. The transfer goes to a `.part` file whose
+//! growth is reported as progress; only a finished, verified download is
+//! renamed to its final name, so the cache never holds half an image under
+//! a name the Image step would offer. A `DownloadControl` pauses the
+//! transfer (ending the `curl` process and later continuing the `.part`
+//! file) or cancels it.
+//!
+//! When an image has mirrors, `fastest_mirror` first fetches the start of
+//! the file from each of them at once and picks the one that delivered it
+//! fastest.
+//!
+//! Magnet links and `.torrent` URLs are fetched with `aria2c` instead, into
+//! a `.part` directory. Seeding is turned off, so `aria2c` exits as soon as
+//! the image is complete.
+//!
+//! A catalog image can come with `PublishedSums`: the distribution's
+//! checksum list, fetched and signature-checked before the image so that
+//! the image is verified against it like a `--checksum` list.
+//!
+//! An `HttpStream` reads an image straight from its URL instead, for
+//! flashing it as it downloads (see `stream`) without a copy in the cache.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::cache;
+use crate::checksum::{Algorithm, Checksum};
+use crate::error::FlashError;
+
+/// Prefix of download progress messages, followed by "done/total" bytes, or
+/// just "done" when the server does not announce a size.
+pub const DOWNLOAD_PREFIX: &str = "Downloaded: ";
+
+/// Prefix of the message naming the mirror a download uses, followed by its URL.
+pub const MIRROR_PREFIX: &str = "Mirror: ";
+
+/// How often download progress is reported.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Bytes fetched from each mirror to time it.
+const PROBE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// How long each mirror gets to deliver `PROBE_BYTES`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Directory downloaded images are kept in, if a home or cache directory is known.
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("flashr-tui").join("images"))
+}
+
+/// File name to store a download from `url` under: its last path segment,
+/// without any query string or fragment. For a torrent this is the file it
+/// holds: the display name of a magnet link, or the `.torrent` file's name
+/// without that suffix.
+pub fn file_name_for(url: &str) -> Result<String> {
+    if let Some(query) = url.strip_prefix("magnet:?") {
+        return query
+            .split('&')
+            .find_map(|param| param.strip_prefix("dn="))
+            .map(percent_decode)
+            .filter(|name| !name.is_empty() && !name.contains('/') && name != "..")
+            .ok_or_else(|| anyhow::anyhow!("magnet link has no file name (dn=): {url}"));
+    }
+    let name = url
+        .split_once("://")
+        .map(|(_, rest)| rest.split(['?', '#']).next().unwrap_or_default())
+        .and_then(|rest| rest.trim_end_matches('/').split_once('/'))
+        .and_then(|(_host, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..");
+    name.map(|name| name.strip_suffix(".torrent").unwrap_or(name))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("cannot tell the file name from URL: {url}"))
+}
+
+/// Decode the %XX escapes and '+' spaces of a URL query value.
+fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(if byte == b'+' { b' ' } else { byte });
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Host name of `url`, to tell mirrors apart (e.g., "mirror.aarnet.edu.au").
+pub fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest)
+}
+
+/// Index of the fastest of `urls`, timing a short ranged download from
+/// each of them at once.
+///
+/// Each mirror's speed, or that it did not answer, goes to `progress`. With
+/// no `curl` to time them, or when none answers, the first URL is used.
+pub fn fastest_mirror(urls: &[String], proxy: &Proxy, progress: &mpsc::Sender<String>) -> usize {
+    if urls.len() < 2 || which::which("curl").is_err() {
+        return 0;
+    }
+    let _ = progress.send(format!("Timing {} mirrors...", urls.len()));
+    let speeds: Vec<Option<f64>> = std::thread::scope(|scope| {
+        let probes: Vec<_> = urls
+            .iter()
+            .map(|url| scope.spawn(|| probe_speed(url, proxy)))
+            .collect();
+        probes
+            .into_iter()
+            .map(|probe| probe.join().ok().flatten())
+            .collect()
+    });
+    for (url, speed) in urls.iter().zip(&speeds) {
+        let _ = progress.send(match speed {
+            Some(speed) => format!("{}: {}/s", host_of(url), crate::format_size(*speed as u64)),
+            None => format!("{}: no answer", host_of(url)),
+        });
+    }
+    pick_fastest(&speeds)
+}
+
+/// Index of the highest speed, or 0 when none was measured.
+fn pick_fastest(speeds: &[Option<f64>]) -> usize {
+    speeds
+        .iter()
+        .enumerate()
+        .filter_map(|(index, speed)| speed.map(|speed| (index, speed)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Bytes per second `url` delivered its first `PROBE_BYTES` at, if it answered.
+fn probe_speed(url: &str, proxy: &Proxy) -> Option<f64> {
+    let output = proxy
+        .apply(&mut Command::new("curl"))
+        .args(["--silent", "--fail", "--location", "--output", "/dev/null"])
+        .arg("--range")
+        .arg(format!("0-{}", PROBE_BYTES - 1))
+        .arg("--max-time")
+        .arg(PROBE_TIMEOUT.as_secs().to_string())
+        .args(["--write-out", "%{speed_download} %{size_download}"])
+        .arg(url)
+        .output()
+        .ok()?;
+    // A mirror still sending when the time is up (curl exits with 28) was
+    // measured all the same; one that failed outright was not.
+    if !output.status.success() && output.status.code() != Some(28) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (speed, size) = text.trim().split_once(' ')?;
+    let size: f64 = size.parse().ok()?;
+    (size > 0.0).then(|| speed.parse().ok()).flatten()
+}
+
+/// Parse a download progress message into (done, total) bytes.
+pub fn parse_download_progress(line: &str) -> Option<(u64, Option<u64>)> {
+    let rest = line.strip_prefix(DOWNLOAD_PREFIX)?;
+    match rest.split_once('/') {
+        Some((done, total)) => Some((done.parse().ok()?, Some(total.parse().ok()?))),
+        None => Some((rest.parse().ok()?, None)),
+    }
+}
+
+/// Proxy settings for downloads, on top of the environment's.
+///
+/// `curl`, `wget`, and `aria2c` all read `http_proxy`, `https_proxy`,
+/// `ftp_proxy`, and `no_proxy` themselves; a setting here replaces the
+/// environment's for the tools flashr-tui starts.
+///
+/// # Fields
+///
+/// * `url` - Proxy for HTTP, HTTPS, and FTP (e.g., "http://proxy.example:3128")
+/// * `no_proxy` - Comma-separated hosts and domains reached without the proxy
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proxy {
+    pub url: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+impl Proxy {
+    /// Set the proxy variables of `cmd` to this proxy's settings.
+    pub fn apply<'a>(&self, cmd: &'a mut Command) -> &'a mut Command {
+        if let Some(url) = &self.url {
+            for var in ["http_proxy", "https_proxy", "ftp_proxy"] {
+                cmd.env(var, url);
+            }
+        }
+        if let Some(hosts) = &self.no_proxy {
+            cmd.env("no_proxy", hosts);
+        }
+        cmd
+    }
+}
+
+/// Pauses or cancels a running `download`.
+///
+/// Pausing stops `curl`/`wget` and keeps the `.part` file; resuming starts
+/// it again, continuing where the file ends. Cancelling removes the `.part`
+/// file and makes the download fail with "download cancelled".
+#[derive(Debug, Clone, Default)]
+pub struct DownloadControl(Arc<AtomicU8>);
+
+impl DownloadControl {
+    const RUNNING: u8 = 0;
+    const PAUSED: u8 = 1;
+    const CANCELLED: u8 = 2;
+
+    /// Stop the transfer until `resume` is called.
+    pub fn pause(&self) {
+        let _ = self.0.compare_exchange(
+            Self::RUNNING,
+            Self::PAUSED,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Continue a paused transfer.
+    pub fn resume(&self) {
+        let _ = self.0.compare_exchange(
+            Self::PAUSED,
+            Self::RUNNING,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Give up on the download.
+    pub fn cancel(&self) {
+        self.0.store(Self::CANCELLED, Ordering::Relaxed);
+    }
+
+    /// Whether `pause` was called and the download not resumed since.
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == Self::PAUSED
+    }
+
+    /// Whether `cancel` has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == Self::CANCELLED
+    }
+}
+
+/// Whether `text` is a URL or magnet link `download` can fetch rather than
+/// a local path.
+pub fn is_url(text: &str) -> bool {
+    let text = text.trim();
+    ["http://", "https://", "ftp://", "magnet:?"]
+        .iter()
+        .any(|scheme| text.len() > scheme.len() && text.starts_with(scheme))
+}
+
+/// Whether `url` is fetched with BitTorrent: a magnet link or a URL of a
+/// `.torrent` file.
+pub fn is_torrent(url: &str) -> bool {
+    let url = url.trim();
+    url.starts_with("magnet:?")
+        || url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or(url)
+            .ends_with(".torrent")
+}
+
+/// Download `url` into `dir` and verify it.
+///
+/// A file already in `dir` that matches `checksum` is reused without
+/// downloading again. Without a checksum the image's SHA-256 is reported so
+/// it can be compared by hand. `control` pauses, resumes, or cancels it.
+///
+/// # Arguments
+///
+/// * `url` - HTTP(S) or FTP URL of the image, or a magnet link or `.torrent` URL
+/// * `dir` - Directory to store the image in (created if missing)
+/// * `checksum` - Digest or checksum list the download must match
+/// * `proxy` - Proxy settings from the config, on top of the environment's
+/// * `progress` - Channel to send progress messages to
+/// * `control` - Pauses or cancels the transfer from another thread
+///
+/// # Returns
+///
+/// Path of the downloaded image.
+///
+/// # Errors
+///
+/// Returns an error if neither `curl` nor `wget` is installed (`aria2c` for
+/// a torrent), the transfer fails or is cancelled, or the downloaded file does not match `checksum`
+/// (it is then removed).
+pub fn download(
+    url: &str,
+    dir: &Path,
+    checksum: Option<&Checksum>,
+    proxy: &Proxy,
+    progress: mpsc::Sender<String>,
+    control: &DownloadControl,
+) -> Result<PathBuf> {
+    let dest = dir.join(file_name_for(url)?);
+    if let Some(checksum) = checksum {
+        if dest.is_file() && checksum.verify(&dest).is_ok() {
+            let _ = progress.send(format!("Already downloaded: {}", dest.display()));
+            cache::touch(&dest);
+            return Ok(dest);
+        }
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+
+    // A torrent is fetched into a directory of its own, next to where a
+    // plain download keeps its .part file.
+    let torrent = is_torrent(url);
+    let scratch = dest.with_file_name(format!(
+        "{}.part",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let discard = || {
+        if torrent {
+            std::fs::remove_dir_all(&scratch).ok();
+        } else {
+            std::fs::remove_file(&scratch).ok();
+        }
+    };
+    let fetched = if torrent {
+        fetch_torrent(url, &scratch, proxy, &progress, control)
+    } else {
+        fetch(url, &scratch, proxy, &progress, control).map(|()| scratch.clone())
+    };
+    let partial = match fetched {
+        Ok(partial) => partial,
+        Err(err) => {
+            discard();
+            return Err(err);
+        }
+    };
+
+    let _ = progress.send("Verifying download...".to_string());
+    // A checksum list names the image, not the .part file it is still in.
+    let expected = checksum.map(|c| c.expected_for(&dest)).transpose()?;
+    let algorithm = expected.as_ref().map_or(Algorithm::Sha256, |(a, _)| *a);
+    let digest = algorithm.hash_file(&partial)?;
+    match expected {
+        Some((algorithm, expected)) if expected != digest => {
+            discard();
+            return Err(FlashError::ChecksumMismatch {
+                image: dest,
+                algorithm,
+                expected,
+                actual: digest,
+            }
+            .into());
+        }
+        Some(_) => {
+            let _ = progress.send("Checksum matches.".to_string());
+        }
+        None => {
+            let _ = progress.send(format!(
+                "No checksum given; compare the SHA-256 by hand: {digest}"
+            ));
+        }
+    }
+    let sha256 = match algorithm {
+        Algorithm::Sha256 => digest,
+        _ => Algorithm::Sha256.hash_file(&partial)?,
+    };
+    std::fs::rename(&partial, &dest)
+        .with_context(|| format!("move download to {}", dest.display()))?;
+    if torrent {
+        discard();
+    }
+    cache::record(&dest, &sha256)?;
+    Ok(dest)
+}
+
+/// Fetch `url` into `partial` with `curl` or `wget`, reporting its growth.
+fn fetch(
+    url: &str,
+    partial: &Path,
+    proxy: &Proxy,
+    progress: &mpsc::Sender<String>,
+    control: &DownloadControl,
+) -> Result<()> {
+    let total = content_length(url, proxy);
+    let spawn = |resume: bool| {
+        fetch_command(url, partial, resume, proxy)?
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("start download")
+    };
+    let mut child = spawn(false)?;
+    let _ = progress.send(format!("Downloading {url}"));
+
+    let report = || {
+        let done = std::fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+        let _ = progress.send(match total {
+            Some(total) => format!("{DOWNLOAD_PREFIX}{done}/{total}"),
+            None => format!("{DOWNLOAD_PREFIX}{done}"),
+        });
+    };
+    let status = loop {
+        if control.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("download cancelled");
+        }
+        if control.is_paused() {
+            // A stopped transfer would time out on the server's side anyway,
+            // so pausing ends it and resuming continues from the .part file.
+            let _ = child.kill();
+            let _ = child.wait();
+            report();
+            wait_while_paused(progress, control);
+            if !control.is_cancelled() {
+                child = spawn(true)?;
+            }
+            continue;
+        }
+        if let Some(status) = child.try_wait().context("wait for download")? {
+            break status;
+        }
+        report();
+        std::thread::sleep(REPORT_INTERVAL);
+    };
+    if !status.success() {
+        let mut stderr = String::new();
+        if let Some(mut pipe) = child.stderr.take() {
+            let _ = pipe.read_to_string(&mut stderr);
+        }
+        anyhow::bail!("download failed: {}", stderr.trim());
+    }
+    report();
+    Ok(())
+}
+
+/// An image read straight from its URL with `curl` (or `wget`), for
+/// `stream::flash_stream_with_progress`.
+///
+/// A transfer that fails part way reads as an error rather than as the end
+/// of the image. Dropping the stream stops the transfer.
+#[derive(Debug)]
+pub struct HttpStream {
+    child: Child,
+    output: ChildStdout,
+    size: Option<u64>,
+}
+
+impl HttpStream {
+    /// Start downloading `url`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a magnet link or `.torrent` URL, whose pieces
+    /// arrive out of order, or if neither `curl` nor `wget` can be started.
+    pub fn open(url: &str, proxy: &Proxy) -> Result<Self> {
+        if is_torrent(url) {
+            anyhow::bail!("a torrent cannot be streamed; download it first");
+        }
+        let size = content_length(url, proxy);
+        let mut child = fetch_command(url, Path::new("-"), false, proxy)?
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("start download")?;
+        let output = child.stdout.take().context("download has no output")?;
+        Ok(Self {
+            child,
+            output,
+            size,
+        })
+    }
+
+    /// Size of the image as announced by the server, if it did.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.output.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = self.child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+                return Err(std::io::Error::other(format!(
+                    "download failed: {}",
+                    stderr.trim()
+                )));
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for HttpStream {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Fetch the torrent or magnet link `source` into the directory `scratch`
+/// with `aria2c`, which stops as soon as the download is complete instead
+/// of seeding it. Returns the path of the downloaded image in `scratch`.
+///
+/// `proxy` reaches the `.torrent` file and HTTP trackers; peers are
+/// connected to directly.
+fn fetch_torrent(
+    source: &str,
+    scratch: &Path,
+    proxy: &Proxy,
+    progress: &mpsc::Sender<String>,
+    control: &DownloadControl,
+) -> Result<PathBuf> {
+    if which::which("aria2c").is_err() {
+        anyhow::bail!("torrent downloads need aria2c");
+    }
+    let name = file_name_for(source)?;
+    std::fs::create_dir_all(scratch).with_context(|| format!("create {}", scratch.display()))?;
+    let spawn = || -> Result<(Child, std::thread::JoinHandle<Vec<String>>)> {
+        let mut child = proxy
+            .apply(&mut Command::new("aria2c"))
+            .args([
+                "--seed-time=0",
+                "--continue=true",
+                "--follow-torrent=mem",
+                "--file-allocation=none",
+                "--summary-interval=1",
+                "--console-log-level=error",
+                "--enable-color=false",
+            ])
+            .arg("--dir")
+            .arg(scratch)
+            .arg(source)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("start aria2c")?;
+        let stdout = child.stdout.take().context("aria2c has no output")?;
+        let progress = progress.clone();
+        let relay = std::thread::spawn(move || relay_aria2_output(stdout, &progress));
+        Ok((child, relay))
+    };
+    let (mut child, mut relay) = spawn()?;
+    let _ = progress.send(format!("Downloading {source} with BitTorrent"));
+
+    let status = loop {
+        if control.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("download cancelled");
+        }
+        if control.is_paused() {
+            // aria2c keeps a control file in `scratch` and picks up from it.
+            let _ = child.kill();
+            let _ = child.wait();
+            wait_while_paused(progress, control);
+            if !control.is_cancelled() {
+                (child, relay) = spawn()?;
+            }
+            continue;
+        }
+        if let Some(status) = child.try_wait().context("wait for aria2c")? {
+            break status;
+        }
+        std::thread::sleep(REPORT_INTERVAL);
+    };
+    let errors = relay.join().unwrap_or_default();
+    if !status.success() {
+        anyhow::bail!(
+            "torrent download failed: {}",
+            match errors.last() {
+                Some(error) => error.clone(),
+                None => format!("aria2c {status}"),
+            }
+        );
+    }
+    let image = scratch.join(&name);
+    if !image.is_file() {
+        anyhow::bail!("the torrent does not hold a file named {name}");
+    }
+    Ok(image)
+}
+
+/// Pass `aria2c`'s progress on as download progress messages until it
+/// exits. Returns the error lines it printed.
+fn relay_aria2_output(stdout: impl Read, progress: &mpsc::Sender<String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        if let Some((done, total)) = parse_aria2_progress(&line) {
+            let _ = progress.send(format!("{DOWNLOAD_PREFIX}{done}/{total}"));
+        } else if line.contains("ERROR") || line.contains("Exception") {
+            errors.push(line.trim().to_string());
+        }
+    }
+    errors
+}
+
+/// Parse an `aria2c` progress line such as
+/// "[#2089b0 400.0KiB/33.2MiB(1%) CN:1 DL:115.7KiB ETA:4m51s]" into
+/// (done, total) bytes. Lines before the size is known have no total.
+fn parse_aria2_progress(line: &str) -> Option<(u64, u64)> {
+    let sizes = line.trim().strip_prefix("[#")?.split_whitespace().nth(1)?;
+    let (done, total) = sizes.split('(').next()?.split_once('/')?;
+    let total = aria2_size(total).filter(|total| *total > 0)?;
+    Some((aria2_size(done)?, total))
+}
+
+/// Bytes in an `aria2c` size such as "33.2MiB" or "0B".
+fn aria2_size(text: &str) -> Option<u64> {
+    let split = text.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = text.split_at(split);
+    let exponent = ["B", "KiB", "MiB", "GiB", "TiB"]
+        .iter()
+        .position(|u| *u == unit)?;
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024f64.powi(exponent as i32)).round() as u64)
+}
+
+/// Report a pause and wait until the download is resumed or cancelled.
+fn wait_while_paused(progress: &mpsc::Sender<String>, control: &DownloadControl) {
+    let _ = progress.send("Download paused".to_string());
+    while control.is_paused() {
+        std::thread::sleep(REPORT_INTERVAL);
+    }
+    if !control.is_cancelled() {
+        let _ = progress.send("Download resumed".to_string());
+    }
+}
+
+/// A checksum list published next to an image, such as `SHA256SUMS`.
+///
+/// # Fields
+///
+/// * `url` - Where the list is downloaded from
+/// * `signature` - Detached OpenPGP signature of the list, if published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedSums {
+    pub url: String,
+    pub signature: Option<String>,
+}
+
+impl PublishedSums {
+    /// Download the list into `dir`, next to the image `file_name`, and
+    /// check its signature with `gpg`: the detached one, or the list's own
+    /// when it is clear-signed. A clear-signed list is then cut down to its
+    /// signed text, so lines outside the signature are never used.
+    ///
+    /// A signature that cannot be checked (no `gpg`, or the signing key is
+    /// not in the user's keyring) is reported to `progress` and otherwise
+    /// ignored, as the list still catches a corrupted download.
+    ///
+    /// # Returns
+    ///
+    /// The list as a checksum, and the files fetched for it, to remove once
+    /// the image is verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be downloaded, the signature is bad,
+    /// or a clear-signed list holds other than one signed message.
+    pub fn fetch(
+        &self,
+        file_name: &str,
+        dir: &Path,
+        proxy: &Proxy,
+        progress: &mpsc::Sender<String>,
+    ) -> Result<(Checksum, Vec<PathBuf>)> {
+        std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        let _ = progress.send(format!("Fetching checksums from {}", self.url));
+        let list = dir.join(format!("{file_name}.{}", file_name_for(&self.url)?));
+        fetch_small(&self.url, &list, proxy)?;
+        let mut files = vec![list.clone()];
+        let checked = (|| {
+            let signature = match &self.signature {
+                Some(url) => {
+                    let path = dir.join(format!("{file_name}.{}", file_name_for(url)?));
+                    files.push(path.clone());
+                    fetch_small(url, &path, proxy)?;
+                    Some(path)
+                }
+                None => None,
+            };
+            let text = std::fs::read_to_string(&list).unwrap_or_default();
+            let clear_signed = text.contains(SIGNED_MESSAGE);
+            if signature.is_some() || clear_signed {
+                let _ = progress.send(check_signature(&list, signature.as_deref())?);
+            }
+            if clear_signed {
+                std::fs::write(&list, signed_text(&text)?)
+                    .with_context(|| format!("write {}", list.display()))?;
+            }
+            Ok(())
+        })();
+        if let Err(err) = checked {
+            for file in &files {
+                std::fs::remove_file(file).ok();
+            }
+            return Err(err);
+        }
+        Ok((Checksum::List(list), files))
+    }
+}
+
+/// Armor line opening a clear-signed message.
+const SIGNED_MESSAGE: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// Armor line ending the text of a clear-signed message.
+const SIGNATURE_START: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// The text a clear-signed message signs: what lies between its armor
+/// headers and its signature, with dash-escaping undone. gpg ignores
+/// anything before and after, so that must not be trusted either.
+///
+/// # Errors
+///
+/// Fails unless `text` holds exactly one complete clear-signed message.
+fn signed_text(text: &str) -> Result<String> {
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    let starts = text
+        .lines()
+        .filter(|line| line.trim_end() == SIGNED_MESSAGE)
+        .count();
+    if starts != 1 {
+        anyhow::bail!("the checksum list holds {starts} signed messages instead of one");
+    }
+    lines.find(|line| line.trim_end() == SIGNED_MESSAGE);
+    // Armor headers ("Hash: SHA256") end at the first empty line.
+    lines.find(|line| line.is_empty());
+    let mut body = String::new();
+    for line in lines {
+        if line == SIGNATURE_START {
+            return Ok(body);
+        }
+        body.push_str(line.strip_prefix("- ").unwrap_or(line));
+        body.push('\n');
+    }
+    anyhow::bail!("the checksum list's signed message has no signature")
+}
+
+/// Download the small file at `url` to `out` in one go.
+pub(crate) fn fetch_small(url: &str, out: &Path, proxy: &Proxy) -> Result<()> {
+    let output = fetch_command(url, out, false, proxy)?
+        .stdout(std::process::Stdio::null())
+        .output()
+        .context("start download")?;
+    if !output.status.success() {
+        std::fs::remove_file(out).ok();
+        anyhow::bail!(
+            "download of {url} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Check the OpenPGP signature of `list` with `gpg`, detached in
+/// `signature` or clear-signed into the list itself. Returns what to tell
+/// the user when the signature is good or cannot be checked.
+fn check_signature(list: &Path, signature: Option<&Path>) -> Result<String> {
+    if which::which("gpg").is_err() {
+        return Ok("Signature not checked: gpg is not installed".to_string());
+    }
+    signature_verdict(&gpg_verify(list, signature)?)
+}
+
+/// Require a good OpenPGP signature of `file`, detached in `signature`,
+/// made by the key with the fingerprint `key` (or a subkey of it), which
+/// must be in the user's GnuPG keyring.
+///
+/// # Returns
+///
+/// The signer, as gpg names them.
+///
+/// # Errors
+///
+/// Fails when `key` is not a full fingerprint, `gpg` is not installed, the
+/// signature is bad or made by another key, or it cannot be checked, e.g.
+/// because the key is not in the keyring.
+pub fn require_signature(file: &Path, signature: &Path, key: &str) -> Result<String> {
+    let key: String = key.chars().filter(|c| !c.is_whitespace()).collect();
+    // A short key id is easily matched by a forged key.
+    if !matches!(key.len(), 40 | 64) || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("{key} is not a full OpenPGP key fingerprint");
+    }
+    if which::which("gpg").is_err() {
+        anyhow::bail!("checking the signature needs gpg, which is not installed");
+    }
+    let status = gpg_verify(file, Some(signature))?;
+    match gpg_verdict(&status) {
+        GpgVerdict::Good { signer } if signed_by(&status, &key) => Ok(signer),
+        GpgVerdict::Good { signer } => {
+            anyhow::bail!("the signature is by {signer}, not by key {key}")
+        }
+        GpgVerdict::Bad => anyhow::bail!("the signature is BAD"),
+        GpgVerdict::NoKey { key } => {
+            anyhow::bail!("the signing key {key} is not in your GnuPG keyring")
+        }
+        GpgVerdict::Unknown => anyhow::bail!("gpg could not verify the signature"),
+    }
+}
+
+/// Run `gpg --verify` on `file` and its detached `signature` (or its own
+/// clear-signed one), returning its `--status-fd` output.
+fn gpg_verify(file: &Path, signature: Option<&Path>) -> Result<String> {
+    let mut cmd = Command::new("gpg");
+    cmd.args(["--batch", "--status-fd", "1", "--verify"]);
+    if let Some(signature) = signature {
+        cmd.arg(signature);
+    }
+    let output = cmd
+        .arg(file)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .context("run gpg")?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// What `gpg --status-fd` output says about a signature.
+#[derive(Debug, PartialEq, Eq)]
+enum GpgVerdict {
+    Good { signer: String },
+    Bad,
+    NoKey { key: String },
+    Unknown,
+}
+
+/// Read the verdict out of `gpg --status-fd` output.
+fn gpg_verdict(status: &str) -> GpgVerdict {
+    let mut lines = status
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] "));
+    let verdict = lines.find_map(|line| {
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "GOODSIG" | "BADSIG" | "NO_PUBKEY" => Some((keyword, rest)),
+            _ => None,
+        }
+    });
+    match verdict {
+        Some(("GOODSIG", rest)) => {
+            let signer = rest.split_once(' ').map_or(rest, |(_key, signer)| signer);
+            GpgVerdict::Good {
+                signer: signer.to_string(),
+            }
+        }
+        Some(("BADSIG", _)) => GpgVerdict::Bad,
+        Some(("NO_PUBKEY", key)) => GpgVerdict::NoKey {
+            key: key.to_string(),
+        },
+        _ => GpgVerdict::Unknown,
+    }
+}
+
+/// Whether `gpg --status-fd` output reports a valid signature by the key
+/// with the fingerprint `key`, or by one of its subkeys.
+fn signed_by(status: &str, key: &str) -> bool {
+    status
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .any(|rest| {
+            // The signing key's fingerprint comes first, the primary key's tenth.
+            let fields: Vec<&str> = rest.split(' ').collect();
+            let mut fingerprints = [fields.first(), fields.get(9)].into_iter().flatten();
+            fingerprints.any(|fingerprint| fingerprint.eq_ignore_ascii_case(key))
+        })
+}
+
+/// Judge `gpg --status-fd` output: a good signature names its signer, a bad
+/// one is an error, and anything else means it could not be checked.
+fn signature_verdict(status: &str) -> Result<String> {
+    match gpg_verdict(status) {
+        GpgVerdict::Good { signer } => Ok(format!("Checksums signed by {signer}")),
+        GpgVerdict::Bad => anyhow::bail!("the checksum list's signature is BAD"),
+        GpgVerdict::NoKey { key } => Ok(format!(
+            "Signature not checked: key {key} is not in your GnuPG keyring"
+        )),
+        GpgVerdict::Unknown => Ok("Signature not checked: gpg could not verify it".to_string()),
+    }
+}
+
+/// Size of the file at `url` as announced by the server, following redirects.
+fn content_length(url: &str, proxy: &Proxy) -> Option<u64> {
+    let output = proxy
+        .apply(&mut Command::new("curl"))
+        .args(["--silent", "--head", "--location", url])
+        .output()
+        .ok()?;
+    // Each redirect hop prints its own headers; the last length is the file's.
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .filter_map(|(_, value)| value.trim().parse().ok())
+        .next_back()
+}
+
+/// The command that fetches `url` to `out` ("-" for its standard output):
+/// `curl`, or `wget` as a fallback. With `resume`, it appends to what `out`
+/// already holds.
+fn fetch_command(url: &str, out: &Path, resume: bool, proxy: &Proxy) -> Result<Command> {
+    if which::which("curl").is_ok() {
+        let mut cmd = Command::new("curl");
+        proxy.apply(&mut cmd);
+        cmd.args(["--fail", "--silent", "--show-error", "--location"]);
+        if resume {
+            cmd.args(["--continue-at", "-"]);
+        }
+        cmd.arg("--output").arg(out).arg(url);
+        Ok(cmd)
+    } else if which::which("wget").is_ok() {
+        let mut cmd = Command::new("wget");
+        proxy.apply(&mut cmd);
+        cmd.arg("--quiet");
+        if resume {
+            cmd.arg("--continue");
+        }
+        cmd.arg("--output-document").arg(out).arg(url);
+        Ok(cmd)
+    } else {
+        anyhow::bail!("downloading needs curl or wget")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_for_takes_the_last_path_segment() {
+        assert_eq!(
+            file_name_for("https://example.org/isos/arch.iso?mirror=1#x").unwrap(),
+            "arch.iso"
+        );
+        assert_eq!(
+            file_name_for("https://example.org/releases/debian.iso/").unwrap(),
+            "debian.iso"
+        );
+        assert!(file_name_for("https://example.org").is_err());
+        assert!(file_name_for("https://example.org/isos/..").is_err());
+        assert!(file_name_for("arch.iso").is_err());
+
+        assert!(is_url(" https://example.org/arch.iso"));
+        assert!(is_url("ftp://example.org/arch.iso"));
+        assert!(!is_url("https://"));
+        assert!(!is_url("/home/me/https://arch.iso"));
+    }
+
+    #[test]
+    fn torrents_are_named_after_the_file_they_hold_and_report_progress() {
+        let magnet = "magnet:?xt=urn:btih:0123abcd&dn=arch%2Dlinux+2024.iso&tr=udp%3A%2F%2Ft";
+        assert!(is_url(magnet) && is_torrent(magnet));
+        assert_eq!(file_name_for(magnet).unwrap(), "arch-linux 2024.iso");
+        assert!(file_name_for("magnet:?xt=urn:btih:0123abcd").is_err());
+        assert!(file_name_for("magnet:?dn=..%2Fetc%2Fpasswd").is_err());
+        let url = "https://example.org/debian.iso.torrent";
+        assert!(is_torrent(url) && !is_torrent("https://example.org/debian.iso"));
+        assert_eq!(file_name_for(url).unwrap(), "debian.iso");
+
+        assert_eq!(
+            parse_aria2_progress("[#2089b0 400.0KiB/1.5MiB(26%) CN:1 DL:115.7KiB ETA:9s]"),
+            Some((409_600, 1_572_864))
+        );
+        assert_eq!(parse_aria2_progress("[#2089b0 0B/0B CN:1 DL:0B]"), None);
+        assert_eq!(parse_aria2_progress("[#2089b0 SEED(0.0) CN:0]"), None);
+    }
+
+    #[test]
+    fn mirrors_are_named_by_host_and_the_fastest_answering_wins() {
+        assert_eq!(
+            host_of("https://mirror.aarnet.edu.au/pub/arch.iso"),
+            "mirror.aarnet.edu.au"
+        );
+        assert_eq!(host_of("ftp://ftp.jaist.ac.jp"), "ftp.jaist.ac.jp");
+        assert_eq!(pick_fastest(&[None, Some(2.0e6), Some(9.0e6)]), 2);
+        assert_eq!(pick_fastest(&[None, None]), 0);
+    }
+
+    #[test]
+    fn only_a_bad_signature_fails_the_checksum_list() {
+        let good = "[GNUPG:] NEWSIG\n\
+                    [GNUPG:] GOODSIG 843938DF228D22F7B3742BC0D94AA3F0EFE21092 Ubuntu CD Image Automatic Signing Key (2012) <cdimage@ubuntu.com>\n";
+        assert_eq!(
+            signature_verdict(good).unwrap(),
+            "Checksums signed by Ubuntu CD Image Automatic Signing Key (2012) <cdimage@ubuntu.com>"
+        );
+        assert!(signature_verdict("[GNUPG:] BADSIG 0123 Someone\n").is_err());
+        assert_eq!(
+            signature_verdict("[GNUPG:] ERRSIG 0123 1 8 00 0 9\n[GNUPG:] NO_PUBKEY 0123\n")
+                .unwrap(),
+            "Signature not checked: key 0123 is not in your GnuPG keyring"
+        );
+        assert!(signature_verdict("")
+            .unwrap()
+            .starts_with("Signature not checked"));
+
+        let valid = "[GNUPG:] VALIDSIG 1111111111111111111111111111111111111111 2031-01-05 \
+                     1925251200 0 4 0 1 10 00 843938DF228D22F7B3742BC0D94AA3F0EFE21092\n";
+        assert!(signed_by(valid, "843938df228d22f7b3742bc0d94aa3f0efe21092"));
+        assert!(signed_by(valid, "1111111111111111111111111111111111111111"));
+        assert!(!signed_by(
+            valid,
+            "2222222222222222222222222222222222222222"
+        ));
+        assert!(!signed_by(good, "843938DF228D22F7B3742BC0D94AA3F0EFE21092"));
+        let file = Path::new("/nonexistent");
+        assert!(require_signature(file, file, "EFE21092").is_err());
+    }
+
+    #[test]
+    fn only_the_signed_text_of_a_clear_signed_list_is_used() {
+        let good = "a".repeat(64);
+        let forged = "b".repeat(64);
+        let text = format!(
+            "{forged}  Fedora.iso\n\
+             -----BEGIN PGP SIGNED MESSAGE-----\n\
+             Hash: SHA256\n\
+             \n\
+             # Fedora.iso: 2 GiB\n\
+             - SHA256 (Fedora.iso) = {good}\n\
+             -----BEGIN PGP SIGNATURE-----\n\
+             \n\
+             iQIzBAEBCAAdFiEE\n\
+             -----END PGP SIGNATURE-----\n\
+             SHA256 (Fedora.iso) = {forged}\n"
+        );
+        let body = signed_text(&text).unwrap();
+        assert_eq!(
+            crate::checksum::find_in_list(&body, "Fedora.iso", Algorithm::Sha256),
+            Some((Algorithm::Sha256, good))
+        );
+        assert!(!body.contains(&forged));
+
+        let twice = format!("{text}{text}");
+        assert!(signed_text(&twice).is_err());
+        assert!(signed_text("-----BEGIN PGP SIGNED MESSAGE-----\n\nabc\n").is_err());
+    }
+
+    #[test]
+    fn a_configured_proxy_replaces_the_environments() {
+        let mut cmd = Command::new("curl");
+        Proxy::default().apply(&mut cmd);
+        assert_eq!(cmd.get_envs().count(), 0);
+
+        let proxy = Proxy {
+            url: Some("http://proxy.example.com:3128".to_string()),
+            no_proxy: Some("localhost,.example.com".to_string()),
+        };
+        let envs: Vec<_> = proxy
+            .apply(&mut cmd)
+            .get_envs()
+            .map(|(var, value)| (var.to_owned(), value.map(|v| v.to_owned())))
+            .collect();
+        assert_eq!(envs.len(), 4);
+        for (var, value) in envs {
+            let expected = match var.to_str() {
+                Some("no_proxy") => "localhost,.example.com",
+                _ => "http://proxy.example.com:3128",
+            };
+            assert_eq!(value.as_deref(), Some(std::ffi::OsStr::new(expected)));
+        }
+    }
+
+    #[test]
+    fn download_progress_round_trips_with_and_without_total() {
+        assert_eq!(
+            parse_download_progress("Downloaded: 512/2048"),
+            Some((512, Some(2048)))
+        );
+        assert_eq!(
+            parse_download_progress("Downloaded: 512"),
+            Some((512, None))
+        );
+        assert_eq!(parse_download_progress("Downloading https://x/y.iso"), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Newline-delimited JSON progress events for `--progress-json`.
+//!
+//! The flash thread's progress messages are turned into one JSON object per
+//! line, so GUIs and CI systems can follow a flash without scraping `dd`
+//! output. Every event has an `"event"` key:
+//!
+//! - `start` - `image`, `device`, and `total` bytes
+//! - `phase` - `phase` is one of the `FlashPhase` names
+//! - `progress` - `phase`, `bytes` done, `total`, and `speed` while writing;
+//!   without `speed` while staging or verifying
+//! - `image_hash` - `algorithm` (e.g. "sha256") and hex `hash` of the image
+//! - `verification` - `result` of the read-back comparison
+//! - `label` - `label` applied to the stick
+//! - `message` - any other progress line, as `text`
+//! - `done` - `ok`, plus `error` when the flash failed
+//!
+//! In a job run every event also carries the 1-based `job` number.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::flash::{self, FlashPhase};
+use crate::stage;
+
+/// Turns the progress messages of one flash into events.
+///
+/// # Fields
+///
+/// * `total` - Image size in bytes, the total of the write phase
+/// * `phase` - Phase announced last
+/// * `job` - Job number added to every event, in a job run
+#[derive(Debug, Clone)]
+pub struct EventStream {
+    total: u64,
+    phase: Option<FlashPhase>,
+    job: Option<usize>,
+}
+
+impl EventStream {
+    /// Start a stream for writing an image of `total` bytes.
+    pub fn new(total: u64, job: Option<usize>) -> Self {
+        Self {
+            total,
+            phase: None,
+            job,
+        }
+    }
+
+    /// The event announcing the flash of `image` to `device`.
+    pub fn started(&self, image: &Path, device: &str) -> Value {
+        self.tagged(json!({
+            "event": "start",
+            "image": image.display().to_string(),
+            "device": device,
+            "total": self.total,
+        }))
+    }
+
+    /// The event for one progress message from the flash thread.
+    pub fn line(&mut self, line: &str) -> Value {
+        let event = if let Some(name) = line.strip_prefix(flash::PHASE_PREFIX) {
+            self.phase = FlashPhase::parse(name);
+            json!({ "event": "phase", "phase": name })
+        } else if let Some((done, total)) = stage::parse_stage_progress(line) {
+            json!({ "event": "progress", "phase": "stage", "bytes": done, "total": total })
+        } else if let Some((done, total)) = flash::parse_verify_progress(line) {
+            json!({ "event": "progress", "phase": "verify", "bytes": done, "total": total })
+        } else if let Some((algorithm, hash)) = line
+            .strip_prefix(flash::IMAGE_HASH_PREFIX)
+            .and_then(|spec| spec.split_once(':'))
+        {
+            json!({ "event": "image_hash", "algorithm": algorithm, "hash": hash })
+        } else if let Some(verdict) = line.strip_prefix(flash::VERDICT_PREFIX) {
+            json!({ "event": "verification", "result": verdict })
+        } else if let Some(label) = line.strip_prefix(flash::LABEL_PREFIX) {
+            json!({ "event": "label", "label": label })
+        } else if let (Some(FlashPhase::Write), Some(bytes)) =
+            (self.phase, flash::parse_dd_bytes(line))
+        {
+            json!({
+                "event": "progress",
+                "phase": "write",
+                "bytes": bytes,
+                "total": self.total,
+                "speed": flash::parse_dd_rate(line),
+            })
+        } else {
+            json!({ "event": "message", "text": line })
+        };
+        self.tagged(event)
+    }
+
+    /// The event reporting how the flash ended.
+    pub fn finished<E: Display>(&self, result: &Result<(), E>) -> Value {
+        self.tagged(match result {
+            Ok(()) => json!({ "event": "done", "ok": true }),
+            Err(err) => json!({ "event": "done", "ok": false, "error": err.to_string() }),
+        })
+    }
+
+    fn tagged(&self, mut event: Value) -> Value {
+        if let (Some(job), Some(object)) = (self.job, event.as_object_mut()) {
+            object.insert("job".to_string(), json!(job));
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_become_typed_events() {
+        let mut stream = EventStream::new(4096, Some(2));
+        assert_eq!(
+            stream.line("Phase: write"),
+            json!({ "event": "phase", "phase": "write", "job": 2 })
+        );
+        assert_eq!(
+            stream.line("2048 bytes (2.0 kB, 2.0 KiB) copied, 1 s, 2.0 kB/s"),
+            json!({
+                "event": "progress",
+                "phase": "write",
+                "bytes": 2048,
+                "total": 4096,
+                "speed": "2.0 kB/s",
+                "job": 2,
+            })
+        );
+        assert_eq!(
+            stream.line("Staged: 1024/4096"),
+            json!({ "event": "progress", "phase": "stage", "bytes": 1024, "total": 4096, "job": 2 })
+        );
+        assert_eq!(
+            stream.line("Verified: 512/8192"),
+            json!({ "event": "progress", "phase": "verify", "bytes": 512, "total": 8192, "job": 2 })
+        );
+        assert_eq!(
+            stream.line("Syncing..."),
+            json!({ "event": "message", "text": "Syncing...", "job": 2 })
+        );
+        assert_eq!(
+            stream.finished(&Err("dd failed".to_string())),
+            json!({ "event": "done", "ok": false, "error": "dd failed", "job": 2 })
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Format a device for normal use with a single partition.
+//!
+//! After an installer has been written to a stick, desktop tools often can't
+//! make sense of its hybrid layout. This module replaces it with a fresh MBR
+//! partition table holding one FAT32, exFAT, or ext4 partition, using the same
+//! elevation and progress plumbing as the flash engine.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::device::DevicePath;
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, sanitize_label,
+    truncate_label, unmount_partitions,
+};
+use crate::platform;
+use crate::wipe::run_quiet;
+
+/// How long to wait for the kernel to create the new partition node.
+const PARTITION_WAIT: Duration = Duration::from_secs(10);
+
+/// Filesystem to create on the formatted device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filesystem {
+    /// FAT32 - readable everywhere, 4 GiB file size limit
+    Fat32,
+    /// exFAT - large files, supported by modern Windows/macOS/Linux
+    Exfat,
+    /// ext4 - Linux-only
+    Ext4,
+}
+
+impl Filesystem {
+    /// All supported filesystems, in the order they are offered in the UI.
+    pub const ALL: [Filesystem; 3] = [Filesystem::Fat32, Filesystem::Exfat, Filesystem::Ext4];
+
+    /// Display name, e.g. "FAT32".
+    pub fn name(self) -> &'static str {
+        match self {
+            Filesystem::Fat32 => "FAT32",
+            Filesystem::Exfat => "exFAT",
+            Filesystem::Ext4 => "ext4",
+        }
+    }
+
+    /// Parse a filesystem name as accepted on the command line.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "fat32" | "vfat" | "fat" => Some(Filesystem::Fat32),
+            "exfat" => Some(Filesystem::Exfat),
+            "ext4" => Some(Filesystem::Ext4),
+            _ => None,
+        }
+    }
+
+    /// The next filesystem in `ALL`, wrapping around.
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|fs| *fs == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Maximum label length supported by the filesystem.
+    pub fn max_label_len(self) -> usize {
+        match self {
+            Filesystem::Fat32 => 11,
+            Filesystem::Exfat => 15,
+            Filesystem::Ext4 => 16,
+        }
+    }
+
+    /// MBR partition type id written by `sfdisk`.
+    fn mbr_type(self) -> &'static str {
+        match self {
+            Filesystem::Fat32 => "c",
+            Filesystem::Exfat => "7",
+            Filesystem::Ext4 => "83",
+        }
+    }
+
+    /// The mkfs tool and its arguments (excluding the partition path).
+    fn mkfs_command(self, label: &str) -> (&'static str, Vec<String>) {
+        match self {
+            Filesystem::Fat32 => (
+                "mkfs.vfat",
+                vec!["-F".into(), "32".into(), "-n".into(), label.into()],
+            ),
+            Filesystem::Exfat => ("mkfs.exfat", vec!["-L".into(), label.into()]),
+            Filesystem::Ext4 => ("mkfs.ext4", vec!["-F".into(), "-L".into(), label.into()]),
+        }
+    }
+}
+
+/// Prepare a user-entered label for a filesystem: sanitize, then truncate.
+///
+/// FAT labels are conventionally upper-case, so FAT32 labels are upper-cased.
+pub fn normalize_label(fs: Filesystem, label: &str) -> String {
+    let label = truncate_label(&sanitize_label(label.trim()), fs.max_label_len());
+    if fs == Filesystem::Fat32 {
+        label.to_uppercase()
+    } else {
+        label
+    }
+}
+
+/// Format a device with a single partition of the chosen filesystem.
+///
+/// Unmounts existing partitions, writes a new MBR partition table with one
+/// partition spanning the device, and creates the filesystem on it.
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `fs` - Filesystem to create
+/// * `label` - Volume label (sanitized and truncated to the filesystem's limit)
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the device is invalid or unsafe, elevation fails, or
+/// `wipefs`, `sfdisk`, or the mkfs tool fail.
+pub fn format_device(
+    device: &str,
+    fs: Filesystem,
+    label: &str,
+    progress: mpsc::Sender<String>,
+) -> Result<()> {
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    ensure_device_safe(dev, true)?;
+    let label = normalize_label(fs, label);
+    let (tool, mut args) = fs.mkfs_command(&label);
+    which::which(tool).with_context(|| format!("{tool} is not installed"))?;
+    let elevator = acquire_elevator(&progress)?;
+
+    let partitions = list_partitions(dev)?;
+    if !partitions.is_empty() {
+        let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+        unmount_partitions(&partitions, elevator);
+    }
+
+    let _ = progress.send(format!("Removing old signatures from {dev}..."));
+    let platform = platform::current();
+    run_quiet(&mut platform.wipe_signatures(dev, elevator), "wipefs")?;
+
+    let _ = progress.send("Writing new partition table...".to_string());
+    let mut sfdisk = elevated_command("sfdisk", elevator)
+        .args(["--quiet", "--wipe", "always", dev])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("run sfdisk")?;
+    if let Some(mut stdin) = sfdisk.stdin.take() {
+        writeln!(stdin, "label: dos\ntype={}", fs.mbr_type()).context("write sfdisk script")?;
+    }
+    let output = sfdisk.wait_with_output().context("wait for sfdisk")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(anyhow::anyhow!("sfdisk failed: {stderr}"));
+    }
+
+    let _ = platform
+        .reread_partitions(dev, elevator)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let partition = wait_for_partition(dev, || Ok(Some(platform.partition_path(dev, 1))))?;
+
+    let _ = progress.send(format!("Creating {} filesystem on {partition}...", fs.name()));
+    args.push(partition.clone());
+    run_quiet(elevated_command(tool, elevator).args(&args), tool)?;
+
+    let label_note = if label.is_empty() {
+        String::new()
+    } else {
+        format!(" labelled {label}")
+    };
+    let _ = progress.send(format!("{dev} formatted as {}{label_note}.", fs.name()));
+    Ok(())
+}
+
+/// Wait until `find` names the new partition on `dev` and its device node
+/// appears (udev may create it asynchronously). Returns the node's path.
+pub(crate) fn wait_for_partition(
+    dev: &str,
+    mut find: impl FnMut() -> Result<Option<String>>,
+) -> Result<String> {
+    let deadline = std::time::Instant::now() + PARTITION_WAIT;
+    loop {
+        if let Some(partition) = find()? {
+            if std::path::Path::new(&partition).exists() {
+                return Ok(partition);
+            }
+        }
+        if std::time::Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("the new partition on {dev} did not appear"));
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_label_applies_filesystem_limits() {
+        assert_eq!(
+            normalize_label(Filesystem::Fat32, "my usb stick"),
+            "MY_USB_STIC"
+        );
+        assert_eq!(normalize_label(Filesystem::Ext4, "backup"), "backup");
+    }
+
+    #[test]
+    fn filesystem_parse_and_cycle() {
+        assert_eq!(Filesystem::parse("VFAT"), Some(Filesystem::Fat32));
+        assert_eq!(Filesystem::parse("ntfs"), None);
+        assert_eq!(Filesystem::Ext4.next(), Filesystem::Fat32);
+    }
+}
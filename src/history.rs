@@ -0,0 +1,262 @@
+//! Record of past flashes.
+//!
+//! Every flash that runs, from the TUI, `--no-tui`, `run`, or the daemon, is
+//! appended once it ends, successful or not, as one JSON line in
+//! `$XDG_STATE_HOME/flashr-tui/history.jsonl` (falling back to
+//! `~/.local/state`). Each line records when it happened, the image, each
+//! device with its serial, and the result. `flashr-tui history` prints it,
+//! and the TUI shows it with 'h', so "which stick did I put Fedora on last
+//! week?" has an answer. Lines that fail to parse are skipped, so a damaged
+//! line never hides the rest.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::state_dir;
+use crate::report::FlashReport;
+
+/// One device written by a recorded flash.
+///
+/// # Fields
+///
+/// * `device` - Kernel device path (e.g., "/dev/sdb")
+/// * `serial` - Hardware serial number, if the device reports one
+/// * `label` - Volume label applied after flashing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryDevice {
+    pub device: String,
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// One finished flash.
+///
+/// # Fields
+///
+/// * `time` - When it ended, in seconds since the Unix epoch
+/// * `image` - Image that was flashed
+/// * `image_hash` - Digest of the image as "<algorithm>:<hex>", if verification hashed it
+/// * `devices` - Target devices, in flashing order
+/// * `ok` - Whether the flash succeeded
+/// * `message` - The result message shown at the time
+/// * `bytes_written` - Image bytes written across all targets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: u64,
+    pub image: PathBuf,
+    #[serde(default)]
+    pub image_hash: Option<String>,
+    pub devices: Vec<HistoryDevice>,
+    pub ok: bool,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub bytes_written: u64,
+}
+
+impl HistoryEntry {
+    /// The entry for a finished flash, or `None` if `report` has no outcome yet.
+    pub fn from_report(report: &FlashReport, time: SystemTime) -> Option<Self> {
+        let outcome = report.outcome.as_ref()?;
+        Some(Self {
+            time: time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            image: report.image.clone(),
+            image_hash: report.image_hash.clone(),
+            devices: report
+                .targets
+                .iter()
+                .map(|target| HistoryDevice {
+                    device: target.device.clone(),
+                    serial: target.serial.clone(),
+                    label: target.label.clone(),
+                })
+                .collect(),
+            ok: outcome.ok,
+            message: outcome.message.clone(),
+            bytes_written: outcome.bytes_written,
+        })
+    }
+
+    /// One line for lists: time, result, image file name, and devices.
+    pub fn summary(&self) -> String {
+        let image = self.image.file_name().map_or_else(
+            || self.image.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let devices: Vec<String> = self
+            .devices
+            .iter()
+            .map(|device| match &device.serial {
+                Some(serial) => format!("{} (serial {serial})", device.device),
+                None => device.device.clone(),
+            })
+            .collect();
+        format!(
+            "{}  {:<6}  {image} -> {}",
+            format_timestamp(self.time),
+            if self.ok { "ok" } else { "FAILED" },
+            devices.join(", ")
+        )
+    }
+
+    /// Whether `query` appears, ignoring case, in the image path, a device
+    /// path, a serial, or a label.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        let contains = |text: &str| text.to_lowercase().contains(&query);
+        contains(&self.image.to_string_lossy())
+            || self.devices.iter().any(|device| {
+                contains(&device.device)
+                    || device.serial.as_deref().is_some_and(contains)
+                    || device.label.as_deref().is_some_and(contains)
+            })
+    }
+}
+
+/// Default location of the history file, if a home or state directory is known.
+pub fn default_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("history.jsonl"))
+}
+
+/// Append `entry` to the history file at `path`, creating it if needed.
+pub fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("create state dir: {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("open history: {}", path.display()))?;
+    let line = serde_json::to_string(entry)?;
+    writeln!(file, "{line}").with_context(|| format!("write history: {}", path.display()))
+}
+
+/// Add the finished flash in `report` to the history at the default location.
+///
+/// Does nothing for a report without an outcome.
+///
+/// # Errors
+///
+/// Returns an error if no state location is known or the file cannot be
+/// written.
+pub fn record(report: &FlashReport) -> Result<()> {
+    let Some(entry) = HistoryEntry::from_report(report, SystemTime::now()) else {
+        return Ok(());
+    };
+    let path =
+        default_path().ok_or_else(|| anyhow::anyhow!("no state location (HOME is not set)"))?;
+    append(&path, &entry)
+}
+
+/// Every recorded flash at `path`, oldest first; none if the file does not
+/// exist.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("read history: {}", path.display()));
+        }
+    };
+    Ok(data
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Every recorded flash at the default location, oldest first.
+pub fn load_default() -> Result<Vec<HistoryEntry>> {
+    match default_path() {
+        Some(path) => load(&path),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Format seconds since the Unix epoch as "YYYY-MM-DD HH:MM UTC".
+///
+/// Written out by hand (days-from-civil, inverted), as the crate has no
+/// date library and the time zone database is not read.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let minutes = secs % 86_400 / 60;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Disk;
+    use crate::FlashResult;
+    use std::time::Duration;
+
+    #[test]
+    fn history_keeps_finished_flashes_in_order() {
+        let disk = Disk {
+            name: "sdb".to_string(),
+            vendor: String::new(),
+            model: String::new(),
+            size: "16G".to_string(),
+            serial: Some("4C5300012309".to_string()),
+            by_id: None,
+            removable: true,
+        };
+        let mut report = FlashReport::new(PathBuf::from("/isos/Fedora-40.iso"), &[disk]);
+        let time = UNIX_EPOCH + Duration::from_secs(1_718_454_600);
+        assert!(HistoryEntry::from_report(&report, time).is_none());
+        report.outcome = Some(FlashResult::finished(&Ok(()), None, 2_000_000_000));
+        let entry = HistoryEntry::from_report(&report, time).unwrap();
+        assert_eq!(
+            entry.summary(),
+            "2024-06-15 12:30 UTC  ok      Fedora-40.iso -> /dev/sdb (serial 4C5300012309)"
+        );
+        assert!(entry.matches("fedora") && entry.matches("4c53"));
+        assert!(!entry.matches("ubuntu"));
+
+        let path =
+            std::env::temp_dir().join(format!("flashr_tui_history_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(load(&path).unwrap().is_empty());
+        append(&path, &entry).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{ truncated\n")
+            .unwrap();
+        let second = HistoryEntry {
+            ok: false,
+            ..entry.clone()
+        };
+        append(&path, &second).unwrap();
+        assert_eq!(load(&path).unwrap(), [entry, second]);
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+    }
+}
@@ -0,0 +1,161 @@
+//! Job files for flashing several devices in one unattended run.
+//!
+//! A job file is JSON, like the config, and lists image-to-device jobs with
+//! their own options:
+//!
+//! ```json
+//! {
+//!   "parallel": true,
+//!   "jobs": [
+//!     { "image": "~/isos/arch.iso", "device": "/dev/disk/by-id/usb-SanDisk_A1-0:0", "verify": true },
+//!     { "image": "/mnt/nas/debian.iso", "device": "/dev/sdc", "label": "DEBIAN",
+//!       "checksum": "sha256:...", "stage": true }
+//!   ]
+//! }
+//! ```
+//!
+//! Unknown keys are rejected so a misspelled option fails loudly instead of
+//! being ignored on a run nobody is watching.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::checksum::Checksum;
+use crate::config::expand_home;
+use crate::flash::{CancelFlag, FlashOptions};
+use crate::Config;
+
+/// A parsed job file.
+///
+/// # Fields
+///
+/// * `parallel` - Flash all jobs at once instead of one after another
+/// * `jobs` - Image-to-device operations, in order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    #[serde(default)]
+    pub parallel: bool,
+    pub jobs: Vec<Job>,
+}
+
+/// One image-to-device operation.
+///
+/// # Fields
+///
+/// * `image` - Image to flash (a leading "~" means home)
+/// * `device` - Target device, e.g. "/dev/sdb" or a by-id link
+/// * `verify` - Fail when the read-back verification cannot run, like `--verify`
+/// * `stage` - Copy the image into RAM before writing it, like `--stage`
+/// * `label` - Volume label to set after flashing, like `--label`
+/// * `checksum` - Checksum the image must match, like `--checksum`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub image: String,
+    pub device: String,
+    #[serde(default)]
+    pub verify: bool,
+    #[serde(default)]
+    pub stage: bool,
+    pub label: Option<String>,
+    pub checksum: Option<String>,
+}
+
+impl JobFile {
+    /// Load and check a job file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, lists no jobs,
+    /// or names the same device in more than one job.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("read job file: {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("job file: {}", path.display()))
+    }
+
+    fn parse(data: &str) -> Result<Self> {
+        let file: Self = serde_json::from_str(data)?;
+        if file.jobs.is_empty() {
+            anyhow::bail!("no jobs listed");
+        }
+        for (index, job) in file.jobs.iter().enumerate() {
+            if let Some(earlier) = file.jobs[..index]
+                .iter()
+                .position(|j| j.device == job.device)
+            {
+                anyhow::bail!(
+                    "jobs {} and {} both write to {}",
+                    earlier + 1,
+                    index + 1,
+                    job.device
+                );
+            }
+        }
+        Ok(file)
+    }
+}
+
+impl Job {
+    /// The image path with a leading "~" expanded.
+    pub fn image_path(&self) -> PathBuf {
+        expand_home(&self.image)
+    }
+
+    /// Flash options for this job, with the post-flash hook, hash
+    /// algorithm, and backend from `config`. Naming the device in the job file stands in
+    /// for the wipe confirmation, as it does for `--no-tui`.
+    pub fn options(&self, config: &Config) -> Result<FlashOptions> {
+        Ok(FlashOptions {
+            confirmed_wipe: true,
+            require_verification: self.verify,
+            checksum: self.checksum.as_deref().map(Checksum::parse).transpose()?,
+            label: self.label.clone(),
+            post_hook: config.post_hook_path(),
+            pre_hook: config.pre_hook_path(),
+            label_hook: config.label_hook_path(),
+            hash: config.hash_algorithm,
+            backend: config.backend,
+            block_size: None,
+            journal_dir: crate::config::journal_dir(),
+            resume: false,
+            stage_dir: self.stage.then(|| config.stage_dir_path()).flatten(),
+            cancel: CancelFlag::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_jobs_and_rejects_mistakes() {
+        let file = JobFile::parse(
+            r#"{"jobs": [{"image": "/isos/a.iso", "device": "/dev/sdb", "verify": true},
+                         {"image": "/isos/b.iso", "device": "/dev/sdc", "label": "B"}]}"#,
+        )
+        .unwrap();
+        assert!(!file.parallel);
+        assert_eq!(file.jobs.len(), 2);
+        assert!(file.jobs[0].verify);
+        assert_eq!(file.jobs[1].label.as_deref(), Some("B"));
+
+        assert!(JobFile::parse(r#"{"jobs": []}"#).is_err());
+        assert!(JobFile::parse(
+            r#"{"jobs": [{"image": "/isos/a.iso", "device": "/dev/sdb", "verfiy": true}]}"#
+        )
+        .is_err());
+        let duplicate = JobFile::parse(
+            r#"{"jobs": [{"image": "/isos/a.iso", "device": "/dev/sdb"},
+                         {"image": "/isos/b.iso", "device": "/dev/sdb"}]}"#,
+        );
+        assert_eq!(
+            duplicate.unwrap_err().to_string(),
+            "jobs 1 and 2 both write to /dev/sdb"
+        );
+    }
+}
@@ -1,18 +1,55 @@
 //! Core application state machine and types for flashr-tui.
 //!
-//! This module defines the `App` struct which represents the entire application state,
-//! the `Step` enum for the state machine, and helper types for file picking and flash results.
+//! This module defines the `App` struct which represents the entire application state
+//! and helper types for file picking and flash results. The steps of the state machine
+//! and the data each one carries are in `step`.
 
-pub mod device;
-pub mod flash;
-pub mod iso;
+pub mod activity;
+pub mod audit;
+pub mod bench;
+pub mod cache;
+pub mod capacity;
+pub mod catalog;
+pub mod clipboard;
+pub mod completion;
+pub mod config;
+pub mod daemon;
+pub mod download;
+pub mod events;
+pub mod format;
+pub mod history;
+pub mod jobs;
+pub mod lineedit;
+pub mod logging;
+pub mod modal;
+pub mod prefs;
+pub mod report;
+pub mod scan;
+pub mod seed;
+pub mod snapshot;
+pub mod step;
+pub mod theme;
 pub mod ui;
+pub mod wipe;
 
-use std::path::PathBuf;
-use std::sync::mpsc::Receiver;
+// The UI-free flashing logic lives in flashr-core; its modules are re-exported
+// under their old paths.
+pub use flashr_core::{
+    backend, checksum, device, error, flash, hook, inhibit, iso, journal, platform, provider,
+    signals, stage, stream, task, udisks,
+};
 
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub use config::Config;
 pub use device::Disk;
+pub use error::{DeviceError, FlashError};
 pub use iso::IsoKind;
+pub use provider::DeviceProvider;
+pub use step::Step;
+pub use task::Task;
 
 /// Represents a file or directory entry in the file picker.
 ///
@@ -21,44 +58,60 @@ pub use iso::IsoKind;
 /// * `name` - Display name of the file or directory (includes ".." for parent)
 /// * `path` - Full path to the file or directory
 /// * `is_dir` - `true` if this entry is a directory, `false` if it's a file
+/// * `size` - File size in bytes (0 for directories)
+/// * `modified` - Last modification time, if the filesystem reports one
 #[derive(Debug, Clone)]
 pub struct FileEntry {
     pub name: String,
     pub path: PathBuf,
     pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<std::time::SystemTime>,
 }
 
-/// Application step/state in the state machine.
+/// Details of the highlighted file picker entry, shown in the preview pane.
+///
+/// # Fields
 ///
-/// The application flows through these states in order:
-/// 1. `Image` - User selects an ISO file via file picker
-/// 2. `Device` - User selects a target USB device from device list
-/// 3. `Confirm` - User reviews selection and confirms before flashing
-/// 4. `ConvertIso` - User confirms in-place isohybrid conversion (if non-hybrid)
-/// 5. `ConfirmWipe` - User confirms overwriting existing partitions on device
-/// 6. `Flashing` - Flash operation in progress (non-interactive)
-/// 7. `Result` - Flash operation completed; displays result
-/// 8. `Error` - An error occurred during operation
+/// * `path` - Entry the preview was built for
+/// * `detection` - Image type detection result, for files that look like disk images
+#[derive(Debug, Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub detection: Option<String>,
+}
+
+/// Order of entries in the file picker.
 ///
-/// User can go back from `Device` -> `Image`, `Confirm` -> `Device`, or `ConfirmWipe` -> `Confirm`.
+/// Directories are always listed before files; the mode orders each group.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Step {
-    /// User is selecting ISO image file from filesystem
-    Image,
-    /// User is selecting target USB device
-    Device,
-    /// User is reviewing selection before flashing
-    Confirm,
-    /// User is confirming in-place isohybrid conversion of a non-hybrid ISO
-    ConvertIso,
-    /// User is confirming overwrite of existing partitions on the target device
-    ConfirmWipe,
-    /// Flashing is in progress; non-interactive
-    Flashing,
-    /// Flash operation completed; showing result (success or failure)
-    Result,
-    /// An error occurred; showing error message
-    Error,
+pub enum SortMode {
+    /// Alphabetical, case-insensitive
+    Name,
+    /// Most recently modified first
+    Newest,
+    /// Largest first
+    Largest,
+}
+
+impl SortMode {
+    /// Short label for the file picker header.
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Newest => "newest",
+            SortMode::Largest => "largest",
+        }
+    }
+
+    /// The next mode in the cycle.
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Newest,
+            SortMode::Newest => SortMode::Largest,
+            SortMode::Largest => SortMode::Name,
+        }
+    }
 }
 
 /// Signals the application to exit.
@@ -74,67 +127,319 @@ pub enum AppExit {
 ///
 /// * `ok` - `true` if flash succeeded, `false` if it failed
 /// * `message` - User-friendly message describing the result
+/// * `error` - Why the flash failed, for matching on the kind of failure
+/// * `elapsed` - Time from starting the flash to its result (`None` for dry runs)
+/// * `bytes_written` - Image bytes written across all targets (0 unless it succeeded)
 #[derive(Debug, Clone)]
 pub struct FlashResult {
     pub ok: bool,
     pub message: String,
+    pub error: Option<Arc<FlashError>>,
+    pub elapsed: Option<Duration>,
+    pub bytes_written: u64,
+}
+
+impl FlashResult {
+    /// The result of a finished flash, with `bytes_written` counted only if
+    /// it succeeded.
+    pub fn finished(
+        result: &Result<(), FlashFailure>,
+        elapsed: Option<Duration>,
+        bytes_written: u64,
+    ) -> Self {
+        match result {
+            Ok(()) => Self {
+                ok: true,
+                message: "Flash completed successfully.".to_string(),
+                error: None,
+                elapsed,
+                bytes_written,
+            },
+            Err(failure) => Self {
+                ok: false,
+                message: format!("{failure:#}"),
+                error: Some(Arc::clone(&failure.error)),
+                elapsed,
+                bytes_written: 0,
+            },
+        }
+    }
+
+    /// What the user can do about the failure, for kinds that have a remedy.
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self.error.as_deref()? {
+            FlashError::Device(DeviceError::NotFound { .. } | DeviceError::Detached { .. }) => {
+                "Check that the device is still plugged in, then rescan."
+            }
+            FlashError::Device(DeviceError::Mounted { .. }) => {
+                "Unmount its partitions, or confirm the wipe to have them unmounted."
+            }
+            FlashError::Device(DeviceError::Busy { .. }) => {
+                "Close the programs using the device and try again."
+            }
+            FlashError::NoElevator => "Install pkexec or sudo, or run flashr-tui as root.",
+            FlashError::AuthenticationFailed => "Check the sudo password and try again.",
+            FlashError::NotHybrid => {
+                "This image cannot be raw-written; use a tool that extracts it instead."
+            }
+            FlashError::ChecksumMismatch { .. } => {
+                "The image is damaged or not the one the checksum is for; download it again."
+            }
+            FlashError::VerificationMismatch => {
+                "The device may be failing or fake; run a capacity test before trusting it."
+            }
+            _ => return None,
+        })
+    }
+
+    /// Duration and average speed, e.g. "Took 3m 12s (average 24.7 MB/s)".
+    ///
+    /// The average covers the whole run, sync and verification included, so
+    /// it compares sticks end to end rather than echoing `dd`'s write rate.
+    pub fn timing_summary(&self) -> Option<String> {
+        let elapsed = self.elapsed?;
+        let took = format!("Took {}", format_duration(elapsed));
+        if self.bytes_written == 0 {
+            return Some(took);
+        }
+        Some(format!(
+            "{took} (average {:.1} MB/s)",
+            bench::throughput_mbps(self.bytes_written, elapsed)
+        ))
+    }
+}
+
+/// A failed flash, and the device it failed on when several were flashed in
+/// one go.
+///
+/// # Fields
+///
+/// * `device` - Device path, set only when flashing more than one device
+/// * `error` - Why the flash failed
+#[derive(Debug, Clone)]
+pub struct FlashFailure {
+    pub device: Option<String>,
+    pub error: Arc<FlashError>,
+}
+
+impl From<FlashError> for FlashFailure {
+    fn from(error: FlashError) -> Self {
+        Self {
+            device: None,
+            error: Arc::new(error),
+        }
+    }
+}
+
+impl std::fmt::Display for FlashFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(device) = &self.device {
+            write!(f, "{device}: ")?;
+        }
+        std::fmt::Display::fmt(&*self.error, f)
+    }
+}
+
+impl std::error::Error for FlashFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// A short-lived notification drawn over the corner of the screen.
+///
+/// # Fields
+///
+/// * `message` - Text of the notification
+/// * `expires` - When `expire_toast` removes it
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub expires: Instant,
+}
+
+/// How the device list changed in the last rescan, highlighted in the list
+/// until `expires`.
+///
+/// # Fields
+///
+/// * `added` - Stable paths of the devices that appeared
+/// * `removed` - Devices that disappeared, still drawn (struck out) below the list
+/// * `expires` - When `expire_device_changes` removes the highlight
+#[derive(Debug, Clone)]
+pub struct DeviceChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<Disk>,
+    pub expires: Instant,
+}
+
+impl DeviceChanges {
+    /// What changed from the `old` device list to the `new` one, matching
+    /// devices by stable path; `None` if the same devices are listed.
+    pub fn between(old: &[Disk], new: &[Disk]) -> Option<Self> {
+        let listed = |disks: &[Disk], disk: &Disk| {
+            let path = disk.stable_path();
+            disks.iter().any(|d| d.stable_path() == path)
+        };
+        let added: Vec<String> = new
+            .iter()
+            .filter(|disk| !listed(old, disk))
+            .map(Disk::stable_path)
+            .collect();
+        let removed: Vec<Disk> = old
+            .iter()
+            .filter(|disk| !listed(new, disk))
+            .cloned()
+            .collect();
+        if added.is_empty() && removed.is_empty() {
+            return None;
+        }
+        Some(Self {
+            added,
+            removed,
+            expires: Instant::now() + DEVICE_CHANGES_DURATION,
+        })
+    }
+
+    /// Whether `disk` is one of the devices that appeared.
+    pub fn is_added(&self, disk: &Disk) -> bool {
+        self.added.contains(&disk.stable_path())
+    }
 }
 
 /// Main application state struct.
 ///
-/// This struct holds all the mutable state needed by the TUI application, including
-/// the current step in the state machine, file picker state, device list, flash progress,
-/// and channels for receiving updates from background threads.
+/// This struct holds all the mutable state needed by the TUI application: the
+/// current step in the state machine, and the state that outlives any one step
+/// (file picker, device list, options from the command line). What a step
+/// needs only while it is shown, such as a running flash or wipe, is carried
+/// by the step itself (see `step`).
 ///
 /// # Fields
 ///
-/// * `step` - Current step in the state machine (Image/Device/Confirm/Flashing/Result/Error)
+/// * `step` - Current step in the state machine, with its data
 /// * `image_input` - User-entered path or filename search string for ISO file
+/// * `image_cursor` - Cursor position in `image_input` (byte offset)
 /// * `cwd` - Current working directory for file picker navigation
 /// * `entries` - Files and directories in the current working directory
 /// * `entry_selected` - Index of selected entry in file picker
+/// * `preview` - Preview of the highlighted entry (refreshed by `refresh_preview`)
 /// * `iso_kind` - Detected ISO type (Hybrid/NonHybrid/Unknown)
 /// * `iso_info` - Human-readable string describing ISO detection result
 /// * `devices` - List of available USB devices
 /// * `selected` - Index of selected device in device list
-/// * `selected_device` - Full `Disk` struct of selected device (or None)
+/// * `device_changes` - Devices the last rescan added or removed, highlighted for a while
+/// * `device_filter` - Text typed after '/' that narrows the device list
+/// * `device_filter_editing` - Whether keys are currently typed into `device_filter`
+/// * `device_details` - Details of the highlighted device (refreshed by `refresh_device_details`)
+/// * `device_provider` - Where the device list and device details come from (a mock in tests)
+/// * `selected_device` - Full `Disk` struct of selected device (or None); the first of `selected_devices`
+/// * `selected_devices` - All target devices carried into Confirm (one unless several were marked)
+/// * `marked` - Stable paths of devices marked with Space in the device list
+/// * `config` - User configuration (hidden and trusted devices, theme)
+/// * `config_overrides` - Command-line settings applied again whenever `config` is reloaded
+/// * `config_modified` - Modification time of the config file when it was last read
+/// * `config_checked` - When `poll_config_changes` last looked at the config file
+/// * `theme` - Styles used for drawing; starts from the config and can be cycled at runtime
+/// * `target_contents` - Existing partitions found on the selected devices (for the Confirm screen)
+/// * `target_holders` - Processes that have the selected devices open (for the Confirm screen)
+/// * `target_warnings` - Every caution that applies to the selected image and devices (for the Confirm screen)
+/// * `device_watcher` - Background hotplug watcher (udev or polling), if started
+/// * `wake` - Wakes the event loop when a task or the device watcher has news; does nothing until the loop sets it
+/// * `rescan_due` - When a debounced automatic rescan should run
 /// * `status` - Status message displayed in UI (empty if no message)
+/// * `activity` - History of progress lines and status messages for the log pane
+/// * `log_open` - Whether the log pane is shown instead of the current step
+/// * `log_scroll` - How many lines the log pane is scrolled up from the newest entry
+/// * `history` - Past flashes, newest first, while the history pane is shown instead of the current step
+/// * `history_scroll` - How many entries of the history pane are scrolled past
+/// * `modal` - Dialog drawn over the current step; takes all keys while open
+/// * `toast` - Transient notification for non-critical events (cleared by `expire_toast`)
 /// * `execute` - `true` to actually flash, `false` for dry-run
 /// * `show_all_disks` - `true` to show all disks, `false` for removable only
-/// * `flash_progress` - Current flashing progress message (updated from background thread)
-/// * `flash_result` - Result of flash operation when complete (success/failure)
-/// * `flash_total` - Total bytes to flash (estimated from file size)
-/// * `flash_done` - Bytes flashed so far (updated in real-time)
-/// * `progress_rx` - Channel receiver for progress updates from flash thread
-/// * `result_rx` - Channel receiver for final result from flash thread
-/// * `partition_info` - Cached partition info for the selected device (if checked)
-/// * `user_confirmed_wipe` - Whether the user has confirmed overwriting existing partitions
+/// * `report_path` - File every finished flash's report is written to (`--report`)
+/// * `compact` - Whether the logo banner is hidden to leave more room for lists
+/// * `zoomed` - Whether the Flashing step is shown full-screen
+/// * `exact_bytes` - Whether flash progress is shown in exact bytes instead of GiB/MiB
+/// * `hint_page` - Page of the key hint footer shown, and the kind of step it was paged on
+/// * `require_verification` - Whether a flash fails when verification cannot run (`--verify`)
+/// * `block_size` - Bytes per write block for the next flash; `flash::DEFAULT_BLOCK_SIZE` when `None`
+/// * `interrupted` - Journal of an interrupted write of the chosen image to a selected device
+/// * `resume` - Whether the flash continues an interrupted write instead of starting over
+/// * `stage_offer` - Filesystem type of the network mount the chosen image is on, when
+///   it is worth staging in RAM first
+/// * `stage` - Whether an image on a network mount is staged before flashing (see `stage`)
+/// * `checksum` - Published checksum the image must match before flashing (`--checksum`)
+/// * `volume_label` - Label applied after flashing instead of one derived from the image name (`--label`)
+/// * `auto_quit` - Exit as soon as a flash reaches the Result step instead of waiting there (`--auto-quit`)
+/// * `catalog` - Distributions offered for download with 'c' in the Image step
+/// * `catalog_update` - Background check of the config's `catalog_url` for a newer
+///   signed catalog, while it runs
 pub struct App {
     pub step: Step,
     pub image_input: String,
+    pub image_cursor: usize,
     pub cwd: PathBuf,
     pub entries: Vec<FileEntry>,
     pub entry_selected: usize,
+    pub preview: Option<FilePreview>,
     pub iso_kind: IsoKind,
     pub iso_info: String,
     pub devices: Vec<Disk>,
     pub selected: usize,
+    pub device_changes: Option<DeviceChanges>,
+    pub device_filter: String,
+    pub device_filter_editing: bool,
+    pub device_details: Option<device::DeviceDetails>,
+    pub device_provider: Box<dyn DeviceProvider>,
     pub selected_device: Option<Disk>,
+    pub selected_devices: Vec<Disk>,
+    pub marked: Vec<String>,
+    pub config: Config,
+    pub config_overrides: config::Overrides,
+    pub config_modified: Option<std::time::SystemTime>,
+    pub config_checked: Instant,
+    pub theme: theme::Theme,
+    pub target_contents: Vec<device::PartitionContent>,
+    pub target_holders: Vec<device::DeviceHolder>,
+    pub target_warnings: Vec<String>,
+    pub device_watcher: Option<device::DeviceWatcher>,
+    pub wake: task::Wake,
+    pub rescan_due: Option<Instant>,
     pub status: String,
+    pub activity: activity::ActivityLog,
+    pub log_open: bool,
+    pub log_scroll: usize,
+    pub history: Option<Vec<history::HistoryEntry>>,
+    pub history_scroll: usize,
+    pub modal: Option<modal::Modal>,
+    pub toast: Option<Toast>,
     pub execute: bool,
     pub show_all_disks: bool,
-    pub flash_progress: String,
-    pub flash_result: Option<FlashResult>,
-    pub flash_total: Option<u64>,
-    pub flash_done: u64,
-    pub progress_rx: Option<Receiver<String>>,
-    pub result_rx: Option<Receiver<Result<(), String>>>,
-    pub partition_info: Option<flash::DevicePartitionInfo>,
-    pub user_confirmed_wipe: bool,
+    pub report_path: Option<PathBuf>,
+    pub compact: bool,
+    pub zoomed: bool,
+    pub exact_bytes: bool,
+    pub hint_page: (std::mem::Discriminant<Step>, usize),
+    pub require_verification: bool,
+    pub block_size: Option<u64>,
+    pub interrupted: Option<journal::Journal>,
+    pub resume: bool,
+    pub stage_offer: Option<String>,
+    pub stage: bool,
+    pub checksum: Option<checksum::Checksum>,
+    pub volume_label: Option<String>,
+    pub auto_quit: bool,
+    pub catalog: catalog::Catalog,
+    pub catalog_update: Option<Task<anyhow::Result<Option<catalog::Update>>>>,
     /// When true, file picker only shows .iso/.img/.raw files (directories always shown).
     pub filter_iso_only: bool,
     /// When true, show hidden files (starting with '.') in the file picker.
     pub show_hidden: bool,
+    /// Order of entries in the file picker.
+    pub sort_mode: SortMode,
+    /// Matches being cycled through by repeated Tab presses in the path input.
+    pub completion: Option<completion::Completion>,
 }
 
 impl App {
@@ -145,32 +450,44 @@ impl App {
     /// # Arguments
     ///
     /// * `image` - Optional path to ISO file (pre-fills image input)
-    /// * `device` - Optional device name like "/dev/sdb" (pre-selects device)
+    /// * `targets` - Devices like "/dev/sdb" or by-id links to pre-select; when
+    ///   several are given they are all marked for multi-device flashing
     /// * `execute` - Whether to actually flash (true) or dry-run (false)
     /// * `devices` - List of available USB devices
+    /// * `config` - User configuration; hidden devices are removed from `devices`
+    /// * `device_provider` - Source of later rescans and device details, usually
+    ///   `SystemDevices`
     ///
     /// # Returns
     ///
     /// A new App with initial step either at Image (if no image given) or Device (if image provided).
     pub fn new(
         image: Option<PathBuf>,
-        device: Option<String>,
+        targets: &[String],
         execute: bool,
         devices: Vec<Disk>,
+        config: Config,
+        device_provider: Box<dyn DeviceProvider>,
     ) -> Self {
-        let mut selected_device = None;
-        let mut selected = 0;
-
-        if let Some(ref device) = device {
-            if let Some((idx, disk)) = devices
-                .iter()
-                .enumerate()
-                .find(|(_, d)| d.device_path() == *device)
-            {
-                selected = idx;
-                selected_device = Some(disk.clone());
-            }
-        }
+        let devices = config.filter_devices(devices);
+        let found: Vec<(usize, &Disk)> = devices
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| targets.iter().any(|target| d.matches_path(target)))
+            .collect();
+        let selected = found.first().map_or(0, |(idx, _)| *idx);
+        let selected_devices: Vec<Disk> = found.iter().map(|(_, d)| (*d).clone()).collect();
+        let selected_device = selected_devices.first().cloned();
+        let marked = if selected_devices.len() > 1 {
+            selected_devices.iter().map(|d| d.stable_path()).collect()
+        } else {
+            Vec::new()
+        };
+        let missing: Vec<&str> = targets
+            .iter()
+            .filter(|target| !devices.iter().any(|d| d.matches_path(target)))
+            .map(String::as_str)
+            .collect();
 
         let image_input = image
             .as_ref()
@@ -181,18 +498,30 @@ impl App {
         let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
         let filter_iso_only = true;
         let show_hidden = false;
-        let entries = load_entries(&cwd, filter_iso_only, show_hidden);
+        let sort_mode = SortMode::Name;
+        let compact = config.compact;
+        let entries = load_entries(&cwd, filter_iso_only, show_hidden, sort_mode);
 
         let step = if image_valid {
             Step::Device
         } else {
             Step::Image
         };
+        let auto_advance = matches!(step, Step::Device);
 
         let mut status = String::new();
         if image.is_some() && !image_valid {
             status.push_str("Provided --image path must point to an existing file.");
         }
+        if !missing.is_empty() {
+            if !status.is_empty() {
+                status.push_str("  ");
+            }
+            status.push_str(&format!(
+                "--device not found (or hidden by the config): {}.",
+                missing.join(", ")
+            ));
+        }
         if devices.is_empty() {
             if !status.is_empty() {
                 status.push_str("  ");
@@ -200,205 +529,1912 @@ impl App {
             status.push_str("No devices detected. Press r to rescan or a to show all.");
         }
 
-        Self {
+        let mut app = Self {
             step,
+            image_cursor: image_input.len(),
             image_input,
             cwd,
             entries,
             entry_selected: 0,
+            preview: None,
             iso_kind: IsoKind::Unknown,
             iso_info: String::new(),
             devices,
             selected,
+            device_changes: None,
+            device_filter: String::new(),
+            device_filter_editing: false,
+            selected_devices,
+            device_details: None,
+            device_provider,
             selected_device,
+            marked,
+            theme: theme::Theme::from_config(&config),
+            config_modified: config.modified(),
+            config,
+            config_overrides: config::Overrides::default(),
+            config_checked: Instant::now(),
+            target_contents: Vec::new(),
+            target_holders: Vec::new(),
+            target_warnings: Vec::new(),
+            device_watcher: None,
+            wake: task::no_wake(),
+            rescan_due: None,
             status,
+            activity: activity::ActivityLog::default(),
+            log_open: false,
+            log_scroll: 0,
+            history: None,
+            history_scroll: 0,
+            modal: None,
+            toast: None,
             execute,
             show_all_disks: false,
-            flash_progress: String::new(),
-            flash_result: None,
-            flash_total: None,
-            flash_done: 0,
-            progress_rx: None,
-            result_rx: None,
-            partition_info: None,
-            user_confirmed_wipe: false,
+            report_path: None,
+            compact,
+            zoomed: false,
+            exact_bytes: false,
+            hint_page: (std::mem::discriminant(&Step::Image), 0),
+            require_verification: false,
+            block_size: None,
+            interrupted: None,
+            resume: true,
+            stage_offer: None,
+            stage: true,
+            checksum: None,
+            volume_label: None,
+            auto_quit: false,
+            catalog: catalog::Catalog::bundled(),
+            catalog_update: None,
             filter_iso_only,
             show_hidden,
+            sort_mode,
+            completion: None,
+        };
+        if auto_advance {
+            app.auto_advance();
         }
+        app
     }
 
-    /// Reload the file picker entries using current filter settings.
-    pub fn reload_entries(&mut self) {
-        self.entries = load_entries(&self.cwd, self.filter_iso_only, self.show_hidden);
-        if self.entry_selected >= self.entries.len() {
+    /// Show the history pane with the recorded flashes, newest first.
+    pub fn open_history(&mut self) {
+        match history::load_default() {
+            Ok(mut entries) => {
+                entries.reverse();
+                self.history = Some(entries);
+                self.history_scroll = 0;
+            }
+            Err(err) => self.status = format!("Cannot show the history: {err:#}"),
+        }
+    }
+
+    /// Start from the preferences remembered by the last run.
+    ///
+    /// The last directory is only used if it still exists.
+    pub fn apply_preferences(&mut self, prefs: &prefs::Preferences) {
+        if let Some(dir) = prefs.last_dir.as_ref().filter(|dir| dir.is_dir()) {
+            self.cwd = dir.clone();
             self.entry_selected = 0;
+            self.reload_entries();
+        }
+        if prefs.show_all_disks && !self.show_all_disks {
+            self.show_all_disks = true;
+            if self.rescan_devices() && self.status.is_empty() {
+                self.status = "Showing all disks (be careful).".to_string();
+            }
         }
+        self.block_size = prefs.block_size;
+        self.require_verification = prefs.require_verification;
     }
 
-    /// Reset flash-related state so the user can start over from the Image step.
-    pub fn reset_to_start(&mut self) {
-        self.step = Step::Image;
-        self.flash_progress.clear();
-        self.flash_result = None;
-        self.flash_total = None;
-        self.flash_done = 0;
-        self.progress_rx = None;
-        self.result_rx = None;
-        self.partition_info = None;
-        self.user_confirmed_wipe = false;
-        self.status.clear();
-        self.iso_kind = IsoKind::Unknown;
-        self.iso_info.clear();
-        self.reload_entries();
+    /// The preferences to remember for the next run.
+    pub fn preferences(&self) -> prefs::Preferences {
+        prefs::Preferences {
+            last_dir: Some(self.cwd.clone()),
+            show_all_disks: self.show_all_disks,
+            block_size: self.block_size,
+            require_verification: self.require_verification,
+        }
     }
 
-    /// Get the image file path from user input string.
-    ///
-    /// Trims whitespace and returns the path, or `None` if input is empty.
-    ///
-    /// # Returns
-    ///
-    /// `Some(PathBuf)` if input is non-empty, `None` otherwise.
-    pub fn image_path(&self) -> Option<PathBuf> {
-        let trimmed = self.image_input.trim();
-        if trimmed.is_empty() {
-            None
+    /// Switch to the next of `BLOCK_SIZES` for the next flash.
+    pub fn cycle_block_size(&mut self) {
+        let current = self.block_size.unwrap_or(flash::DEFAULT_BLOCK_SIZE);
+        let next = BLOCK_SIZES
+            .iter()
+            .position(|size| *size == current)
+            .map_or(flash::DEFAULT_BLOCK_SIZE, |i| {
+                BLOCK_SIZES[(i + 1) % BLOCK_SIZES.len()]
+            });
+        self.block_size = Some(next);
+        self.toast(format!("Block size: {}", format_size(next)));
+    }
+
+    /// Toggle whether the next flash fails when verification cannot run.
+    pub fn toggle_verification(&mut self) {
+        self.require_verification = !self.require_verification;
+        self.toast(if self.require_verification {
+            "Verification required"
         } else {
-            Some(PathBuf::from(trimmed))
+            "Verification best effort"
+        });
+    }
+
+    /// Toggle between resuming the `interrupted` write and starting over.
+    pub fn toggle_resume(&mut self) {
+        if self.interrupted.is_none() {
+            return;
         }
+        self.resume = !self.resume;
+        self.toast(if self.resume {
+            "Resuming the interrupted write"
+        } else {
+            "Starting the write over"
+        });
     }
 
-    /// Validate that the user-entered image path points to an existing file.
-    ///
-    /// If valid, clears the status message. If invalid, sets an error message
-    /// and resets ISO type to Unknown.
-    ///
-    /// # Returns
-    ///
-    /// `true` if image path is valid (file exists), `false` otherwise.
-    pub fn validate_image(&mut self) -> bool {
-        match self.image_path() {
-            Some(path) if path.is_file() => {
-                self.status.clear();
-                true
+    /// Toggle whether an image on a network mount is copied into RAM first.
+    pub fn toggle_stage(&mut self) {
+        if self.stage_offer.is_none() {
+            return;
+        }
+        self.stage = !self.stage;
+        self.toast(if self.stage {
+            "Staging the image in RAM first"
+        } else {
+            "Reading the image from the network mount"
+        });
+    }
+
+    /// Mention writes a crash or power loss interrupted, found in the
+    /// journal, so the user knows to pick the same image and device again.
+    pub fn note_interrupted_writes(&mut self) {
+        let Some(dir) = config::journal_dir() else {
+            return;
+        };
+        for (_, journal) in journal::Journal::list(&dir) {
+            if journal
+                .resume_offset(&journal.image, &journal.device, 1)
+                .is_none()
+            {
+                continue;
             }
-            _ => {
-                self.status = "Image path must point to a file.".to_string();
-                self.iso_kind = IsoKind::Unknown;
-                self.iso_info.clear();
-                false
+            let message = format!(
+                "The flash of {} to {} stopped at {:.0}%; choose them again to resume it.",
+                journal.image.display(),
+                journal.device,
+                journal.fraction() * 100.0
+            );
+            self.activity.push(&message);
+            if self.status.is_empty() {
+                self.status = message;
             }
         }
     }
 
-    /// Detect the ISO type (Hybrid/NonHybrid) of the selected image.
-    ///
-    /// Reads the MBR header of the ISO file to check for a partition table.
-    /// Updates `iso_kind` and `iso_info` with the result or error message.
-    ///
-    /// # Note
+    /// The journal of an interrupted write of the chosen image to one of the
+    /// selected devices, if there is one to resume.
+    fn find_interrupted(&self) -> Option<journal::Journal> {
+        let image = self.image_path()?;
+        let dir = config::journal_dir()?;
+        self.selected_devices.iter().find_map(|disk| {
+            let device = disk.stable_path();
+            let journal =
+                journal::Journal::load(&journal::Journal::path_for(&dir, &device)).ok()??;
+            journal.resume_offset(&image, &device, 1)?;
+            Some(journal)
+        })
+    }
+
+    /// Reload the file picker entries using current filter settings.
+    pub fn reload_entries(&mut self) {
+        self.entries = load_entries(
+            &self.cwd,
+            self.filter_iso_only,
+            self.show_hidden,
+            self.sort_mode,
+        );
+        if self.entry_selected >= self.entries.len() {
+            self.entry_selected = 0;
+        }
+    }
+
+    /// Rebuild the preview if the highlighted file picker entry changed.
     ///
-    /// This operation requires only read access to the file — no root privileges needed.
-    /// If `iso_kind` is `NonHybrid`, the flash operation will be blocked in the `Confirm` step.
-    pub fn refresh_iso_kind(&mut self) {
-        let Some(path) = self.image_path() else {
-            self.iso_kind = IsoKind::Unknown;
-            self.iso_info.clear();
+    /// Image detection reads the start of the file, so it runs once per newly
+    /// highlighted entry rather than on every frame.
+    pub fn refresh_preview(&mut self) {
+        let Some(entry) = self.entries.get(self.entry_selected) else {
+            self.preview = None;
             return;
         };
+        if self.preview.as_ref().map(|p| &p.path) == Some(&entry.path) {
+            return;
+        }
+        let lower = entry.name.to_lowercase();
+        let plausible_image =
+            !entry.is_dir && ISO_EXTENSIONS.iter().any(|ext| lower.ends_with(ext));
+        let detection = plausible_image.then(|| match iso::detect(&entry.path) {
+            Ok(IsoKind::Hybrid) => "Hybrid image; can be written raw".to_string(),
+            Ok(IsoKind::NonHybrid) => "Non-hybrid ISO; will not boot if written raw".to_string(),
+            Ok(IsoKind::Unknown) => "Not a recognized image".to_string(),
+            Err(err) => format!("Cannot read: {err:#}"),
+        });
+        self.preview = Some(FilePreview {
+            path: entry.path.clone(),
+            detection,
+        });
+    }
 
-        match iso::detect(&path) {
-            Ok(kind) => {
-                self.iso_kind = kind;
-                self.iso_info = match kind {
-                    IsoKind::Hybrid => "Hybrid ISO detected (raw write).".to_string(),
-                    IsoKind::NonHybrid => "Non-hybrid ISO (unsupported).".to_string(),
-                    IsoKind::Unknown => "ISO type unknown.".to_string(),
+    /// Bookmark the current directory, or remove it if already bookmarked, and
+    /// save the bookmark list to the config file.
+    ///
+    /// Removing a bookmark asks for confirmation first.
+    pub fn toggle_bookmark(&mut self) {
+        let dirs = self.config.bookmark_dirs();
+        if let Some(index) = dirs.iter().position(|dir| *dir == self.cwd) {
+            self.modal = Some(modal::Modal::confirm(
+                "Remove bookmark",
+                format!("Remove bookmark {} ({})?", index + 1, self.cwd.display()),
+                modal::ModalAction::RemoveBookmark(index),
+            ));
+            return;
+        }
+        self.config.bookmarks.push(self.cwd.display().to_string());
+        match self.config.save_bookmarks() {
+            Err(err) => self.status = format!("Bookmark not saved: {err:#}"),
+            Ok(()) => self.toast(format!(
+                "Bookmarked {} as {}",
+                self.cwd.display(),
+                self.config.bookmarks.len()
+            )),
+        }
+    }
+
+    /// Carry out the action of a modal that was just answered.
+    pub fn resolve_modal(&mut self, action: modal::ModalAction, response: modal::ModalResponse) {
+        if response == modal::ModalResponse::Cancelled {
+            return;
+        }
+        match action {
+            modal::ModalAction::RemoveBookmark(index) => {
+                if index >= self.config.bookmarks.len() {
+                    return;
+                }
+                let removed = self.config.bookmarks.remove(index);
+                match self.config.save_bookmarks() {
+                    Err(err) => self.status = format!("Bookmark not saved: {err:#}"),
+                    Ok(()) => self.toast(format!("Removed bookmark {removed}")),
+                }
+            }
+            modal::ModalAction::DeleteCached(path) => match cache::remove(&path) {
+                Ok(()) => {
+                    self.toast(format!("Deleted {}", path.display()));
+                    self.reload_cache();
+                }
+                Err(err) => self.status = format!("Cannot delete the download: {err:#}"),
+            },
+            modal::ModalAction::GoToPath => {
+                if let modal::ModalResponse::Text(path) = response {
+                    self.go_to_path(&path);
+                }
+            }
+            modal::ModalAction::SaveReport => {
+                let (
+                    Step::Result(step::Outcome {
+                        report: Some(report),
+                        ..
+                    }),
+                    modal::ModalResponse::Text(path),
+                ) = (&self.step, response)
+                else {
+                    return;
                 };
+                let path = PathBuf::from(path.trim());
+                match report.save(&path) {
+                    Err(err) => self.status = format!("Report not saved: {err:#}"),
+                    Ok(()) => self.toast(format!("Report saved to {}", path.display())),
+                }
             }
-            Err(err) => {
-                self.iso_kind = IsoKind::Unknown;
-                self.iso_info = format!("ISO check failed: {err}");
+            modal::ModalAction::LoadSeed => {
+                if let modal::ModalResponse::Text(path) = response {
+                    self.load_seed(Path::new(path.trim()));
+                }
+            }
+            modal::ModalAction::Destructive(operation) => {
+                let Some(disk) = self.step.side_disk() else {
+                    return;
+                };
+                if let modal::ModalResponse::Text(typed) = response {
+                    let phrase = erase_phrase(disk);
+                    if typed.trim() != phrase {
+                        self.status = format!("Type '{phrase}' exactly to confirm.");
+                        return;
+                    }
+                }
+                self.start_destructive(operation);
+            }
+            modal::ModalAction::CatalogDistro => {
+                if let modal::ModalResponse::Chose(distro) = response {
+                    self.choose_catalog_release(distro);
+                }
+            }
+            modal::ModalAction::CatalogRelease(distro) => {
+                if let modal::ModalResponse::Chose(release) = response {
+                    self.choose_catalog_image(distro, release);
+                }
+            }
+            modal::ModalAction::CatalogImage(distro, release) => {
+                if let modal::ModalResponse::Chose(image) = response {
+                    self.choose_catalog_source(distro, release, image);
+                }
+            }
+            modal::ModalAction::CatalogSource(distro, release, image) => {
+                if let modal::ModalResponse::Chose(source) = response {
+                    self.start_catalog_download(distro, release, image, source);
+                }
             }
         }
     }
 
-    /// Poll for updates from the background flash thread.
-    ///
-    /// Non-blocking: receives any pending progress messages and checks if flash is complete.
-    /// Updates:
-    /// - `flash_progress` with latest message
-    /// - `flash_done` with bytes flashed so far
-    /// - `step` to `Result` when flash thread completes
-    /// - `flash_result` with final success/failure message
+    /// Look for a newer signed catalog at the config's `catalog_url` in the
+    /// background, if one is configured (see `poll_catalog_update`).
+    pub fn start_catalog_update(&mut self) {
+        let Some(remote) = self.config.catalog_remote() else {
+            return;
+        };
+        let current = self.catalog.clone();
+        let proxy = self.config.download_proxy();
+        self.catalog_update = Some(Task::spawn_waking(
+            "catalog",
+            self.wake.clone(),
+            move |_| catalog::Catalog::fetch_update(&remote, &current, &proxy),
+        ));
+    }
+
+    /// Switch to the catalog fetched by `start_catalog_update` once it is in.
     ///
-    /// Called once per event loop iteration (every 250ms in main loop).
-    pub fn poll_flash(&mut self) {
-        if let Some(rx) = &self.progress_rx {
-            while let Ok(line) = rx.try_recv() {
-                if let Some(bytes) = flash::parse_dd_bytes(&line) {
-                    self.flash_done = bytes;
+    /// A catalog menu open at that moment is closed, since its choices
+    /// refer to the old catalog.
+    pub fn poll_catalog_update(&mut self) {
+        let Some(task) = &mut self.catalog_update else {
+            return;
+        };
+        let Some(result) = task.poll().1 else {
+            return;
+        };
+        self.catalog_update = None;
+        match result.map_err(anyhow::Error::new).and_then(|result| result) {
+            Ok(Some(catalog::Update { catalog, signer })) => {
+                self.catalog = catalog;
+                let open = self.modal.as_ref().map(|modal| &modal.action);
+                if matches!(
+                    open,
+                    Some(
+                        modal::ModalAction::CatalogDistro
+                            | modal::ModalAction::CatalogRelease(_)
+                            | modal::ModalAction::CatalogImage(..)
+                            | modal::ModalAction::CatalogSource(..)
+                    )
+                ) {
+                    self.modal = None;
                 }
-                self.flash_progress = line;
+                self.toast(format!(
+                    "Catalog updated to {} (signed by {signer}).",
+                    self.catalog.updated
+                ));
             }
+            Ok(None) => self.activity.push("Catalog is up to date."),
+            Err(err) => self.activity.push(&format!("Catalog not updated: {err:#}")),
         }
+    }
 
-        if let Some(rx) = &self.result_rx {
-            if let Ok(result) = rx.try_recv() {
-                self.progress_rx = None;
-                self.result_rx = None;
-                self.flash_result = Some(match result {
-                    Ok(()) => FlashResult {
-                        ok: true,
-                        message: "Flash completed successfully.".to_string(),
-                    },
-                    Err(err) => FlashResult {
-                        ok: false,
-                        message: err,
-                    },
-                });
-                self.step = Step::Result;
-            }
+    /// Offer the catalog's distributions to download one of them.
+    pub fn open_catalog(&mut self) {
+        let options = self
+            .catalog
+            .distros
+            .iter()
+            .map(|distro| match distro.summary.as_str() {
+                "" => distro.name.clone(),
+                summary => format!("{} - {summary}", distro.name),
+            })
+            .collect();
+        self.modal = Some(modal::Modal::choice(
+            "Choose from catalog",
+            "Download a distribution and flash it:",
+            options,
+            0,
+            modal::ModalAction::CatalogDistro,
+        ));
+    }
+
+    /// Offer the releases of catalog distribution `distro`, skipping the
+    /// menu when there is only one.
+    fn choose_catalog_release(&mut self, distro: usize) {
+        let Some(entry) = self.catalog.distros.get(distro) else {
+            return;
+        };
+        if entry.releases.len() == 1 {
+            self.choose_catalog_image(distro, 0);
+            return;
         }
+        self.modal = Some(modal::Modal::choice(
+            "Choose from catalog",
+            format!("Release of {}:", entry.name),
+            entry.releases.iter().map(|r| r.version.clone()).collect(),
+            0,
+            modal::ModalAction::CatalogRelease(distro),
+        ));
     }
 
-    /// Start the flash operation in a background thread.
+    /// Offer the architectures of a catalog release, starting on the one
+    /// this machine runs, and skipping the menu when there is only one.
+    fn choose_catalog_image(&mut self, distro: usize, release: usize) {
+        let Some(entry) = self.catalog.distros.get(distro) else {
+            return;
+        };
+        let Some(chosen) = entry.releases.get(release) else {
+            return;
+        };
+        if chosen.images.len() == 1 {
+            self.choose_catalog_source(distro, release, 0);
+            return;
+        }
+        self.modal = Some(modal::Modal::choice(
+            "Choose from catalog",
+            format!("Architecture of {} {}:", entry.name, chosen.version),
+            chosen.images.iter().map(|i| i.arch.clone()).collect(),
+            chosen.host_image(),
+            modal::ModalAction::CatalogImage(distro, release),
+        ));
+    }
+
+    /// Offer the ways to download a catalog image (see
+    /// `CatalogImage::download_choices`), skipping the menu when there is
+    /// only one.
+    fn choose_catalog_source(&mut self, distro: usize, release: usize, image: usize) {
+        let Some(file) = self.catalog_image(distro, release, image) else {
+            return;
+        };
+        let choices = file.download_choices();
+        if choices.len() == 1 {
+            self.start_catalog_download(distro, release, image, 0);
+            return;
+        }
+        self.modal = Some(modal::Modal::choice(
+            "Choose from catalog",
+            "Download from:",
+            choices.into_iter().map(|(label, _)| label).collect(),
+            0,
+            modal::ModalAction::CatalogSource(distro, release, image),
+        ));
+    }
+
+    /// The catalog image at these indices, if there is one.
+    fn catalog_image(
+        &self,
+        distro: usize,
+        release: usize,
+        image: usize,
+    ) -> Option<&catalog::CatalogImage> {
+        self.catalog
+            .distros
+            .get(distro)?
+            .releases
+            .get(release)?
+            .images
+            .get(image)
+    }
+
+    /// Download a catalog image on the Download step, the way at index
+    /// `source` of its `download_choices`.
+    fn start_catalog_download(
+        &mut self,
+        distro: usize,
+        release: usize,
+        image: usize,
+        source: usize,
+    ) {
+        let Some(file) = self.catalog_image(distro, release, image) else {
+            return;
+        };
+        let Some((_, urls)) = file.download_choices().into_iter().nth(source) else {
+            return;
+        };
+        let sums = file.published_sums();
+        let entry = &self.catalog.distros[distro];
+        let name = format!(
+            "{} {} {}",
+            entry.name, entry.releases[release].version, file.arch
+        );
+        self.start_download(name, urls, None, sums);
+    }
+
+    /// Download the image at `url`, named after its file, checking it
+    /// against `checksum` when one is given.
+    pub fn download_url(&mut self, url: &str, checksum: Option<checksum::Checksum>) {
+        match download::file_name_for(url) {
+            Err(err) => self.status = format!("{err:#}"),
+            Ok(name) => self.start_download(name, vec![url.to_string()], checksum, None),
+        }
+    }
+
+    /// Start downloading `url` into the cache in a background thread and
+    /// move to the `Download` step.
     ///
-    /// Creates progress and result channels, spawns a background thread to perform the flash,
-    /// and transitions to the `Flashing` step.
+    /// Call `poll_download()` regularly to receive progress and, once the
+    /// image is downloaded and verified, move on to the Device step with it.
     ///
     /// # Arguments
     ///
-    /// * `image` - Path to the ISO image file
-    /// * `device` - Device name (e.g., "/dev/sdb")
-    ///
-    /// # Note
+    /// * `name` - What is downloaded, for display
+    /// * `urls` - HTTP(S) or FTP URLs of the image; when there are several
+    ///   mirrors, the fastest of them is used
+    /// * `checksum` - Checksum the download must match (`download --checksum`)
+    /// * `sums` - Published checksum list to fetch and match when there is no
+    ///   `checksum` (catalog images)
+    pub fn start_download(
+        &mut self,
+        name: String,
+        urls: Vec<String>,
+        checksum: Option<checksum::Checksum>,
+        sums: Option<download::PublishedSums>,
+    ) {
+        let Some(dir) = download::cache_dir() else {
+            self.status = "Cannot download: no cache location (HOME is not set).".to_string();
+            return;
+        };
+        let Some(first) = urls.first().cloned() else {
+            return;
+        };
+        let control = download::DownloadControl::default();
+        let proxy = self.config.download_proxy();
+        let limit = self.config.cache_limit();
+        let task = {
+            let control = control.clone();
+            Task::spawn_waking("download", self.wake.clone(), move |progress_tx| {
+                let url = &urls[download::fastest_mirror(&urls, &proxy, &progress_tx)];
+                let _ = progress_tx.send(format!("{}{url}", download::MIRROR_PREFIX));
+                let (checksum, fetched) = match (checksum, &sums) {
+                    (None, Some(sums)) => {
+                        let file_name = download::file_name_for(url)?;
+                        let (checksum, fetched) =
+                            sums.fetch(&file_name, &dir, &proxy, &progress_tx)?;
+                        (Some(checksum), fetched)
+                    }
+                    (checksum, _) => (checksum, Vec::new()),
+                };
+                let result = download::download(
+                    url,
+                    &dir,
+                    checksum.as_ref(),
+                    &proxy,
+                    progress_tx.clone(),
+                    &control,
+                );
+                for file in fetched {
+                    std::fs::remove_file(file).ok();
+                }
+                if let Ok(image) = &result {
+                    cache::enforce_limit(&dir, limit, image, &progress_tx);
+                }
+                result
+            })
+        };
+        self.status.clear();
+        self.step = Step::Download(Box::new(step::DownloadRun::new(name, first, task, control)));
+    }
+
+    /// Poll for updates from the background download thread.
+    ///
+    /// Once the download ends, a downloaded image becomes the chosen image
+    /// and the TUI moves on to the Device step. A cancelled download returns
+    /// to the Image step; a failed one shows the Error step, from where the
+    /// URL left in the path input can be retried.
+    pub fn poll_download(&mut self) {
+        let Step::Download(run) = &mut self.step else {
+            return;
+        };
+        let (lines, result) = run.task.poll();
+        for line in lines {
+            if download::parse_download_progress(&line).is_none() {
+                self.activity.push(&line);
+            }
+            run.apply(line);
+        }
+        let Some(result) = result else {
+            return;
+        };
+        let Step::Download(run) = std::mem::replace(&mut self.step, Step::Image) else {
+            unreachable!("checked above");
+        };
+        match result.map_err(anyhow::Error::new).and_then(|result| result) {
+            Ok(path) => {
+                self.toast(format!("Downloaded {}", path.display()));
+                self.set_image_input(path.display().to_string());
+                if self.validate_image() {
+                    self.refresh_iso_kind();
+                    self.enter_device_step();
+                }
+            }
+            Err(_) if run.control.is_cancelled() => {
+                self.status = format!("Download of {} cancelled.", run.name);
+            }
+            Err(err) => {
+                self.set_image_input(run.url);
+                self.fail(format!("Download of {} failed: {err:#}", run.name));
+            }
+        }
+    }
+
+    /// Show the images in the download cache on the `Cache` step.
+    pub fn open_cache(&mut self) {
+        let Some(dir) = download::cache_dir() else {
+            self.status = "No download cache (HOME is not set).".to_string();
+            return;
+        };
+        match cache::list(&dir) {
+            Ok(images) => self.step = Step::Cache(step::CacheView::new(images)),
+            Err(err) => self.status = format!("Cannot list the download cache: {err:#}"),
+        }
+    }
+
+    /// List the cached images again, after one was deleted or re-hashed.
+    pub fn reload_cache(&mut self) {
+        let Step::Cache(view) = &mut self.step else {
+            return;
+        };
+        let Some(dir) = download::cache_dir() else {
+            return;
+        };
+        match cache::list(&dir) {
+            Ok(images) => view.set_images(images),
+            Err(err) => self.status = format!("Cannot list the download cache: {err:#}"),
+        }
+    }
+
+    /// Flash the highlighted cached image: choose it and move on to the
+    /// Device step.
+    pub fn use_cached_image(&mut self) {
+        let Step::Cache(view) = &self.step else {
+            return;
+        };
+        let Some(image) = view.selected_image() else {
+            return;
+        };
+        let path = image.path.display().to_string();
+        self.step = Step::Image;
+        self.set_image_input(path);
+        if self.validate_image() {
+            self.refresh_iso_kind();
+            self.enter_device_step();
+        }
+    }
+
+    /// Ask before deleting the highlighted cached image.
+    pub fn confirm_delete_cached(&mut self) {
+        let Step::Cache(view) = &self.step else {
+            return;
+        };
+        let Some(image) = view.selected_image() else {
+            return;
+        };
+        self.modal = Some(modal::Modal::confirm(
+            "Delete download",
+            format!(
+                "Delete {} ({}) from the cache?",
+                image.name(),
+                format_size(image.size)
+            ),
+            modal::ModalAction::DeleteCached(image.path.clone()),
+        ));
+    }
+
+    /// Hash the highlighted cached image again in a background thread and
+    /// compare it with the digest recorded when it was downloaded.
+    ///
+    /// Call `poll_cache()` regularly to receive the outcome.
+    pub fn verify_cached_image(&mut self) {
+        let Step::Cache(view) = &mut self.step else {
+            return;
+        };
+        let Some(image) = view.selected_image().cloned() else {
+            return;
+        };
+        let path = image.path.clone();
+        let task = Task::spawn_waking("verify cached image", self.wake.clone(), move |_| {
+            cache::verify(&image)
+        });
+        view.verifying = Some((path, task));
+    }
+
+    /// Poll for the outcome of the re-verification started on the `Cache`
+    /// step.
+    pub fn poll_cache(&mut self) {
+        let Step::Cache(view) = &mut self.step else {
+            return;
+        };
+        let Some((path, task)) = &mut view.verifying else {
+            return;
+        };
+        let (_, result) = task.poll();
+        let Some(result) = result else {
+            return;
+        };
+        let path = path.clone();
+        view.verifying = None;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let outcome = match result.map_err(anyhow::Error::new).and_then(|result| result) {
+            Ok(true) => {
+                self.toast(format!("{name} matches its recorded SHA-256"));
+                Ok(())
+            }
+            Ok(false) => {
+                self.toast(format!("{name} had no recorded SHA-256; recorded it now"));
+                Ok(())
+            }
+            Err(err) => {
+                let message = format!("{err:#}");
+                self.status = format!("{name}: {message}");
+                Err(message)
+            }
+        };
+        if let Step::Cache(view) = &mut self.step {
+            view.checked.insert(path, outcome);
+        }
+        self.reload_cache();
+    }
+
+    /// Ask where to save the flash report, suggesting a timestamped file in `cwd`.
+    pub fn prompt_save_report(&mut self) {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let initial = self.cwd.join(format!("flashr-report-{stamp}.txt"));
+        self.modal = Some(modal::Modal::prompt(
+            "Save report",
+            "Save the flash report to:",
+            &initial.display().to_string(),
+            modal::ModalAction::SaveReport,
+        ));
+    }
+
+    /// Ask for a directory to jump the file picker to, starting from `cwd`.
+    pub fn prompt_go_to_path(&mut self) {
+        let initial = self.cwd.join("").display().to_string();
+        self.modal = Some(modal::Modal::prompt(
+            "Go to directory",
+            "Type or paste an absolute directory path:",
+            &initial,
+            modal::ModalAction::GoToPath,
+        ));
+    }
+
+    /// Jump the file picker to the absolute directory `path`.
+    pub fn go_to_path(&mut self, path: &str) {
+        let dir = PathBuf::from(path.trim());
+        if !dir.is_absolute() {
+            self.status = format!("Not an absolute path: {}", path.trim());
+            return;
+        }
+        if !dir.is_dir() {
+            self.status = format!("Not a directory: {}", dir.display());
+            return;
+        }
+        self.cwd = dir;
+        self.entry_selected = 0;
+        self.reload_entries();
+        self.set_image_input(String::new());
+        self.status.clear();
+    }
+
+    /// Jump the file picker to bookmark `number` (1-based).
+    pub fn open_bookmark(&mut self, number: usize) {
+        let Some(dir) = number
+            .checked_sub(1)
+            .and_then(|index| self.config.bookmark_dirs().into_iter().nth(index))
+        else {
+            self.status = format!("No bookmark {number} (Ctrl+B bookmarks the current dir)");
+            return;
+        };
+        if !dir.is_dir() {
+            self.status = format!("Bookmark {number} is not a directory: {}", dir.display());
+            return;
+        }
+        self.cwd = dir;
+        self.entry_selected = 0;
+        self.reload_entries();
+        self.status.clear();
+    }
+
+    /// Replace the typed image path and put the cursor at its end.
+    pub fn set_image_input(&mut self, text: String) {
+        self.image_cursor = text.len();
+        self.image_input = text;
+    }
+
+    /// Tab-complete the path typed in `image_input`.
+    ///
+    /// Completes a unique match outright, extends ambiguous input to the
+    /// common prefix, and otherwise cycles through the matches on each call.
+    pub fn complete_image_input(&mut self) {
+        if let Some(completion) = self.completion.as_mut() {
+            let completed = completion.advance().to_string();
+            self.status = format!(
+                "Match {}/{}",
+                completion.index + 1,
+                completion.candidates.len()
+            );
+            self.set_image_input(completed);
+            return;
+        }
+
+        let candidates = completion::candidates(&self.image_input, &self.cwd, self.show_hidden);
+        match candidates.as_slice() {
+            [] => self.status = "No matches".to_string(),
+            [only] => {
+                self.set_image_input(only.clone());
+                self.status.clear();
+            }
+            _ => {
+                let prefix = completion::common_prefix(&candidates);
+                if prefix.len() > self.image_input.len() {
+                    self.set_image_input(prefix);
+                    self.status = format!("{} matches (Tab again to cycle)", candidates.len());
+                } else {
+                    let completion = completion::Completion {
+                        candidates,
+                        index: 0,
+                    };
+                    self.set_image_input(completion.current().to_string());
+                    self.status = format!("Match 1/{}", completion.candidates.len());
+                    self.completion = Some(completion);
+                }
+            }
+        }
+    }
+
+    /// Show a transient notification for a non-critical event.
+    ///
+    /// Unlike `status`, toasts disappear on their own after `TOAST_DURATION`;
+    /// they are kept in the activity log.
+    pub fn toast(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        self.activity.push(&message);
+        self.toast = Some(Toast {
+            message,
+            expires: Instant::now() + TOAST_DURATION,
+        });
+    }
+
+    /// When the event loop has to run again even if nothing wakes it: the
+    /// next spinner frame while an operation runs or a rescan is pending, the
+    /// toast or the device list highlight expiring, the debounced rescan, or
+    /// the next config file check.
+    pub fn next_wakeup(&self) -> Instant {
+        let mut due = self.config_checked + CONFIG_POLL_INTERVAL;
+        if self.step.busy() || self.rescan_due.is_some() {
+            due = due.min(Instant::now() + ui::SPINNER_INTERVAL);
+        }
+        [
+            self.toast.as_ref().map(|toast| toast.expires),
+            self.device_changes.as_ref().map(|changes| changes.expires),
+            self.rescan_due,
+        ]
+        .into_iter()
+        .flatten()
+        .fold(due, Instant::min)
+    }
+
+    /// Drop the toast once it has been shown for `TOAST_DURATION`.
+    pub fn expire_toast(&mut self) {
+        if self
+            .toast
+            .as_ref()
+            .is_some_and(|toast| Instant::now() >= toast.expires)
+        {
+            self.toast = None;
+        }
+    }
+
+    /// Show an error, remembering the current step so the user can go back or retry.
+    ///
+    /// An error on the Error step keeps the step the first error happened in.
+    pub fn fail(&mut self, message: impl Into<String>) {
+        self.status = message.into();
+        if !matches!(self.step, Step::Error(_)) {
+            let origin = std::mem::replace(&mut self.step, Step::Image);
+            self.step = Step::Error(Box::new(origin));
+        }
+    }
+
+    /// Go back from the Error step to the step the error happened in.
+    pub fn leave_error(&mut self) {
+        self.step = match std::mem::replace(&mut self.step, Step::Image) {
+            Step::Error(origin) => *origin,
+            step => step,
+        };
+    }
+
+    /// Reset flash-related state so the user can start over from the Image step.
+    ///
+    /// Leaving the current step drops its data, including the last result.
+    pub fn reset_to_start(&mut self) {
+        self.step = Step::Image;
+        self.marked.clear();
+        self.status.clear();
+        self.iso_kind = IsoKind::Unknown;
+        self.iso_info.clear();
+        self.reload_entries();
+    }
+
+    /// Mount the flashed devices' partitions through UDisks2.
+    ///
+    /// Sets `status` to the resulting mount points or the first error.
+    pub fn mount_targets(&mut self) {
+        if !udisks::available() {
+            self.status = "Mounting requires udisksctl and an unprivileged session.".to_string();
+            return;
+        }
+        let mut mounted = Vec::new();
+        for disk in &self.selected_devices {
+            let partitions = device::DevicePath::resolve(&disk.stable_path())
+                .map_err(anyhow::Error::from)
+                .and_then(|dev| flash::list_partitions(dev.as_str()));
+            let partitions = match partitions {
+                Ok(partitions) => partitions,
+                Err(err) => {
+                    self.status = format!("Mount failed: {err:#}");
+                    return;
+                }
+            };
+            // Hybrid ISOs often carry partitions without a mountable filesystem;
+            // those are skipped rather than reported.
+            mounted.extend(partitions.iter().filter_map(|p| udisks::mount(p).ok()));
+        }
+        self.status = if mounted.is_empty() {
+            "No mountable partitions found.".to_string()
+        } else {
+            format!("Mounted at {}", mounted.join(", "))
+        };
+    }
+
+    /// Power off the flashed devices so they can be unplugged.
+    ///
+    /// Uses UDisks2 when unprivileged and `eject` when running as root.
+    pub fn eject_targets(&mut self) {
+        for disk in &self.selected_devices {
+            let resolved = device::DevicePath::resolve(&disk.stable_path());
+            let result = resolved.map_err(anyhow::Error::from).and_then(|dev| {
+                if udisks::available() {
+                    udisks::power_off(dev.as_str())
+                } else {
+                    let status = std::process::Command::new("eject")
+                        .arg(dev.as_str())
+                        .status()
+                        .map_err(|err| anyhow::anyhow!("run eject: {err}"))?;
+                    if status.success() {
+                        Ok(())
+                    } else {
+                        Err(anyhow::anyhow!("eject failed"))
+                    }
+                }
+            });
+            if let Err(err) = result {
+                self.status = format!("Eject failed for {}: {err:#}", disk.device_path());
+                return;
+            }
+        }
+        self.status = "Device ejected; it is safe to unplug.".to_string();
+    }
+
+    /// Re-enumerate devices, honoring `show_all_disks` and the config's hidden devices.
+    ///
+    /// The new list is compared with the last one: the highlighted device stays
+    /// selected if it is still present (otherwise the cursor stays on the same
+    /// row), and the devices that came or went are kept in `device_changes`.
+    /// On failure the device list is cleared and the error is returned.
+    pub fn refresh_devices(&mut self) -> anyhow::Result<()> {
+        match self.device_provider.list(self.show_all_disks) {
+            Ok(devices) => {
+                let devices = self.config.filter_devices(devices);
+                let previous = self.devices.get(self.selected).map(Disk::stable_path);
+                if let Some(changes) = DeviceChanges::between(&self.devices, &devices) {
+                    self.device_changes = Some(changes);
+                }
+                self.devices = devices;
+                self.selected = previous
+                    .and_then(|path| self.devices.iter().position(|d| d.stable_path() == path))
+                    .unwrap_or_else(|| self.selected.min(self.devices.len().saturating_sub(1)));
+                self.keep_selection_visible();
+                // Partitions may have changed along with the device list.
+                self.device_details = None;
+                Ok(())
+            }
+            Err(err) => {
+                self.devices = Vec::new();
+                self.selected = 0;
+                self.device_changes = None;
+                Err(err)
+            }
+        }
+    }
+
+    /// Drop the `device_changes` highlight once it has been shown for
+    /// `DEVICE_CHANGES_DURATION`.
+    pub fn expire_device_changes(&mut self) {
+        if self
+            .device_changes
+            .as_ref()
+            .is_some_and(|changes| Instant::now() >= changes.expires)
+        {
+            self.device_changes = None;
+        }
+    }
+
+    /// Whether a device passes the device list filter.
+    pub fn device_visible(&self, disk: &Disk) -> bool {
+        disk.matches_filter(&self.device_filter)
+    }
+
+    /// Indices into `devices` of the devices that pass the filter.
+    pub fn visible_devices(&self) -> Vec<usize> {
+        (0..self.devices.len())
+            .filter(|&index| self.device_visible(&self.devices[index]))
+            .collect()
+    }
+
+    /// Move the selection to the first device passing the filter if the
+    /// selected one is filtered out (or no longer exists).
+    pub fn keep_selection_visible(&mut self) {
+        let visible = self.visible_devices();
+        if !visible.contains(&self.selected) {
+            if let Some(&first) = visible.first() {
+                self.selected = first;
+            }
+        }
+    }
+
+    /// Reload the details pane if the highlighted device changed.
+    ///
+    /// Details come from `lsblk` and a read of the device's first sectors, so
+    /// they are gathered once per newly highlighted device, not every frame.
+    pub fn refresh_device_details(&mut self) {
+        let Some(disk) = self.devices.get(self.selected) else {
+            self.device_details = None;
+            return;
+        };
+        let path = disk.device_path();
+        if self.device_details.as_ref().map(|d| &d.device) != Some(&path) {
+            self.device_details = Some(self.device_provider.details(&path));
+        }
+    }
+
+    /// Handle hotplug notifications from the device watcher.
+    ///
+    /// Changes are debounced: a rescan runs once no new change has arrived for
+    /// `RESCAN_DEBOUNCE`. The rescan keeps the highlighted device selected if it
+    /// is still present, so hotplugging another stick doesn't move the cursor.
+    pub fn poll_device_changes(&mut self) {
+        let Some(watcher) = &self.device_watcher else {
+            return;
+        };
+        if watcher.changed() {
+            self.rescan_due = Some(Instant::now() + RESCAN_DEBOUNCE);
+        }
+        if self.rescan_due.is_some_and(|due| Instant::now() >= due) {
+            self.rescan_due = None;
+            if self.rescan_devices() {
+                self.toast("Device list updated.");
+            }
+        }
+    }
+
+    /// Rescan devices, showing a failure in the status line.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the rescan succeeded.
+    fn rescan_devices(&mut self) -> bool {
+        match self.refresh_devices() {
+            Ok(()) => true,
+            Err(err) => {
+                self.status = format!("Rescan failed: {err}");
+                false
+            }
+        }
+    }
+
+    /// Reload the config file if it changed since it was last read.
+    ///
+    /// Checked at most every `CONFIG_POLL_INTERVAL`. A file that fails to
+    /// parse is reported once and not retried until it changes again.
+    pub fn poll_config_changes(&mut self) {
+        if self.config_checked.elapsed() < CONFIG_POLL_INTERVAL {
+            return;
+        }
+        self.config_checked = Instant::now();
+        if self.config.modified() == self.config_modified {
+            return;
+        }
+        match self.apply_config_file() {
+            Ok(true) => self.toast("Config file changed; settings reloaded."),
+            Ok(false) => {}
+            Err(err) => self.status = format!("Config not reloaded: {err:#}"),
+        }
+    }
+
+    /// Reload the config file now (Ctrl+R).
+    pub fn reload_config(&mut self) {
+        match self.apply_config_file() {
+            Ok(true) => self.toast("Config reloaded."),
+            Ok(false) => self.toast("Config reloaded; nothing changed."),
+            Err(err) => self.status = format!("Config not reloaded: {err:#}"),
+        }
+    }
+
+    /// Read the config file again and apply it, with `config_overrides` on top.
+    ///
+    /// Settings read when needed (limits, trusted devices, bookmarks, flash
+    /// options) take effect from the next action; a flash already running
+    /// keeps its options. The theme and compact mode are only reset when the
+    /// file changed them, so Ctrl+T and Ctrl+O choices survive unrelated edits.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the reloaded config differs from the current one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed; the current
+    /// config is kept.
+    fn apply_config_file(&mut self) -> anyhow::Result<bool> {
+        self.config_modified = self.config.modified();
+        let mut config = self.config.reload()?;
+        self.config_overrides.apply(&mut config);
+        if config == self.config {
+            return Ok(false);
+        }
+        let previous = std::mem::replace(&mut self.config, config);
+        if previous.theme != self.config.theme || previous.theme_colors != self.config.theme_colors
+        {
+            self.theme = theme::Theme::from_config(&self.config);
+        }
+        if previous.compact != self.config.compact {
+            self.compact = self.config.compact;
+        }
+        if previous.hidden_devices != self.config.hidden_devices && !self.step.busy() {
+            self.rescan_devices();
+        }
+        if let Some(path) = &self.config.path {
+            log::info!("reloaded config from {}", path.display());
+        }
+        Ok(true)
+    }
+
+    /// Toggle the multi-select mark on the highlighted device.
+    pub fn toggle_mark(&mut self) {
+        let Some(disk) = self.devices.get(self.selected) else {
+            return;
+        };
+        let path = disk.stable_path();
+        if let Some(pos) = self.marked.iter().position(|p| *p == path) {
+            self.marked.remove(pos);
+        } else {
+            self.marked.push(path);
+        }
+    }
+
+    /// Whether a device is marked for multi-device flashing.
+    pub fn is_marked(&self, disk: &Disk) -> bool {
+        self.marked.contains(&disk.stable_path())
+    }
+
+    /// Choose the flash targets from the device list.
+    ///
+    /// Uses all marked devices (in list order) when any are marked, otherwise the
+    /// highlighted device. Marks for devices that are no longer listed are dropped.
+    ///
+    /// # Returns
+    ///
+    /// `true` if at least one target was selected.
+    pub fn select_targets(&mut self) -> bool {
+        let marked: Vec<Disk> = self
+            .devices
+            .iter()
+            .filter(|d| self.is_marked(d))
+            .cloned()
+            .collect();
+        self.marked = marked.iter().map(|d| d.stable_path()).collect();
+        self.selected_devices = if marked.is_empty() {
+            self.devices.get(self.selected).cloned().into_iter().collect()
+        } else {
+            marked
+        };
+        self.selected_device = self.selected_devices.first().cloned();
+        self.refresh_target_contents();
+        self.selected_device.is_some()
+    }
+
+    /// Move on from a chosen image to the Device step, or straight to Confirm
+    /// when `auto_advance` is configured and there is only one device to pick.
+    pub fn enter_device_step(&mut self) {
+        self.step = Step::Device;
+        self.auto_advance();
+    }
+
+    /// Select the only removable, trusted device and jump to Confirm, if the
+    /// config opts into it. A different device chosen with `--device` wins.
+    fn auto_advance(&mut self) {
+        if !self.config.auto_advance {
+            return;
+        }
+        let mut candidates = self.devices.iter().enumerate().filter(|(_, d)| {
+            d.removable && self.config.is_trusted(d) && self.size_limit_error(d).is_none()
+        });
+        let (Some((index, disk)), None) = (candidates.next(), candidates.next()) else {
+            return;
+        };
+        let path = disk.stable_path();
+        if self
+            .selected_device
+            .as_ref()
+            .is_some_and(|d| d.stable_path() != path)
+        {
+            return;
+        }
+        self.selected = index;
+        self.marked.clear();
+        if self.iso_kind == IsoKind::Unknown {
+            self.refresh_iso_kind();
+        }
+        if self.select_targets() {
+            self.step = Step::confirm();
+            self.toast(format!(
+                "Only one device present: selected {}",
+                self.devices[index].device_path()
+            ));
+        }
+    }
+
+    /// Text the user must type on the Confirm screen before an execute-mode flash:
+    /// the kernel names of the selected devices separated by spaces (e.g., "sdb").
+    ///
+    /// When an internal disk is among the targets, the names are prefixed with
+    /// "ERASE" (e.g., "ERASE sda") so a habitual name-and-Enter cannot wipe it.
+    pub fn confirm_phrase(&self) -> String {
+        let names = self
+            .selected_devices
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.targets_internal_disk() {
+            format!("ERASE {names}")
+        } else {
+            names
+        }
+    }
+
+    /// Switch between execute and dry-run mode from the Confirm screen.
+    ///
+    /// Anything typed toward the old mode's confirmation is discarded.
+    pub fn toggle_execute(&mut self) {
+        self.execute = !self.execute;
+        if let Step::Confirm(input) = &mut self.step {
+            input.clear();
+        }
+        self.toast(if self.execute {
+            "Execute mode: the device will be overwritten"
+        } else {
+            "Dry run: nothing will be written"
+        });
+    }
+
+    /// Why `disk` may not be written under the config's size limit, with its
+    /// size from `device_provider`; `None` if it may.
+    pub fn size_limit_error(&self, disk: &Disk) -> Option<String> {
+        self.config
+            .size_limit_error_sized(disk, self.device_provider.size_bytes(&disk.name))
+    }
+
+    /// Why `disk` may not be written in execute mode: it is outside
+    /// `trusted_devices` or over the size limit. `None` if it may.
+    pub fn write_refusal(&self, disk: &Disk) -> Option<String> {
+        if !self.config.is_trusted(disk) {
+            return Some(format!(
+                "{} is not in the configured trusted_devices list.",
+                disk.device_path()
+            ));
+        }
+        self.size_limit_error(disk)
+    }
+
+    /// Ask before `operation` overwrites `disk`: yes or no for a removable
+    /// disk, the typed `erase_phrase` for an internal one, as on the Confirm
+    /// screen. Untrusted and oversized disks are refused in the status line.
+    pub fn confirm_destructive(&mut self, disk: &Disk, operation: modal::Destructive) {
+        if let Some(message) = self.write_refusal(disk) {
+            self.status = message;
+            return;
+        }
+        let action = modal::ModalAction::Destructive(operation);
+        self.modal = Some(if disk.removable {
+            modal::Modal::confirm(
+                operation.title(),
+                format!("{} {}. Continue?", operation.warning(), disk.device_path()),
+                action,
+            )
+        } else {
+            modal::Modal::prompt(
+                operation.title(),
+                format!(
+                    "{} {}, an internal disk. Type '{}' to continue:",
+                    operation.warning(),
+                    disk.device_path(),
+                    erase_phrase(disk)
+                ),
+                "",
+                action,
+            )
+        });
+    }
+
+    /// Start `operation` on the current side step's device.
+    pub fn start_destructive(&mut self, operation: modal::Destructive) {
+        match operation {
+            modal::Destructive::WriteTest => self.start_benchmark(true),
+            modal::Destructive::CapacityTest => self.start_capacity_test(),
+            modal::Destructive::SurfaceScan => self.start_surface_scan(),
+            modal::Destructive::Wipe => self.start_wipe(),
+            modal::Destructive::Format => self.start_format(),
+        }
+    }
+
+    /// Whether any selected device is an internal (non-removable) disk.
+    pub fn targets_internal_disk(&self) -> bool {
+        self.selected_devices.iter().any(|d| !d.removable)
+    }
+
+    /// Re-read the existing partitions on the selected devices and the
+    /// processes holding them open, and re-collect the warnings built from them.
+    ///
+    /// Devices that cannot be inspected are skipped; the flash engine performs
+    /// its own safety checks regardless.
+    pub fn refresh_target_contents(&mut self) {
+        self.target_contents = self
+            .selected_devices
+            .iter()
+            .filter_map(|d| self.device_provider.content_summary(&d.device_path()).ok())
+            .flatten()
+            .collect();
+        self.target_holders = self
+            .selected_devices
+            .iter()
+            .flat_map(|d| self.device_provider.holders(&d.device_path()))
+            .collect();
+        self.target_warnings = self.collect_target_warnings();
+        self.interrupted = self.find_interrupted();
+        self.stage_offer = self
+            .image_path()
+            .and_then(|image| stage::network_filesystem(&image));
+    }
+
+    /// Gather every warning that applies to flashing the chosen image onto the
+    /// selected devices, so the Confirm screen can list them in one place.
+    fn collect_target_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let image = self.image_path();
+        let image_size = image
+            .as_deref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len());
+
+        for disk in &self.selected_devices {
+            let dev = disk.device_path();
+            if !disk.removable {
+                warnings.push(format!("{dev} is an internal (non-removable) disk"));
+            }
+            if let (Some(image_size), Some(capacity)) =
+                (image_size, self.device_provider.size_bytes(&disk.name))
+            {
+                if image_size > capacity {
+                    warnings.push(format!(
+                        "Image ({}) is larger than {dev} ({}); the write will fail",
+                        device::format_lsblk_size(image_size),
+                        device::format_lsblk_size(capacity)
+                    ));
+                } else if image_size.saturating_mul(100) >= capacity * BARELY_FITS_PERCENT {
+                    warnings.push(format!(
+                        "Image barely fits: it fills {}% of {dev}",
+                        image_size.saturating_mul(100) / capacity.max(1)
+                    ));
+                }
+            }
+        }
+        for content in &self.target_contents {
+            if let Some(mountpoint) = &content.mountpoint {
+                warnings.push(format!("{} is mounted at {mountpoint}", content.name));
+            }
+        }
+        if !self.target_holders.is_empty() {
+            warnings.push(format!(
+                "Target is open in {} other program(s)",
+                self.target_holders.len()
+            ));
+        }
+        if !self.target_contents.is_empty() {
+            warnings.push(format!(
+                "Target still holds {} partition(s) of earlier data",
+                self.target_contents.len()
+            ));
+        }
+        if self.iso_kind != IsoKind::Hybrid {
+            warnings.push("Image is not a recognized hybrid ISO and may not boot".to_string());
+        }
+        if let Some(image) = &image {
+            if iso::find_signature(image).is_none() {
+                warnings.push(
+                    "No signature or checksum file next to the image; it cannot be verified"
+                        .to_string(),
+                );
+            }
+        }
+        warnings
+    }
+
+    /// Get the image file path from user input string.
+    ///
+    /// Trims whitespace and returns the path, or `None` if input is empty.
+    /// A leading "~" means home, relative paths are resolved against the file
+    /// picker's current directory (matching what Tab completion offers), and
+    /// "." and ".." are folded away (see `config::resolve_path`).
+    ///
+    /// # Returns
+    ///
+    /// `Some(PathBuf)` if input is non-empty, `None` otherwise.
+    pub fn image_path(&self) -> Option<PathBuf> {
+        let trimmed = self.image_input.trim();
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(config::resolve_path(trimmed, &self.cwd))
+        }
+    }
+
+    /// Validate that the user-entered image path points to an existing file.
+    ///
+    /// If valid, clears the status message. If invalid, sets an error message
+    /// naming the resolved path and resets ISO type to Unknown.
+    ///
+    /// # Returns
+    ///
+    /// `true` if image path is valid (file exists), `false` otherwise.
+    pub fn validate_image(&mut self) -> bool {
+        match self.image_path() {
+            Some(path) if path.is_file() => {
+                self.status.clear();
+                true
+            }
+            path => {
+                self.status = match path {
+                    Some(path) if path.is_dir() => format!("{} is a directory.", path.display()),
+                    Some(path) if path.exists() => {
+                        format!("{} is not a regular file.", path.display())
+                    }
+                    Some(path) => format!("{} does not exist.", path.display()),
+                    None => "Image path must point to a file.".to_string(),
+                };
+                self.iso_kind = IsoKind::Unknown;
+                self.iso_info.clear();
+                false
+            }
+        }
+    }
+
+    /// Detect the ISO type (Hybrid/NonHybrid) of the selected image.
+    ///
+    /// Reads the MBR header of the ISO file to check for a partition table.
+    /// Updates `iso_kind` and `iso_info` with the result or error message.
+    ///
+    /// # Note
+    ///
+    /// This operation requires only read access to the file — no root privileges needed.
+    /// If `iso_kind` is `NonHybrid`, the flash operation will be blocked in the `Confirm` step.
+    pub fn refresh_iso_kind(&mut self) {
+        let Some(path) = self.image_path() else {
+            self.iso_kind = IsoKind::Unknown;
+            self.iso_info.clear();
+            return;
+        };
+
+        match iso::detect(&path) {
+            Ok(kind) => {
+                self.iso_kind = kind;
+                self.iso_info = match kind {
+                    IsoKind::Hybrid => "Hybrid ISO detected (raw write).".to_string(),
+                    IsoKind::NonHybrid => "Non-hybrid ISO (unsupported).".to_string(),
+                    IsoKind::Unknown => "ISO type unknown.".to_string(),
+                };
+            }
+            Err(err) => {
+                self.iso_kind = IsoKind::Unknown;
+                self.iso_info = format!("ISO check failed: {err}");
+            }
+        }
+    }
+
+    /// Poll for updates from the background flash thread.
+    ///
+    /// Non-blocking: receives any pending progress messages (see `FlashRun::apply`)
+    /// and, once the flash thread completes, moves on to the `Result` step with
+    /// the final success/failure message and the report.
+    ///
+    /// Called once per event loop iteration (every 250ms in main loop).
+    pub fn poll_flash(&mut self) {
+        let Step::Flashing(run) = &mut self.step else {
+            return;
+        };
+        let (lines, result) = run.task.poll();
+        for line in lines {
+            self.activity.push(&line);
+            run.apply(line);
+        }
+        let Some(result) = result else {
+            return;
+        };
+        let Step::Flashing(run) = std::mem::replace(&mut self.step, Step::Image) else {
+            unreachable!("checked above");
+        };
+
+        let result = result
+            .unwrap_or_else(|panicked| Err(FlashError::Other(anyhow::Error::new(panicked)).into()));
+        let flash_result = FlashResult::finished(
+            &result,
+            Some(run.started.elapsed()),
+            run.total.unwrap_or(0) * self.selected_devices.len() as u64,
+        );
+        self.activity.push(&flash_result.message);
+        if let Some(timing) = flash_result.timing_summary() {
+            self.activity.push(&timing);
+        }
+        let mut report = run.report;
+        report.outcome = Some(flash_result.clone());
+        if let Err(err) = history::record(&report) {
+            self.activity
+                .push(&format!("Flash not added to the history: {err:#}"));
+        }
+        if let Some(path) = &self.report_path {
+            match report.save(path) {
+                Err(err) => self.activity.push(&format!("Report not saved: {err:#}")),
+                Ok(()) => self
+                    .activity
+                    .push(&format!("Report saved to {}", path.display())),
+            }
+        }
+        self.step = Step::Result(step::Outcome {
+            result: flash_result,
+            report: Some(report),
+        });
+    }
+
+    /// Start the flash operation in a background thread.
+    ///
+    /// Spawns a `Task` to perform the flash and transitions to the `Flashing` step. Multiple devices are flashed one after another;
+    /// the first failure stops the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - Path to the ISO image file
+    /// * `devices` - Stable device paths (by-id links, or e.g. "/dev/sdb" when none exists)
+    /// * `confirmed_wipe` - Whether the user agreed to overwrite existing partitions
+    ///
+    /// # Note
+    ///
+    /// The task streams progress lines and then the final result through the
+    /// `Flashing` step. Call `poll_flash()` regularly to receive these updates.
+    pub fn start_flash(&mut self, image: PathBuf, devices: Vec<String>, confirmed_wipe: bool) {
+        cache::note_use(&image);
+        let total = std::fs::metadata(&image).map(|m| m.len()).ok();
+        let report = report::FlashReport::new(image.clone(), &self.selected_devices);
+
+        let options = flash::FlashOptions {
+            confirmed_wipe,
+            require_verification: self.require_verification,
+            checksum: self.checksum.clone(),
+            label: self.volume_label.clone(),
+            post_hook: self.config.post_hook_path(),
+            pre_hook: self.config.pre_hook_path(),
+            label_hook: self.config.label_hook_path(),
+            hash: self.config.hash_algorithm,
+            backend: self.config.backend,
+            block_size: self.block_size,
+            journal_dir: config::journal_dir(),
+            resume: self.resume,
+            stage_dir: if self.stage_offer.is_some() && self.stage {
+                self.config.stage_dir_path()
+            } else {
+                None
+            },
+            cancel: flash::CancelFlag::default(),
+        };
+
+        let task = Task::spawn_waking("flash", self.wake.clone(), move |progress_tx| {
+            audit::flash_started(&image, &devices);
+            let count = devices.len();
+            let mut result = Ok(());
+            for (idx, device) in devices.iter().enumerate() {
+                let prefix = if count > 1 {
+                    format!("[{}/{}] ", idx + 1, count)
+                } else {
+                    String::new()
+                };
+                let _ = progress_tx.send(format!(
+                    "{prefix}Flashing {} -> {}",
+                    image.display(),
+                    device
+                ));
+                let flashed =
+                    flash::flash_image_with_progress(&image, device, progress_tx.clone(), &options);
+                if let Err(err) = flashed {
+                    result = Err(FlashFailure {
+                        device: (count > 1).then(|| device.clone()),
+                        error: Arc::new(err),
+                    });
+                    break;
+                }
+            }
+            audit::flash_finished(&image, &devices, &result);
+            result
+        });
+        self.step = Step::Flashing(Box::new(step::FlashRun::new(task, total, report)));
+    }
+
+    /// Start a quick wipe of the `Wipe` step's device in a background thread.
+    ///
+    /// Stays on the `Wipe` step; call `poll_wipe()` regularly to receive
+    /// progress lines and the final result. Does nothing on other steps.
+    pub fn start_wipe(&mut self) {
+        let Step::Wipe(job) = &mut self.step else {
+            return;
+        };
+        let device = job.disk.stable_path();
+        job.start(Task::spawn_waking(
+            "wipe",
+            self.wake.clone(),
+            move |progress_tx| {
+                wipe::quick_wipe(&device, progress_tx).map_err(|err| format!("{err:#}"))
+            },
+        ));
+    }
+
+    /// Poll for updates from the background wipe thread.
+    pub fn poll_wipe(&mut self) {
+        if let Step::Wipe(job) = &mut self.step {
+            for line in job.poll() {
+                self.activity.push(&line);
+            }
+        }
+    }
+
+    /// Start formatting the `Format` step's device in a background thread.
+    ///
+    /// Uses the filesystem and label chosen on the step. Stays on the `Format`
+    /// step; call `poll_format()` regularly to receive progress lines and the
+    /// final result. Does nothing on other steps.
+    pub fn start_format(&mut self) {
+        let Step::Format(format) = &mut self.step else {
+            return;
+        };
+        let device = format.job.disk.stable_path();
+        let fs = format.fs;
+        let label = format.label.clone();
+        format.job.start(Task::spawn_waking(
+            "format",
+            self.wake.clone(),
+            move |progress_tx| {
+                format::format_device(&device, fs, &label, progress_tx)
+                    .map_err(|err| format!("{err:#}"))
+            },
+        ));
+    }
+
+    /// Poll for updates from the background format thread.
+    pub fn poll_format(&mut self) {
+        if let Step::Format(format) = &mut self.step {
+            for line in format.job.poll() {
+                self.activity.push(&line);
+            }
+        }
+    }
+
+    /// Open the cloud-init editor for the flashed devices (see `seed`).
+    ///
+    /// Only works on the Result step, whose outcome the `Seed` step keeps
+    /// until `leave_seed()` shows it again.
+    pub fn open_seed(&mut self) {
+        if !matches!(self.step, Step::Result(_)) {
+            return;
+        }
+        let Step::Result(outcome) = std::mem::replace(&mut self.step, Step::Image) else {
+            unreachable!("checked above");
+        };
+        self.step = Step::Seed(Box::new(step::SeedJob::new(outcome)));
+    }
+
+    /// Return from the `Seed` step to the Result step it was opened from.
+    pub fn leave_seed(&mut self) {
+        if !matches!(self.step, Step::Seed(_)) {
+            return;
+        }
+        let Step::Seed(job) = std::mem::replace(&mut self.step, Step::Image) else {
+            unreachable!("checked above");
+        };
+        self.step = Step::Result(job.outcome);
+    }
+
+    /// Ask for the seed directory or user-data file to load into the
+    /// cloud-init editor, starting from `cwd`.
+    pub fn prompt_load_seed(&mut self) {
+        let initial = self.cwd.join("").display().to_string();
+        self.modal = Some(modal::Modal::prompt(
+            "Load cloud-init seed",
+            "Seed directory or user-data file (meta-data and network-config beside it are used too):",
+            &initial,
+            modal::ModalAction::LoadSeed,
+        ));
+    }
+
+    /// Replace the `Seed` step's seed with the files at `path`.
+    pub fn load_seed(&mut self, path: &Path) {
+        let Step::Seed(job) = &mut self.step else {
+            return;
+        };
+        match seed::Seed::load(path) {
+            Ok(seed) => {
+                job.seed = seed;
+                job.cursor = 0;
+                job.source = Some(path.to_path_buf());
+                self.toast(format!("Loaded {}", path.display()));
+            }
+            Err(err) => self.status = format!("Seed not loaded: {err:#}"),
+        }
+    }
+
+    /// Start writing the `Seed` step's seed to the flashed devices in a
+    /// background thread, one after another.
+    ///
+    /// Stays on the `Seed` step; call `poll_seed()` regularly to receive
+    /// progress lines and the final result. Does nothing on other steps.
+    pub fn start_seed(&mut self) {
+        let Step::Seed(job) = &mut self.step else {
+            return;
+        };
+        let devices: Vec<String> = self
+            .selected_devices
+            .iter()
+            .map(Disk::stable_path)
+            .collect();
+        let seed = job.seed.clone();
+        job.progress = "Starting...".to_string();
+        job.result = None;
+        job.task = Some(Task::spawn_waking(
+            "seed",
+            self.wake.clone(),
+            move |progress_tx| {
+                for device in &devices {
+                    seed::write_seed(device, &seed, progress_tx.clone())
+                        .map_err(|err| format!("{device}: {err:#}"))?;
+                }
+                Ok(())
+            },
+        ));
+    }
+
+    /// Poll for updates from the background seed thread.
+    pub fn poll_seed(&mut self) {
+        if let Step::Seed(job) = &mut self.step {
+            for line in job.poll() {
+                self.activity.push(&line);
+            }
+        }
+    }
+
+    /// Start a benchmark of the `Benchmark` step's device in a background thread.
+    ///
+    /// Call `poll_benchmark()` regularly to receive progress lines and the
+    /// final result. Does nothing on other steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `destructive` - Whether to include the write pass (destroys data at the start of the device)
+    pub fn start_benchmark(&mut self, destructive: bool) {
+        self.start_test(|device, wake| {
+            step::RunningTest::Speed(Task::spawn_waking("benchmark", wake, move |progress_tx| {
+                bench::run_benchmark(&device, destructive, progress_tx)
+                    .map_err(|err| err.to_string())
+            }))
+        });
+    }
+
+    /// Start a destructive counterfeit-capacity test in a background thread.
+    ///
+    /// Shares the `Benchmark` step and its progress line with the speed test;
+    /// `poll_benchmark()` also collects this test's result.
+    pub fn start_capacity_test(&mut self) {
+        self.start_test(|device, wake| {
+            step::RunningTest::Capacity(Task::spawn_waking(
+                "capacity test",
+                wake,
+                move |progress_tx| {
+                    capacity::run_capacity_test(&device, progress_tx)
+                        .map_err(|err| format!("{err:#}"))
+                },
+            ))
+        });
+    }
+
+    /// Start a destructive full-surface scan in a background thread.
     ///
-    /// The background thread sends progress updates through `progress_rx` and final result
-    /// through `result_rx`. Call `poll_flash()` regularly to receive these updates.
-    pub fn start_flash(&mut self, image: PathBuf, device: String) {
-        let (progress_tx, progress_rx) = std::sync::mpsc::channel();
-        let (result_tx, result_rx) = std::sync::mpsc::channel();
-
-        self.flash_progress = "Starting...".to_string();
-        self.flash_done = 0;
-        self.flash_total = std::fs::metadata(&image).map(|m| m.len()).ok();
-        self.progress_rx = Some(progress_rx);
-        self.result_rx = Some(result_rx);
-        self.step = Step::Flashing;
-
-        let confirmed_wipe = self.user_confirmed_wipe;
-
-        std::thread::spawn(move || {
-            let _ = progress_tx.send(format!("Flashing {} -> {}", image.display(), device));
-            let result =
-                flash::flash_image_with_progress(&image, &device, progress_tx, confirmed_wipe);
-            let result = result.map_err(|err| err.to_string());
-            let _ = result_tx.send(result);
+    /// Shares the `Benchmark` step like `start_capacity_test()`.
+    pub fn start_surface_scan(&mut self) {
+        self.start_test(|device, wake| {
+            step::RunningTest::Scan(Task::spawn_waking(
+                "surface scan",
+                wake,
+                move |progress_tx| {
+                    scan::run_surface_scan(&device, progress_tx).map_err(|err| format!("{err:#}"))
+                },
+            ))
         });
     }
+
+    /// Start the test `spawn` builds for the `Benchmark` step's device (its
+    /// stable path) and the loop's `wake`, replacing the last test's progress
+    /// and result.
+    fn start_test(&mut self, spawn: impl FnOnce(String, task::Wake) -> step::RunningTest) {
+        if let Step::Benchmark(tests) = &mut self.step {
+            let test = spawn(tests.disk.stable_path(), self.wake.clone());
+            tests.start(test);
+        }
+    }
+
+    /// Poll for updates from the background benchmark, capacity test, or surface scan thread.
+    ///
+    /// Non-blocking: logs the progress lines and lets the `Benchmark` step keep
+    /// the latest one and the final outcome.
+    pub fn poll_benchmark(&mut self) {
+        if let Step::Benchmark(tests) = &mut self.step {
+            for text in tests.poll() {
+                self.activity.push(&text);
+            }
+        }
+    }
 }
 
+/// Images filling at least this share of the target (in percent) get a warning.
+const BARELY_FITS_PERCENT: u64 = 95;
+
+/// How long a toast stays on screen.
+pub const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+/// How long the devices a rescan added or removed stay highlighted.
+pub const DEVICE_CHANGES_DURATION: Duration = Duration::from_secs(5);
+
+/// Quiet period after a hotplug notification before the device list is re-enumerated.
+const RESCAN_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Block sizes the Confirm screen cycles through.
+pub const BLOCK_SIZES: [u64; 4] = [1 << 20, 4 << 20, 8 << 20, 16 << 20];
+
+/// How often the config file is checked for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 /// File extensions shown when the ISO filter is active.
 const ISO_EXTENSIONS: &[&str] = &["iso", "img", "raw"];
 
@@ -412,12 +2448,18 @@ const ISO_EXTENSIONS: &[&str] = &["iso", "img", "raw"];
 /// * `cwd` - Current working directory path to list
 /// * `filter_iso` - When true, only show files with ISO-related extensions
 /// * `show_hidden` - When true, include files starting with '.'
+/// * `sort` - Order within the directory and file groups
 ///
 /// # Returns
 ///
-/// Vector of `FileEntry` structs sorted by directory-first, then alphabetically.
+/// Vector of `FileEntry` structs sorted by directory-first, then by `sort`.
 /// If directory cannot be read, returns an empty vector.
-pub fn load_entries(cwd: &std::path::Path, filter_iso: bool, show_hidden: bool) -> Vec<FileEntry> {
+pub fn load_entries(
+    cwd: &std::path::Path,
+    filter_iso: bool,
+    show_hidden: bool,
+    sort: SortMode,
+) -> Vec<FileEntry> {
     let mut entries: Vec<FileEntry> = std::fs::read_dir(cwd)
         .ok()
         .into_iter()
@@ -435,7 +2477,18 @@ pub fn load_entries(cwd: &std::path::Path, filter_iso: bool, show_hidden: bool)
                     return None;
                 }
             }
-            Some(FileEntry { name, path, is_dir })
+            let metadata = entry.metadata().ok();
+            Some(FileEntry {
+                name,
+                path,
+                is_dir,
+                size: if is_dir {
+                    0
+                } else {
+                    metadata.as_ref().map_or(0, |m| m.len())
+                },
+                modified: metadata.and_then(|m| m.modified().ok()),
+            })
         })
         .collect();
 
@@ -444,16 +2497,456 @@ pub fn load_entries(cwd: &std::path::Path, filter_iso: bool, show_hidden: bool)
             name: "..".to_string(),
             path: parent.to_path_buf(),
             is_dir: true,
+            size: 0,
+            modified: None,
         });
     }
 
+    sort_entries(&mut entries, sort);
+    entries
+}
+
+/// Text to type before a side step overwrites the internal disk `disk`,
+/// matching the Confirm screen's (e.g., "ERASE sda").
+fn erase_phrase(disk: &Disk) -> String {
+    format!("ERASE {}", disk.name)
+}
+
+/// Describe how long ago `modified` was, relative to `now` (e.g., "5 min ago").
+pub fn format_age(modified: std::time::SystemTime, now: std::time::SystemTime) -> String {
+    let Ok(age) = now.duration_since(modified) else {
+        return "in the future".to_string();
+    };
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3_599 => format!("{} min ago", secs / 60),
+        3_600..=86_399 => format!("{} h ago", secs / 3_600),
+        _ => format!("{} days ago", secs / 86_400),
+    }
+}
+
+/// Format a duration as "45s", "3m 12s", or "1h 02m 05s".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s:02}s"),
+        (h, m, s) => format!("{h}h {m:02}m {s:02}s"),
+    }
+}
+
+/// Format a byte count in binary units, e.g. "4.7 GiB" or "512 B".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Progress as "2.3 GiB / 4.7 GiB (49%)", or in exact bytes when `exact`.
+pub fn format_progress(done: u64, total: u64, exact: bool) -> String {
+    let percent = done
+        .saturating_mul(100)
+        .checked_div(total)
+        .unwrap_or(0)
+        .min(100);
+    if exact {
+        format!("{done} / {total} bytes ({percent}%)")
+    } else {
+        format!(
+            "{} / {} ({percent}%)",
+            format_size(done),
+            format_size(total)
+        )
+    }
+}
+
+/// Sort file picker entries: ".." first, then directories, then files, each
+/// group ordered by `sort` (ties broken by name).
+pub fn sort_entries(entries: &mut [FileEntry], sort: SortMode) {
     entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
         _ if a.name == ".." => std::cmp::Ordering::Less,
         _ if b.name == ".." => std::cmp::Ordering::Greater,
         (true, false) => std::cmp::Ordering::Less,
         (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        _ => {
+            let by_name = a.name.to_lowercase().cmp(&b.name.to_lowercase());
+            match sort {
+                SortMode::Name => by_name,
+                SortMode::Newest => b.modified.cmp(&a.modified).then(by_name),
+                SortMode::Largest => b.size.cmp(&a.size).then(by_name),
+            }
+        }
     });
+}
 
-    entries
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockDevices;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn entry(name: &str, is_dir: bool, size: u64, age_secs: u64) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            is_dir,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(1_000_000 - age_secs)),
+        }
+    }
+
+    fn names(entries: &[FileEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_entries_keeps_directories_first_in_every_mode() {
+        let mut entries = vec![
+            entry("old.iso", false, 300, 500),
+            entry("new.iso", false, 100, 10),
+            entry("isos", true, 0, 1000),
+            entry("..", true, 0, 0),
+        ];
+
+        sort_entries(&mut entries, SortMode::Newest);
+        assert_eq!(names(&entries), ["..", "isos", "new.iso", "old.iso"]);
+
+        sort_entries(&mut entries, SortMode::Largest);
+        assert_eq!(names(&entries), ["..", "isos", "old.iso", "new.iso"]);
+
+        sort_entries(&mut entries, SortMode::Name);
+        assert_eq!(names(&entries), ["..", "isos", "new.iso", "old.iso"]);
+    }
+
+    #[test]
+    fn format_age_picks_a_readable_unit() {
+        let now = UNIX_EPOCH + Duration::from_secs(10_000_000);
+        let ago = |secs| format_age(now - Duration::from_secs(secs), now);
+        assert_eq!(ago(5), "just now");
+        assert_eq!(ago(300), "5 min ago");
+        assert_eq!(ago(7_200), "2 h ago");
+        assert_eq!(ago(3 * 86_400), "3 days ago");
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(4_700_000_000), "4.4 GiB");
+        assert_eq!(format_size(3 << 40), "3.0 TiB");
+    }
+
+    #[test]
+    fn format_progress_switches_to_exact_bytes() {
+        let total = 5 << 30;
+        assert_eq!(
+            format_progress(total / 2, total, false),
+            "2.5 GiB / 5.0 GiB (50%)"
+        );
+        assert_eq!(
+            format_progress(1_000, 4_000, true),
+            "1000 / 4000 bytes (25%)"
+        );
+        assert_eq!(format_progress(0, 0, false), "0 B / 0 B (0%)");
+    }
+
+    #[test]
+    fn format_duration_drops_leading_zero_units() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(format_duration(Duration::from_secs(3_725)), "1h 02m 05s");
+    }
+
+    #[test]
+    fn timing_summary_averages_over_the_whole_run() {
+        let mut result = FlashResult {
+            ok: true,
+            message: String::new(),
+            error: None,
+            elapsed: Some(Duration::from_secs(100)),
+            bytes_written: 2_470_000_000,
+        };
+        assert_eq!(
+            result.timing_summary().as_deref(),
+            Some("Took 1m 40s (average 24.7 MB/s)")
+        );
+        result.bytes_written = 0;
+        assert_eq!(result.timing_summary().as_deref(), Some("Took 1m 40s"));
+        result.elapsed = None;
+        assert_eq!(result.timing_summary(), None);
+    }
+
+    #[test]
+    fn failed_results_name_the_device_and_keep_the_error_kind() {
+        let failure = FlashFailure {
+            device: Some("/dev/sdc".to_string()),
+            error: Arc::new(FlashError::Device(DeviceError::Busy {
+                holders: vec!["gnome-disks (pid 412)".to_string()],
+            })),
+        };
+        let result = FlashResult::finished(&Err(failure), None, 4_000_000);
+        assert!(!result.ok);
+        assert_eq!(result.bytes_written, 0);
+        assert_eq!(
+            result.message,
+            "/dev/sdc: Target device is in use by gnome-disks (pid 412). \
+             Close these programs before flashing."
+        );
+        assert!(matches!(
+            result.error.as_deref(),
+            Some(FlashError::Device(DeviceError::Busy { .. }))
+        ));
+        assert_eq!(
+            result.hint(),
+            Some("Close the programs using the device and try again.")
+        );
+
+        let cancelled = FlashResult::finished(&Err(FlashError::Cancelled.into()), None, 0);
+        assert_eq!(cancelled.message, "Flash cancelled");
+        assert_eq!(cancelled.hint(), None);
+    }
+
+    /// A small image with an MBR partition entry, which `iso::detect` reads
+    /// as hybrid.
+    fn hybrid_image(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("flashr_tui_{name}_{}.iso", std::process::id()));
+        let mut bytes = vec![0u8; 1024];
+        bytes[446] = 0x80;
+        bytes[510] = 0x55;
+        bytes[511] = 0xAA;
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn device_and_confirm_steps_run_on_mock_devices() {
+        let image = hybrid_image("mock_flow");
+        let mock = MockDevices::default()
+            .with_disk("sda", "Internal SSD", 500_000_000_000, false)
+            .with_disk("sdb", "Cruzer", 16_000_000_000, true)
+            .with_disk("sdc", "DataTraveler", 8_000_000_000, true)
+            .with_partition("/dev/sdc", "/dev/sdc1", "vfat", Some("/media/stick"))
+            .with_holder("/dev/sdc", "gnome-disks", 4242);
+        let app_with = |targets: &[String]| {
+            let devices = mock.list(false).unwrap();
+            let provider = Box::new(mock.clone());
+            App::new(
+                Some(image.clone()),
+                targets,
+                false,
+                devices,
+                Config::default(),
+                provider,
+            )
+        };
+        let enter = KeyEvent::from(KeyCode::Enter);
+        let flash = KeyEvent::from(KeyCode::Char('f'));
+
+        // A blank stick goes from Device through Confirm straight to the dry run.
+        let mut app = app_with(&[]);
+        assert!(matches!(app.step, Step::Device));
+        assert_eq!(app.devices.len(), 2);
+        ui::handle_key(&mut app, enter);
+        assert!(matches!(app.step, Step::Confirm(_)));
+        assert_eq!(app.selected_devices[0].name, "sdb");
+        assert!(app.target_contents.is_empty());
+        ui::handle_key(&mut app, flash);
+        let Step::Result(outcome) = &app.step else {
+            panic!("expected the Result step");
+        };
+        assert!(outcome.result.message.contains("/dev/sdb"));
+        assert!(outcome.report.is_none());
+
+        // A stick in use is warned about and needs the wipe confirmed.
+        let mut app = app_with(&["/dev/sdc".to_string()]);
+        ui::handle_key(&mut app, enter);
+        assert!(matches!(app.step, Step::Confirm(_)));
+        assert!(app
+            .target_warnings
+            .contains(&"/dev/sdc1 is mounted at /media/stick".to_string()));
+        assert!(app
+            .target_warnings
+            .contains(&"Target is open in 1 other program(s)".to_string()));
+        ui::handle_key(&mut app, flash);
+        let Step::ConfirmWipe(info) = &app.step else {
+            panic!("expected the ConfirmWipe step");
+        };
+        assert!(info.has_mounted);
+
+        // Declining goes back to Confirm; an error there returns to it.
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Char('n')));
+        assert!(matches!(app.step, Step::Confirm(_)));
+        app.fail("boom");
+        app.fail("again");
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Char('b')));
+        assert!(matches!(app.step, Step::Confirm(_)));
+
+        let _ = std::fs::remove_file(image);
+    }
+
+    #[test]
+    fn rescans_keep_the_selection_and_note_changes() {
+        let mock = MockDevices::default()
+            .with_disk("sdb", "Cruzer", 16_000_000_000, true)
+            .with_disk("sdc", "DataTraveler", 8_000_000_000, true);
+        let devices = mock.list(false).unwrap();
+        let mut app = App::new(None, &[], false, devices, Config::default(), Box::new(mock));
+        app.step = Step::Device;
+        app.selected = 1;
+
+        // sdb is unplugged and another stick arrives; sdc stays highlighted.
+        app.device_provider = Box::new(
+            MockDevices::default()
+                .with_disk("sdd", "Ultra", 32_000_000_000, true)
+                .with_disk("sdc", "DataTraveler", 8_000_000_000, true),
+        );
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Char('r')));
+        assert_eq!(app.devices[app.selected].name, "sdc");
+        let changes = app.device_changes.clone().unwrap();
+        assert_eq!(changes.added, vec!["/dev/sdd".to_string()]);
+        assert_eq!(changes.removed[0].name, "sdb");
+        assert!(changes.is_added(&app.devices[0]));
+
+        // Nothing changed: the highlight stays until it expires.
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Char('a')));
+        assert_eq!(app.devices[app.selected].name, "sdc");
+        assert_eq!(app.device_changes.as_ref().unwrap().added, changes.added);
+        app.device_changes.as_mut().unwrap().expires = Instant::now();
+        app.expire_device_changes();
+        assert!(app.device_changes.is_none());
+    }
+
+    #[test]
+    fn catalog_menus_skip_single_choices() {
+        let mut app = App::new(
+            None,
+            &[],
+            false,
+            Vec::new(),
+            Config::default(),
+            Box::new(MockDevices::default()),
+        );
+        app.catalog = catalog::Catalog::parse(
+            r#"{"distros": [
+                {"name": "Arch Linux", "releases": [{"version": "2024.10.01", "images": [
+                    {"arch": "x86_64", "url": "https://example.org/arch.iso"}]}]},
+                {"name": "Debian", "releases": [{"version": "12.7", "images": [
+                    {"arch": "amd64", "url": "https://example.org/amd64.iso",
+                     "mirrors": ["https://example.net/amd64.iso"]},
+                    {"arch": "arm64", "url": "https://example.org/arm64.iso"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Char('c')));
+        let modal = app.modal.take().unwrap();
+        assert_eq!(modal.action, modal::ModalAction::CatalogDistro);
+        // Debian has one release, so its architectures come next.
+        app.resolve_modal(modal.action, modal::ModalResponse::Chose(1));
+        let modal = app.modal.take().unwrap();
+        assert_eq!(modal.action, modal::ModalAction::CatalogImage(1, 0));
+        assert!(matches!(
+            modal.kind,
+            modal::ModalKind::Choice { ref options, .. } if options == &["amd64", "arm64"]
+        ));
+        // amd64 has a mirror, so the source menu follows, led by "Fastest".
+        app.resolve_modal(modal.action, modal::ModalResponse::Chose(0));
+        let modal = app.modal.take().unwrap();
+        assert_eq!(modal.action, modal::ModalAction::CatalogSource(1, 0, 0));
+        let modal::ModalKind::Choice { options, .. } = modal.kind else {
+            panic!("not a menu");
+        };
+        assert_eq!(options[1..], ["example.org", "example.net"]);
+        assert!(matches!(app.step, Step::Image));
+    }
+
+    #[test]
+    fn reloading_the_config_keeps_command_line_overrides() {
+        let path =
+            std::env::temp_dir().join(format!("flashr_tui_reload_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"size_limit_gb": 64}"#).unwrap();
+        let mut mock = MockDevices::default()
+            .with_disk("sdb", "Cruzer", 16_000_000_000, true)
+            .with_disk("sdc", "Backup", 500_000_000_000, true);
+        mock.disks[1].serial = Some("BACKUP123".to_string());
+        let overrides = config::Overrides {
+            size_limit_gb: Some(32),
+            ..Default::default()
+        };
+        let mut config = Config::load_from(&path).unwrap();
+        overrides.apply(&mut config);
+        let devices = mock.list(false).unwrap();
+        let mut app = App::new(None, &[], false, devices, config, Box::new(mock));
+        app.config_overrides = overrides;
+        assert_eq!(app.devices.len(), 2);
+
+        std::fs::write(
+            &path,
+            r#"{"size_limit_gb": 16, "compact": true, "hidden_devices": ["BACKUP123"]}"#,
+        )
+        .unwrap();
+        app.reload_config();
+        assert_eq!(app.config.size_limit_gb, Some(16));
+        assert!(app.compact);
+        assert_eq!(app.devices.len(), 1);
+
+        std::fs::write(&path, r#"{"size_limit_gb": 128}"#).unwrap();
+        app.compact = false;
+        app.reload_config();
+        assert_eq!(app.config.size_limit_gb, Some(32));
+        assert!(
+            !app.compact,
+            "compact was turned off by hand, not by the file"
+        );
+        assert_eq!(app.devices.len(), 2);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        app.reload_config();
+        assert_eq!(app.config.size_limit_gb, Some(32));
+        assert!(app.status.starts_with("Config not reloaded"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn preferences_carry_over_to_the_next_run() {
+        let mock = MockDevices::default()
+            .with_disk("sda", "Internal SSD", 500_000_000_000, false)
+            .with_disk("sdb", "Cruzer", 16_000_000_000, true);
+        let devices = mock.list(false).unwrap();
+        let mut app = App::new(None, &[], false, devices, Config::default(), Box::new(mock));
+        assert_eq!(app.devices.len(), 1);
+
+        let dir = std::env::temp_dir();
+        app.apply_preferences(&prefs::Preferences {
+            last_dir: Some(dir.clone()),
+            show_all_disks: true,
+            block_size: None,
+            require_verification: true,
+        });
+        assert_eq!(app.cwd, dir);
+        assert_eq!(app.devices.len(), 2);
+        assert!(app.require_verification);
+
+        app.cycle_block_size();
+        assert_eq!(app.block_size, Some(8 << 20));
+        for _ in 0..3 {
+            app.cycle_block_size();
+        }
+        assert_eq!(app.block_size, Some(flash::DEFAULT_BLOCK_SIZE));
+        let saved = app.preferences();
+        assert_eq!(saved.last_dir, Some(dir));
+        assert!(saved.show_all_disks && saved.require_verification);
+    }
 }
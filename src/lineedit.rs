@@ -0,0 +1,287 @@
+//! Cursor editing for text inputs.
+//!
+//! The cursor is a byte offset into the text that always sits on a character
+//! boundary. Every function clamps a stale cursor first, so callers that replace
+//! the text wholesale only need to move the cursor if they care where it ends up.
+//!
+//! Movement and deletion work on grapheme clusters rather than `char`s, so an
+//! accented letter typed as a base plus combining mark, or an emoji sequence,
+//! is stepped over and erased as one character. Drawing measures display width,
+//! so wide (e.g. CJK) characters take the two cells a terminal gives them.
+//!
+//! Most inputs are a single line. The cloud-init editor's text spans several,
+//! and moves between them with `up`, `down`, `home`, and `end`.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Insert a character at the cursor and move the cursor past it.
+pub fn insert(text: &mut String, cursor: &mut usize, ch: char) {
+    clamp(text, cursor);
+    text.insert(*cursor, ch);
+    *cursor += ch.len_utf8();
+}
+
+/// Insert a string at the cursor and move the cursor past it.
+pub fn insert_str(text: &mut String, cursor: &mut usize, s: &str) {
+    clamp(text, cursor);
+    text.insert_str(*cursor, s);
+    *cursor += s.len();
+}
+
+/// Delete the character before the cursor. Returns `false` if there was none.
+pub fn backspace(text: &mut String, cursor: &mut usize) -> bool {
+    clamp(text, cursor);
+    let Some(grapheme) = text[..*cursor].graphemes(true).next_back() else {
+        return false;
+    };
+    let start = *cursor - grapheme.len();
+    text.replace_range(start..*cursor, "");
+    *cursor = start;
+    true
+}
+
+/// Delete the character under the cursor.
+pub fn delete(text: &mut String, cursor: &mut usize) {
+    clamp(text, cursor);
+    if let Some(grapheme) = text[*cursor..].graphemes(true).next() {
+        let end = *cursor + grapheme.len();
+        text.replace_range(*cursor..end, "");
+    }
+}
+
+/// Delete the last character of an input that is only ever appended to.
+pub fn pop(text: &mut String) {
+    let mut cursor = text.len();
+    backspace(text, &mut cursor);
+}
+
+/// Move the cursor one character left.
+pub fn left(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    if let Some(grapheme) = text[..*cursor].graphemes(true).next_back() {
+        *cursor -= grapheme.len();
+    }
+}
+
+/// Move the cursor one character right.
+pub fn right(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    if let Some(grapheme) = text[*cursor..].graphemes(true).next() {
+        *cursor += grapheme.len();
+    }
+}
+
+/// Move the cursor to the start of its line.
+pub fn home(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    *cursor = line_start(text, *cursor);
+}
+
+/// Move the cursor to the end of its line.
+pub fn end(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    *cursor += text[*cursor..].find('\n').unwrap_or(text.len() - *cursor);
+}
+
+/// Move the cursor to the line above, keeping its column where that line is
+/// long enough. Stays put on the first line.
+pub fn up(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    let start = line_start(text, *cursor);
+    if start == 0 {
+        return;
+    }
+    let column = text[start..*cursor].graphemes(true).count();
+    *cursor = column_of(text, line_start(text, start - 1), column);
+}
+
+/// Move the cursor to the line below, keeping its column where that line is
+/// long enough. Stays put on the last line.
+pub fn down(text: &str, cursor: &mut usize) {
+    clamp(text, cursor);
+    let Some(newline) = text[*cursor..].find('\n') else {
+        return;
+    };
+    let column = text[line_start(text, *cursor)..*cursor]
+        .graphemes(true)
+        .count();
+    *cursor = column_of(text, *cursor + newline + 1, column);
+}
+
+/// The line the cursor is on (from 0) and the byte offset of the cursor in
+/// that line, for drawing multi-line text.
+pub fn line_and_offset(text: &str, cursor: usize) -> (usize, usize) {
+    let mut cursor = cursor;
+    clamp(text, &mut cursor);
+    let start = line_start(text, cursor);
+    (text[..start].matches('\n').count(), cursor - start)
+}
+
+/// Byte offset of the line containing `cursor`.
+fn line_start(text: &str, cursor: usize) -> usize {
+    text[..cursor].rfind('\n').map_or(0, |newline| newline + 1)
+}
+
+/// Byte offset of the `column`th character of the line starting at `start`,
+/// or of its end when it is shorter.
+fn column_of(text: &str, start: usize, column: usize) -> usize {
+    let line = text[start..].split('\n').next().unwrap_or_default();
+    start
+        + line
+            .graphemes(true)
+            .take(column)
+            .map(str::len)
+            .sum::<usize>()
+}
+
+/// Delete the word before the cursor (Ctrl+W).
+///
+/// Words end at '/' or whitespace, so in a path this removes one component
+/// (plus its trailing separators) at a time.
+pub fn delete_word(text: &mut String, cursor: &mut usize) {
+    clamp(text, cursor);
+    let is_separator = |ch: char| ch == '/' || ch.is_whitespace();
+    let before = &text[..*cursor];
+    let trimmed = before.trim_end_matches(is_separator);
+    let start = trimmed.rfind(is_separator).map_or(0, |index| index + 1);
+    text.replace_range(start..*cursor, "");
+    *cursor = start;
+}
+
+/// Split the text at the cursor into (before, under cursor, after) for drawing.
+///
+/// The middle part is a single space when the cursor is at the end.
+pub fn split_at_cursor(text: &str, cursor: usize) -> (&str, &str, &str) {
+    let mut cursor = cursor;
+    clamp(text, &mut cursor);
+    let (before, rest) = text.split_at(cursor);
+    match rest.graphemes(true).next() {
+        Some(at) => (before, at, &rest[at.len()..]),
+        None => (before, " ", ""),
+    }
+}
+
+/// Like `split_at_cursor`, but clipped to the part of the text that fits in
+/// `width` terminal cells, scrolled so the cursor stays visible.
+///
+/// Text before the cursor is dropped from the left first; text after it is cut
+/// on the right. Widths are display widths, so a wide character counts twice.
+pub fn split_visible(text: &str, cursor: usize, width: usize) -> (&str, &str, &str) {
+    let (before, at, after) = split_at_cursor(text, cursor);
+    let room = width.saturating_sub(at.width());
+    let mut start = before.len();
+    let mut used = 0;
+    for grapheme in before.graphemes(true).rev() {
+        if used + grapheme.width() > room {
+            break;
+        }
+        used += grapheme.width();
+        start -= grapheme.len();
+    }
+    let mut end = 0;
+    for grapheme in after.graphemes(true) {
+        if used + grapheme.width() > room {
+            break;
+        }
+        used += grapheme.width();
+        end += grapheme.len();
+    }
+    (&before[start..], at, &after[..end])
+}
+
+/// Pull the cursor back inside the text and onto a character boundary.
+fn clamp(text: &str, cursor: &mut usize) {
+    *cursor = (*cursor).min(text.len());
+    while !text.is_char_boundary(*cursor) {
+        *cursor -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_happen_at_the_cursor() {
+        let mut text = "ubuntu.iso".to_string();
+        let mut cursor = 6;
+        insert(&mut text, &mut cursor, '2');
+        assert_eq!((text.as_str(), cursor), ("ubuntu2.iso", 7));
+        left(&text, &mut cursor);
+        assert!(backspace(&mut text, &mut cursor));
+        assert_eq!((text.as_str(), cursor), ("ubunt2.iso", 5));
+        delete(&mut text, &mut cursor);
+        assert_eq!(text, "ubunt.iso");
+        cursor = 0;
+        assert!(!backspace(&mut text, &mut cursor));
+    }
+
+    #[test]
+    fn delete_word_removes_one_path_component() {
+        let mut text = "/home/me/Downloads/".to_string();
+        let mut cursor = text.len();
+        delete_word(&mut text, &mut cursor);
+        assert_eq!(text, "/home/me/");
+        delete_word(&mut text, &mut cursor);
+        assert_eq!(text, "/home/");
+        assert_eq!(cursor, text.len());
+    }
+
+    #[test]
+    fn combined_characters_are_edited_as_one() {
+        // "e" followed by a combining acute accent, then a flag emoji.
+        let mut text = "ae\u{301}\u{1F1F8}\u{1F1EA}".to_string();
+        let mut cursor = text.len();
+        left(&text, &mut cursor);
+        assert_eq!(cursor, 4);
+        assert!(backspace(&mut text, &mut cursor));
+        assert_eq!((text.as_str(), cursor), ("a\u{1F1F8}\u{1F1EA}", 1));
+        delete(&mut text, &mut cursor);
+        assert_eq!(text, "a");
+        pop(&mut text);
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn split_visible_keeps_the_cursor_in_view() {
+        let text = "/isos/日本語.iso";
+        // Wide characters take two cells: the window ends before "語".
+        assert_eq!(split_visible(text, 0, 10), ("", "/", "isos/日本"));
+        assert_eq!(split_visible(text, text.len(), 8), ("語.iso", " ", ""));
+        assert_eq!(split_visible(text, 6, 5), ("os/", "日", ""));
+    }
+
+    #[test]
+    fn up_and_down_keep_the_column() {
+        let text = "#cloud-config\nhostname: box\nusers:";
+        let mut cursor = text.len();
+        up(text, &mut cursor);
+        assert!(text[cursor..].starts_with("me: box"));
+        up(text, &mut cursor);
+        assert_eq!(cursor, 6);
+        up(text, &mut cursor);
+        assert_eq!(cursor, 6);
+        down(text, &mut cursor);
+        down(text, &mut cursor);
+        assert_eq!(cursor, text.len());
+        home(text, &mut cursor);
+        assert_eq!(line_and_offset(text, cursor), (2, 0));
+        up(text, &mut cursor);
+        end(text, &mut cursor);
+        assert_eq!(line_and_offset(text, cursor), (1, 13));
+    }
+
+    #[test]
+    fn cursor_stays_on_char_boundaries() {
+        let text = "añb".to_string();
+        let mut cursor = text.len();
+        left(&text, &mut cursor);
+        left(&text, &mut cursor);
+        assert_eq!(cursor, 1);
+        right(&text, &mut cursor);
+        assert_eq!(cursor, 3);
+        assert_eq!(split_at_cursor(&text, 2), ("a", "ñ", "b"));
+        assert_eq!(split_at_cursor(&text, 99), ("añb", " ", ""));
+    }
+}
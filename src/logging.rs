@@ -0,0 +1,297 @@
+//! Diagnostic log file for bug reports.
+//!
+//! flashr-core and flashr-tui report what they do through the `log` facade:
+//! device scans, ISO detection, privilege elevation, and each step of a
+//! flash. `init` sends those records to a log file, by default
+//! `$XDG_STATE_HOME/flashr-tui/flashr-tui.log` (falling back to
+//! `~/.local/state`). The file is rotated once it reaches `MAX_LOG_BYTES`,
+//! keeping `KEEP_ROTATED` older files (`flashr-tui.log.1` is the newest).
+//!
+//! What gets written is chosen with the `FLASHR_LOG` environment variable, in
+//! the style of `RUST_LOG`: a default level and per-module levels separated
+//! by commas, e.g. `FLASHR_LOG=warn,flashr_core::flash=debug`. Without it,
+//! `info` and above are written.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Environment variable holding the filter directives.
+pub const LOG_ENV: &str = "FLASHR_LOG";
+
+/// Size at which the log file is rotated.
+pub const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Number of rotated files kept next to the current one.
+pub const KEEP_ROTATED: usize = 3;
+
+/// Default log file location, or `None` when neither `XDG_STATE_HOME` nor
+/// `HOME` is set.
+pub fn default_path() -> Option<PathBuf> {
+    crate::config::state_dir().map(|dir| dir.join("flashr-tui.log"))
+}
+
+/// Which records are written: a default level, overridden for modules whose
+/// path starts with one of the `targets`.
+///
+/// # Fields
+///
+/// * `default` - Level for records no target directive matches
+/// * `targets` - Module path prefixes and their levels, longest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub default: LevelFilter,
+    pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// Parse comma-separated directives: a bare level (`debug`) sets the
+    /// default, `module=level` sets one module. Unknown levels are skipped.
+    pub fn parse(spec: &str) -> Self {
+        let mut filter = Self {
+            default: LevelFilter::Info,
+            targets: Vec::new(),
+        };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.targets.push((target.trim().to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = directive.parse() {
+                        filter.default = level;
+                    }
+                }
+            }
+        }
+        filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        filter
+    }
+
+    /// The filter from `FLASHR_LOG`, or `info` when it is unset.
+    pub fn from_env() -> Self {
+        Self::parse(&std::env::var(LOG_ENV).unwrap_or_default())
+    }
+
+    /// Level that applies to records from `target` (a module path).
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target == prefix
+                    || target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level any directive allows, for `log::set_max_level`.
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+/// A log file that moves itself aside once it grows past `max_bytes`.
+///
+/// # Fields
+///
+/// * `path` - Current log file
+/// * `file` - Open handle to `path`, appended to
+/// * `written` - Size of `path` so far
+/// * `max_bytes` - Size that triggers a rotation
+/// * `keep` - Number of rotated files kept (`path.1` to `path.<keep>`)
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending, creating it and its directory if needed.
+    pub fn open(path: &Path, max_bytes: u64, keep: usize) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("create log directory {}", dir.display()))?;
+        }
+        let file = Self::open_file(path)?;
+        let written = file.metadata().map_or(0, |meta| meta.len());
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("open log file {}", path.display()))
+    }
+
+    /// Append `line` and a newline, rotating first if it would not fit.
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}").context("write log file")?;
+        self.written += len;
+        Ok(())
+    }
+
+    /// Shift `path.N` to `path.N+1` (dropping the oldest), move the current
+    /// file to `path.1`, and start a new one.
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            PathBuf::from(name)
+        };
+        if self.keep == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            let _ = std::fs::remove_file(rotated(self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = std::fs::rename(rotated(n), rotated(n + 1));
+            }
+            std::fs::rename(&self.path, rotated(1)).context("rotate log file")?;
+        }
+        self.file = Self::open_file(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// `log` backend writing filtered records to a `RotatingFile`.
+#[derive(Debug)]
+pub struct FileLogger {
+    filter: Filter,
+    file: Mutex<RotatingFile>,
+}
+
+impl FileLogger {
+    pub fn new(filter: Filter, file: RotatingFile) -> Self {
+        Self {
+            filter,
+            file: Mutex::new(file),
+        }
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        let line = format!(
+            "{}.{:03} {:<5} [{}] {}: {}",
+            stamp.as_secs(),
+            stamp.subsec_millis(),
+            record.level(),
+            thread.name().unwrap_or("?"),
+            record.target(),
+            record.args()
+        );
+        if let Ok(mut file) = self.file.lock() {
+            // A full disk must not take the flash down with it.
+            let _ = file.write_line(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Send log records to `path` (or `default_path()`), filtered by `FLASHR_LOG`.
+///
+/// # Returns
+///
+/// The log file in use.
+///
+/// # Errors
+///
+/// Returns an error if there is no default location, the file cannot be
+/// opened, or a logger was already installed.
+pub fn init(path: Option<&Path>) -> Result<PathBuf> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            default_path().ok_or_else(|| anyhow::anyhow!("no log location (HOME is not set)"))?
+        }
+    };
+    let filter = Filter::from_env();
+    let max_level = filter.max_level();
+    let file = RotatingFile::open(&path, MAX_LOG_BYTES, KEEP_ROTATED)?;
+    log::set_boxed_logger(Box::new(FileLogger::new(filter, file)))
+        .map_err(|err| anyhow::anyhow!("install logger: {err}"))?;
+    log::set_max_level(max_level);
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_picks_the_longest_matching_module() {
+        let filter = Filter::parse("warn, flashr_core=info,flashr_core::flash=trace,bogus=loud");
+        assert_eq!(filter.level_for("flashr_core::flash"), LevelFilter::Trace);
+        assert_eq!(
+            filter.level_for("flashr_core::flash::x"),
+            LevelFilter::Trace
+        );
+        assert_eq!(filter.level_for("flashr_core::flasher"), LevelFilter::Info);
+        assert_eq!(filter.level_for("flashr_core::device"), LevelFilter::Info);
+        assert_eq!(filter.level_for("ratatui"), LevelFilter::Warn);
+        assert_eq!(filter.targets.len(), 2);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert_eq!(Filter::parse("").default, LevelFilter::Info);
+    }
+
+    #[test]
+    fn rotating_file_keeps_the_newest_files() {
+        let dir = std::env::temp_dir().join(format!("flashr-log-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("test.log");
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["one", "two", "three", "four"] {
+            file.write_line(&format!("{line}12345")).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("test.log"), "four12345\n");
+        assert_eq!(read("test.log.1"), "three12345\n");
+        assert_eq!(read("test.log.2"), "two12345\n");
+        assert!(!dir.join("test.log.3").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -6,112 +6,1253 @@
 //! - Main event loop
 
 use std::io::{self, Stdout};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use crossterm::event::{self, Event};
+use clap::{Parser, Subcommand};
+use crossterm::event::{self, DisableBracketedPaste, EnableBracketedPaste, Event};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::ExecutableCommand;
 use ratatui::backend::CrosstermBackend;
-use ratatui::Terminal;
+use ratatui::layout::Position;
+use ratatui::widgets::{Paragraph, Widget};
+use ratatui::{Terminal, TerminalOptions, Viewport};
 
-use flashr_tui::{App, AppExit, Step};
+use flashr_tui::audit;
+use flashr_tui::backend::BackendKind;
+use flashr_tui::checksum::{Algorithm, Checksum};
+use flashr_tui::config::{resolve_path, Overrides};
+use flashr_tui::daemon;
+use flashr_tui::device::Disk;
+use flashr_tui::download;
+use flashr_tui::events::EventStream;
+use flashr_tui::flash::{CancelFlag, FlashOptions};
+use flashr_tui::format::Filesystem;
+use flashr_tui::history;
+use flashr_tui::jobs::JobFile;
+use flashr_tui::prefs::Preferences;
+use flashr_tui::provider::{DeviceProvider, SystemDevices};
+use flashr_tui::report::FlashReport;
+use flashr_tui::signals;
+use flashr_tui::task::Wake;
+use flashr_tui::{App, AppExit, Config, FlashError, FlashFailure, FlashResult, Step, Task};
 
 /// Command-line arguments.
 #[derive(Parser, Debug)]
 #[command(version, about = "Flash images to USB drives (TUI MVP)")]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Pre-fill image path, skip to device selection
     #[arg(long)]
     image: Option<std::path::PathBuf>,
-    /// Pre-select device (e.g. /dev/sdb)
+    /// Pre-select device (e.g. /dev/sdb), or "auto" for the only removable
+    /// device; repeat to flash several devices
     #[arg(long)]
-    device: Option<String>,
+    device: Vec<String>,
     /// Actually execute dd (default is dry-run)
-    #[arg(long)]
+    #[arg(long, global = true)]
     execute: bool,
+    /// Config file (default: ~/.config/flashr-tui/config.json)
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+    /// Draw below the shell prompt instead of on the alternate screen, leaving
+    /// the log and the final screen in the scrollback
+    #[arg(long)]
+    inline: bool,
+    /// Refuse to write to devices larger than this many GB (the smaller of
+    /// this and the config's size_limit_gb applies)
+    #[arg(long, global = true, value_name = "GB")]
+    size_limit: Option<u64>,
+    /// Fail the flash when the read-back verification cannot run
+    #[arg(long)]
+    verify: bool,
+    /// With --no-tui, continue a write that a crash or power loss
+    /// interrupted instead of starting over (the TUI offers it on the
+    /// Confirm screen)
+    #[arg(long)]
+    resume: bool,
+    /// With --no-tui, copy the image into RAM (or the config's stage_dir)
+    /// before writing, so a slow network mount does not stall the write (the
+    /// TUI offers it on the Confirm screen)
+    #[arg(long)]
+    stage: bool,
+    /// Refuse to flash unless the image matches this checksum: sha256:<hex>,
+    /// sha512:<hex>, or a checksum file such as SHA256SUMS
+    #[arg(long, value_name = "SPEC")]
+    checksum: Option<String>,
+    /// Volume label to set after flashing (default: derived from the image name)
+    #[arg(long)]
+    label: Option<String>,
+    /// Script to run after a successful flash, with FLASHR_IMAGE,
+    /// FLASHR_DEVICE, FLASHR_LABEL, and FLASHR_SHA256 set (overrides the
+    /// config's post_hook)
+    #[arg(long, global = true, value_name = "SCRIPT")]
+    post_hook: Option<String>,
+    /// Write a report of each finished flash (image, hash, device serials,
+    /// verification, duration) to this file: JSON for ".json", Markdown for
+    /// ".md", plain text otherwise
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+    /// Start flashing --image to --device without asking for confirmation
+    #[arg(long, requires_all = ["image", "device", "execute"])]
+    yes: bool,
+    /// Exit when the flash finishes instead of waiting on the Result screen,
+    /// with a non-zero exit code if it failed
+    #[arg(long, conflicts_with = "no_tui")]
+    auto_quit: bool,
+    /// Hash algorithm for verifying flashes: sha256, sha512, or blake3 (needs
+    /// b3sum; overrides the config's hash_algorithm)
+    #[arg(long, global = true, value_name = "ALGORITHM", value_parser = parse_hash)]
+    hash: Option<Algorithm>,
+    /// How to write the image: dd (default) or native (in-process, needs
+    /// root; overrides the config's backend)
+    #[arg(long, global = true, value_name = "BACKEND", value_parser = parse_backend)]
+    backend: Option<BackendKind>,
+    /// Print progress as newline-delimited JSON events (with --no-tui and run)
+    #[arg(long, global = true)]
+    progress_json: bool,
+    /// Print only the final result line, and errors to stderr (with --no-tui
+    /// and run)
+    #[arg(long, global = true, conflicts_with = "progress_json")]
+    quiet: bool,
+    /// Flash --image to --device without the TUI, printing progress lines
+    #[arg(long, requires_all = ["image", "device"], conflicts_with = "inline")]
+    no_tui: bool,
+    /// Diagnostic log file (default: ~/.local/state/flashr-tui/flashr-tui.log;
+    /// filter with FLASHR_LOG, e.g. FLASHR_LOG=debug)
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+}
+
+/// Parse `--hash`.
+fn parse_hash(name: &str) -> Result<Algorithm, String> {
+    Algorithm::parse(name).ok_or_else(|| format!("unknown hash algorithm '{name}'"))
+}
+
+/// Parse `--backend`.
+fn parse_backend(name: &str) -> Result<BackendKind, String> {
+    BackendKind::parse(name).ok_or_else(|| format!("unknown backend '{name}'"))
+}
+
+/// How headless runs (`--no-tui` and `run`) report their progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// Progress lines as they come, then a summary
+    Text,
+    /// Newline-delimited JSON events (`--progress-json`)
+    Json,
+    /// Only the final result line; errors go to stderr (`--quiet`)
+    Quiet,
+}
+
+impl Output {
+    fn from_cli(cli: &Cli) -> Self {
+        if cli.progress_json {
+            Self::Json
+        } else if cli.quiet {
+            Self::Quiet
+        } else {
+            Self::Text
+        }
+    }
+}
+
+/// Non-interactive subcommands.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Wipe signatures and zero the first and last MiB of a device
+    Wipe {
+        /// Device to wipe (e.g. /dev/sdb)
+        device: String,
+    },
+    /// Print the detected devices and exit
+    List {
+        /// Include internal (non-removable) disks
+        #[arg(long)]
+        all: bool,
+        /// Print a JSON array instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Download an image into the cache, verify it, and optionally flash it
+    Download {
+        /// URL of the image, or a magnet link or .torrent URL
+        url: String,
+        /// Checksum the download must match: sha256:<hex>, sha512:<hex>, or a
+        /// checksum file such as SHA256SUMS
+        #[arg(long, value_name = "SPEC")]
+        checksum: Option<String>,
+        /// Download in the TUI, which opens the Device step with the image once
+        /// it is verified (with --no-tui, download first and then flash)
+        #[arg(long)]
+        flash: bool,
+        /// Write the image to --device as it downloads instead, without the
+        /// TUI and without keeping a copy (not for torrents)
+        #[arg(long, conflicts_with = "flash")]
+        stream: bool,
+    },
+    /// Flash the image-to-device jobs listed in a JSON job file
+    Run {
+        /// Job file (see the README for the format)
+        file: PathBuf,
+    },
+    /// Serve a JSON-RPC API on a Unix socket for frontends to drive flashing
+    Daemon {
+        /// Socket to listen on (default: $XDG_RUNTIME_DIR/flashr-tui.sock)
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Print past flashes, oldest first
+    History {
+        /// Only flashes whose image, device, serial, or label contains this
+        query: Option<String>,
+        /// Print at most this many of the most recent flashes
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+        /// Print the entries as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+    /// Format a device with a single FAT32, exFAT, or ext4 partition
+    Format {
+        /// Device to format (e.g. /dev/sdb)
+        device: String,
+        /// Filesystem: fat32, exfat, or ext4
+        #[arg(long, default_value = "fat32")]
+        fs: String,
+        /// Volume label
+        #[arg(long, default_value = "")]
+        label: String,
+    },
 }
 
 /// Main entry point.
+///
+/// SIGINT, SIGTERM, and SIGHUP end the run through the normal cleanup paths
+/// (see `signals`); the process then exits with 128 plus the signal number.
 fn main() -> Result<()> {
+    signals::install()?;
+    let result = run();
+    if let Some(signal) = signals::received() {
+        if let Err(err) = &result {
+            eprintln!("Error: {err:?}");
+        }
+        eprintln!("Interrupted by {}", signals::name(signal));
+        std::process::exit(signals::exit_code(signal));
+    }
+    result
+}
+
+/// Parse the command line and do what it asks.
+fn run() -> Result<()> {
     let cli = Cli::parse();
-    let devices = flashr_tui::device::list(false).unwrap_or_else(|err| {
+    if let Err(err) = flashr_tui::logging::init(cli.log_file.as_deref()) {
+        eprintln!("Warning: logging disabled: {err:#}");
+    }
+    log::info!("flashr-tui {} starting", env!("CARGO_PKG_VERSION"));
+    let output = Output::from_cli(&cli);
+    let mut config = match &cli.config {
+        Some(path) => Config::load_from(path),
+        None => Config::load(),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Warning: failed to load config: {err:#}");
+        // Keep the path, so the TUI picks the file up once it is fixed.
+        Config {
+            path: cli.config.clone().or_else(Config::default_path),
+            ..Config::default()
+        }
+    });
+    let overrides = Overrides {
+        size_limit_gb: cli.size_limit,
+        post_hook: cli.post_hook.clone(),
+        hash_algorithm: cli.hash,
+        backend: cli.backend,
+    };
+    overrides.apply(&mut config);
+    // Shells leave "~" alone in "--image=~/..." and in quotes.
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut image = cli
+        .image
+        .as_deref()
+        .map(|path| resolve_path(&path.to_string_lossy(), &cwd));
+    let mut pending_download = None;
+    let mut pending_stream = None;
+    match &cli.command {
+        Some(Command::Wipe { device }) => return run_wipe(device, cli.execute, &config),
+        Some(Command::Format { device, fs, label }) => {
+            return run_format(device, fs, label, cli.execute, &config)
+        }
+        Some(Command::List { all, json }) => return run_list(*all, *json, &config),
+        Some(Command::History { query, limit, json }) => {
+            return run_history(query.as_deref(), *limit, *json)
+        }
+        Some(Command::Daemon { socket }) => {
+            let socket = socket.clone().unwrap_or_else(daemon::default_socket);
+            return daemon::serve(&socket, config, cli.execute);
+        }
+        Some(Command::Run { file }) => {
+            if cli.report.is_some() {
+                anyhow::bail!("--report is not supported with run");
+            }
+            return run_jobs(file, cli.execute, &config, output);
+        }
+        // Streaming needs the devices resolved first, like --no-tui.
+        Some(Command::Download {
+            url,
+            checksum,
+            stream: true,
+            ..
+        }) => {
+            download::file_name_for(url)?;
+            pending_stream = Some((url.clone(), checksum.clone()));
+        }
+        // The TUI downloads on its Download step, where it can be paused and
+        // cancelled; --yes would have nothing to confirm until it is done.
+        Some(Command::Download {
+            url,
+            checksum,
+            flash: true,
+            ..
+        }) if !cli.no_tui && !cli.yes => {
+            download::file_name_for(url)?;
+            let checksum = checksum.as_deref().map(Checksum::parse).transpose()?;
+            pending_download = Some((url.clone(), checksum));
+        }
+        Some(Command::Download {
+            url,
+            checksum,
+            flash,
+            ..
+        }) => {
+            let downloaded = run_download(url, checksum.as_deref(), &config)?;
+            if !flash {
+                return Ok(());
+            }
+            image = Some(downloaded);
+        }
+        None => {}
+    }
+    config.ensure_flash_prerequisites()?;
+    let checksum = cli.checksum.as_deref().map(Checksum::parse).transpose()?;
+    let mut targets = cli.device.clone();
+    if targets.iter().any(|d| d == flashr_tui::device::AUTO) {
+        if targets.len() > 1 {
+            anyhow::bail!("--device auto cannot be combined with other devices");
+        }
+        let removable = config.filter_devices(flashr_tui::device::list(false)?);
+        let disk = flashr_tui::device::single_removable(&removable)?;
+        if output != Output::Quiet {
+            eprintln!(
+                "--device auto: using {} ({} {}, {})",
+                disk.device_path(),
+                disk.vendor,
+                disk.model,
+                disk.size
+            );
+        }
+        targets = vec![disk.stable_path()];
+    }
+    if let Some((url, spec)) = pending_stream {
+        // The download's own --checksum wins over the global one.
+        let checksum = match spec {
+            Some(spec) => Some(Checksum::parse(&spec)?),
+            None => checksum,
+        };
+        let options = headless_options(&cli, &config, checksum);
+        return run_stream(
+            &url,
+            &targets,
+            cli.execute,
+            options,
+            &config,
+            output,
+            cli.report.as_deref(),
+        );
+    }
+    if cli.no_tui {
+        if let Some(image) = &image {
+            let options = headless_options(&cli, &config, checksum);
+            return run_headless(
+                image,
+                &targets,
+                cli.execute,
+                options,
+                &config,
+                output,
+                cli.report.as_deref(),
+            );
+        }
+    }
+    if cli.progress_json {
+        anyhow::bail!("--progress-json needs --no-tui or the run subcommand");
+    }
+    if cli.resume {
+        anyhow::bail!("--resume needs --no-tui; the TUI offers to resume on the Confirm screen");
+    }
+    if cli.stage {
+        anyhow::bail!("--stage needs --no-tui; the TUI offers to stage on the Confirm screen");
+    }
+    if cli.quiet {
+        anyhow::bail!("--quiet needs --no-tui or the run subcommand");
+    }
+    let devices = SystemDevices.list(false).unwrap_or_else(|err| {
         eprintln!("Warning: failed to list devices: {err}");
         Vec::new()
     });
 
-    let mut app = App::new(cli.image, cli.device, cli.execute, devices);
-    run_tui(&mut app)?;
+    if cli.yes {
+        // The TUI would quietly flash only the devices it found.
+        let listed = config.filter_devices(devices.clone());
+        if let Some(missing) = targets
+            .iter()
+            .find(|target| !listed.iter().any(|d| d.matches_path(target)))
+        {
+            anyhow::bail!("--yes: device not found (or hidden by the config): {missing}");
+        }
+    }
+    let mut app = App::new(
+        image,
+        &targets,
+        cli.execute,
+        devices,
+        config,
+        Box::new(SystemDevices),
+    );
+    app.config_overrides = overrides;
+    let prefs = Preferences::load().unwrap_or_else(|err| {
+        eprintln!("Warning: failed to load preferences: {err:#}");
+        Preferences::default()
+    });
+    app.apply_preferences(&prefs);
+    app.note_interrupted_writes();
+    app.require_verification |= cli.verify;
+    app.checksum = checksum;
+    app.volume_label = cli.label;
+    app.auto_quit = cli.auto_quit;
+    app.report_path = cli.report;
+    app.catalog = flashr_tui::catalog::Catalog::load(app.config.catalog_key.as_deref());
+    let channel = LoopChannel::new();
+    app.wake = channel.wake();
+    app.start_catalog_update();
+    app.device_watcher = Some(flashr_tui::device::DeviceWatcher::start_waking(
+        app.wake.clone(),
+    ));
+    if let Some((url, checksum)) = pending_download {
+        app.download_url(&url, checksum);
+    }
+    if cli.yes {
+        flashr_tui::ui::confirm_unattended(&mut app);
+    }
+    run_tui(&mut app, cli.inline, channel)?;
+    if let Err(err) = app.preferences().save() {
+        eprintln!("Warning: failed to save preferences: {err:#}");
+    }
 
+    if app.auto_quit {
+        if let Step::Result(outcome) = &app.step {
+            if !outcome.result.ok {
+                anyhow::bail!("{}", outcome.result.message);
+            }
+        }
+    }
     Ok(())
 }
 
+/// Download an image into the cache and verify it, printing progress to stdout.
+///
+/// Returns the path of the downloaded image.
+fn run_download(url: &str, checksum: Option<&str>, config: &Config) -> Result<std::path::PathBuf> {
+    let checksum = checksum.map(Checksum::parse).transpose()?;
+    let proxy = config.download_proxy();
+    let limit = config.cache_limit();
+    let dir = download::cache_dir()
+        .ok_or_else(|| anyhow::anyhow!("no cache location (HOME is not set)"))?;
+    let url = url.to_string();
+    let task = Task::spawn("download", move |progress_tx| {
+        let control = download::DownloadControl::default();
+        let image = download::download(
+            &url,
+            &dir,
+            checksum.as_ref(),
+            &proxy,
+            progress_tx.clone(),
+            &control,
+        )?;
+        flashr_tui::cache::enforce_limit(&dir, limit, &image, &progress_tx);
+        anyhow::Ok(image)
+    });
+    // Byte counts redraw a single line; other messages get lines of their own.
+    let mut counting = false;
+    let image = task.wait(|line| match download::parse_download_progress(&line) {
+        Some((done, total)) => {
+            let text = match total {
+                Some(total) => flashr_tui::format_progress(done, total, false),
+                None => flashr_tui::format_size(done),
+            };
+            print!("\r{text:<40}");
+            io::Write::flush(&mut io::stdout()).ok();
+            counting = true;
+        }
+        None => {
+            if std::mem::take(&mut counting) {
+                println!();
+            }
+            println!("{line}");
+        }
+    });
+    if counting {
+        println!();
+    }
+    let image = image??;
+    println!("Saved {}", image.display());
+    Ok(image)
+}
+
+/// Flash options for a run without the TUI, from the command line and `config`.
+///
+/// Asking for the device on the command line stands in for the wipe
+/// confirmation; mounted and busy devices are still refused.
+fn headless_options(cli: &Cli, config: &Config, checksum: Option<Checksum>) -> FlashOptions {
+    FlashOptions {
+        confirmed_wipe: true,
+        require_verification: cli.verify,
+        checksum,
+        label: cli.label.clone(),
+        post_hook: config.post_hook_path(),
+        pre_hook: config.pre_hook_path(),
+        label_hook: config.label_hook_path(),
+        hash: config.hash_algorithm,
+        backend: config.backend,
+        block_size: None,
+        journal_dir: flashr_tui::config::journal_dir(),
+        resume: cli.resume,
+        stage_dir: cli.stage.then(|| config.stage_dir_path()).flatten(),
+        cancel: CancelFlag::default(),
+    }
+}
+
+/// Find `device` for a wipe or format without the TUI and check it may be written.
+///
+/// Like a flash target it must be trusted and within the size limit. Internal
+/// disks are refused, since the typed confirmation they take needs the TUI.
+fn writable_target(device: &str, config: &Config) -> Result<Disk> {
+    let devices = config.filter_devices(flashr_tui::device::list(true)?);
+    let disk = devices
+        .iter()
+        .find(|d| d.matches_path(device))
+        .ok_or_else(|| anyhow::anyhow!("device not found (or hidden by the config): {device}"))?;
+    if !disk.removable {
+        anyhow::bail!(
+            "{} is an internal (non-removable) disk; use the TUI, which asks to type ERASE first",
+            disk.device_path()
+        );
+    }
+    config.ensure_writable(disk)?;
+    Ok(disk.clone())
+}
+
+/// Run a quick wipe without the TUI, printing progress lines to stdout.
+fn run_wipe(device: &str, execute: bool, config: &Config) -> Result<()> {
+    if !execute {
+        println!("Dry run: would wipe {device} (pass --execute to wipe)");
+        return Ok(());
+    }
+    let device = writable_target(device, config)?.stable_path();
+    let task = Task::spawn("wipe", move |progress_tx| {
+        flashr_tui::wipe::quick_wipe(&device, progress_tx)
+    });
+    task.wait(|line| println!("{line}"))?
+}
+
+/// Format a device without the TUI, printing progress lines to stdout.
+fn run_format(device: &str, fs: &str, label: &str, execute: bool, config: &Config) -> Result<()> {
+    let fs = Filesystem::parse(fs)
+        .ok_or_else(|| anyhow::anyhow!("unsupported filesystem '{fs}' (use fat32, exfat, or ext4)"))?;
+    if !execute {
+        println!(
+            "Dry run: would format {device} as {} (pass --execute to format)",
+            fs.name()
+        );
+        return Ok(());
+    }
+    let device = writable_target(device, config)?.stable_path();
+    let label = label.to_string();
+    let task = Task::spawn("format", move |progress_tx| {
+        flashr_tui::format::format_device(&device, fs, &label, progress_tx)
+    });
+    task.wait(|line| println!("{line}"))?
+}
+
+/// Print the devices the TUI would offer (minus hidden ones) and exit.
+fn run_list(all: bool, json: bool, config: &Config) -> Result<()> {
+    let devices = config.filter_devices(flashr_tui::device::list(all)?);
+    if json {
+        let list: Vec<_> = devices.iter().map(|d| d.to_json()).collect();
+        println!("{}", serde_json::to_string_pretty(&list)?);
+        return Ok(());
+    }
+    println!(
+        "{:<12} {:>8}  {:<9}  {:<24}  SERIAL",
+        "PATH", "SIZE", "TYPE", "MODEL"
+    );
+    for disk in &devices {
+        let kind = if disk.removable { "removable" } else { "internal" };
+        println!(
+            "{:<12} {:>8}  {:<9}  {:<24}  {}",
+            disk.device_path(),
+            disk.size,
+            kind,
+            format!("{} {}", disk.vendor, disk.model).trim(),
+            disk.serial.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+/// Print the most recent `limit` flashes in the history that match `query`.
+fn run_history(query: Option<&str>, limit: usize, json: bool) -> Result<()> {
+    let mut entries = history::load_default()?;
+    if let Some(query) = query {
+        entries.retain(|entry| entry.matches(query));
+    }
+    let entries = &entries[entries.len().saturating_sub(limit)..];
+    if json {
+        println!("{}", serde_json::to_string_pretty(entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("No flashes recorded.");
+    }
+    for entry in entries {
+        println!("{}", entry.summary());
+        if !entry.ok {
+            println!("    {}", entry.message);
+        }
+    }
+    Ok(())
+}
+
+/// Flash an image to one or more devices without the TUI, printing progress
+/// to stdout as `output` asks.
+///
+/// Applies the same device rules as the TUI's Confirm step: hidden and
+/// untrusted devices are refused. Internal disks are refused outright. Every
+/// device is checked before anything is written; with several devices they
+/// are flashed one after another, numbered like jobs, and the first failure
+/// stops the rest. The run is added to the history, and with `report_path` a
+/// report of it is written there, whether it succeeded or not.
+fn run_headless(
+    image: &Path,
+    targets: &[String],
+    execute: bool,
+    options: FlashOptions,
+    config: &Config,
+    output: Output,
+    report_path: Option<&Path>,
+) -> Result<()> {
+    if !image.is_file() {
+        anyhow::bail!("image not found: {}", image.display());
+    }
+    let devices = config.filter_devices(flashr_tui::device::list(true)?);
+    let mut disks: Vec<&Disk> = Vec::new();
+    for target in targets {
+        let disk = flashr_tui::device::find_unattended_target(&devices, target)?;
+        if disks.iter().any(|d| d.stable_path() == disk.stable_path()) {
+            anyhow::bail!("{} is given more than once", disk.device_path());
+        }
+        disks.push(disk);
+    }
+    let numbered = disks.len() > 1;
+    let number = |index: usize| numbered.then_some(index + 1);
+    if !execute {
+        for (index, disk) in disks.iter().enumerate() {
+            if output == Output::Json {
+                println!("{}", dry_run_event(image, disk, number(index)));
+            } else {
+                println!(
+                    "Dry run: would flash {} to {}",
+                    image.display(),
+                    disk.device_path()
+                );
+            }
+        }
+        if output == Output::Text {
+            println!("Pass --execute to flash.");
+        }
+        return Ok(());
+    }
+    for disk in &disks {
+        config.ensure_writable(disk)?;
+    }
+
+    let targets: Vec<Disk> = disks.iter().map(|&disk| disk.clone()).collect();
+    let mut report = FlashReport::new(image.to_path_buf(), &targets);
+    let started = Instant::now();
+    let mut result = Ok(());
+    for (index, disk) in disks.iter().enumerate() {
+        result = flash_and_print(
+            image,
+            disk.stable_path(),
+            options.clone(),
+            number(index),
+            output,
+            Some(&mut report),
+        )
+        .map_err(FlashFailure::from);
+        if result.is_err() {
+            break;
+        }
+    }
+    let total = std::fs::metadata(image).map_or(0, |m| m.len());
+    report.outcome = Some(FlashResult::finished(
+        &result,
+        Some(started.elapsed()),
+        total * disks.len() as u64,
+    ));
+    if let Err(err) = history::record(&report) {
+        eprintln!("Warning: flash not added to the history: {err:#}");
+    }
+    let saved = match report_path {
+        Some(path) => report.save(path),
+        None => Ok(()),
+    };
+    // A failed flash matters more than a report that could not be written.
+    result.map_err(anyhow::Error::from).and(saved)?;
+    match output {
+        Output::Text => println!("Flash completed successfully."),
+        Output::Json => {}
+        Output::Quiet => {
+            let devices: Vec<_> = disks.iter().map(|disk| disk.device_path()).collect();
+            println!("Flashed {} to {}", image.display(), devices.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Write the image at `url` to the one device in `targets` as it downloads,
+/// without the TUI and without a copy in the cache, printing progress like
+/// `run_headless`.
+///
+/// The device is checked as for `run_headless`, and an image the server
+/// announces as larger than the device is refused before anything is
+/// written. The run is added to the history under its URL.
+fn run_stream(
+    url: &str,
+    targets: &[String],
+    execute: bool,
+    options: FlashOptions,
+    config: &Config,
+    output: Output,
+    report_path: Option<&Path>,
+) -> Result<()> {
+    let [target] = targets else {
+        anyhow::bail!("--stream writes to exactly one --device");
+    };
+    let name = download::file_name_for(url)?;
+    let devices = config.filter_devices(flashr_tui::device::list(true)?);
+    let disk = flashr_tui::device::find_unattended_target(&devices, target)?;
+    let image = Path::new(url);
+    if !execute {
+        if output == Output::Json {
+            println!("{}", dry_run_event(image, disk, None));
+        } else {
+            println!("Dry run: would stream {url} to {}", disk.device_path());
+        }
+        if output == Output::Text {
+            println!("Pass --execute to flash.");
+        }
+        return Ok(());
+    }
+    config.ensure_writable(disk)?;
+
+    let mut stream = download::HttpStream::open(url, &config.download_proxy())?;
+    let total = stream.size().unwrap_or(0);
+    if let Some(capacity) = flashr_tui::device::size_bytes(&disk.name) {
+        if total > capacity {
+            anyhow::bail!(
+                "{name} ({}) does not fit on {} ({})",
+                flashr_tui::format_size(total),
+                disk.device_path(),
+                flashr_tui::format_size(capacity)
+            );
+        }
+    }
+    let mut report = FlashReport::new(image.to_path_buf(), std::slice::from_ref(disk));
+    let started = Instant::now();
+    let result = print_flash(
+        image,
+        disk.stable_path(),
+        total,
+        None,
+        output,
+        Some(&mut report),
+        move |target, progress_tx| {
+            flashr_tui::stream::flash_stream_with_progress(
+                &mut stream,
+                &name,
+                &target,
+                progress_tx,
+                &options,
+            )
+        },
+    )
+    .map_err(FlashFailure::from);
+    report.outcome = Some(FlashResult::finished(
+        &result,
+        Some(started.elapsed()),
+        total,
+    ));
+    if let Err(err) = history::record(&report) {
+        eprintln!("Warning: flash not added to the history: {err:#}");
+    }
+    let saved = match report_path {
+        Some(path) => report.save(path),
+        None => Ok(()),
+    };
+    result.map_err(anyhow::Error::from).and(saved)?;
+    match output {
+        Output::Text => println!("Flash completed successfully."),
+        Output::Json => {}
+        Output::Quiet => println!("Flashed {url} to {}", disk.device_path()),
+    }
+    Ok(())
+}
+
+/// Run the jobs in a job file without the TUI, printing progress lines
+/// prefixed with the job number (or as `output` asks).
+///
+/// Every job is checked (image present, device found, removable, trusted,
+/// within the size limit) before anything is written. A failing job does not
+/// stop the others; the run fails if any job did.
+fn run_jobs(path: &Path, execute: bool, config: &Config, output: Output) -> Result<()> {
+    let file = JobFile::load(path)?;
+    config.ensure_flash_prerequisites()?;
+    let devices = config.filter_devices(flashr_tui::device::list(true)?);
+
+    let mut prepared: Vec<(PathBuf, &Disk, FlashOptions)> = Vec::new();
+    for (index, job) in file.jobs.iter().enumerate() {
+        let number = index + 1;
+        let image = job.image_path();
+        if !image.is_file() {
+            anyhow::bail!("job {number}: image not found: {}", image.display());
+        }
+        let disk = flashr_tui::device::find_unattended_target(&devices, &job.device)
+            .with_context(|| format!("job {number}"))?;
+        if let Some(other) = prepared
+            .iter()
+            .position(|(_, d, _)| d.stable_path() == disk.stable_path())
+        {
+            anyhow::bail!(
+                "jobs {} and {number} both write to {}",
+                other + 1,
+                disk.device_path()
+            );
+        }
+        if execute {
+            config
+                .ensure_writable(disk)
+                .with_context(|| format!("job {number}"))?;
+        }
+        let options = job
+            .options(config)
+            .with_context(|| format!("job {number}"))?;
+        prepared.push((image, disk, options));
+    }
+
+    if !execute {
+        for (index, (image, disk, _)) in prepared.iter().enumerate() {
+            if output == Output::Json {
+                println!("{}", dry_run_event(image, disk, Some(index + 1)));
+            } else {
+                println!(
+                    "Dry run: job {} would flash {} to {}",
+                    index + 1,
+                    image.display(),
+                    disk.device_path()
+                );
+            }
+        }
+        if output == Output::Text {
+            println!("Pass --execute to run the jobs.");
+        }
+        return Ok(());
+    }
+
+    let run = |index: usize, (image, disk, options): (PathBuf, &Disk, FlashOptions)| {
+        let mut report = FlashReport::new(image.clone(), std::slice::from_ref(disk));
+        let started = Instant::now();
+        let result = flash_and_print(
+            &image,
+            disk.stable_path(),
+            options,
+            Some(index + 1),
+            output,
+            Some(&mut report),
+        )
+        .map_err(FlashFailure::from);
+        let total = std::fs::metadata(&image).map_or(0, |m| m.len());
+        report.outcome = Some(FlashResult::finished(
+            &result,
+            Some(started.elapsed()),
+            total,
+        ));
+        if let Err(err) = history::record(&report) {
+            eprintln!(
+                "Warning: job {} not added to the history: {err:#}",
+                index + 1
+            );
+        }
+        result
+    };
+    let results: Vec<Result<(), FlashFailure>> = if file.parallel {
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = prepared
+                .into_iter()
+                .enumerate()
+                .map(|(index, job)| scope.spawn(move || run(index, job)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker.join().unwrap_or_else(|_| {
+                        Err(FlashError::Other(anyhow::anyhow!("job thread panicked")).into())
+                    })
+                })
+                .collect()
+        })
+    } else {
+        prepared
+            .into_iter()
+            .enumerate()
+            .map(|(index, job)| run(index, job))
+            .collect()
+    };
+
+    // With JSON events each job's "done" event already reports its outcome.
+    let total = results.len();
+    let mut failed = 0;
+    for (index, result) in results.iter().enumerate() {
+        match (result, output) {
+            (Ok(()), Output::Text) => println!("Job {}: done", index + 1),
+            (Err(err), Output::Text) => println!("Job {}: failed: {err:#}", index + 1),
+            (Err(err), Output::Quiet) => eprintln!("Job {}: failed: {err:#}", index + 1),
+            _ => {}
+        }
+        failed += usize::from(result.is_err());
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} of {total} jobs failed");
+    }
+    if output != Output::Json {
+        println!("All {total} jobs completed successfully.");
+    }
+    Ok(())
+}
+
+/// The `--progress-json` event for a dry run of flashing `image` to `disk`.
+fn dry_run_event(image: &Path, disk: &Disk, job: Option<usize>) -> serde_json::Value {
+    let mut event = serde_json::json!({
+        "event": "dry_run",
+        "image": image.display().to_string(),
+        "device": disk.device_path(),
+    });
+    if let Some(job) = job {
+        event["job"] = job.into();
+    }
+    event
+}
+
+/// Flash `image` to `target` on a worker thread, printing its progress lines
+/// (prefixed with the job number, if any), JSON events, or nothing, and
+/// record the flash in the audit log. The progress also fills in `report`.
+fn flash_and_print(
+    image: &Path,
+    target: String,
+    options: FlashOptions,
+    job: Option<usize>,
+    output: Output,
+    report: Option<&mut FlashReport>,
+) -> Result<(), FlashError> {
+    let total = std::fs::metadata(image).map(|m| m.len()).unwrap_or(0);
+    let source = image.to_path_buf();
+    print_flash(
+        image,
+        target,
+        total,
+        job,
+        output,
+        report,
+        move |target, progress_tx| {
+            flashr_tui::flash::flash_image_with_progress(&source, &target, progress_tx, &options)
+        },
+    )
+}
+
+/// Run `flash` for `image` (`total` bytes, 0 when unknown) and `target` on
+/// a worker thread, printing and recording its progress like
+/// `flash_and_print`.
+fn print_flash<F>(
+    image: &Path,
+    target: String,
+    total: u64,
+    job: Option<usize>,
+    output: Output,
+    mut report: Option<&mut FlashReport>,
+    flash: F,
+) -> Result<(), FlashError>
+where
+    F: FnOnce(String, mpsc::Sender<String>) -> Result<(), FlashError> + Send + 'static,
+{
+    let prefix = job.map(|n| format!("[job {n}] ")).unwrap_or_default();
+    let mut events = EventStream::new(total, job);
+    match output {
+        Output::Text => println!("{prefix}Flashing {} -> {target}", image.display()),
+        Output::Json => println!("{}", events.started(image, &target)),
+        Output::Quiet => {}
+    }
+    let devices = [target.clone()];
+    audit::flash_started(image, &devices);
+    let task = Task::spawn("flash", move |progress_tx| flash(target, progress_tx));
+    let result = task
+        .wait(|line| {
+            if let Some(report) = report.as_deref_mut() {
+                report.apply(&line);
+            }
+            match output {
+                Output::Text => println!("{prefix}{line}"),
+                Output::Json => println!("{}", events.line(&line)),
+                Output::Quiet => {}
+            }
+        })
+        .unwrap_or_else(|panicked| Err(FlashError::Other(panicked.into())));
+    audit::flash_finished(image, &devices, &result);
+    if output == Output::Json {
+        println!("{}", events.finished(&result));
+    }
+    result
+}
+
+/// Height of the inline viewport; the terminal's own height caps it.
+const INLINE_HEIGHT: u16 = 24;
+
 /// Set up the terminal in raw mode and render the TUI.
 ///
 /// Enables raw mode, enters alternate screen, creates a ratatui Terminal,
 /// runs the event loop, and restores normal terminal state on exit.
 ///
+/// In inline mode the alternate screen is skipped: the UI is drawn in a
+/// viewport below the prompt, and the last frame is left in place on exit.
+///
 /// # Arguments
 ///
 /// * `app` - Mutable reference to app state
+/// * `inline` - Draw in an inline viewport instead of the alternate screen
+/// * `channel` - The event loop's channel, whose `wake` the app already has
 ///
 /// # Returns
 ///
 /// `Ok(())` if successful, `Err` if terminal setup or event loop failed.
-fn run_tui(app: &mut App) -> Result<()> {
+fn run_tui(app: &mut App, inline: bool, channel: LoopChannel) -> Result<()> {
     enable_raw_mode().context("enable raw mode")?;
     let mut stdout = io::stdout();
-    stdout.execute(EnterAlternateScreen).context("enter alternate screen")?;
+    if !inline {
+        stdout
+            .execute(EnterAlternateScreen)
+            .context("enter alternate screen")?;
+    }
+    // Not every terminal supports bracketed paste; pastes then arrive as keys.
+    stdout.execute(EnableBracketedPaste).ok();
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).context("create terminal")?;
+    let mut terminal = if inline {
+        let options = TerminalOptions {
+            viewport: Viewport::Inline(INLINE_HEIGHT),
+        };
+        Terminal::with_options(backend, options)
+    } else {
+        Terminal::new(backend)
+    }
+    .context("create terminal")?;
 
-    let result = run_loop(&mut terminal, app);
+    let result = run_loop(&mut terminal, app, inline, channel);
 
+    if inline {
+        // Park the cursor on the last line of the final frame so the shell
+        // prompt starts below it rather than drawing over it.
+        let area = terminal.get_frame().area();
+        terminal
+            .set_cursor_position(Position::new(0, area.bottom().saturating_sub(1)))
+            .ok();
+        terminal.show_cursor().ok();
+    }
     disable_raw_mode().ok();
     let mut stdout = io::stdout();
-    stdout.execute(LeaveAlternateScreen).ok();
+    stdout.execute(DisableBracketedPaste).ok();
+    if inline {
+        println!();
+    } else {
+        stdout.execute(LeaveAlternateScreen).ok();
+    }
 
     result
 }
 
+/// What the event loop wakes up for.
+enum LoopEvent {
+    /// A key, paste, or resize from the terminal
+    Input(Event),
+    /// A task reported progress or finished, or the device list changed
+    Wake,
+}
+
+/// The channel the event loop sleeps on: terminal input is read on its own
+/// thread and sent here, and `App::wake` sends `LoopEvent::Wake`.
+struct LoopChannel {
+    tx: mpsc::Sender<LoopEvent>,
+    events: mpsc::Receiver<LoopEvent>,
+}
+
+impl LoopChannel {
+    fn new() -> Self {
+        let (tx, events) = mpsc::channel();
+        Self { tx, events }
+    }
+
+    /// A `Wake` that sends `LoopEvent::Wake` on this channel.
+    fn wake(&self) -> Wake {
+        let tx = self.tx.clone();
+        Arc::new(move || {
+            let _ = tx.send(LoopEvent::Wake);
+        })
+    }
+}
+
+/// How often the input thread checks whether the loop has ended, so it
+/// stops before the shell gets the terminal back.
+const INPUT_STOP_CHECK: Duration = Duration::from_millis(100);
+
+/// Read terminal events on a new thread and send them on `tx` until `stop`
+/// is set or the loop's channel is gone.
+fn spawn_input_reader(tx: mpsc::Sender<LoopEvent>, stop: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match event::poll(INPUT_STOP_CHECK) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(_) => break,
+            }
+            let Ok(event) = event::read() else { break };
+            if tx.send(LoopEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Sets its flag when dropped, however the event loop ends.
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 /// Main event loop for the TUI.
 ///
 /// Continuously:
 /// 1. Polls the background flash thread for updates (if flashing)
 /// 2. Draws the current frame
-/// 3. Waits for keyboard events with a 250ms timeout
-/// 4. Dispatches key events to the UI handler
-/// 5. Exits on 'q' key or window close
+/// 3. Sleeps until terminal input, a task's progress, or a hotplug event
+///    arrives, or `App::next_wakeup` is reached
+/// 4. Dispatches key and paste events to the UI handler, all pending ones
+///    before the next frame
+/// 5. Exits on 'q' key or window close, or once a flash finishes with `--auto-quit`
+/// 6. Exits on a signal, once a running flash, wipe, format, or benchmark has ended
+///
+/// While nothing runs, the loop only wakes for input and the once-a-second
+/// config file check, so an idle TUI uses next to no CPU.
 ///
 /// # Arguments
 ///
 /// * `terminal` - Mutable reference to ratatui Terminal
 /// * `app` - Mutable reference to app state
+/// * `inline` - Also print new log entries above the inline viewport
+/// * `channel` - Where input and wakeups arrive
 ///
 /// # Returns
 ///
 /// `Ok(())` when user exits normally, `Err` if an error occurs.
-fn run_loop(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
-    let mut last_tick = Instant::now();
+fn run_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    inline: bool,
+    channel: LoopChannel,
+) -> Result<()> {
+    let stop = StopOnDrop(Arc::new(AtomicBool::new(false)));
+    spawn_input_reader(channel.tx.clone(), stop.0.clone());
+    let mut printed = 0;
     loop {
-        if app.step == Step::Flashing {
-            app.poll_flash();
+        match app.step {
+            Step::Download(_) => app.poll_download(),
+            Step::Cache(_) => app.poll_cache(),
+            Step::Flashing(_) => app.poll_flash(),
+            Step::Benchmark(_) => app.poll_benchmark(),
+            Step::Wipe(_) => app.poll_wipe(),
+            Step::Format(_) => app.poll_format(),
+            Step::Seed(_) => app.poll_seed(),
+            Step::Image => app.refresh_preview(),
+            Step::Device => {
+                app.poll_device_changes();
+                app.refresh_device_details();
+            }
+            _ => {}
+        }
+        app.poll_config_changes();
+        app.poll_catalog_update();
+        app.expire_toast();
+        app.expire_device_changes();
+        app.activity.note_status(&app.status);
+        if inline {
+            // Scrolled into the scrollback above the viewport, where they stay.
+            for entry in app.activity.since(printed) {
+                terminal.insert_before(1, |buf| {
+                    Paragraph::new(entry.as_str()).render(buf.area, buf)
+                })?;
+            }
+            printed = app.activity.total();
         }
         terminal.draw(|frame| flashr_tui::ui::draw(frame, app))?;
+        if app.auto_quit && matches!(app.step, Step::Result(_)) {
+            return Ok(());
+        }
+        if signals::received().is_some() && !app.step.busy() {
+            return Ok(());
+        }
 
-        let timeout = Duration::from_millis(250).saturating_sub(last_tick.elapsed());
-        if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if let Some(exit) = flashr_tui::ui::handle_key(app, key) {
-                    let AppExit::Quit = exit;
-                    return Ok(());
+        let timeout = app.next_wakeup().saturating_duration_since(Instant::now());
+        let first = match channel.events.recv_timeout(timeout) {
+            Ok(event) => Some(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(anyhow::anyhow!("event loop channel closed"));
+            }
+        };
+        for event in first.into_iter().chain(channel.events.try_iter()) {
+            match event {
+                LoopEvent::Input(Event::Key(key)) => {
+                    if let Some(exit) = flashr_tui::ui::handle_key(app, key) {
+                        let AppExit::Quit = exit;
+                        return Ok(());
+                    }
                 }
+                LoopEvent::Input(Event::Paste(text)) => flashr_tui::ui::handle_paste(app, &text),
+                LoopEvent::Input(_) | LoopEvent::Wake => {}
             }
         }
-
-        if last_tick.elapsed() >= Duration::from_millis(250) {
-            last_tick = Instant::now();
-        }
     }
 }
@@ -0,0 +1,288 @@
+//! Modal dialogs drawn over the current step.
+//!
+//! A modal takes all key input until it is answered: a yes/no confirmation, a
+//! one-line text prompt, or a choice from a short menu. The answer is handed
+//! back together with the `ModalAction` the dialog was opened for, and
+//! `App::resolve_modal` carries the action out. Drawing lives in `ui`.
+
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::lineedit;
+
+/// What to do once a modal is answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModalAction {
+    /// Remove the bookmark at this index from the config
+    RemoveBookmark(usize),
+    /// Jump the file picker to the directory typed in a prompt
+    GoToPath,
+    /// Save the flash report to the path typed in a prompt
+    SaveReport,
+    /// Show the releases of the chosen catalog distribution
+    CatalogDistro,
+    /// Show the images of the chosen release of the catalog distribution at this index
+    CatalogRelease(usize),
+    /// Show the download sources of the chosen image of this catalog distribution and release
+    CatalogImage(usize, usize),
+    /// Download this catalog image (distribution, release, image) the chosen
+    /// way: from a mirror, the fastest mirror, or BitTorrent
+    CatalogSource(usize, usize, usize),
+    /// Delete this image from the download cache
+    DeleteCached(PathBuf),
+    /// Load the cloud-init seed files at the path typed in a prompt
+    LoadSeed,
+    /// Start this destructive operation on the current step's device
+    Destructive(Destructive),
+}
+
+/// A side-step operation that overwrites its device and so takes a
+/// confirmation first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destructive {
+    WriteTest,
+    CapacityTest,
+    SurfaceScan,
+    Wipe,
+    Format,
+}
+
+impl Destructive {
+    /// Title of the confirmation dialog.
+    pub fn title(self) -> &'static str {
+        match self {
+            Destructive::WriteTest => "Write test",
+            Destructive::CapacityTest => "Capacity test",
+            Destructive::SurfaceScan => "Surface scan",
+            Destructive::Wipe => "Wipe",
+            Destructive::Format => "Format",
+        }
+    }
+
+    /// What the operation does to the device, completed with its path.
+    pub fn warning(self) -> &'static str {
+        match self {
+            Destructive::WriteTest => "The write test overwrites the first 64 MiB of",
+            Destructive::CapacityTest | Destructive::SurfaceScan => {
+                "This test overwrites everything on"
+            }
+            Destructive::Wipe => "Wiping erases the partition table and filesystems of",
+            Destructive::Format => "Formatting replaces everything on",
+        }
+    }
+}
+
+/// The kind of input a modal asks for.
+///
+/// - `Confirm` - Yes or no
+/// - `Prompt` - One line of text, edited like the path input
+/// - `Choice` - One of a list of options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModalKind {
+    Confirm,
+    Prompt {
+        input: String,
+        cursor: usize,
+    },
+    Choice {
+        options: Vec<String>,
+        selected: usize,
+    },
+}
+
+/// How a modal was answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModalResponse {
+    /// A confirmation was accepted
+    Confirmed,
+    /// A prompt was submitted with this text
+    Text(String),
+    /// The option at this index was chosen
+    Chose(usize),
+    /// The modal was dismissed
+    Cancelled,
+}
+
+/// An open modal dialog.
+///
+/// # Fields
+///
+/// * `title` - Title on the dialog border
+/// * `message` - Explanation shown above the input
+/// * `kind` - What the dialog asks for, with its input state
+/// * `action` - What the answer is for
+#[derive(Debug, Clone)]
+pub struct Modal {
+    pub title: String,
+    pub message: String,
+    pub kind: ModalKind,
+    pub action: ModalAction,
+}
+
+impl Modal {
+    /// A yes/no confirmation.
+    pub fn confirm(title: &str, message: impl Into<String>, action: ModalAction) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.into(),
+            kind: ModalKind::Confirm,
+            action,
+        }
+    }
+
+    /// A one-line text prompt, pre-filled with `initial`.
+    pub fn prompt(
+        title: &str,
+        message: impl Into<String>,
+        initial: &str,
+        action: ModalAction,
+    ) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.into(),
+            kind: ModalKind::Prompt {
+                input: initial.to_string(),
+                cursor: initial.len(),
+            },
+            action,
+        }
+    }
+
+    /// A menu of options with `selected` highlighted.
+    pub fn choice(
+        title: &str,
+        message: impl Into<String>,
+        options: Vec<String>,
+        selected: usize,
+        action: ModalAction,
+    ) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.into(),
+            kind: ModalKind::Choice { options, selected },
+            action,
+        }
+    }
+
+    /// Feed a key to the modal. Returns the answer once the modal is done.
+    ///
+    /// Esc always cancels. Confirmations accept 'y' or Enter and decline 'n';
+    /// prompts submit with Enter; menus move with Up/Down and choose with
+    /// Enter or the option's number.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<ModalResponse> {
+        if key.code == KeyCode::Esc {
+            return Some(ModalResponse::Cancelled);
+        }
+        match &mut self.kind {
+            ModalKind::Confirm => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                    Some(ModalResponse::Confirmed)
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') => Some(ModalResponse::Cancelled),
+                _ => None,
+            },
+            ModalKind::Prompt { input, cursor } => {
+                match key.code {
+                    KeyCode::Enter => return Some(ModalResponse::Text(input.clone())),
+                    KeyCode::Backspace => {
+                        lineedit::backspace(input, cursor);
+                    }
+                    KeyCode::Delete => lineedit::delete(input, cursor),
+                    KeyCode::Left => lineedit::left(input, cursor),
+                    KeyCode::Right => lineedit::right(input, cursor),
+                    KeyCode::Home => *cursor = 0,
+                    KeyCode::End => *cursor = input.len(),
+                    KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        lineedit::delete_word(input, cursor);
+                    }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        input.clear();
+                        *cursor = 0;
+                    }
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        lineedit::insert(input, cursor, c);
+                    }
+                    _ => {}
+                }
+                None
+            }
+            ModalKind::Choice { options, selected } => match key.code {
+                KeyCode::Up => {
+                    *selected = selected.saturating_sub(1);
+                    None
+                }
+                KeyCode::Down => {
+                    *selected = (*selected + 1).min(options.len().saturating_sub(1));
+                    None
+                }
+                KeyCode::Enter if !options.is_empty() => Some(ModalResponse::Chose(*selected)),
+                KeyCode::Char(c @ '1'..='9') => {
+                    let index = c as usize - '1' as usize;
+                    (index < options.len()).then_some(ModalResponse::Chose(index))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Insert pasted text into a prompt; other kinds ignore pastes.
+    pub fn paste(&mut self, text: &str) {
+        if let ModalKind::Prompt { input, cursor } = &mut self.kind {
+            lineedit::insert_str(input, cursor, text.lines().next().unwrap_or_default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(modal: &mut Modal, code: KeyCode) -> Option<ModalResponse> {
+        modal.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn confirm_accepts_y_and_cancels_on_esc() {
+        let mut modal = Modal::confirm("Remove", "Sure?", ModalAction::RemoveBookmark(0));
+        assert_eq!(press(&mut modal, KeyCode::Char('x')), None);
+        assert_eq!(
+            press(&mut modal, KeyCode::Char('y')),
+            Some(ModalResponse::Confirmed)
+        );
+        assert_eq!(
+            press(&mut modal, KeyCode::Esc),
+            Some(ModalResponse::Cancelled)
+        );
+    }
+
+    #[test]
+    fn prompt_edits_and_submits_text() {
+        let mut modal = Modal::prompt("Label", "", "UBU", ModalAction::RemoveBookmark(0));
+        press(&mut modal, KeyCode::Char('N'));
+        press(&mut modal, KeyCode::Left);
+        press(&mut modal, KeyCode::Backspace);
+        modal.paste("TU\nignored");
+        assert_eq!(
+            press(&mut modal, KeyCode::Enter),
+            Some(ModalResponse::Text("UBTUN".to_string()))
+        );
+    }
+
+    #[test]
+    fn choice_moves_within_bounds_and_picks_by_number() {
+        let options = vec!["a".to_string(), "b".to_string()];
+        let mut modal = Modal::choice("Pick", "", options, 0, ModalAction::RemoveBookmark(0));
+        press(&mut modal, KeyCode::Down);
+        press(&mut modal, KeyCode::Down);
+        assert_eq!(
+            press(&mut modal, KeyCode::Enter),
+            Some(ModalResponse::Chose(1))
+        );
+        assert_eq!(
+            press(&mut modal, KeyCode::Char('1')),
+            Some(ModalResponse::Chose(0))
+        );
+        assert_eq!(press(&mut modal, KeyCode::Char('3')), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Per-user state remembered between runs.
+//!
+//! The config holds what the user writes by hand; preferences hold what
+//! flashr-tui notices on its own and writes back when the TUI exits: the
+//! directory last browsed in the file picker, whether all disks were shown,
+//! the block size, and whether flashes must be verified. They live in
+//! `$XDG_STATE_HOME/flashr-tui/state.json` (falling back to `~/.local/state`).
+//! A missing file means defaults.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::state_dir;
+
+/// Choices carried over from the last run of the TUI.
+///
+/// # Fields
+///
+/// * `last_dir` - Directory the file picker was showing on exit
+/// * `show_all_disks` - Whether the device list showed internal disks too
+/// * `block_size` - Bytes per write block picked on the Confirm screen; the
+///   default when unset
+/// * `require_verification` - Whether a flash fails when verification cannot run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub last_dir: Option<PathBuf>,
+    pub show_all_disks: bool,
+    pub block_size: Option<u64>,
+    pub require_verification: bool,
+}
+
+impl Preferences {
+    /// Default location of the state file, if a home or state directory is known.
+    pub fn default_path() -> Option<PathBuf> {
+        state_dir().map(|dir| dir.join("state.json"))
+    }
+
+    /// Load the preferences from the default location.
+    ///
+    /// Returns the defaults when the file does not exist.
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the preferences from a specific file.
+    ///
+    /// Returns the defaults when the file does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data)
+                .with_context(|| format!("parse state: {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("read state: {}", path.display())),
+        }
+    }
+
+    /// Write the preferences to the default location.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no state location is known or the file cannot be
+    /// written.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::default_path()
+            .ok_or_else(|| anyhow::anyhow!("no state location (HOME is not set)"))?;
+        self.save_to(&path)
+    }
+
+    /// Write the preferences to `path`, creating its directory if needed.
+    ///
+    /// The file is replaced in one step, so a crash never leaves half of it.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("create state dir: {}", dir.display()))?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".tmp");
+        std::fs::write(&partial, data + "\n")
+            .and_then(|()| std::fs::rename(&partial, path))
+            .with_context(|| format!("write state: {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences_survive_a_round_trip() {
+        let dir = std::env::temp_dir().join(format!("flashr_tui_prefs_{}", std::process::id()));
+        let path = dir.join("state.json");
+        assert_eq!(
+            Preferences::load_from(&path).unwrap(),
+            Preferences::default()
+        );
+
+        let prefs = Preferences {
+            last_dir: Some(PathBuf::from("/isos")),
+            show_all_disks: true,
+            block_size: Some(8 * 1024 * 1024),
+            require_verification: true,
+        };
+        prefs.save_to(&path).unwrap();
+        assert_eq!(Preferences::load_from(&path).unwrap(), prefs);
+
+        // Keys from older or newer versions are ignored or defaulted.
+        std::fs::write(&path, r#"{"show_all_disks": true, "window": 3}"#).unwrap();
+        let loaded = Preferences::load_from(&path).unwrap();
+        assert!(loaded.show_all_disks && loaded.last_dir.is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
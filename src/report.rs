@@ -0,0 +1,362 @@
+//! Summary of a finished flash for the Result screen and `--report`.
+//!
+//! The report is started when a flash begins and filled in from the flash
+//! thread's tagged progress messages (image hash, verification verdict, label)
+//! as they arrive. Once the result is in, it renders as plain text lines that
+//! are both drawn on the Result screen and written to a file on request, or
+//! as JSON or Markdown for labs that keep a record of every stick.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+use crate::device::Disk;
+use crate::{bench, flash, format_duration, format_size, FlashResult};
+
+/// File format of a saved report, chosen by the file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// The Result screen's lines (any other extension)
+    Text,
+    /// A JSON object (".json")
+    Json,
+    /// A Markdown document (".md" or ".markdown")
+    Markdown,
+}
+
+impl ReportFormat {
+    /// The format for a report saved to `path`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown") => {
+                Self::Markdown
+            }
+            _ => Self::Text,
+        }
+    }
+}
+
+/// What happened on one target device.
+///
+/// # Fields
+///
+/// * `device` - Kernel device path (e.g., "/dev/sdb")
+/// * `serial` - Hardware serial number, if the device reports one
+/// * `verification` - Verification verdict (e.g., "passed: SHA-256 checksums match.")
+/// * `label` - Volume label applied after flashing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetReport {
+    pub device: String,
+    pub serial: Option<String>,
+    pub verification: Option<String>,
+    pub label: Option<String>,
+}
+
+/// Summary of one flash run across all its targets.
+///
+/// # Fields
+///
+/// * `image` - Image that was flashed
+/// * `image_hash` - Digest of the image as "<algorithm>:<hex>", once verification has hashed it
+/// * `targets` - One entry per selected device, in flashing order
+/// * `started` - Number of targets whose write has begun
+/// * `outcome` - Final result, once the flash thread has finished
+#[derive(Debug, Clone)]
+pub struct FlashReport {
+    pub image: PathBuf,
+    pub image_hash: Option<String>,
+    pub targets: Vec<TargetReport>,
+    pub started: usize,
+    pub outcome: Option<FlashResult>,
+}
+
+impl FlashReport {
+    /// Start a report for flashing `image` to `devices`.
+    pub fn new(image: PathBuf, devices: &[Disk]) -> Self {
+        Self {
+            image,
+            image_hash: None,
+            targets: devices
+                .iter()
+                .map(|disk| TargetReport {
+                    device: disk.device_path(),
+                    serial: disk.serial.clone(),
+                    verification: None,
+                    label: None,
+                })
+                .collect(),
+            started: 0,
+            outcome: None,
+        }
+    }
+
+    /// Note that the next target's write has begun.
+    pub fn begin_target(&mut self) {
+        self.started = (self.started + 1).min(self.targets.len());
+    }
+
+    /// The target currently being flashed.
+    pub fn current_target(&mut self) -> Option<&mut TargetReport> {
+        self.started
+            .checked_sub(1)
+            .and_then(|index| self.targets.get_mut(index))
+    }
+
+    /// Take in one progress message from the flash thread.
+    pub fn apply(&mut self, line: &str) {
+        if let Some(phase) = line.strip_prefix(flash::PHASE_PREFIX) {
+            // Each device of a batch starts over with a fresh write.
+            if flash::FlashPhase::parse(phase) == Some(flash::FlashPhase::Write) {
+                self.begin_target();
+            }
+        } else if let Some(hash) = line.strip_prefix(flash::IMAGE_HASH_PREFIX) {
+            self.image_hash = Some(hash.to_string());
+        } else if let Some(target) = self.current_target() {
+            if let Some(verdict) = line.strip_prefix(flash::VERDICT_PREFIX) {
+                target.verification = Some(verdict.to_string());
+            } else if let Some(label) = line.strip_prefix(flash::LABEL_PREFIX) {
+                target.label = Some(label.to_string());
+            }
+        }
+    }
+
+    /// Render the report as plain text lines.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        match &self.outcome {
+            Some(outcome) => {
+                let verdict = if outcome.ok { "Success" } else { "Failed" };
+                lines.push(format!("Result       : {verdict} - {}", outcome.message));
+            }
+            None => lines.push("Result       : in progress".to_string()),
+        }
+        lines.push(format!("Image        : {}", self.image.display()));
+        lines.push(format!(
+            "Image hash   : {}",
+            self.image_hash.as_deref().unwrap_or("not computed")
+        ));
+        for target in &self.targets {
+            match &target.serial {
+                Some(serial) => lines.push(format!(
+                    "Device       : {} (serial {serial})",
+                    target.device
+                )),
+                None => lines.push(format!("Device       : {}", target.device)),
+            }
+            lines.push(format!(
+                "  Verified   : {}",
+                target.verification.as_deref().unwrap_or("not completed")
+            ));
+            lines.push(format!(
+                "  Label      : {}",
+                target.label.as_deref().unwrap_or("none")
+            ));
+        }
+        if let Some(outcome) = &self.outcome {
+            lines.push(format!(
+                "Bytes written: {} ({} bytes)",
+                format_size(outcome.bytes_written),
+                outcome.bytes_written
+            ));
+            if let Some(elapsed) = outcome.elapsed {
+                lines.push(format!("Duration     : {}", format_duration(elapsed)));
+                if outcome.bytes_written > 0 {
+                    lines.push(format!(
+                        "Average speed: {:.1} MB/s",
+                        bench::throughput_mbps(outcome.bytes_written, elapsed)
+                    ));
+                }
+            }
+        }
+        lines
+    }
+
+    /// Render the report as a JSON object. Fields the flash never reached
+    /// are `null`.
+    pub fn to_json(&self) -> Value {
+        let outcome = self.outcome.as_ref();
+        let elapsed = outcome.and_then(|outcome| outcome.elapsed);
+        let bytes_written = outcome.map_or(0, |outcome| outcome.bytes_written);
+        json!({
+            "result": match outcome {
+                Some(outcome) if outcome.ok => "success",
+                Some(_) => "failed",
+                None => "in_progress",
+            },
+            "message": outcome.map(|outcome| &outcome.message),
+            "image": self.image.display().to_string(),
+            "image_hash": self.image_hash,
+            "targets": self.targets.iter().map(|target| json!({
+                "device": target.device,
+                "serial": target.serial,
+                "verification": target.verification,
+                "label": target.label,
+            })).collect::<Vec<_>>(),
+            "bytes_written": bytes_written,
+            "duration_secs": elapsed.map(|elapsed| elapsed.as_secs_f64()),
+            "average_mbps": elapsed
+                .filter(|_| bytes_written > 0)
+                .map(|elapsed| bench::throughput_mbps(bytes_written, elapsed)),
+        })
+    }
+
+    /// Render the report as a Markdown document: the run's details as a
+    /// list, then a table with a row per device.
+    pub fn to_markdown(&self) -> String {
+        let mut lines = vec!["# Flash report".to_string(), String::new()];
+        match &self.outcome {
+            Some(outcome) => {
+                let verdict = if outcome.ok { "Success" } else { "Failed" };
+                lines.push(format!("- **Result:** {verdict} - {}", outcome.message));
+            }
+            None => lines.push("- **Result:** in progress".to_string()),
+        }
+        lines.push(format!("- **Image:** `{}`", self.image.display()));
+        lines.push(format!(
+            "- **Image hash:** {}",
+            self.image_hash
+                .as_deref()
+                .map_or("not computed".to_string(), |hash| format!("`{hash}`"))
+        ));
+        if let Some(outcome) = &self.outcome {
+            lines.push(format!(
+                "- **Bytes written:** {} ({} bytes)",
+                format_size(outcome.bytes_written),
+                outcome.bytes_written
+            ));
+            if let Some(elapsed) = outcome.elapsed {
+                lines.push(format!("- **Duration:** {}", format_duration(elapsed)));
+                if outcome.bytes_written > 0 {
+                    lines.push(format!(
+                        "- **Average speed:** {:.1} MB/s",
+                        bench::throughput_mbps(outcome.bytes_written, elapsed)
+                    ));
+                }
+            }
+        }
+        lines.push(String::new());
+        lines.push("| Device | Serial | Verification | Label |".to_string());
+        lines.push("| --- | --- | --- | --- |".to_string());
+        for target in &self.targets {
+            let cells = [
+                target.device.as_str(),
+                target.serial.as_deref().unwrap_or("-"),
+                target.verification.as_deref().unwrap_or("not completed"),
+                target.label.as_deref().unwrap_or("none"),
+            ];
+            let cells: Vec<_> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+        lines.join("\n")
+    }
+
+    /// Write the report to `path`, in the format its extension asks for
+    /// (see `ReportFormat`).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = match ReportFormat::from_path(path) {
+            ReportFormat::Text => self.lines().join("\n"),
+            ReportFormat::Json => serde_json::to_string_pretty(&self.to_json())?,
+            ReportFormat::Markdown => self.to_markdown(),
+        };
+        text.push('\n');
+        std::fs::write(path, text).with_context(|| format!("write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn disk(name: &str, serial: Option<&str>) -> Disk {
+        Disk {
+            name: name.to_string(),
+            vendor: String::new(),
+            model: String::new(),
+            size: "8G".to_string(),
+            serial: serial.map(str::to_string),
+            by_id: None,
+            removable: true,
+        }
+    }
+
+    #[test]
+    fn lines_follow_each_target_in_flashing_order() {
+        let devices = [disk("sdb", Some("A1")), disk("sdc", None)];
+        let mut report = FlashReport::new(PathBuf::from("/isos/arch.iso"), &devices);
+        report.begin_target();
+        report.image_hash = Some("sha256:abc123".to_string());
+        report.current_target().unwrap().verification = Some("passed".to_string());
+        report.current_target().unwrap().label = Some("ARCH".to_string());
+        report.begin_target();
+        report.outcome = Some(FlashResult {
+            ok: false,
+            message: "/dev/sdc: dd failed".to_string(),
+            error: None,
+            elapsed: Some(Duration::from_secs(75)),
+            bytes_written: 0,
+        });
+
+        assert_eq!(
+            report.lines(),
+            [
+                "Result       : Failed - /dev/sdc: dd failed",
+                "Image        : /isos/arch.iso",
+                "Image hash   : sha256:abc123",
+                "Device       : /dev/sdb (serial A1)",
+                "  Verified   : passed",
+                "  Label      : ARCH",
+                "Device       : /dev/sdc",
+                "  Verified   : not completed",
+                "  Label      : none",
+                "Bytes written: 0 B (0 bytes)",
+                "Duration     : 1m 15s",
+            ]
+        );
+    }
+
+    #[test]
+    fn saved_format_follows_the_extension() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("r.json")),
+            ReportFormat::Json
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("r.MD")),
+            ReportFormat::Markdown
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("r.txt")),
+            ReportFormat::Text
+        );
+
+        let mut report = FlashReport::new(PathBuf::from("/isos/a.iso"), &[disk("sdb", None)]);
+        report.apply("Phase: write");
+        report.apply("Image hash: blake3:abc123");
+        report.apply("Verification passed: SHA-256 checksums match.");
+        report.outcome = Some(FlashResult::finished(
+            &Ok(()),
+            Some(Duration::from_secs(2)),
+            4_000_000,
+        ));
+        let json = report.to_json();
+        assert_eq!(json["result"], "success");
+        assert_eq!(json["image_hash"], "blake3:abc123");
+        assert_eq!(json["targets"][0]["serial"], Value::Null);
+        assert_eq!(json["average_mbps"], 2.0);
+        assert!(report
+            .to_markdown()
+            .ends_with("| /dev/sdb | - | passed: SHA-256 checksums match. | none |"));
+
+        let dir = std::env::temp_dir().join(format!("flashr-report-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.json");
+        report.save(&path).unwrap();
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, json);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,280 @@
+//! Destructive full-surface scan for suspect media.
+//!
+//! Runs `badblocks` in write mode over the whole device: each test pattern is
+//! written to every block and read back, and blocks that fail to compare are
+//! reported. This finds worn-out or damaged areas that a quick benchmark or
+//! capacity probe would miss, at the cost of taking a long time on large media.
+//! Privileged commands are elevated the same way as the flash engine.
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::process::Stdio;
+use std::sync::mpsc;
+
+use crate::device::{format_lsblk_size, DevicePath};
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, unmount_partitions,
+};
+use crate::wipe::device_sectors;
+
+/// Block size handed to `badblocks`, in bytes.
+const SCAN_BLOCK_BYTES: u64 = 4096;
+
+/// Patterns written on each pass. Together they flip every bit both ways.
+const SCAN_PATTERNS: [&str; 2] = ["0xaa", "0x55"];
+
+/// Resolution of the "[done/total]" progress prefix (basis points).
+const PROGRESS_SCALE: u64 = 10_000;
+
+/// Outcome of a surface scan.
+///
+/// # Fields
+///
+/// * `block_size` - Size of one scanned block in bytes
+/// * `total_blocks` - Number of blocks scanned
+/// * `bad_blocks` - Block numbers that failed to read back intact, in ascending order
+#[derive(Debug, Clone)]
+pub struct ScanReport {
+    pub block_size: u64,
+    pub total_blocks: u64,
+    pub bad_blocks: Vec<u64>,
+}
+
+impl ScanReport {
+    /// One-line human-readable summary.
+    pub fn summary(&self) -> String {
+        let scanned = format_lsblk_size(self.block_size * self.total_blocks);
+        match self.bad_blocks.first() {
+            None => format!("No bad blocks found across {scanned}."),
+            Some(first) => format!(
+                "{} bad block(s) found across {scanned}; first at {}.",
+                self.bad_blocks.len(),
+                format_lsblk_size(first * self.block_size)
+            ),
+        }
+    }
+
+    /// Bad block locations as byte-offset ranges, e.g. "1.2G (blocks 314572-314575)".
+    ///
+    /// Adjacent bad blocks are merged; at most `limit` ranges are returned.
+    pub fn locations(&self, limit: usize) -> Vec<String> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for &block in &self.bad_blocks {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == block => *end = block,
+                _ => ranges.push((block, block)),
+            }
+        }
+        ranges
+            .into_iter()
+            .take(limit)
+            .map(|(start, end)| {
+                let offset = format_lsblk_size(start * self.block_size);
+                if start == end {
+                    format!("{offset} (block {start})")
+                } else {
+                    format!("{offset} (blocks {start}-{end})")
+                }
+            })
+            .collect()
+    }
+}
+
+/// Run a destructive write/read surface scan over an entire device.
+///
+/// Progress messages start with "[done/total]" (see `bench::split_progress`).
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the device is invalid or unsafe, `badblocks` is not
+/// installed, elevation fails, or `badblocks` itself fails. Bad blocks are
+/// reported in the `ScanReport`, not as errors.
+pub fn run_surface_scan(device: &str, progress: mpsc::Sender<String>) -> Result<ScanReport> {
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    ensure_device_safe(dev, true)?;
+    which::which("badblocks")
+        .map_err(|_| anyhow::anyhow!("badblocks not found; install e2fsprogs"))?;
+    let total_blocks = device_sectors(dev)? * 512 / SCAN_BLOCK_BYTES;
+    let elevator = acquire_elevator(&progress)?;
+
+    let partitions = list_partitions(dev)?;
+    if !partitions.is_empty() {
+        let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+        unmount_partitions(&partitions, elevator);
+    }
+
+    let mut cmd = elevated_command("badblocks", elevator);
+    cmd.arg("-w")
+        .arg("-s")
+        .arg("-b")
+        .arg(SCAN_BLOCK_BYTES.to_string());
+    for pattern in SCAN_PATTERNS {
+        cmd.arg("-t").arg(pattern);
+    }
+    let mut child = cmd
+        .arg(dev)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("run badblocks")?;
+
+    // Bad block numbers arrive on stdout, one per line, while progress is on stderr.
+    let stdout = child.stdout.take();
+    let collector = std::thread::spawn(move || -> Vec<u64> {
+        let Some(stdout) = stdout else {
+            return Vec::new();
+        };
+        BufReader::new(stdout)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| line.trim().parse().ok())
+            .collect()
+    });
+
+    let mut tracker = ScanProgress::new(SCAN_PATTERNS.len() as u64 * 2);
+    let mut last_error = String::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        let mut buf = [0u8; 4096];
+        let mut pending = String::new();
+        loop {
+            let read = stderr.read(&mut buf).context("read badblocks output")?;
+            if read == 0 {
+                break;
+            }
+            for ch in String::from_utf8_lossy(&buf[..read]).chars() {
+                // badblocks redraws its status line with backspaces.
+                if matches!(ch, '\n' | '\r' | '\u{8}') {
+                    if let Some(message) = tracker.feed(pending.trim()) {
+                        let _ = progress.send(message);
+                    } else if !pending.trim().is_empty() {
+                        last_error = pending.trim().to_string();
+                    }
+                    pending.clear();
+                } else {
+                    pending.push(ch);
+                }
+            }
+        }
+    }
+
+    let status = child.wait().context("wait for badblocks")?;
+    let mut bad_blocks = collector.join().unwrap_or_default();
+    if !status.success() {
+        return Err(anyhow::anyhow!("badblocks failed: {last_error}"));
+    }
+    bad_blocks.sort_unstable();
+    bad_blocks.dedup();
+
+    Ok(ScanReport {
+        block_size: SCAN_BLOCK_BYTES,
+        total_blocks,
+        bad_blocks,
+    })
+}
+
+/// Turns `badblocks -s` status fragments into overall progress messages.
+struct ScanProgress {
+    phases: u64,
+    phase: u64,
+    action: String,
+}
+
+impl ScanProgress {
+    fn new(phases: u64) -> Self {
+        Self {
+            phases,
+            phase: 0,
+            action: String::new(),
+        }
+    }
+
+    /// Consume one status fragment; returns a progress message for recognized ones.
+    fn feed(&mut self, fragment: &str) -> Option<String> {
+        if let Some(pattern) = fragment.strip_prefix("Testing with pattern ") {
+            self.phase = self.phase_start(self.phase, false);
+            self.action = format!("Writing pattern {}", pattern.trim_end_matches(':'));
+            return Some(self.message(0.0, ""));
+        }
+        if fragment.starts_with("Reading and comparing") {
+            self.phase = self.phase_start(self.phase, true);
+            self.action = "Reading back and comparing".to_string();
+            return Some(self.message(0.0, ""));
+        }
+        let (percent, rest) = fragment.split_once("% done")?;
+        let percent: f64 = percent.trim().parse().ok()?;
+        let errors = rest
+            .split_once('(')
+            .and_then(|(_, tail)| tail.split_once(" errors"))
+            .map(|(counts, _)| format!(", {counts} read/write/compare errors"))
+            .unwrap_or_default();
+        Some(self.message(percent, &errors))
+    }
+
+    /// Phase index after a new phase begins (the first phase keeps index 0).
+    fn phase_start(&self, current: u64, reading: bool) -> u64 {
+        if self.action.is_empty() && !reading {
+            current
+        } else {
+            (current + 1).min(self.phases - 1)
+        }
+    }
+
+    fn message(&self, percent: f64, errors: &str) -> String {
+        let overall = (self.phase as f64 + percent / 100.0) / self.phases as f64;
+        let done = (overall * PROGRESS_SCALE as f64) as u64;
+        format!(
+            "[{done}/{PROGRESS_SCALE}] Pass {}/{}: {} ({percent:.1}%{errors})",
+            self.phase + 1,
+            self.phases,
+            self.action
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations_merge_adjacent_blocks() {
+        let report = ScanReport {
+            block_size: 4096,
+            total_blocks: 1_000_000,
+            bad_blocks: vec![256, 257, 258, 1024],
+        };
+        assert_eq!(
+            report.locations(10),
+            vec![
+                "1M (blocks 256-258)".to_string(),
+                "4M (block 1024)".to_string()
+            ]
+        );
+        assert_eq!(report.locations(1).len(), 1);
+    }
+
+    #[test]
+    fn progress_tracks_passes_across_patterns() {
+        let mut tracker = ScanProgress::new(4);
+        assert!(tracker
+            .feed("Testing with pattern 0xaa:")
+            .unwrap()
+            .starts_with("[0/10000] Pass 1/4: Writing pattern 0xaa"));
+        let message = tracker
+            .feed("50.00% done, 0:10 elapsed. (0/0/0 errors)")
+            .unwrap();
+        assert!(message.starts_with("[1250/10000] Pass 1/4"));
+        assert!(message.contains("0/0/0 read/write/compare errors"));
+        assert!(tracker
+            .feed("Reading and comparing:")
+            .unwrap()
+            .starts_with("[2500/10000] Pass 2/4"));
+        assert_eq!(tracker.feed("done"), None);
+    }
+}
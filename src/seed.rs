@@ -0,0 +1,412 @@
+//! Cloud-init seed for a flashed cloud image.
+//!
+//! Cloud images configure themselves on first boot with cloud-init, which
+//! reads a "NoCloud" seed from any filesystem labelled CIDATA: a `user-data`
+//! file (users, SSH keys, packages...), a `meta-data` file (the instance id),
+//! and optionally a `network-config`. After a cloud image has been written,
+//! 'i' on the Result step opens an editor for the user-data, prefilled with
+//! `TEMPLATE` or loaded from the user's own seed files, and writes the seed
+//! to the stick so it boots straight into a configured system.
+//!
+//! An existing CIDATA partition is reused. Otherwise a small FAT partition is
+//! added after the image's own partitions, which is why the seed can only be
+//! written once the image is on the device.
+
+use std::io::{Read, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::mpsc;
+
+use anyhow::{Context, Result};
+
+use crate::device::{self, DevicePath};
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, unmount_partitions,
+};
+use crate::format::wait_for_partition;
+use crate::platform;
+use crate::wipe::run_quiet;
+
+/// Label cloud-init looks for (case-insensitively) on the seed filesystem.
+pub const LABEL: &str = "CIDATA";
+
+/// Size of a newly added seed partition, in MiB.
+const PARTITION_MIB: u64 = 16;
+
+/// GPT type of a newly added seed partition (Microsoft basic data).
+const GPT_BASIC_DATA: &str = "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7";
+
+/// User-data the editor starts with.
+pub const TEMPLATE: &str = "#cloud-config
+hostname: cloudbox
+users:
+  - name: admin
+    groups: [sudo]
+    shell: /bin/bash
+    sudo: ALL=(ALL) NOPASSWD:ALL
+    ssh_authorized_keys:
+      - ssh-ed25519 AAAA... you@example.com
+ssh_pwauth: false
+package_update: true
+";
+
+/// First lines cloud-init recognises as user-data.
+const HEADERS: [&str; 7] = [
+    "#cloud-config",
+    "#!",
+    "#include",
+    "#cloud-boothook",
+    "#part-handler",
+    "## template: jinja",
+    "Content-Type:",
+];
+
+/// The files of a NoCloud seed.
+///
+/// # Fields
+///
+/// * `user_data` - Contents of `user-data`
+/// * `meta_data` - Contents of `meta-data`; one with a fresh instance id is
+///   written when `None`
+/// * `network_config` - Contents of `network-config`, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seed {
+    pub user_data: String,
+    pub meta_data: Option<String>,
+    pub network_config: Option<String>,
+}
+
+impl Default for Seed {
+    fn default() -> Self {
+        Self {
+            user_data: TEMPLATE.to_string(),
+            meta_data: None,
+            network_config: None,
+        }
+    }
+}
+
+impl Seed {
+    /// Read a seed from the user's files: a directory holding `user-data` and
+    /// optionally `meta-data` and `network-config`, or a user-data file with
+    /// those two optionally beside it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the user-data cannot be read or a file beside it exists but
+    /// cannot be read.
+    pub fn load(path: &Path) -> Result<Self> {
+        let (dir, user_data) = if path.is_dir() {
+            (path.to_path_buf(), path.join("user-data"))
+        } else {
+            let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+            (dir, path.to_path_buf())
+        };
+        let optional = |name: &str| -> Result<Option<String>> {
+            let file = dir.join(name);
+            match std::fs::read_to_string(&file) {
+                Ok(text) => Ok(Some(text)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err).with_context(|| format!("read {}", file.display())),
+            }
+        };
+        Ok(Self {
+            user_data: std::fs::read_to_string(&user_data)
+                .with_context(|| format!("read {}", user_data.display()))?,
+            meta_data: optional("meta-data")?,
+            network_config: optional("network-config")?,
+        })
+    }
+
+    /// The files to write, by name.
+    fn files(&self) -> Vec<(&'static str, String)> {
+        let meta_data = self.meta_data.clone().unwrap_or_else(|| {
+            let stamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            format!("instance-id: flashr-{stamp}\n")
+        });
+        let mut files = vec![
+            ("user-data", self.user_data.clone()),
+            ("meta-data", meta_data),
+        ];
+        if let Some(network_config) = &self.network_config {
+            files.push(("network-config", network_config.clone()));
+        }
+        files
+    }
+}
+
+/// Whether an image's file name looks like a cloud image, which is when the
+/// Result step offers a seed. Debian's "nocloud" images ship without
+/// cloud-init and don't count.
+pub fn looks_like_cloud_image(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.contains("cloud") && !name.contains("nocloud")
+}
+
+/// Check that cloud-init will act on `user_data`: it ignores user-data that
+/// doesn't start with one of the headers it knows.
+///
+/// # Errors
+///
+/// Names the expected header when the first line is not one.
+pub fn check_user_data(user_data: &str) -> Result<()> {
+    let first = user_data.trim_start().lines().next().unwrap_or_default();
+    if HEADERS.iter().any(|header| first.starts_with(header)) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "user-data must start with '#cloud-config' (or '#!' for a script); cloud-init ignores it otherwise"
+        ))
+    }
+}
+
+/// Write `seed` to a CIDATA partition of `device`.
+///
+/// Reuses the device's CIDATA partition (reformatting it as FAT unless it
+/// already is), or adds one after the last partition. Existing files on a
+/// reused FAT partition are replaced, and kept when the seed has none of
+/// that name.
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `seed` - The files to write
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the user-data has no cloud-init header, the device is
+/// invalid or unsafe, elevation fails, there is no room for a partition, or
+/// `sfdisk`, `mkfs.vfat`, `mount`, or `cp` fail.
+pub fn write_seed(device: &str, seed: &Seed, progress: mpsc::Sender<String>) -> Result<()> {
+    check_user_data(&seed.user_data)?;
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    ensure_device_safe(dev, true)?;
+    which::which("mkfs.vfat").context("mkfs.vfat is not installed")?;
+    let elevator = acquire_elevator(&progress)?;
+
+    let partitions = list_partitions(dev)?;
+    if !partitions.is_empty() {
+        let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+        unmount_partitions(&partitions, elevator);
+    }
+
+    let existing = device::content_summary(dev)?.into_iter().find(|part| {
+        part.label
+            .as_deref()
+            .is_some_and(|label| label.eq_ignore_ascii_case(LABEL))
+    });
+    let partition = match existing {
+        Some(part) if part.fstype.as_deref() == Some("vfat") => {
+            let _ = progress.send(format!("Reusing the {LABEL} partition {}", part.name));
+            part.name
+        }
+        Some(part) => {
+            make_fat(&part.name, elevator, &progress)?;
+            part.name
+        }
+        None => {
+            let partition = add_partition(dev, &partitions, elevator, &progress)?;
+            make_fat(&partition, elevator, &progress)?;
+            partition
+        }
+    };
+
+    let _ = progress.send(format!("Writing the cloud-init seed to {partition}..."));
+    copy_files(&partition, seed, elevator)?;
+    let _ = progress.send(format!("Cloud-init seed written to {partition}."));
+    Ok(())
+}
+
+/// Add a `PARTITION_MIB` partition after the last one on `dev`, which had
+/// `partitions`, and return its path.
+fn add_partition(
+    dev: &str,
+    partitions: &[String],
+    elevator: Option<&str>,
+    progress: &mpsc::Sender<String>,
+) -> Result<String> {
+    let output = platform::current()
+        .lsblk()
+        .args(["-dno", "PTTYPE", dev])
+        .output()
+        .context("run lsblk for the partition table type")?;
+    let table = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let kind = match table.as_str() {
+        "gpt" => GPT_BASIC_DATA,
+        "dos" => "c",
+        "" => anyhow::bail!("{dev} has no partition table to add a {LABEL} partition to"),
+        other => anyhow::bail!("cannot add a partition to a {other} partition table"),
+    };
+    if table == "gpt" {
+        // The image's backup GPT ends where the image did, not at the end of
+        // the stick; move it there so the space after the image can be used.
+        run_quiet(
+            elevated_command("sfdisk", elevator).args(["--relocate", "gpt-bak-std", dev]),
+            "sfdisk",
+        )?;
+    }
+
+    let _ = progress.send(format!(
+        "Adding a {PARTITION_MIB} MiB {LABEL} partition to {dev}..."
+    ));
+    let mut sfdisk = elevated_command("sfdisk", elevator)
+        .args(["--quiet", "--append", dev])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("run sfdisk")?;
+    if let Some(mut stdin) = sfdisk.stdin.take() {
+        writeln!(stdin, "size={PARTITION_MIB}MiB, type={kind}").context("write sfdisk script")?;
+    }
+    let output = sfdisk.wait_with_output().context("wait for sfdisk")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(anyhow::anyhow!("sfdisk failed: {stderr}"));
+    }
+
+    let _ = platform::current()
+        .reread_partitions(dev, elevator)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    wait_for_partition(dev, || {
+        Ok(list_partitions(dev)?
+            .into_iter()
+            .find(|partition| !partitions.contains(partition)))
+    })
+}
+
+/// Create a FAT filesystem labelled `LABEL` on `partition`.
+fn make_fat(
+    partition: &str,
+    elevator: Option<&str>,
+    progress: &mpsc::Sender<String>,
+) -> Result<()> {
+    let _ = progress.send(format!("Creating a FAT filesystem on {partition}..."));
+    run_quiet(
+        elevated_command("mkfs.vfat", elevator).args(["-n", LABEL, partition]),
+        "mkfs.vfat",
+    )
+}
+
+/// Mount `partition` on a temporary directory, copy the seed's files onto
+/// it, and unmount it again.
+fn copy_files(partition: &str, seed: &Seed, elevator: Option<&str>) -> Result<()> {
+    let staging = private_dir()?;
+    let mount_point = staging.join("mnt");
+    std::fs::create_dir(&mount_point)
+        .with_context(|| format!("create {}", mount_point.display()))?;
+    let mut files = Vec::new();
+    for (name, contents) in seed.files() {
+        let file = staging.join(name);
+        std::fs::write(&file, contents).with_context(|| format!("write {}", file.display()))?;
+        files.push(file);
+    }
+
+    let copied = run_quiet(
+        elevated_command("mount", elevator)
+            .args(["-t", "vfat", partition])
+            .arg(&mount_point),
+        "mount",
+    )
+    .and_then(|()| {
+        let copied = run_quiet(
+            elevated_command("cp", elevator)
+                .args(&files)
+                .arg(&mount_point),
+            "cp",
+        );
+        let unmounted = run_quiet(
+            elevated_command("umount", elevator).arg(&mount_point),
+            "umount",
+        );
+        copied.and(unmounted)
+    });
+    // Not remove_dir_all: if unmounting failed, the stick is still mounted there.
+    for file in &files {
+        std::fs::remove_file(file).ok();
+    }
+    std::fs::remove_dir(&mount_point).ok();
+    std::fs::remove_dir(&staging).ok();
+    copied
+}
+
+/// Create a new directory only the current user can enter, under a random
+/// name in the temp directory. An existing one is never reused, since others
+/// could have planted files in it for the elevated copy to pick up.
+fn private_dir() -> Result<PathBuf> {
+    let mut random = [0u8; 8];
+    for _ in 0..8 {
+        std::fs::File::open("/dev/urandom")
+            .and_then(|mut urandom| urandom.read_exact(&mut random))
+            .context("read /dev/urandom")?;
+        let name: String = random.iter().map(|byte| format!("{byte:02x}")).collect();
+        let dir = std::env::temp_dir().join(format!("flashr-seed-{name}"));
+        match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err).with_context(|| format!("create {}", dir.display())),
+        }
+    }
+    anyhow::bail!(
+        "cannot create a staging directory in {}",
+        std::env::temp_dir().display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_data_needs_a_cloud_init_header() {
+        assert!(check_user_data(TEMPLATE).is_ok());
+        assert!(check_user_data("\n#!/bin/sh\necho hi\n").is_ok());
+        assert!(check_user_data("hostname: box\n").is_err());
+        assert!(check_user_data("").is_err());
+        assert!(looks_like_cloud_image("noble-server-cloudimg-amd64.img"));
+        assert!(looks_like_cloud_image("Fedora-Cloud-Base-Generic-41.raw"));
+        assert!(!looks_like_cloud_image("debian-12-nocloud-amd64.raw"));
+        assert!(!looks_like_cloud_image("ubuntu-24.04-desktop-amd64.iso"));
+    }
+
+    #[test]
+    fn load_picks_up_the_files_beside_user_data() {
+        let dir = std::env::temp_dir().join(format!("flashr_tui_seed_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("user-data"), "#cloud-config\n").unwrap();
+        std::fs::write(dir.join("meta-data"), "instance-id: box\n").unwrap();
+
+        let seed = Seed::load(&dir).unwrap();
+        assert_eq!(seed, Seed::load(&dir.join("user-data")).unwrap());
+        assert_eq!(seed.meta_data.as_deref(), Some("instance-id: box\n"));
+        assert_eq!(seed.network_config, None);
+        let names: Vec<&str> = seed.files().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["user-data", "meta-data"]);
+
+        let generated = Seed::default().files();
+        assert!(generated[1].1.starts_with("instance-id: flashr-"));
+        assert!(Seed::load(&dir.join("missing")).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn staging_dirs_are_fresh_and_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = private_dir().unwrap();
+        let second = private_dir().unwrap();
+        assert_ne!(first, second);
+        let mode = std::fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        std::fs::remove_dir(&first).unwrap();
+        std::fs::remove_dir(&second).unwrap();
+    }
+}
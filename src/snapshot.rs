@@ -0,0 +1,129 @@
+//! Rendering the TUI without a terminal.
+//!
+//! `render` draws an `App` with `ui::draw` onto a ratatui `TestBackend` of
+//! any size and returns the buffer. `render_to_string` returns the screen as
+//! plain text instead, one line per row, for tests to assert on. Packagers
+//! can render each step at the sizes they ship for (`COMMON_SIZES`, anything
+//! down to `ui::MIN_WIDTH`x`ui::MIN_HEIGHT`) to check that nothing is cut off.
+//!
+//! Frames are drawn at `SNAPSHOT_TIME` rather than the system clock, so the
+//! same `App` always gives the same frame, spinners and file ages included.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use ratatui::backend::TestBackend;
+use ratatui::buffer::Buffer;
+use ratatui::Terminal;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{ui, App};
+
+/// The time every snapshot is drawn at, in seconds since the Unix epoch:
+/// 2024-01-01 00:00 UTC.
+pub const SNAPSHOT_TIME: u64 = 1_704_067_200;
+
+/// Terminal sizes (columns, rows) worth checking a layout at: the classic
+/// 80x24, a compact split pane, the smallest that is still drawn, and a
+/// large window.
+pub const COMMON_SIZES: [(u16, u16); 4] = [
+    (80, 24),
+    (60, 16),
+    (ui::MIN_WIDTH, ui::MIN_HEIGHT),
+    (160, 48),
+];
+
+/// `SNAPSHOT_TIME` as a `SystemTime`.
+pub fn snapshot_time() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(SNAPSHOT_TIME)
+}
+
+/// Draw `app` on a `width` x `height` screen.
+///
+/// # Errors
+///
+/// Returns an error if the frame cannot be drawn.
+pub fn render(app: &App, width: u16, height: u16) -> Result<Buffer> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+    terminal.draw(|frame| ui::draw_at(frame, app, snapshot_time()))?;
+    Ok(terminal.backend().buffer().clone())
+}
+
+/// Draw `app` on a `width` x `height` screen and return it as text.
+pub fn render_to_string(app: &App, width: u16, height: u16) -> Result<String> {
+    Ok(buffer_to_string(&render(app, width, height)?))
+}
+
+/// The symbols of `buffer`, one line per row with trailing blanks removed.
+/// Styles are dropped, and a wide character takes up its columns once.
+pub fn buffer_to_string(buffer: &Buffer) -> String {
+    let width = buffer.area.width as usize;
+    let mut lines = Vec::new();
+    for row in buffer.content.chunks(width.max(1)) {
+        let mut line = String::new();
+        let mut covered = 0;
+        for cell in row {
+            if covered > 0 {
+                covered -= 1;
+                continue;
+            }
+            let symbol = cell.symbol();
+            covered = symbol.width().saturating_sub(1);
+            line.push_str(symbol);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{DeviceProvider, MockDevices};
+    use crate::Config;
+    use crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::layout::Rect;
+    use ratatui::text::Line;
+    use ratatui::widgets::Widget;
+
+    #[test]
+    fn confirm_screen_fits_common_sizes() {
+        let image =
+            std::env::temp_dir().join(format!("flashr_tui_snapshot_{}.iso", std::process::id()));
+        let mut bytes = vec![0u8; 1024];
+        bytes[446] = 0x80;
+        bytes[510] = 0x55;
+        bytes[511] = 0xAA;
+        std::fs::write(&image, bytes).unwrap();
+        let mock = MockDevices::default().with_disk("sdb", "Cruzer", 16_000_000_000, true);
+        let mut app = App::new(
+            Some(image.clone()),
+            &[],
+            false,
+            mock.list(false).unwrap(),
+            Config::default(),
+            Box::new(mock),
+        );
+        ui::handle_key(&mut app, KeyEvent::from(KeyCode::Enter));
+
+        let screen = render_to_string(&app, 80, 24).unwrap();
+        assert_eq!(screen, render_to_string(&app, 80, 24).unwrap());
+        assert_eq!(screen.split('\n').count(), 24);
+        assert!(screen.contains("Device: /dev/sdb"));
+        assert!(screen.contains("DRY RUN"));
+        for (width, height) in COMMON_SIZES {
+            let screen = render_to_string(&app, width, height).unwrap();
+            assert!(!screen.contains("Terminal too small"), "{width}x{height}");
+        }
+        let tiny = render_to_string(&app, ui::MIN_WIDTH - 1, ui::MIN_HEIGHT).unwrap();
+        assert!(tiny.contains("Terminal too small"));
+        let _ = std::fs::remove_file(image);
+    }
+
+    #[test]
+    fn wide_characters_are_written_once() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 8, 2));
+        Line::from("ab 日本 ").render(Rect::new(0, 0, 8, 1), &mut buffer);
+        assert_eq!(buffer_to_string(&buffer), "ab 日本\n");
+    }
+}
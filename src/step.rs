@@ -0,0 +1,699 @@
+//! The steps of the TUI's state machine and the data each one owns.
+//!
+//! A step carries what only matters while it is shown: the running flash and
+//! its progress, the outcome on the Result screen, the side steps' tasks and
+//! results, the typed confirmation. Entering a step builds its data and
+//! leaving drops it, so nothing from one visit (a finished wipe's result, a
+//! stale progress line) can leak into the next. State that is kept while
+//! going back and forth, like the file picker and the device list and
+//! selection, stays on `App`.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::bench::{self, BenchResult};
+use crate::cache::CachedImage;
+use crate::capacity::CapacityReport;
+use crate::device::Disk;
+use crate::download::{self, DownloadControl};
+use crate::flash::{self, DevicePartitionInfo, FlashPhase};
+use crate::format::Filesystem;
+use crate::report::FlashReport;
+use crate::scan::ScanReport;
+use crate::seed::Seed;
+use crate::stage;
+use crate::task::{Panicked, Task};
+use crate::{FlashFailure, FlashResult};
+
+/// Application step/state in the state machine.
+///
+/// The application flows through these states in order:
+/// 1. `Image` - User selects an ISO file via file picker, or a URL or catalog
+///    entry, which goes through `Download` first
+/// 2. `Device` - User selects a target USB device from device list
+/// 3. `Confirm` - User reviews selection and confirms before flashing
+/// 4. `ConvertIso` - User confirms in-place isohybrid conversion (if non-hybrid)
+/// 5. `ConfirmWipe` - User confirms overwriting existing partitions on device
+/// 6. `Flashing` - Flash operation in progress (non-interactive)
+/// 7. `Result` - Flash operation completed; displays result
+/// 8. `Error` - An error occurred during operation
+///
+/// `Seed` is a side step of `Result` for putting a cloud-init seed on the
+/// flashed devices.
+///
+/// `Benchmark`, `Wipe`, and `Format` are side steps reachable from `Device` for testing a
+/// stick's speed, clearing leftover boot records, and returning it to normal use.
+///
+/// User can go back from `Device` -> `Image`, `Confirm` -> `Device`, `ConfirmWipe` -> `Confirm`,
+/// or from any side step back to `Device`.
+#[derive(Debug)]
+pub enum Step {
+    /// User is selecting ISO image file from filesystem
+    Image,
+    /// An image chosen by URL or from the catalog is being downloaded
+    Download(Box<DownloadRun>),
+    /// User is managing the images in the download cache
+    Cache(CacheView),
+    /// User is selecting target USB device
+    Device,
+    /// User is reviewing selection before flashing, with the device name
+    /// typed so far (required in execute mode)
+    Confirm(String),
+    /// User is confirming in-place isohybrid conversion of a non-hybrid ISO
+    ConvertIso,
+    /// User is confirming overwrite of the partitions found on the target devices
+    ConfirmWipe(DevicePartitionInfo),
+    /// Flashing is in progress; non-interactive
+    Flashing(Box<FlashRun>),
+    /// Flash operation completed; showing result (success or failure)
+    Result(Outcome),
+    /// An error occurred; showing error message with options to go back, retry,
+    /// or rescan. Holds the step the error happened in, where 'b' returns to.
+    Error(Box<Step>),
+    /// User is testing a device's read/write speed or real capacity
+    Benchmark(DeviceTests),
+    /// User is quick-wiping a device
+    Wipe(DeviceJob),
+    /// User is formatting a device with a fresh filesystem
+    Format(FormatJob),
+    /// User is writing a cloud-init seed to the flashed devices
+    Seed(Box<SeedJob>),
+}
+
+impl Step {
+    /// The Confirm step with nothing typed yet.
+    pub fn confirm() -> Self {
+        Step::Confirm(String::new())
+    }
+
+    /// Whether a background task of this step (flash, download, test, wipe,
+    /// format, seed, or re-verification) is still running.
+    pub fn busy(&self) -> bool {
+        match self {
+            Step::Flashing(_) | Step::Download(_) => true,
+            Step::Benchmark(tests) => tests.running.is_some(),
+            Step::Wipe(job) => job.running(),
+            Step::Format(format) => format.job.running(),
+            Step::Seed(seed) => seed.task.is_some(),
+            Step::Cache(view) => view.verifying.is_some(),
+            _ => false,
+        }
+    }
+
+    /// The device a side step (benchmark, wipe, or format) works on.
+    pub fn side_disk(&self) -> Option<&Disk> {
+        match self {
+            Step::Benchmark(tests) => Some(&tests.disk),
+            Step::Wipe(job) => Some(&job.disk),
+            Step::Format(format) => Some(&format.job.disk),
+            _ => None,
+        }
+    }
+}
+
+/// The download cache screen.
+///
+/// # Fields
+///
+/// * `images` - Cached images, most recently used first
+/// * `selected` - Index of the highlighted image
+/// * `verifying` - Re-verification running in the background, with the image it checks
+/// * `checked` - Outcome of the latest re-verification of each image, by path
+#[derive(Debug)]
+pub struct CacheView {
+    pub images: Vec<CachedImage>,
+    pub selected: usize,
+    pub verifying: Option<(PathBuf, Task<anyhow::Result<bool>>)>,
+    pub checked: HashMap<PathBuf, Result<(), String>>,
+}
+
+impl CacheView {
+    /// The screen listing `images`, with the first one highlighted.
+    pub fn new(images: Vec<CachedImage>) -> Self {
+        Self {
+            images,
+            selected: 0,
+            verifying: None,
+            checked: HashMap::new(),
+        }
+    }
+
+    /// Replace the listed images, keeping the highlight on the same image
+    /// when it is still there.
+    pub fn set_images(&mut self, images: Vec<CachedImage>) {
+        let selected = self.selected_image().map(|image| image.path.clone());
+        self.images = images;
+        self.selected = selected
+            .and_then(|path| self.images.iter().position(|image| image.path == path))
+            .unwrap_or(self.selected)
+            .min(self.images.len().saturating_sub(1));
+    }
+
+    /// The highlighted image, if the cache is not empty.
+    pub fn selected_image(&self) -> Option<&CachedImage> {
+        self.images.get(self.selected)
+    }
+
+    /// Bytes the listed images take up.
+    pub fn total_size(&self) -> u64 {
+        self.images.iter().map(|image| image.size).sum()
+    }
+}
+
+/// How long the download speed is averaged over.
+const RATE_WINDOW: Duration = Duration::from_secs(3);
+
+/// A running download and what it has reported so far.
+///
+/// # Fields
+///
+/// * `name` - What is downloaded, for display (e.g., "Debian 12.7 amd64")
+/// * `url` - Where it is downloaded from; the first mirror until the download
+///   thread reports the one it picked
+/// * `task` - The download thread, yielding the path of the verified image
+/// * `control` - Pauses and cancels the download
+/// * `done` - Bytes downloaded so far
+/// * `total` - Size announced by the server, if it announced one
+/// * `rate` - Bytes per second over the last few seconds, while transferring
+/// * `phase` - Latest message that was not a byte count (e.g., "Verifying download...")
+#[derive(Debug)]
+pub struct DownloadRun {
+    pub name: String,
+    pub url: String,
+    pub task: Task<anyhow::Result<PathBuf>>,
+    pub control: DownloadControl,
+    pub done: u64,
+    pub total: Option<u64>,
+    pub rate: Option<f64>,
+    pub phase: String,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl DownloadRun {
+    /// A download that was just spawned as `task`, controlled by `control`.
+    pub fn new(
+        name: String,
+        url: String,
+        task: Task<anyhow::Result<PathBuf>>,
+        control: DownloadControl,
+    ) -> Self {
+        Self {
+            name,
+            url,
+            task,
+            control,
+            done: 0,
+            total: None,
+            rate: None,
+            phase: "Starting...".to_string(),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Take in one progress line from the download thread.
+    pub fn apply(&mut self, line: String) {
+        if let Some(url) = line.strip_prefix(download::MIRROR_PREFIX) {
+            self.url = url.to_string();
+            return;
+        }
+        match download::parse_download_progress(&line) {
+            Some((done, total)) => {
+                self.done = done;
+                self.total = total;
+                self.sample(Instant::now());
+            }
+            None => self.phase = line,
+        }
+    }
+
+    /// Note the current byte count at `now` and update `rate`. A paused
+    /// download has no rate, and starts afresh when resumed.
+    fn sample(&mut self, now: Instant) {
+        if self.control.is_paused() {
+            self.samples.clear();
+            self.rate = None;
+            return;
+        }
+        self.samples.push_back((now, self.done));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > RATE_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        let (at, done) = self.samples[0];
+        let elapsed = now.duration_since(at).as_secs_f64();
+        if elapsed > 0.0 {
+            self.rate = Some(self.done.saturating_sub(done) as f64 / elapsed);
+        }
+    }
+
+    /// Time left at the current rate, when both the size and a rate are known.
+    pub fn remaining(&self) -> Option<Duration> {
+        let rate = self.rate.filter(|rate| *rate > 0.0)?;
+        let left = self.total?.saturating_sub(self.done);
+        Some(Duration::from_secs_f64(left as f64 / rate))
+    }
+}
+
+/// The running flash and what it has reported so far.
+///
+/// # Fields
+///
+/// * `task` - The flash thread, streaming its progress lines and result
+/// * `started` - When the flash was started
+/// * `report` - Summary being filled in from the progress lines
+/// * `progress` - Latest progress message
+/// * `total` - Total bytes to flash per device (the image size), if known
+/// * `done` - Bytes written so far to the current device, counting what a
+///   resumed write found already there
+/// * `resumed_at` - Offset the current device's write resumed at (0 when it started over)
+/// * `rate` - Latest transfer rate reported by the backend (e.g., "24.7 MB/s")
+/// * `phase` - Latest message that was not a byte count (e.g., "Verifying flash integrity...")
+/// * `stage` - Phase the flash thread announced last (stage, write, sync, verify, post-flash)
+/// * `staged` - Progress of copying the image into RAM as (done, total) bytes,
+///   once the flash thread started staging it
+/// * `verify` - Verification progress as (done, total) bytes, once reported
+/// * `elevation` - Elevation tool used for the write (or "none (running as root)")
+/// * `command` - Exact write command line, as reported by the flash thread
+#[derive(Debug)]
+pub struct FlashRun {
+    pub task: Task<Result<(), FlashFailure>>,
+    pub started: Instant,
+    pub report: FlashReport,
+    pub progress: String,
+    pub total: Option<u64>,
+    pub done: u64,
+    pub resumed_at: u64,
+    pub rate: Option<String>,
+    pub phase: String,
+    pub stage: Option<FlashPhase>,
+    pub staged: Option<(u64, u64)>,
+    pub verify: Option<(u64, u64)>,
+    pub elevation: Option<String>,
+    pub command: Option<String>,
+}
+
+impl FlashRun {
+    /// A flash that was just spawned as `task`.
+    pub fn new(
+        task: Task<Result<(), FlashFailure>>,
+        total: Option<u64>,
+        report: FlashReport,
+    ) -> Self {
+        Self {
+            task,
+            started: Instant::now(),
+            report,
+            progress: "Starting...".to_string(),
+            total,
+            done: 0,
+            resumed_at: 0,
+            rate: None,
+            phase: "Starting...".to_string(),
+            stage: None,
+            staged: None,
+            verify: None,
+            elevation: None,
+            command: None,
+        }
+    }
+
+    /// Take in one progress line from the flash thread.
+    pub fn apply(&mut self, line: String) {
+        self.report.apply(&line);
+        if let Some(elevation) = line.strip_prefix(flash::ELEVATION_PREFIX) {
+            self.elevation = Some(elevation.to_string());
+            return;
+        }
+        if let Some(command) = line.strip_prefix(flash::COMMAND_PREFIX) {
+            self.command = Some(command.to_string());
+            return;
+        }
+        if let Some(phase) = line.strip_prefix(flash::PHASE_PREFIX) {
+            self.stage = FlashPhase::parse(phase);
+            // Each device of a batch stages its own copy and starts over
+            // with a fresh write.
+            if self.stage == Some(FlashPhase::Stage) {
+                self.staged = Some((0, self.total.unwrap_or(0)));
+            }
+            if self.stage == Some(FlashPhase::Write) {
+                self.done = 0;
+                self.resumed_at = 0;
+                self.verify = None;
+            }
+            return;
+        }
+        if let Some(offset) = line.strip_prefix(flash::RESUME_PREFIX) {
+            self.resumed_at = offset.parse().unwrap_or(0);
+            self.done = self.resumed_at;
+        }
+        if line.starts_with(flash::IMAGE_HASH_PREFIX) {
+            return;
+        }
+        if let Some(staged) = stage::parse_stage_progress(&line) {
+            self.staged = Some(staged);
+            return;
+        }
+        // Verdicts and labels are also shown as the current phase.
+        if let Some(verified) = flash::parse_verify_progress(&line) {
+            self.verify = Some(verified);
+            return;
+        }
+        if let Some(bytes) = flash::parse_dd_bytes(&line) {
+            self.done = self.resumed_at + bytes;
+            self.rate = flash::parse_dd_rate(&line);
+        } else {
+            self.phase = line.clone();
+        }
+        self.progress = line;
+    }
+
+    /// Whether the flash is in a phase without byte progress (starting,
+    /// syncing, verifying, labeling): the latest message was not a byte count.
+    pub fn indeterminate(&self) -> bool {
+        self.progress == self.phase
+    }
+}
+
+/// What the Result step shows.
+///
+/// # Fields
+///
+/// * `result` - How the flash (or dry run) ended
+/// * `report` - Summary of a real flash, saved from the Result screen; `None` for dry runs
+#[derive(Debug)]
+pub struct Outcome {
+    pub result: FlashResult,
+    pub report: Option<FlashReport>,
+}
+
+/// A wipe or format of one device: idle, running, or finished.
+///
+/// # Fields
+///
+/// * `disk` - Device the step was opened for
+/// * `progress` - Latest progress message (or the dry-run message)
+/// * `task` - The running operation
+/// * `result` - Outcome once finished (success or error message)
+#[derive(Debug)]
+pub struct DeviceJob {
+    pub disk: Disk,
+    pub progress: String,
+    pub task: Option<Task<Result<(), String>>>,
+    pub result: Option<Result<(), String>>,
+}
+
+impl DeviceJob {
+    /// An idle job for `disk`.
+    pub fn new(disk: Disk) -> Self {
+        Self {
+            disk,
+            progress: String::new(),
+            task: None,
+            result: None,
+        }
+    }
+
+    /// Whether the operation is running in the background.
+    pub fn running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Start `task`, clearing the last outcome.
+    pub fn start(&mut self, task: Task<Result<(), String>>) {
+        self.progress = "Starting...".to_string();
+        self.result = None;
+        self.task = Some(task);
+    }
+
+    /// Collect progress lines and the result. Returns the new lines for the
+    /// activity log.
+    pub fn poll(&mut self) -> Vec<String> {
+        let Some(task) = self.task.as_mut() else {
+            return Vec::new();
+        };
+        let (lines, result) = task.poll();
+        if let Some(last) = lines.last() {
+            self.progress = last.clone();
+        }
+        if let Some(result) = result {
+            self.task = None;
+            self.result = Some(result.unwrap_or_else(|panicked| Err(panicked.to_string())));
+        }
+        lines
+    }
+}
+
+/// The Format step: the chosen filesystem and label, and the format itself.
+///
+/// # Fields
+///
+/// * `job` - The format of the device
+/// * `fs` - Filesystem to create
+/// * `label` - Volume label typed so far
+#[derive(Debug)]
+pub struct FormatJob {
+    pub job: DeviceJob,
+    pub fs: Filesystem,
+    pub label: String,
+}
+
+impl FormatJob {
+    /// The Format step for `disk`, starting with FAT32 and no label.
+    pub fn new(disk: Disk) -> Self {
+        Self {
+            job: DeviceJob::new(disk),
+            fs: Filesystem::Fat32,
+            label: String::new(),
+        }
+    }
+
+    /// Whether the label is being typed: nothing has run yet.
+    pub fn editing(&self) -> bool {
+        self.job.result.is_none() && !self.job.running()
+    }
+}
+
+/// The cloud-init step: the seed being edited, and writing it.
+///
+/// # Fields
+///
+/// * `outcome` - The Result step this was opened from, shown again when it is left
+/// * `seed` - The seed files; `seed.user_data` is the text being edited
+/// * `cursor` - Byte offset of the cursor in the user-data
+/// * `source` - Seed directory or user-data file the seed was loaded from, if any
+/// * `progress` - Latest progress message (or the dry-run message)
+/// * `task` - The seed being written
+/// * `result` - Outcome once finished (success or error message)
+#[derive(Debug)]
+pub struct SeedJob {
+    pub outcome: Outcome,
+    pub seed: Seed,
+    pub cursor: usize,
+    pub source: Option<PathBuf>,
+    pub progress: String,
+    pub task: Option<Task<Result<(), String>>>,
+    pub result: Option<Result<(), String>>,
+}
+
+impl SeedJob {
+    /// The editor for the devices flashed in `outcome`, starting from the
+    /// template.
+    pub fn new(outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            seed: Seed::default(),
+            cursor: 0,
+            source: None,
+            progress: String::new(),
+            task: None,
+            result: None,
+        }
+    }
+
+    /// Whether the user-data is being edited: nothing has run yet.
+    pub fn editing(&self) -> bool {
+        self.result.is_none() && self.task.is_none()
+    }
+
+    /// Collect progress lines and the result. Returns the new lines for the
+    /// activity log.
+    pub fn poll(&mut self) -> Vec<String> {
+        let Some(task) = self.task.as_mut() else {
+            return Vec::new();
+        };
+        let (lines, result) = task.poll();
+        if let Some(last) = lines.last() {
+            self.progress = last.clone();
+        }
+        if let Some(result) = result {
+            self.task = None;
+            self.result = Some(result.unwrap_or_else(|panicked| Err(panicked.to_string())));
+        }
+        lines
+    }
+}
+
+/// A running speed test, capacity test, or surface scan. Only one runs at a time.
+#[derive(Debug)]
+pub enum RunningTest {
+    Speed(Task<Result<BenchResult, String>>),
+    Capacity(Task<Result<CapacityReport, String>>),
+    Scan(Task<Result<ScanReport, String>>),
+}
+
+/// The outcome of the last test (success or error message).
+#[derive(Debug)]
+pub enum TestOutcome {
+    Speed(Result<BenchResult, String>),
+    Capacity(Result<CapacityReport, String>),
+    Scan(Result<ScanReport, String>),
+}
+
+/// The Benchmark step: the device tests and their results.
+///
+/// # Fields
+///
+/// * `disk` - Device the step was opened for
+/// * `progress` - Latest progress message
+/// * `fraction` - Work done so far and total during a capacity test or surface scan
+/// * `running` - The test in progress
+/// * `outcome` - Result of the last test
+#[derive(Debug)]
+pub struct DeviceTests {
+    pub disk: Disk,
+    pub progress: String,
+    pub fraction: Option<(u64, u64)>,
+    pub running: Option<RunningTest>,
+    pub outcome: Option<TestOutcome>,
+}
+
+impl DeviceTests {
+    /// No test run yet on `disk`.
+    pub fn new(disk: Disk) -> Self {
+        Self {
+            disk,
+            progress: String::new(),
+            fraction: None,
+            running: None,
+            outcome: None,
+        }
+    }
+
+    /// Start `test`, clearing the last outcome.
+    pub fn start(&mut self, test: RunningTest) {
+        self.progress = "Starting...".to_string();
+        self.fraction = None;
+        self.outcome = None;
+        self.running = Some(test);
+    }
+
+    /// Collect progress lines and the outcome. Returns the progress texts for
+    /// the activity log.
+    pub fn poll(&mut self) -> Vec<String> {
+        fn finish<T>(result: Result<Result<T, String>, Panicked>) -> Result<T, String> {
+            result.unwrap_or_else(|panicked| Err(panicked.to_string()))
+        }
+        let (lines, outcome) = match self.running.as_mut() {
+            None => return Vec::new(),
+            Some(RunningTest::Speed(task)) => {
+                let (lines, result) = task.poll();
+                (lines, result.map(|r| TestOutcome::Speed(finish(r))))
+            }
+            Some(RunningTest::Capacity(task)) => {
+                let (lines, result) = task.poll();
+                (lines, result.map(|r| TestOutcome::Capacity(finish(r))))
+            }
+            Some(RunningTest::Scan(task)) => {
+                let (lines, result) = task.poll();
+                (lines, result.map(|r| TestOutcome::Scan(finish(r))))
+            }
+        };
+
+        let mut texts = Vec::new();
+        for line in &lines {
+            let (fraction, text) = bench::split_progress(line);
+            if fraction.is_some() {
+                self.fraction = fraction;
+            }
+            self.progress = text.to_string();
+            texts.push(text.to_string());
+        }
+        if let Some(outcome) = outcome {
+            // The speed test leaves its last pass's progress on screen.
+            if !matches!(outcome, TestOutcome::Speed(_)) {
+                self.fraction = None;
+            }
+            self.running = None;
+            self.outcome = Some(outcome);
+        }
+        texts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_run_tracks_progress_lines() {
+        let task = Task::spawn("test", |_| Ok(()));
+        let report = FlashReport::new("/isos/a.iso".into(), &[]);
+        let mut run = FlashRun::new(task, Some(4_000_000), report);
+        assert!(run.indeterminate());
+
+        run.apply(format!("{}stage", flash::PHASE_PREFIX));
+        assert_eq!(run.staged, Some((0, 4_000_000)));
+        run.apply(format!("{}1000000/4000000", stage::STAGE_PREFIX));
+        assert_eq!(run.staged, Some((1_000_000, 4_000_000)));
+        assert_eq!(run.done, 0);
+
+        run.apply(format!("{}write", flash::PHASE_PREFIX));
+        run.apply(format!("{}dd", flash::COMMAND_PREFIX));
+        run.apply("2000000 bytes (2.0 MB) copied, 1 s, 2.0 MB/s".to_string());
+        assert_eq!(run.stage, Some(FlashPhase::Write));
+        assert_eq!(run.command.as_deref(), Some("dd"));
+        assert_eq!(run.done, 2_000_000);
+        assert_eq!(run.rate.as_deref(), Some("2.0 MB/s"));
+        assert!(!run.indeterminate());
+
+        run.apply("Syncing...".to_string());
+        assert!(run.indeterminate());
+        run.apply(format!("{}write", flash::PHASE_PREFIX));
+        assert_eq!(run.done, 0);
+
+        run.apply(format!("{}3000000", flash::RESUME_PREFIX));
+        run.apply("500000 bytes (500 kB) copied, 1 s, 0.5 MB/s".to_string());
+        assert_eq!(run.done, 3_500_000);
+    }
+
+    #[test]
+    fn download_rate_covers_the_last_seconds_and_stops_when_paused() {
+        let task = Task::spawn("test", |_| Ok(PathBuf::from("/cache/a.iso")));
+        let control = DownloadControl::default();
+        let mut run = DownloadRun::new("a".into(), "https://x/a.iso".into(), task, control);
+        run.apply(format!("{}https://y/a.iso", download::MIRROR_PREFIX));
+        assert_eq!(run.url, "https://y/a.iso");
+        run.apply(format!("{}0/10000000", download::DOWNLOAD_PREFIX));
+        assert_eq!(run.total, Some(10_000_000));
+        assert_eq!(run.remaining(), None);
+
+        let start = Instant::now();
+        for (secs, done) in [(0, 0), (1, 1_000_000), (5, 3_000_000), (6, 4_000_000)] {
+            run.done = done;
+            run.sample(start + Duration::from_secs(secs));
+        }
+        // Only the samples at 5 s and 6 s are within the window.
+        assert_eq!(run.rate, Some(1_000_000.0));
+        assert_eq!(run.remaining(), Some(Duration::from_secs(6)));
+
+        run.control.pause();
+        run.sample(start + Duration::from_secs(7));
+        assert_eq!(run.rate, None);
+        run.apply("Download paused".to_string());
+        assert_eq!(run.phase, "Download paused");
+    }
+}
@@ -0,0 +1,252 @@
+//! Color themes for the TUI.
+//!
+//! Every style the UI draws with comes from a `Theme`, so the whole screen can
+//! be re-colored at once. A few themes are built in; the config file picks one
+//! with `"theme"` and may override individual colors with `"theme_colors"`.
+//! The `FLASHR_THEME` environment variable takes precedence over the config,
+//! and `NO_COLOR` selects the monochrome theme when neither names one.
+
+use ratatui::style::{Color, Modifier, Style};
+use std::str::FromStr;
+
+use crate::config::Config;
+
+/// Names of the built-in themes, in the order Ctrl+T cycles through them.
+pub const BUILTIN_THEMES: [&str; 5] = [
+    "default",
+    "light",
+    "high-contrast",
+    "colorblind",
+    "monochrome",
+];
+
+/// Environment variable naming the theme to use, overriding the config file.
+pub const THEME_ENV: &str = "FLASHR_THEME";
+
+/// Styles used by the UI.
+///
+/// # Fields
+///
+/// * `name` - Theme name shown when switching themes
+/// * `logo` - Title banner
+/// * `success` - Completed operations
+/// * `error` - Failures, destructive warnings, and internal disks
+/// * `warning` - Cautions and values that deserve attention
+/// * `muted` - Footer and secondary text
+/// * `highlight` - Selected list row
+/// * `progress` - Progress gauges
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub logo: Style,
+    pub success: Style,
+    pub error: Style,
+    pub warning: Style,
+    pub muted: Style,
+    pub highlight: Style,
+    pub progress: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            logo: Style::default().fg(Color::Green),
+            success: Style::default().fg(Color::Green),
+            error: Style::default().fg(Color::Red),
+            warning: Style::default().fg(Color::Yellow),
+            muted: Style::default().fg(Color::Gray),
+            highlight: Style::default().fg(Color::Black).bg(Color::Cyan),
+            progress: Style::default().fg(Color::Green),
+        }
+    }
+}
+
+impl Theme {
+    /// Look up a built-in theme by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            // Yellow and light gray are unreadable on white backgrounds.
+            "light" => Some(Self {
+                name: "light".to_string(),
+                logo: Style::default().fg(Color::Blue),
+                success: Style::default().fg(Color::Green),
+                error: Style::default().fg(Color::Red),
+                warning: Style::default().fg(Color::Magenta),
+                muted: Style::default().fg(Color::DarkGray),
+                highlight: Style::default().fg(Color::White).bg(Color::Blue),
+                progress: Style::default().fg(Color::Blue),
+            }),
+            // Basic ANSI colors only, and success and failure differ by
+            // background and attributes rather than by hue.
+            "high-contrast" => Some(Self {
+                name: "high-contrast".to_string(),
+                logo: Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                success: Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                error: Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Red)
+                    .add_modifier(Modifier::BOLD),
+                warning: Style::default().fg(Color::Black).bg(Color::Yellow),
+                muted: Style::default().fg(Color::White),
+                highlight: Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+                progress: Style::default().fg(Color::White),
+            }),
+            // Success is blue rather than green, so it never has to be told
+            // apart from red by hue alone; errors are also underlined.
+            "colorblind" => Some(Self {
+                name: "colorblind".to_string(),
+                logo: Style::default().fg(Color::Blue),
+                success: Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+                error: Style::default()
+                    .fg(Color::Red)
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                warning: Style::default().fg(Color::Yellow),
+                muted: Style::default().fg(Color::Gray),
+                highlight: Style::default().fg(Color::Black).bg(Color::LightBlue),
+                progress: Style::default().fg(Color::Blue),
+            }),
+            // No colors at all; emphasis comes from text attributes only.
+            "monochrome" => Some(Self {
+                name: "monochrome".to_string(),
+                logo: Style::default(),
+                success: Style::default(),
+                error: Style::default().add_modifier(Modifier::UNDERLINED),
+                warning: Style::default(),
+                muted: Style::default().add_modifier(Modifier::DIM),
+                highlight: Style::default().add_modifier(Modifier::REVERSED),
+                progress: Style::default(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Build the theme selected by the environment or the config file.
+    ///
+    /// Unknown theme names fall back to the default theme; color overrides that
+    /// don't parse are ignored.
+    pub fn from_config(config: &Config) -> Self {
+        let env_theme = std::env::var(THEME_ENV).ok();
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let mut theme = selected_name(config, env_theme.as_deref(), no_color)
+            .and_then(Self::builtin)
+            .unwrap_or_default();
+
+        let colors = &config.theme_colors;
+        let overrides = [
+            (&colors.logo, &mut theme.logo),
+            (&colors.success, &mut theme.success),
+            (&colors.error, &mut theme.error),
+            (&colors.warning, &mut theme.warning),
+            (&colors.muted, &mut theme.muted),
+            (&colors.progress, &mut theme.progress),
+        ];
+        for (value, style) in overrides {
+            if let Some(color) = parse_color(value.as_deref()) {
+                *style = style.fg(color);
+            }
+        }
+        if let Some(color) = parse_color(colors.highlight_fg.as_deref()) {
+            theme.highlight = theme.highlight.fg(color);
+        }
+        if let Some(color) = parse_color(colors.highlight_bg.as_deref()) {
+            theme.highlight = theme.highlight.bg(color);
+        }
+        theme
+    }
+
+    /// The built-in theme after this one, wrapping around.
+    pub fn next(&self) -> Self {
+        let index = BUILTIN_THEMES
+            .iter()
+            .position(|name| *name == self.name)
+            .map_or(0, |i| (i + 1) % BUILTIN_THEMES.len());
+        Self::builtin(BUILTIN_THEMES[index]).unwrap_or_default()
+    }
+}
+
+/// Name of the theme to start with: `FLASHR_THEME`, then the config file, then
+/// monochrome if `NO_COLOR` is set.
+fn selected_name<'a>(
+    config: &'a Config,
+    env_theme: Option<&'a str>,
+    no_color: bool,
+) -> Option<&'a str> {
+    env_theme
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .or(config.theme.as_deref())
+        .or(no_color.then_some("monochrome"))
+}
+
+/// Parse a color name ("red", "lightblue") or hex value ("#ff8800").
+fn parse_color(value: Option<&str>) -> Option<Color> {
+    Color::from_str(value?.trim()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ThemeColors;
+
+    #[test]
+    fn from_config_applies_overrides_to_named_theme() {
+        let config = Config {
+            theme: Some("light".to_string()),
+            theme_colors: ThemeColors {
+                error: Some("#ff8800".to_string()),
+                warning: Some("not-a-color".to_string()),
+                ..ThemeColors::default()
+            },
+            ..Config::default()
+        };
+        let theme = Theme::from_config(&config);
+        assert_eq!(theme.name, "light");
+        assert_eq!(theme.error.fg, Some(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(theme.warning.fg, Some(Color::Magenta));
+    }
+
+    #[test]
+    fn next_cycles_through_builtins() {
+        let theme = Theme::default();
+        assert_eq!(theme.next().name, "light");
+        assert_eq!(theme.next().next().next().next().next().name, "default");
+    }
+
+    #[test]
+    fn environment_overrides_config_theme() {
+        let config = Config {
+            theme: Some("light".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(
+            selected_name(&config, Some("colorblind"), true),
+            Some("colorblind")
+        );
+        assert_eq!(selected_name(&config, Some(" "), true), Some("light"));
+        assert_eq!(
+            selected_name(&Config::default(), None, true),
+            Some("monochrome")
+        );
+        assert_eq!(selected_name(&Config::default(), None, false), None);
+    }
+
+    #[test]
+    fn accessible_themes_do_not_tell_success_from_error_by_red_and_green() {
+        for name in ["high-contrast", "colorblind"] {
+            let theme = Theme::builtin(name).unwrap();
+            assert_ne!(theme.success.fg, Some(Color::Green), "{name}");
+            assert_ne!(theme.error.add_modifier, Modifier::empty(), "{name}");
+        }
+    }
+}
@@ -3,21 +3,45 @@
 //! This module uses ratatui for rendering UI screens and crossterm for reading keyboard events.
 //! It dispatches events to step-specific handlers and renders the appropriate screen based on the current step.
 
+use std::cell::Cell;
+use std::time::{Duration, SystemTime};
+
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::layout::{Constraint, Direction, Layout};
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::layout::{Constraint, Direction, Layout, Margin};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, Paragraph, Wrap};
+use ratatui::widgets::{
+    Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Scrollbar, ScrollbarOrientation,
+    ScrollbarState, Wrap,
+};
 
-use crate::{App, AppExit, Step};
+use crate::device::Disk;
+use crate::flash::{DevicePartitionInfo, FlashPhase};
+use crate::modal::{Destructive, Modal, ModalKind};
+use crate::step::{
+    CacheView, DeviceJob, DeviceTests, DownloadRun, FlashRun, FormatJob, Outcome, SeedJob,
+    TestOutcome,
+};
+use crate::{clipboard, download, lineedit, App, AppExit, Step};
 
 /// ASCII art logo for the title banner, loaded from logo.txt at compile time.
 const LOGO: &str = include_str!("logo.txt");
 
+thread_local! {
+    /// The time `draw_at` is drawing at; `None` follows the system clock.
+    static DRAW_TIME: Cell<Option<SystemTime>> = const { Cell::new(None) };
+}
+
+/// The time the current frame is drawn at: the spinner frame and file ages
+/// are derived from it.
+fn now() -> SystemTime {
+    DRAW_TIME.get().unwrap_or_else(SystemTime::now)
+}
+
 /// Handle a keyboard event for the current step.
 ///
-/// Routes the event to the appropriate step handler.
-/// 'q' always quits the application.
+/// Routes the event to the open modal, if any, and otherwise to the
+/// appropriate step handler. 'q' quits unless something is running.
 ///
 /// # Arguments
 ///
@@ -28,238 +52,652 @@ const LOGO: &str = include_str!("logo.txt");
 ///
 /// `Some(AppExit)` to exit the application, `None` to continue running.
 pub fn handle_key(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    if let Some(modal) = app.modal.as_mut() {
+        if let Some(response) = modal.handle_key(key) {
+            let action = modal.action.clone();
+            app.modal = None;
+            app.resolve_modal(action, response);
+        }
+        return None;
+    }
+    if key.code == KeyCode::Char('t') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.theme = app.theme.next();
+        app.toast(format!("Theme: {}", app.theme.name));
+        return None;
+    }
+    if key.code == KeyCode::Char('o') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.compact = !app.compact;
+        app.toast(if app.compact {
+            "Compact mode on"
+        } else {
+            "Compact mode off"
+        });
+        return None;
+    }
+    if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.reload_config();
+        return None;
+    }
+    // '?' is typed into text fields, where only F1 pages the hints.
+    if key.code == KeyCode::F(1) || (key.code == KeyCode::Char('?') && !in_text_field(app)) {
+        let step = std::mem::discriminant(&app.step);
+        let page = match app.hint_page {
+            (paged, page) if paged == step => page + 1,
+            _ => 1,
+        };
+        app.hint_page = (step, page);
+        return None;
+    }
+    if app.log_open {
+        handle_log_pane(app, key);
+        return None;
+    }
+    if app.history.is_some() {
+        handle_history_pane(app, key);
+        return None;
+    }
+    let ctrl_l = key.code == KeyCode::Char('l') && key.modifiers.contains(KeyModifiers::CONTROL);
+    if ctrl_l || (key.code == KeyCode::Char('l') && !in_text_field(app)) {
+        app.log_open = true;
+        app.log_scroll = 0;
+        return None;
+    }
+    if key.code == KeyCode::F(2) || (key.code == KeyCode::Char('h') && !in_text_field(app)) {
+        app.open_history();
+        return None;
+    }
     if key.code == KeyCode::Char('q') {
-        if app.step == Step::Flashing {
-            app.status = "Cannot quit while flashing is in progress.".to_string();
+        if app.step.busy() {
+            app.status = match app.step {
+                Step::Flashing(_) => "Cannot quit while flashing is in progress.",
+                Step::Download(_) => "Cannot quit while downloading; press 'c' to cancel first.",
+                Step::Cache(_) => "Cannot quit while an image is being verified.",
+                Step::Benchmark(_) => "Cannot quit while a benchmark is running.",
+                Step::Wipe(_) => "Cannot quit while a wipe is running.",
+                Step::Seed(_) => "Cannot quit while the cloud-init seed is being written.",
+                _ => "Cannot quit while formatting is in progress.",
+            }
+            .to_string();
             return None;
         }
+        // The Format step has a text field; 'q' is typed into the label there.
+        if let Step::Format(format) = &app.step {
+            if format.job.result.is_none() {
+                return handle_format_step(app, key);
+            }
+        }
+        // Likewise for the cloud-init editor.
+        if let Step::Seed(job) = &app.step {
+            if job.editing() {
+                return handle_seed_step(app, key);
+            }
+        }
+        // Likewise for the typed device-name confirmation in execute mode.
+        if matches!(app.step, Step::Confirm(_)) && app.execute {
+            return handle_confirm_step(app, key);
+        }
+        // And for the device list filter while it is being typed.
+        if matches!(app.step, Step::Device) && app.device_filter_editing {
+            return handle_device_step(app, key);
+        }
         return Some(AppExit::Quit);
     }
 
     match app.step {
         Step::Image => handle_image_step(app, key),
         Step::Device => handle_device_step(app, key),
-        Step::Confirm => handle_confirm_step(app, key),
+        Step::Confirm(_) => handle_confirm_step(app, key),
         Step::ConvertIso => handle_convert_iso_step(app, key),
-        Step::ConfirmWipe => handle_confirm_wipe_step(app, key),
-        Step::Flashing => handle_flashing_step(app, key),
-        Step::Result => handle_result_step(app, key),
-        Step::Error => handle_done_step(app, key),
+        Step::ConfirmWipe(_) => handle_confirm_wipe_step(app, key),
+        Step::Download(_) => handle_download_step(app, key),
+        Step::Cache(_) => handle_cache_step(app, key),
+        Step::Flashing(_) => handle_flashing_step(app, key),
+        Step::Result(_) => handle_result_step(app, key),
+        Step::Error(_) => handle_error_step(app, key),
+        Step::Benchmark(_) => handle_benchmark_step(app, key),
+        Step::Wipe(_) => handle_wipe_step(app, key),
+        Step::Format(_) => handle_format_step(app, key),
+        Step::Seed(_) => handle_seed_step(app, key),
     }
 }
 
 /// Number of entries to skip for PageUp/PageDown.
 const PAGE_SIZE: usize = 15;
 
+/// Whether the current step has a text field that plain letter keys type into.
+fn in_text_field(app: &App) -> bool {
+    match &app.step {
+        Step::Image => true,
+        Step::Format(format) => format.editing(),
+        Step::Seed(job) => job.editing(),
+        Step::Confirm(_) => app.execute,
+        Step::Device => app.device_filter_editing,
+        _ => false,
+    }
+}
+
+/// Scroll or close the log pane. `log_scroll` counts lines up from the newest
+/// entry and is clamped when drawing.
+fn handle_log_pane(app: &mut App, key: KeyEvent) {
+    let len = app.activity.entries().len();
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('l') | KeyCode::Char('q') => app.log_open = false,
+        KeyCode::Up => app.log_scroll = (app.log_scroll + 1).min(len),
+        KeyCode::Down => app.log_scroll = app.log_scroll.saturating_sub(1),
+        KeyCode::PageUp => app.log_scroll = (app.log_scroll + PAGE_SIZE).min(len),
+        KeyCode::PageDown => app.log_scroll = app.log_scroll.saturating_sub(PAGE_SIZE),
+        KeyCode::Home => app.log_scroll = len,
+        KeyCode::End => app.log_scroll = 0,
+        _ => {}
+    }
+}
+
+/// Scroll or close the history pane. `history_scroll` counts entries down
+/// from the newest one.
+fn handle_history_pane(app: &mut App, key: KeyEvent) {
+    let last = app
+        .history
+        .as_ref()
+        .map_or(0, |entries| entries.len().saturating_sub(1));
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('q') | KeyCode::F(2) => {
+            app.history = None;
+        }
+        KeyCode::Up => app.history_scroll = app.history_scroll.saturating_sub(1),
+        KeyCode::Down => app.history_scroll = (app.history_scroll + 1).min(last),
+        KeyCode::PageUp => app.history_scroll = app.history_scroll.saturating_sub(PAGE_SIZE),
+        KeyCode::PageDown => app.history_scroll = (app.history_scroll + PAGE_SIZE).min(last),
+        KeyCode::Home => app.history_scroll = 0,
+        KeyCode::End => app.history_scroll = last,
+        _ => {}
+    }
+}
+
+/// Handle pasted text (a bracketed-paste event or the clipboard key).
+///
+/// Pastes go straight into the active text field instead of being replayed as
+/// keypresses, so characters like 'q' or digits can't trigger other keys. The
+/// typed device-name confirmation deliberately does not accept pastes.
+pub fn handle_paste(app: &mut App, text: &str) {
+    if let Some(modal) = app.modal.as_mut() {
+        modal.paste(text);
+        return;
+    }
+    match &mut app.step {
+        Step::Image => {
+            app.completion = None;
+            let path = clipboard::clean_path(text);
+            lineedit::insert_str(&mut app.image_input, &mut app.image_cursor, &path);
+        }
+        Step::Format(format) if format.editing() => {
+            let line = text.lines().next().unwrap_or_default();
+            format.label.push_str(line.trim());
+        }
+        Step::Seed(job) if job.editing() => {
+            let text = text.replace("\r\n", "\n").replace('\r', "\n");
+            lineedit::insert_str(&mut job.seed.user_data, &mut job.cursor, &text);
+        }
+        _ => {}
+    }
+}
+
 fn handle_image_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    if key.code != KeyCode::Tab {
+        app.completion = None;
+    }
+
     match key.code {
+        KeyCode::Enter if download::is_url(&app.image_input) => {
+            let url = app.image_input.trim().to_string();
+            app.download_url(&url, None);
+        }
         KeyCode::Enter => {
             if let Some(entry) = app.entries.get(app.entry_selected).cloned() {
                 if entry.is_dir {
                     app.cwd = entry.path;
                     app.reload_entries();
-                    app.image_input.clear();
+                    app.set_image_input(String::new());
                     app.status.clear();
                 } else {
-                    app.image_input = entry.path.display().to_string();
+                    app.set_image_input(entry.path.display().to_string());
                     if app.validate_image() {
                         app.refresh_iso_kind();
-                        app.step = Step::Device;
+                        app.enter_device_step();
                     }
                 }
             } else if !app.image_input.trim().is_empty() && app.validate_image() {
                 app.refresh_iso_kind();
-                app.step = Step::Device;
+                app.enter_device_step();
             }
         }
         KeyCode::Backspace => {
-            if !app.image_input.is_empty() {
-                app.image_input.pop();
-            } else if let Some(parent) = app.cwd.parent() {
-                app.cwd = parent.to_path_buf();
-                app.reload_entries();
+            if app.image_input.is_empty() {
+                if let Some(parent) = app.cwd.parent() {
+                    app.cwd = parent.to_path_buf();
+                    app.reload_entries();
+                }
+            } else {
+                lineedit::backspace(&mut app.image_input, &mut app.image_cursor);
             }
         }
-        KeyCode::Up => {
-            if app.entry_selected > 0 {
-                app.entry_selected -= 1;
-            }
+        KeyCode::Delete => {
+            lineedit::delete(&mut app.image_input, &mut app.image_cursor);
         }
-        KeyCode::Down => {
-            if app.entry_selected + 1 < app.entries.len() {
-                app.entry_selected += 1;
-            }
+        KeyCode::Left => {
+            lineedit::left(&app.image_input, &mut app.image_cursor);
+        }
+        KeyCode::Right => {
+            lineedit::right(&app.image_input, &mut app.image_cursor);
+        }
+        // Home/End edit the input while something is typed, otherwise they jump in the list.
+        KeyCode::Home if !app.image_input.is_empty() => {
+            app.image_cursor = 0;
+        }
+        KeyCode::End if !app.image_input.is_empty() => {
+            app.image_cursor = app.image_input.len();
+        }
+        KeyCode::Up if app.entry_selected > 0 => {
+            app.entry_selected -= 1;
+        }
+        KeyCode::Down if app.entry_selected + 1 < app.entries.len() => {
+            app.entry_selected += 1;
         }
         KeyCode::PageUp => {
             app.entry_selected = app.entry_selected.saturating_sub(PAGE_SIZE);
         }
-        KeyCode::PageDown => {
-            if !app.entries.is_empty() {
-                app.entry_selected =
-                    (app.entry_selected + PAGE_SIZE).min(app.entries.len() - 1);
-            }
+        KeyCode::PageDown if !app.entries.is_empty() => {
+            app.entry_selected = (app.entry_selected + PAGE_SIZE).min(app.entries.len() - 1);
         }
         KeyCode::Home => {
             app.entry_selected = 0;
         }
-        KeyCode::End => {
-            if !app.entries.is_empty() {
-                app.entry_selected = app.entries.len() - 1;
-            }
+        KeyCode::End if !app.entries.is_empty() => {
+            app.entry_selected = app.entries.len() - 1;
+        }
+        KeyCode::Tab if !app.image_input.is_empty() => {
+            app.complete_image_input();
         }
         KeyCode::Tab => {
             app.filter_iso_only = !app.filter_iso_only;
             app.reload_entries();
-            app.status = if app.filter_iso_only {
-                "Filter: ISO/IMG/RAW only".to_string()
+            app.toast(if app.filter_iso_only {
+                "Filter: ISO/IMG/RAW only"
             } else {
-                "Filter: showing all files".to_string()
-            };
+                "Filter: showing all files"
+            });
         }
         KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.show_hidden = !app.show_hidden;
-            app.reload_entries();
-            app.status = if app.show_hidden {
-                "Showing hidden files".to_string()
-            } else {
-                "Hidden files hidden".to_string()
-            };
+            toggle_hidden(app);
+        }
+        // Many terminals send Ctrl+H as Backspace, so '.' also toggles hidden
+        // files -- but only while nothing is typed, since '.' is common in paths.
+        KeyCode::Char('.') if app.image_input.is_empty() => {
+            toggle_hidden(app);
         }
         KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            app.image_input.clear();
+            app.set_image_input(String::new());
         }
-        KeyCode::Char(c) => {
-            if !key.modifiers.contains(KeyModifiers::CONTROL) {
-                app.image_input.push(c);
+        KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            match clipboard::read() {
+                Ok(text) => handle_paste(app, &text),
+                Err(err) => app.status = format!("Paste failed: {err:#}"),
             }
         }
+        KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            lineedit::delete_word(&mut app.image_input, &mut app.image_cursor);
+        }
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.toggle_bookmark();
+        }
+        // Digits are common in file names, so they only jump while nothing is typed.
+        KeyCode::Char(c @ '1'..='9') if app.image_input.is_empty() => {
+            app.open_bookmark(c as usize - '0' as usize);
+        }
+        // Like the digits, 'g' only opens the prompt while nothing is typed.
+        KeyCode::Char('g') if app.image_input.is_empty() => {
+            app.prompt_go_to_path();
+        }
+        KeyCode::Char('c') if app.image_input.is_empty() => {
+            app.open_catalog();
+        }
+        KeyCode::Char('d') if app.image_input.is_empty() => {
+            app.open_cache();
+        }
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.sort_mode = app.sort_mode.next();
+            app.reload_entries();
+            app.entry_selected = 0;
+            app.toast(format!("Sort: {}", app.sort_mode.label()));
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            lineedit::insert(&mut app.image_input, &mut app.image_cursor, c);
+        }
         _ => {}
     }
 
     None
 }
 
+fn toggle_hidden(app: &mut App) {
+    app.show_hidden = !app.show_hidden;
+    app.reload_entries();
+    app.toast(if app.show_hidden {
+        "Showing hidden files"
+    } else {
+        "Hidden files hidden"
+    });
+}
+
 fn handle_device_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    if app.device_filter_editing {
+        handle_device_filter(app, key);
+        return None;
+    }
+    // With everything filtered out, `selected` points at a hidden device.
+    let acts_on_device = matches!(
+        key.code,
+        KeyCode::Enter
+            | KeyCode::Char(' ')
+            | KeyCode::Char('t')
+            | KeyCode::Char('w')
+            | KeyCode::Char('m')
+    );
+    if acts_on_device && !app.devices.is_empty() && app.visible_devices().is_empty() {
+        app.status = "No device matches the filter (Esc clears it).".to_string();
+        return None;
+    }
+
     match key.code {
-        KeyCode::Up => {
-            if app.selected > 0 {
-                app.selected -= 1;
-            }
+        KeyCode::Up => move_device_selection(app, false),
+        KeyCode::Down => move_device_selection(app, true),
+        KeyCode::Char('/') => {
+            app.device_filter_editing = true;
         }
-        KeyCode::Down => {
-            if app.selected + 1 < app.devices.len() {
-                app.selected += 1;
-            }
+        KeyCode::Esc if !app.device_filter.is_empty() => {
+            app.device_filter.clear();
         }
-        KeyCode::Char('r') => {
-            match crate::device::list(app.show_all_disks) {
-                Ok(devices) => {
-                    app.devices = devices;
-                    app.status = if app.devices.is_empty() {
-                        "No devices detected.".to_string()
-                    } else {
-                        "Devices re-scanned.".to_string()
-                    };
-                }
-                Err(err) => {
-                    app.devices = Vec::new();
-                    app.status = format!("Rescan failed: {err}");
-                }
+        KeyCode::Char('r') => match app.refresh_devices() {
+            Ok(()) if app.devices.is_empty() => {
+                app.status = "No devices detected.".to_string();
             }
-            app.selected = 0;
-        }
+            Ok(()) => {
+                app.status.clear();
+                app.toast("Devices re-scanned.");
+            }
+            Err(err) => {
+                app.status = format!("Rescan failed: {err}");
+            }
+        },
         KeyCode::Char('a') => {
             app.show_all_disks = !app.show_all_disks;
-            match crate::device::list(app.show_all_disks) {
-                Ok(devices) => {
-                    app.devices = devices;
+            match app.refresh_devices() {
+                Ok(()) => {
+                    // The all-disks warning stays in the footer; it is not a passing event.
                     app.status = if app.show_all_disks {
                         "Showing all disks (be careful).".to_string()
                     } else {
-                        "Showing removable disks only.".to_string()
+                        app.toast("Showing removable disks only.");
+                        String::new()
                     };
                     if app.devices.is_empty() {
                         app.status = "No devices detected.".to_string();
                     }
                 }
                 Err(err) => {
-                    app.devices = Vec::new();
                     app.status = format!("Disk list failed: {err}");
                 }
             }
-            app.selected = 0;
+        }
+        KeyCode::Char(' ') => {
+            app.toggle_mark();
         }
         KeyCode::Enter => {
-            if let Some(disk) = app.devices.get(app.selected).cloned() {
-                app.selected_device = Some(disk);
-                if app.iso_kind == crate::iso::IsoKind::Unknown {
-                    app.refresh_iso_kind();
-                }
-                app.step = Step::Confirm;
+            // The ISO type feeds into the Confirm warnings collected by select_targets.
+            if app.iso_kind == crate::iso::IsoKind::Unknown {
+                app.refresh_iso_kind();
+            }
+            if app.select_targets() {
+                app.step = Step::confirm();
             } else {
-                app.status = "No removable devices found.".to_string();
-                app.step = Step::Error;
+                app.fail("No removable devices found.");
             }
         }
+        KeyCode::Char('t') => match app.devices.get(app.selected) {
+            Some(disk) => app.step = Step::Benchmark(DeviceTests::new(disk.clone())),
+            None => app.status = "No device to benchmark.".to_string(),
+        },
+        KeyCode::Char('w') => match app.devices.get(app.selected) {
+            Some(disk) => app.step = Step::Wipe(DeviceJob::new(disk.clone())),
+            None => app.status = "No device to wipe.".to_string(),
+        },
+        KeyCode::Char('m') => match app.devices.get(app.selected) {
+            Some(disk) => app.step = Step::Format(FormatJob::new(disk.clone())),
+            None => app.status = "No device to format.".to_string(),
+        },
         KeyCode::Char('b') => {
             app.step = Step::Image;
         }
         _ => {}
     }
+    app.keep_selection_visible();
 
     None
 }
 
-fn handle_confirm_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+/// Type into the device list filter. Enter keeps the filter and returns to
+/// the list keys; Esc clears it. Up/Down still move through the matches.
+fn handle_device_filter(app: &mut App, key: KeyEvent) {
     match key.code {
-        KeyCode::Char('f') => {
-            if app.iso_kind == crate::iso::IsoKind::NonHybrid {
-                if crate::flash::has_isohybrid() {
-                    app.step = Step::ConvertIso;
-                } else {
-                    app.status =
-                        "ISO has no partition table; hybrid ISO required. Install syslinux for isohybrid conversion."
-                            .to_string();
-                    app.step = Step::Error;
-                }
-            } else if let (Some(image), Some(device)) =
-                (app.image_path(), app.selected_device.clone())
-            {
-                // Re-verify the device still exists before proceeding.
-                if let Err(e) = crate::device::DevicePath::validate(&device.device_path()) {
-                    app.status = format!("Device error: {e}");
-                    app.step = Step::Error;
-                    return None;
-                }
+        KeyCode::Enter => app.device_filter_editing = false,
+        KeyCode::Esc => {
+            app.device_filter_editing = false;
+            app.device_filter.clear();
+        }
+        KeyCode::Backspace => lineedit::pop(&mut app.device_filter),
+        KeyCode::Up => move_device_selection(app, false),
+        KeyCode::Down => move_device_selection(app, true),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.device_filter.push(c);
+        }
+        _ => {}
+    }
+    app.keep_selection_visible();
+}
 
-                // Check if the target device has existing partitions
-                match crate::flash::check_device_partitions(&device.device_path()) {
-                    Ok(info) if info.has_partitions => {
-                        // Device has partitions -- ask the user to confirm the wipe
-                        app.partition_info = Some(info);
-                        app.user_confirmed_wipe = false;
-                        app.step = Step::ConfirmWipe;
-                    }
-                    _ => {
-                        // No partitions or couldn't check -- proceed directly
-                        app.user_confirmed_wipe = false;
-                        if app.execute {
-                            app.start_flash(image, device.device_path());
-                        } else {
-                            app.flash_result = Some(crate::FlashResult {
-                                ok: true,
-                                message: format!(
-                                    "Dry run: would flash {} to {}",
-                                    image.display(),
-                                    device.device_path()
-                                ),
-                            });
-                            app.step = Step::Result;
-                        }
-                    }
+/// Move the device selection to the next or previous device passing the filter.
+fn move_device_selection(app: &mut App, forward: bool) {
+    let visible = app.visible_devices();
+    let next = if forward {
+        visible.into_iter().find(|&index| index > app.selected)
+    } else {
+        visible
+            .into_iter()
+            .rev()
+            .find(|&index| index < app.selected)
+    };
+    if let Some(index) = next {
+        app.selected = index;
+    }
+}
+
+fn handle_format_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Format(format) = &mut app.step else {
+        return None;
+    };
+    if format.job.running() {
+        return None;
+    }
+
+    if format.job.result.is_some() {
+        if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('b')) {
+            app.step = Step::Device;
+        }
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Tab => {
+            format.fs = format.fs.next();
+        }
+        KeyCode::Enter => {
+            if app.execute {
+                let disk = format.job.disk.clone();
+                start_or_confirm(app, &disk, Destructive::Format);
+            } else {
+                format.job.progress = format!(
+                    "Dry run: would format {} as {}",
+                    format.job.disk.stable_path(),
+                    format.fs.name()
+                );
+                format.job.result = Some(Ok(()));
+            }
+        }
+        KeyCode::Esc => {
+            app.step = Step::Device;
+        }
+        KeyCode::Backspace => {
+            lineedit::pop(&mut format.label);
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            format.label.push(c);
+        }
+        _ => {}
+    }
+
+    None
+}
+
+/// Start `operation` on a removable `disk`, whose side step already asked,
+/// or ask first for the typed confirmation an internal disk takes. Either
+/// way untrusted and oversized disks are refused.
+fn start_or_confirm(app: &mut App, disk: &Disk, operation: Destructive) {
+    if !disk.removable {
+        app.confirm_destructive(disk, operation);
+    } else if let Some(message) = app.write_refusal(disk) {
+        app.status = message;
+    } else {
+        app.start_destructive(operation);
+    }
+}
+
+fn handle_wipe_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Wipe(job) = &mut app.step else {
+        return None;
+    };
+    if job.running() {
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Char('y') if job.result.is_none() => {
+            if app.execute {
+                let disk = job.disk.clone();
+                start_or_confirm(app, &disk, Destructive::Wipe);
+            } else {
+                job.result = Some(Ok(()));
+                job.progress = format!("Dry run: would wipe {}", job.disk.stable_path());
+            }
+        }
+        KeyCode::Char('n') | KeyCode::Char('b') => {
+            app.step = Step::Device;
+        }
+        _ => {}
+    }
+
+    None
+}
+
+fn handle_benchmark_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Benchmark(tests) = &app.step else {
+        return None;
+    };
+    if tests.running.is_some() {
+        return None;
+    }
+    // Only the read test leaves the device untouched; the others are
+    // confirmed like a flash.
+    let disk = tests.disk.clone();
+    let operation = match key.code {
+        KeyCode::Char('w') => Destructive::WriteTest,
+        KeyCode::Char('c') => Destructive::CapacityTest,
+        KeyCode::Char('s') => Destructive::SurfaceScan,
+        KeyCode::Char('r') => {
+            app.start_benchmark(false);
+            return None;
+        }
+        KeyCode::Char('b') => {
+            app.step = Step::Device;
+            return None;
+        }
+        _ => return None,
+    };
+    if app.execute {
+        app.confirm_destructive(&disk, operation);
+    } else {
+        app.status = format!(
+            "{} is destructive; turn on execute mode with 'x' on the Confirm screen first.",
+            operation.title()
+        );
+    }
+
+    None
+}
+
+fn handle_confirm_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    // In execute mode the device name must be typed out, so letters go to the
+    // confirmation field instead of acting as shortcuts.
+    if app.execute {
+        let phrase = app.confirm_phrase();
+        let Step::Confirm(input) = &mut app.step else {
+            return None;
+        };
+        match key.code {
+            KeyCode::Enter => {
+                if input.trim() == phrase {
+                    confirm_flash(app, false);
+                } else {
+                    app.status = format!("Type '{phrase}' exactly to confirm.");
                 }
             }
+            KeyCode::Esc => {
+                app.step = Step::Device;
+            }
+            KeyCode::Backspace => {
+                lineedit::pop(input);
+            }
+            // Plain 'x' can be part of a device name (e.g. xvda), so going
+            // back to a dry run takes Ctrl+X.
+            KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_execute();
+            }
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.cycle_block_size();
+            }
+            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_verification();
+            }
+            // Ctrl+R already reloads the config.
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_resume();
+            }
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_stage();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.push(c);
+            }
+            _ => {}
         }
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Char('f') => confirm_flash(app, false),
+        KeyCode::Char('x') => app.toggle_execute(),
+        KeyCode::Char('s') => app.cycle_block_size(),
+        KeyCode::Char('v') => app.toggle_verification(),
+        KeyCode::Char('r') => app.toggle_resume(),
+        KeyCode::Char('m') => app.toggle_stage(),
         KeyCode::Char('b') => {
             app.step = Step::Device;
         }
@@ -269,6 +707,109 @@ fn handle_confirm_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
     None
 }
 
+/// Start flashing the `--image` and `--device` given on the command line
+/// without waiting for the Confirm keypress or the wipe confirmation (`--yes`).
+///
+/// Internal disks still stop at the Confirm screen, where the ERASE phrase
+/// must be typed.
+pub fn confirm_unattended(app: &mut App) {
+    if app.selected_device.is_none() {
+        app.fail("--yes: the --device given was not found (or is hidden by the config).");
+        return;
+    }
+    if app.image_path().is_none() {
+        return;
+    }
+    if app.iso_kind == crate::iso::IsoKind::Unknown {
+        app.refresh_iso_kind();
+    }
+    if !app.select_targets() {
+        return;
+    }
+    app.step = Step::confirm();
+    if app.targets_internal_disk() {
+        app.status = format!(
+            "--yes does not apply to internal disks; type '{}' to confirm.",
+            app.confirm_phrase()
+        );
+        return;
+    }
+    confirm_flash(app, true);
+}
+
+/// Run the pre-flash checks for the confirmed targets and start flashing
+/// (or move on to the ISO conversion / wipe confirmation steps).
+///
+/// With `assume_yes` (from `--yes`) existing partitions are wiped without
+/// the wipe confirmation step.
+fn confirm_flash(app: &mut App, assume_yes: bool) {
+    if app.iso_kind == crate::iso::IsoKind::NonHybrid {
+        if crate::flash::has_isohybrid() {
+            app.step = Step::ConvertIso;
+        } else {
+            app.fail(
+                "ISO has no partition table; hybrid ISO required. Install syslinux for isohybrid conversion.",
+            );
+        }
+    } else if let Some(image) = app.image_path() {
+        if app.selected_devices.is_empty() {
+            return;
+        }
+
+        if app.execute {
+            if let Some(disk) = app.selected_devices.iter().find(|d| !app.config.is_trusted(d)) {
+                let message = format!(
+                    "{} is not in the configured trusted_devices list.",
+                    disk.device_path()
+                );
+                app.fail(message);
+                return;
+            }
+            let oversized = app
+                .selected_devices
+                .iter()
+                .find_map(|d| app.size_limit_error(d));
+            if let Some(message) = oversized {
+                app.fail(message);
+                return;
+            }
+        }
+
+        app.refresh_target_contents();
+        if app.execute && !app.target_holders.is_empty() {
+            app.status = "Device is still open in other programs; close them and confirm again."
+                .to_string();
+            return;
+        }
+
+        // Re-verify the devices still exist and check them for existing partitions.
+        let mut info = crate::flash::DevicePartitionInfo::default();
+        for device in &app.selected_devices {
+            if let Err(e) = app.device_provider.ensure_attached(&device.stable_path()) {
+                app.fail(format!("Device error: {e}"));
+                return;
+            }
+            // Failing to check is not fatal -- the flash engine re-checks mount safety.
+            if let Ok(device_info) = app.device_provider.partition_info(&device.device_path()) {
+                info.merge(device_info);
+            }
+        }
+
+        // Internal disks always take the explicit wipe confirmation,
+        // even when they look empty.
+        let internal = app.targets_internal_disk();
+        if info.has_partitions && assume_yes && !internal {
+            begin_flash(app, image, true);
+        } else if info.has_partitions || (internal && app.execute) {
+            // Device has partitions -- ask the user to confirm the wipe
+            app.step = Step::ConfirmWipe(info);
+        } else {
+            // No partitions or couldn't check -- proceed directly
+            begin_flash(app, image, false);
+        }
+    }
+}
+
 fn handle_convert_iso_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
     match key.code {
         KeyCode::Char('y') => {
@@ -277,23 +818,22 @@ fn handle_convert_iso_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
                     Ok(()) => {
                         app.refresh_iso_kind();
                         if app.iso_kind == crate::iso::IsoKind::Hybrid {
-                            app.status = "ISO converted to hybrid format.".to_string();
-                            app.step = Step::Confirm;
+                            app.refresh_target_contents();
+                            app.status.clear();
+                            app.toast("ISO converted to hybrid format.");
+                            app.step = Step::confirm();
                         } else {
-                            app.status =
-                                "Conversion ran but ISO is still not hybrid.".to_string();
-                            app.step = Step::Error;
+                            app.fail("Conversion ran but ISO is still not hybrid.");
                         }
                     }
                     Err(e) => {
-                        app.status = format!("isohybrid failed: {e}");
-                        app.step = Step::Error;
+                        app.fail(format!("isohybrid failed: {e}"));
                     }
                 }
             }
         }
         KeyCode::Char('n') | KeyCode::Char('b') => {
-            app.step = Step::Confirm;
+            app.step = Step::confirm();
         }
         _ => {}
     }
@@ -305,28 +845,13 @@ fn handle_confirm_wipe_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
     match key.code {
         KeyCode::Char('y') => {
             // User confirmed the wipe -- proceed with flashing
-            app.user_confirmed_wipe = true;
-            if let (Some(image), Some(device)) = (app.image_path(), app.selected_device.clone()) {
-                if app.execute {
-                    app.start_flash(image, device.device_path());
-                } else {
-                    app.flash_result = Some(crate::FlashResult {
-                        ok: true,
-                        message: format!(
-                            "Dry run: would flash {} to {} (with partition wipe)",
-                            image.display(),
-                            device.device_path()
-                        ),
-                    });
-                    app.step = Step::Result;
-                }
+            if let Some(image) = app.image_path() {
+                begin_flash(app, image, true);
             }
         }
         KeyCode::Char('n') | KeyCode::Char('b') => {
             // User declined -- go back to the confirm screen
-            app.partition_info = None;
-            app.user_confirmed_wipe = false;
-            app.step = Step::Confirm;
+            app.step = Step::confirm();
         }
         _ => {}
     }
@@ -334,80 +859,561 @@ fn handle_confirm_wipe_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
     None
 }
 
-fn handle_flashing_step(_app: &mut App, _key: KeyEvent) -> Option<AppExit> {
+/// Start flashing the selected devices, or record a dry-run result when not executing.
+///
+/// `wipe` says the existing partitions may be overwritten; the dry-run message
+/// mentions it.
+fn begin_flash(app: &mut App, image: std::path::PathBuf, wipe: bool) {
+    if app.execute {
+        let devices = app.selected_devices.iter().map(|d| d.stable_path()).collect();
+        app.start_flash(image, devices, wipe);
+    } else {
+        let dry_run_note = match &app.interrupted {
+            Some(journal) if app.resume => {
+                format!(" (resuming at {:.0}%)", journal.fraction() * 100.0)
+            }
+            _ if wipe => " (with partition wipe)".to_string(),
+            _ => String::new(),
+        };
+        let targets = app
+            .selected_devices
+            .iter()
+            .map(|d| d.device_path())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let result = crate::FlashResult {
+            ok: true,
+            message: format!(
+                "Dry run: would flash {} to {}{}",
+                image.display(),
+                targets,
+                dry_run_note
+            ),
+            error: None,
+            elapsed: None,
+            bytes_written: 0,
+        };
+        app.step = Step::Result(Outcome {
+            result,
+            report: None,
+        });
+    }
+}
+
+fn handle_download_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Download(run) = &app.step else {
+        return None;
+    };
+    match key.code {
+        KeyCode::Char('p') | KeyCode::Char(' ') if run.control.is_paused() => run.control.resume(),
+        KeyCode::Char('p') | KeyCode::Char(' ') => run.control.pause(),
+        KeyCode::Char('c') | KeyCode::Esc => run.control.cancel(),
+        KeyCode::Char('u') => app.exact_bytes = !app.exact_bytes,
+        _ => {}
+    }
     None
 }
 
-fn handle_result_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
-    if key.code == KeyCode::Char('r') {
-        app.reset_to_start();
+fn handle_cache_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Cache(view) = &mut app.step else {
+        return None;
+    };
+    if view.verifying.is_some() {
+        return None;
+    }
+    let last = view.images.len().saturating_sub(1);
+    match key.code {
+        KeyCode::Up => view.selected = view.selected.saturating_sub(1),
+        KeyCode::Down => view.selected = (view.selected + 1).min(last),
+        KeyCode::PageUp => view.selected = view.selected.saturating_sub(PAGE_SIZE),
+        KeyCode::PageDown => view.selected = (view.selected + PAGE_SIZE).min(last),
+        KeyCode::Home => view.selected = 0,
+        KeyCode::End => view.selected = last,
+        KeyCode::Enter => app.use_cached_image(),
+        KeyCode::Char('v') => app.verify_cached_image(),
+        KeyCode::Char('d') | KeyCode::Delete => app.confirm_delete_cached(),
+        KeyCode::Char('r') => app.reload_cache(),
+        KeyCode::Char('b') | KeyCode::Esc => app.step = Step::Image,
+        _ => {}
     }
     None
 }
 
-fn handle_done_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
-    if key.code == KeyCode::Char('r') {
-        app.reset_to_start();
+fn handle_flashing_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    match key.code {
+        KeyCode::Char('z') => app.zoomed = !app.zoomed,
+        KeyCode::Char('u') => app.exact_bytes = !app.exact_bytes,
+        _ => {}
     }
     None
 }
 
-/// Render the entire TUI screen.
-///
-/// Renders a 3-section layout:
-/// 1. **Top** - Title bar
-/// 2. **Middle** - Content specific to current step
+fn handle_result_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Result(outcome) = &app.step else {
+        return None;
+    };
+    let flashed = app.execute && outcome.result.ok;
+    let has_report = outcome.report.is_some();
+    match key.code {
+        KeyCode::Char('r') => app.reset_to_start(),
+        KeyCode::Char('o') if flashed => app.mount_targets(),
+        KeyCode::Char('e') if flashed => app.eject_targets(),
+        KeyCode::Char('s') if has_report => app.prompt_save_report(),
+        KeyCode::Char('i') if flashed => app.open_seed(),
+        _ => {}
+    }
+    None
+}
+
+fn handle_seed_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    let Step::Seed(job) = &mut app.step else {
+        return None;
+    };
+    if job.task.is_some() {
+        return None;
+    }
+
+    if job.result.is_some() {
+        if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('b')) {
+            app.leave_seed();
+        }
+        return None;
+    }
+
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let text = &mut job.seed.user_data;
+    match key.code {
+        KeyCode::Char('s') if ctrl => {
+            if let Err(err) = crate::seed::check_user_data(text) {
+                app.status = format!("{err:#}");
+            } else if app.execute {
+                app.start_seed();
+            } else {
+                let devices: Vec<String> =
+                    app.selected_devices.iter().map(Disk::device_path).collect();
+                job.progress = format!(
+                    "Dry run: would write a cloud-init seed to {}",
+                    devices.join(", ")
+                );
+                job.result = Some(Ok(()));
+            }
+        }
+        KeyCode::Char('f') if ctrl => app.prompt_load_seed(),
+        KeyCode::Char('v') if ctrl => match clipboard::read() {
+            Ok(text) => handle_paste(app, &text),
+            Err(err) => app.status = format!("Paste failed: {err:#}"),
+        },
+        KeyCode::Esc => app.leave_seed(),
+        KeyCode::Enter => lineedit::insert(text, &mut job.cursor, '\n'),
+        // YAML has no tabs; indent with spaces.
+        KeyCode::Tab => lineedit::insert_str(text, &mut job.cursor, "  "),
+        KeyCode::Backspace => {
+            lineedit::backspace(text, &mut job.cursor);
+        }
+        KeyCode::Delete => lineedit::delete(text, &mut job.cursor),
+        KeyCode::Left => lineedit::left(text, &mut job.cursor),
+        KeyCode::Right => lineedit::right(text, &mut job.cursor),
+        KeyCode::Up => lineedit::up(text, &mut job.cursor),
+        KeyCode::Down => lineedit::down(text, &mut job.cursor),
+        KeyCode::Home => lineedit::home(text, &mut job.cursor),
+        KeyCode::End => lineedit::end(text, &mut job.cursor),
+        KeyCode::Char(c) if !ctrl => lineedit::insert(text, &mut job.cursor, c),
+        _ => {}
+    }
+
+    None
+}
+
+fn handle_error_step(app: &mut App, key: KeyEvent) -> Option<AppExit> {
+    match key.code {
+        KeyCode::Char('r') => app.reset_to_start(),
+        KeyCode::Char('b') => {
+            app.status.clear();
+            app.leave_error();
+        }
+        KeyCode::Char('t') => {
+            app.status.clear();
+            app.leave_error();
+            // Re-run the action that failed by replaying its key on the origin step.
+            let retry = match app.step {
+                Step::Image if download::is_url(&app.image_input) => Some(KeyCode::Enter),
+                Step::Device => Some(KeyCode::Enter),
+                Step::ConvertIso => Some(KeyCode::Char('y')),
+                _ => None,
+            };
+            if matches!(app.step, Step::Confirm(_)) {
+                // The typed confirmation was already given before the failure.
+                confirm_flash(app, false);
+            } else if let Some(code) = retry {
+                return handle_key(app, KeyEvent::new(code, KeyModifiers::NONE));
+            }
+        }
+        KeyCode::Char('d') => {
+            app.step = Step::Device;
+            match app.refresh_devices() {
+                Ok(()) => {
+                    app.status.clear();
+                    app.toast(format!("Found {} device(s).", app.devices.len()));
+                }
+                Err(err) => app.status = format!("Disk list failed: {err}"),
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Render the entire TUI screen.
+///
+/// Renders a 3-section layout:
+/// 1. **Top** - Title bar and wizard breadcrumb
+/// 2. **Middle** - Content specific to current step
 /// 3. **Bottom** - Footer with status message and key bindings
 ///
-/// Delegates to step-specific draw functions for the middle section.
+/// Delegates to step-specific draw functions for the middle section. The
+/// title bar is dropped and the layout tightened on small terminals (see
+/// `screen_layout`); below `MIN_WIDTH`x`MIN_HEIGHT` only a notice is drawn.
 ///
 /// # Arguments
 ///
 /// * `frame` - ratatui Frame to render to
 /// * `app` - Current application state (immutable)
 pub fn draw(frame: &mut ratatui::Frame, app: &App) {
+    let area = frame.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        draw_too_small(frame, area);
+        return;
+    }
+    if let (Step::Flashing(run), true) = (&app.step, app.zoomed) {
+        draw_flashing_zoomed(frame, app, run, area);
+        if let Some(modal) = &app.modal {
+            draw_modal(frame, app, modal, area);
+        }
+        return;
+    }
+
+    let layout = screen_layout(area, app.compact);
+    if let Some(logo) = layout.logo {
+        let title = Paragraph::new(LOGO)
+            .style(app.theme.logo.add_modifier(Modifier::BOLD))
+            .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(title, logo);
+    }
+    frame.render_widget(Paragraph::new(breadcrumb(app)), layout.breadcrumb);
+
+    let body = layout.body;
+    match &app.step {
+        _ if app.log_open => draw_log_pane(frame, app, body),
+        _ if app.history.is_some() => draw_history_pane(frame, app, body),
+        Step::Image => draw_image_step(frame, app, body),
+        Step::Download(run) => draw_download_step(frame, app, run, body),
+        Step::Cache(view) => draw_cache_step(frame, app, view, body),
+        Step::Device => draw_device_step(frame, app, body),
+        Step::Confirm(input) => draw_confirm_step(frame, app, input, body),
+        Step::ConvertIso => draw_convert_iso_step(frame, app, body),
+        Step::ConfirmWipe(info) => draw_confirm_wipe_step(frame, app, info, body),
+        Step::Flashing(run) => draw_flashing_step(frame, app, run, body),
+        Step::Result(outcome) => draw_result_step(frame, app, outcome, body),
+        Step::Error(_) => draw_error_step(frame, app, body),
+        Step::Benchmark(tests) => draw_benchmark_step(frame, app, tests, body),
+        Step::Wipe(job) => draw_wipe_step(frame, app, job, body),
+        Step::Format(format) => draw_format_step(frame, app, format, body),
+        Step::Seed(job) => draw_seed_step(frame, app, job, body),
+    }
+
+    let footer = Paragraph::new(footer_text(app, layout.footer))
+        .style(app.theme.muted)
+        .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(footer, layout.footer);
+
+    if let Some(toast) = &app.toast {
+        draw_toast(frame, app, &toast.message, body);
+    }
+    if let Some(modal) = &app.modal {
+        draw_modal(frame, app, modal, area);
+    }
+}
+
+/// `draw` as if the time were `now`, so the same `App` always renders the
+/// same frame (see `snapshot`).
+pub fn draw_at(frame: &mut ratatui::Frame, app: &App, now: SystemTime) {
+    DRAW_TIME.set(Some(now));
+    draw(frame, app);
+    DRAW_TIME.set(None);
+}
+
+/// Draw a toast in the top-right corner of `area`, over whatever is there.
+fn draw_toast(frame: &mut ratatui::Frame, app: &App, message: &str, area: ratatui::layout::Rect) {
+    let width = (Span::raw(message).width() as u16 + 4).min(area.width);
+    let rect = ratatui::layout::Rect {
+        x: area.x + area.width - width,
+        y: area.y,
+        width,
+        height: 3.min(area.height),
+    };
+    let paragraph = Paragraph::new(Span::styled(message.to_string(), app.theme.success))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(app.theme.muted),
+        )
+        .alignment(ratatui::layout::Alignment::Center);
+    frame.render_widget(Clear, rect);
+    frame.render_widget(paragraph, rect);
+}
+
+/// Widest a modal dialog gets, border included.
+const MODAL_MAX_WIDTH: u16 = 64;
+
+/// Draw a modal dialog centered over `area`, clearing what is beneath it.
+fn draw_modal(frame: &mut ratatui::Frame, app: &App, modal: &Modal, area: ratatui::layout::Rect) {
+    let mut lines = vec![Line::from(modal.message.clone()), Line::from("")];
+    let hint = match &modal.kind {
+        ModalKind::Confirm => "y/Enter=yes  n/Esc=no",
+        ModalKind::Prompt { input, cursor } => {
+            let input_width = (MODAL_MAX_WIDTH.min(area.width.saturating_sub(4)) as usize)
+                .saturating_sub(2 + "> ".len());
+            let (before, at, after) = lineedit::split_visible(input, *cursor, input_width);
+            lines.push(Line::from(vec![
+                Span::raw("> "),
+                Span::raw(before.to_string()),
+                Span::styled(
+                    at.to_string(),
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ),
+                Span::raw(after.to_string()),
+            ]));
+            "Enter=ok  Esc=cancel"
+        }
+        ModalKind::Choice { options, selected } => {
+            for (index, option) in options.iter().enumerate() {
+                let marker = if index == *selected { ">" } else { " " };
+                let text = format!("{marker} {}. {option}", index + 1);
+                lines.push(if index == *selected {
+                    Line::from(Span::styled(text, app.theme.highlight))
+                } else {
+                    Line::from(text)
+                });
+            }
+            "Up/Down=move  Enter=choose  Esc=cancel"
+        }
+    };
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(hint, app.theme.muted)));
+
+    let width = MODAL_MAX_WIDTH.min(area.width.saturating_sub(4));
+    // Wrapped message lines need extra rows; estimate them from the inner width.
+    let inner_width = width.saturating_sub(2).max(1) as usize;
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().max(1).div_ceil(inner_width))
+        .sum();
+    let height = (rows as u16 + 2).min(area.height);
+    let rect = ratatui::layout::Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(app.theme.warning)
+        .title(modal.title.clone());
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(Clear, rect);
+    frame.render_widget(paragraph, rect);
+}
+
+/// Smallest terminal the UI is drawn in; below this a notice is shown instead.
+pub const MIN_WIDTH: u16 = 40;
+pub const MIN_HEIGHT: u16 = 12;
+
+/// Smallest terminal that still shows the logo banner.
+const LOGO_MIN_WIDTH: u16 = 50;
+const LOGO_MIN_HEIGHT: u16 = 30;
+
+/// Below this height the outer margin and the second footer line are dropped.
+const ROOMY_MIN_HEIGHT: u16 = 20;
+
+/// Areas of the main screen.
+struct ScreenLayout {
+    logo: Option<ratatui::layout::Rect>,
+    breadcrumb: ratatui::layout::Rect,
+    body: ratatui::layout::Rect,
+    footer: ratatui::layout::Rect,
+}
+
+/// Split the screen, giving the step's content as much room as the terminal
+/// allows: the logo only appears on large terminals outside compact mode, and
+/// small terminals lose the outer margin and get a one-line footer.
+fn screen_layout(area: ratatui::layout::Rect, compact: bool) -> ScreenLayout {
+    let show_logo =
+        !compact && area.width >= LOGO_MIN_WIDTH && area.height >= LOGO_MIN_HEIGHT;
+    let roomy = area.height >= ROOMY_MIN_HEIGHT;
+    let logo_height = if show_logo { 8 } else { 0 };
+    let footer_height = if roomy { 4 } else { 3 };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .margin(1)
+        .margin(u16::from(roomy))
         .constraints([
-            Constraint::Length(8),
-            Constraint::Min(10),
-            Constraint::Length(4),
+            Constraint::Length(logo_height),
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(footer_height),
         ])
-        .split(frame.area());
+        .split(area);
 
-    let title = Paragraph::new(LOGO)
-        .style(
-            Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        )
-        .block(Block::default().borders(Borders::ALL));
-    frame.render_widget(title, chunks[0]);
+    ScreenLayout {
+        logo: show_logo.then_some(chunks[0]),
+        breadcrumb: chunks[1],
+        body: chunks[2],
+        footer: chunks[3],
+    }
+}
 
-    match app.step {
-        Step::Image => draw_image_step(frame, app, chunks[1]),
-        Step::Device => draw_device_step(frame, app, chunks[1]),
-        Step::Confirm => draw_confirm_step(frame, app, chunks[1]),
-        Step::ConvertIso => draw_convert_iso_step(frame, app, chunks[1]),
-        Step::ConfirmWipe => draw_confirm_wipe_step(frame, app, chunks[1]),
-        Step::Flashing => draw_flashing_step(frame, app, chunks[1]),
-        Step::Result => draw_result_step(frame, app, chunks[1]),
-        Step::Error => draw_error_step(frame, app, chunks[1]),
-    }
-
-    let footer = Paragraph::new(status_line(app))
-        .style(Style::default().fg(Color::Gray))
-        .block(Block::default().borders(Borders::ALL));
-    frame.render_widget(footer, chunks[2]);
+/// Shown instead of the UI when the terminal is below the minimum size.
+fn draw_too_small(frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
+    let text = Text::from(vec![
+        Line::from("Terminal too small"),
+        Line::from(format!(
+            "{}x{}, need {MIN_WIDTH}x{MIN_HEIGHT}",
+            area.width, area.height
+        )),
+        Line::from("Enlarge the window or press q to quit"),
+    ]);
+    let paragraph = Paragraph::new(text)
+        .alignment(ratatui::layout::Alignment::Center)
+        .wrap(Wrap { trim: true });
+    let top = area.height.saturating_sub(3) / 2;
+    let area = ratatui::layout::Rect {
+        y: area.y + top,
+        height: area.height - top,
+        ..area
+    };
+    frame.render_widget(paragraph, area);
 }
 
-fn draw_image_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
-    let sections = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(4), Constraint::Min(5)])
-        .split(area);
+/// Stages of the flashing wizard shown in the breadcrumb.
+const WIZARD_STAGES: [&str; 5] = ["Image", "Device", "Confirm", "Flash", "Done"];
+
+/// Index into `WIZARD_STAGES` for a step. Device tools count as the Device
+/// stage; errors show the stage they happened in.
+fn wizard_stage(step: &Step) -> usize {
+    match step {
+        Step::Image | Step::Download(_) | Step::Cache(_) => 0,
+        Step::Device | Step::Benchmark(_) | Step::Wipe(_) | Step::Format(_) => 1,
+        Step::Confirm(_) | Step::ConvertIso | Step::ConfirmWipe(_) => 2,
+        Step::Flashing(_) => 3,
+        Step::Result(_) | Step::Seed(_) => 4,
+        Step::Error(origin) => wizard_stage(origin),
+    }
+}
+
+/// "Image ▸ Device ▸ Confirm ▸ Flash ▸ Done" with finished stages, the current
+/// stage, and remaining stages styled differently.
+fn breadcrumb(app: &App) -> Line<'static> {
+    let current = wizard_stage(&app.step);
+    let current_style = if matches!(app.step, Step::Error(_)) {
+        app.theme.error.add_modifier(Modifier::BOLD)
+    } else {
+        app.theme.highlight.add_modifier(Modifier::BOLD)
+    };
+    let mut spans = vec![Span::raw(" ")];
+    for (index, stage) in WIZARD_STAGES.iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::styled(" ▸ ", app.theme.muted));
+        }
+        let style = match index.cmp(&current) {
+            std::cmp::Ordering::Less => app.theme.success,
+            std::cmp::Ordering::Equal => current_style,
+            std::cmp::Ordering::Greater => app.theme.muted,
+        };
+        spans.push(Span::styled(format!(" {stage} "), style));
+    }
+    Line::from(spans)
+}
+
+/// Recent progress and status messages, newest at the bottom.
+fn draw_log_pane(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let entries = app.activity.entries();
+    let visible = area.height.saturating_sub(2) as usize;
+    let max_scroll = entries.len().saturating_sub(visible);
+    let scroll = app.log_scroll.min(max_scroll);
+    let end = entries.len() - scroll;
+    let start = end.saturating_sub(visible);
+
+    let lines: Vec<Line> = if entries.is_empty() {
+        vec![Line::from(Span::styled("Nothing logged yet.", app.theme.muted))]
+    } else {
+        entries
+            .range(start..end)
+            .map(|entry| Line::from(entry.as_str()))
+            .collect()
+    };
+    let title = if scroll > 0 {
+        format!("Log ({} lines, {scroll} newer below)", entries.len())
+    } else {
+        format!("Log ({} lines)", entries.len())
+    };
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, area);
+
+    if entries.len() > visible {
+        let mut scrollbar_state = ScrollbarState::new(max_scroll).position(max_scroll - scroll);
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None);
+        frame.render_stateful_widget(
+            scrollbar,
+            area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut scrollbar_state,
+        );
+    }
+}
+
+/// Recorded flashes, newest at the top; failed ones in the error color.
+fn draw_history_pane(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let entries = app.history.as_deref().unwrap_or_default();
+    let lines: Vec<Line> = if entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "No flashes recorded yet.",
+            app.theme.muted,
+        ))]
+    } else {
+        entries
+            .iter()
+            .skip(app.history_scroll)
+            .map(|entry| {
+                let style = if entry.ok {
+                    Style::default()
+                } else {
+                    app.theme.error
+                };
+                Line::from(Span::styled(entry.summary(), style))
+            })
+            .collect()
+    };
+    let title = match app.history_scroll {
+        0 => format!("History ({} flashes)", entries.len()),
+        scroll => format!("History ({} flashes, {scroll} newer above)", entries.len()),
+    };
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(paragraph, area);
+}
 
+/// Narrowest list area that still gets a preview or details pane beside it.
+const SIDE_PANE_MIN_WIDTH: u16 = 80;
+
+fn draw_image_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
     let filter_label = if app.filter_iso_only {
         "ISO/IMG/RAW"
     } else {
@@ -415,16 +1421,52 @@ fn draw_image_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout:
     };
     let hidden_label = if app.show_hidden { " +hidden" } else { "" };
 
-    let header = Text::from(vec![
+    let mut lines = vec![
         Line::from(format!(
-            "Step 1: Choose image file  [filter: {filter_label}{hidden_label}]"
+            "Choose image file  [filter: {filter_label}{hidden_label}]  [sort: {}]",
+            app.sort_mode.label()
         )),
         Line::from(format!("Current dir: {}", app.cwd.display())),
-        Line::from(Span::styled(
-            format!("Input: {}", app.image_input),
-            Style::default().fg(Color::Yellow),
-        )),
-    ]);
+    ];
+    lines.push(Line::from(Span::styled(
+        "Or type a URL to download, press c to choose a distribution from the catalog, or d for earlier downloads",
+        app.theme.muted,
+    )));
+    if !app.config.bookmarks.is_empty() {
+        let bookmarks: Vec<String> = app
+            .config
+            .bookmarks
+            .iter()
+            .take(9)
+            .enumerate()
+            .map(|(i, bookmark)| format!("{}:{bookmark}", i + 1))
+            .collect();
+        lines.push(Line::from(Span::styled(
+            format!("Bookmarks: {}", bookmarks.join("  ")),
+            app.theme.muted,
+        )));
+    }
+    // Long paths scroll sideways instead of wrapping, so the cursor stays visible.
+    let input_width = (area.width as usize).saturating_sub(2 + "Input: ".len());
+    let (before, at_cursor, after) =
+        lineedit::split_visible(&app.image_input, app.image_cursor, input_width);
+    lines.push(Line::from(vec![
+        Span::styled(format!("Input: {before}"), app.theme.warning),
+        Span::styled(
+            at_cursor.to_string(),
+            app.theme.warning.add_modifier(Modifier::REVERSED),
+        ),
+        Span::styled(after.to_string(), app.theme.warning),
+    ]));
+
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16 + 2),
+            Constraint::Min(5),
+        ])
+        .split(area);
+    let header = Text::from(lines);
 
     let block = Block::default().borders(Borders::ALL).title("Image");
     let paragraph = Paragraph::new(header)
@@ -447,98 +1489,518 @@ fn draw_image_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout:
 
     let list = List::new(items)
         .block(Block::default().borders(Borders::ALL).title("Files"))
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .highlight_style(app.theme.highlight)
         .highlight_symbol("> ");
 
-    let mut state = ratatui::widgets::ListState::default();
-    if !app.entries.is_empty() {
-        state.select(Some(app.entry_selected));
+    let (list_area, preview_area) = split_side_pane(sections[1]);
+
+    let selected = (!app.entries.is_empty()).then_some(app.entry_selected);
+    render_scrolling_list(frame, list, list_area, selected, app.entries.len());
+    if let Some(area) = preview_area {
+        draw_file_preview(frame, app, area);
+    }
+}
+
+/// Size, age, and image detection for the highlighted file picker entry.
+fn draw_file_preview(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let mut lines = Vec::new();
+    if let Some(entry) = app.entries.get(app.entry_selected) {
+        lines.push(Line::from(Span::styled(
+            entry.name.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        if entry.is_dir {
+            lines.push(Line::from("Directory"));
+        } else {
+            lines.push(Line::from(format!(
+                "Size    : {}",
+                crate::device::format_lsblk_size(entry.size)
+            )));
+        }
+        if let Some(modified) = entry.modified {
+            lines.push(Line::from(format!(
+                "Modified: {}",
+                crate::format_age(modified, now())
+            )));
+        }
+        let detection = app
+            .preview
+            .as_ref()
+            .filter(|preview| preview.path == entry.path)
+            .and_then(|preview| preview.detection.as_deref());
+        if let Some(detection) = detection {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                detection.to_string(),
+                app.theme.warning,
+            )));
+        }
+    }
+
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title("Preview"))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+/// " ⠋ rescanning" while a hotplug rescan is pending, otherwise empty.
+fn rescan_indicator(app: &App) -> String {
+    if app.rescan_due.is_some() {
+        format!(" {} rescanning", spinner())
+    } else {
+        String::new()
     }
+}
+
+/// How long each spinner frame is shown; the event loop redraws at least
+/// this often while the spinner is on screen.
+pub const SPINNER_INTERVAL: Duration = Duration::from_millis(250);
 
-    frame.render_stateful_widget(list, sections[1], &mut state);
+/// Current frame of the spinner shown while an operation has no measurable
+/// progress. Derived from the clock, one frame per `SPINNER_INTERVAL`, so it
+/// advances with every redraw.
+fn spinner() -> &'static str {
+    const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    let ticks = now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() / SPINNER_INTERVAL.as_millis())
+        .unwrap_or_default();
+    FRAMES[(ticks % FRAMES.len() as u128) as usize]
+}
+
+/// Prefix `text` with the spinner when `busy`.
+fn with_spinner(text: &str, busy: bool) -> String {
+    if busy {
+        format!("{} {text}", spinner())
+    } else {
+        text.to_string()
+    }
 }
 
 fn draw_device_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
     if app.devices.is_empty() {
-        let text = Text::from(vec![
+        let mut lines = vec![
             Line::from("No devices detected."),
             Line::from("Press 'r' to rescan or 'a' to show all disks."),
-        ]);
+        ];
+        lines.extend(removed_device_lines(app));
+        let text = Text::from(lines);
         let block = Block::default()
             .borders(Borders::ALL)
-            .title("Select Device");
+            .title(format!("Select Device{}", rescan_indicator(app)));
         let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
         frame.render_widget(paragraph, area);
         return;
     }
 
-    let items: Vec<ListItem> = app
-        .devices
-        .iter()
-        .map(|disk| {
-            let label = format!(
-                "{}  {}  {}",
-                disk.device_path(),
-                disk.size,
-                if disk.model.is_empty() {
-                    "(unknown)"
-                } else {
-                    disk.model.as_str()
-                }
-            );
-            ListItem::new(Line::from(label))
-        })
-        .collect();
+    // With "show all" on, removable and internal disks are drawn as two
+    // sections; `app.devices` is already ordered removable-first. Devices
+    // hidden by the filter get no row.
+    let grouped = app.show_all_disks;
+    let changes = app.device_changes.as_ref();
+    let mut items: Vec<ListItem> = Vec::new();
+    let mut selected_row = None;
+    let mut previous: Option<&crate::Disk> = None;
+    for (index, disk) in app.devices.iter().enumerate() {
+        if !app.device_visible(disk) {
+            continue;
+        }
+        let starts_section = match previous {
+            Some(prev) => prev.removable != disk.removable,
+            None => true,
+        };
+        previous = Some(disk);
+        if grouped && starts_section {
+            let (header, style) = if disk.removable {
+                (
+                    "-- Removable --",
+                    Style::default().add_modifier(Modifier::BOLD),
+                )
+            } else {
+                (
+                    "-- Internal (dangerous) --",
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )
+            };
+            items.push(ListItem::new(Line::from(Span::styled(header, style))));
+        }
+        if index == app.selected {
+            selected_row = Some(items.len());
+        }
 
-    let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Select Device"),
+        let mark = if app.is_marked(disk) { "[x]" } else { "[ ]" };
+        let label = format!(
+            "{mark} {}  {}  {}",
+            disk.device_path(),
+            disk.size,
+            if disk.model.is_empty() {
+                "(unknown)"
+            } else {
+                disk.model.as_str()
+            }
+        );
+        let style = if disk.removable {
+            Style::default()
+        } else {
+            app.theme.error
+        };
+        let mut spans = vec![Span::styled(label, style)];
+        if changes.is_some_and(|changes| changes.is_added(disk)) {
+            spans.push(Span::styled("  (new)", app.theme.success));
+        }
+        items.push(ListItem::new(Line::from(spans)));
+    }
+
+    let mut title = if app.marked.is_empty() {
+        format!("Select Device{}", rescan_indicator(app))
+    } else {
+        format!(
+            "Select Device ({} marked){}",
+            app.marked.len(),
+            rescan_indicator(app)
         )
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+    };
+    if app.device_filter_editing || !app.device_filter.is_empty() {
+        let cursor = if app.device_filter_editing { "_" } else { "" };
+        title.push_str(&format!(" [/{}{cursor}]", app.device_filter));
+    }
+    if items.is_empty() {
+        items.push(ListItem::new(Line::from(Span::styled(
+            format!(
+                "No device matches '{}' (Esc clears the filter)",
+                app.device_filter
+            ),
+            app.theme.muted,
+        ))));
+    }
+    // After every selectable row, so the selection's row index is unaffected.
+    items.extend(removed_device_lines(app).into_iter().map(ListItem::new));
+    let items_len = items.len();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(app.theme.highlight)
         .highlight_symbol("> ");
 
-    let mut state = ratatui::widgets::ListState::default();
-    if !app.devices.is_empty() {
-        state.select(Some(app.selected));
+    let (list_area, details_area) = split_side_pane(area);
+    render_scrolling_list(frame, list, list_area, selected_row, items_len);
+    if let Some(details_area) = details_area {
+        draw_device_details(frame, app, details_area);
     }
+}
 
-    frame.render_stateful_widget(list, area, &mut state);
+/// Struck-out lines for the devices the last rescan no longer found.
+fn removed_device_lines(app: &App) -> Vec<Line<'static>> {
+    let Some(changes) = &app.device_changes else {
+        return Vec::new();
+    };
+    changes
+        .removed
+        .iter()
+        .map(|disk| {
+            Line::from(vec![
+                Span::styled(
+                    format!("    {}  {}  {}", disk.device_path(), disk.size, disk.model),
+                    app.theme.muted.add_modifier(Modifier::CROSSED_OUT),
+                ),
+                Span::styled("  (removed)", app.theme.muted),
+            ])
+        })
+        .collect()
 }
 
-fn draw_confirm_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
-    let image = app.image_input.trim();
-    let device = app
-        .selected_device
-        .as_ref()
-        .map(|d| d.device_path())
-        .unwrap_or_else(|| "<none>".to_string());
+/// Split a list area into the list and, on wide terminals, a side pane.
+fn split_side_pane(
+    area: ratatui::layout::Rect,
+) -> (ratatui::layout::Rect, Option<ratatui::layout::Rect>) {
+    if area.width < SIDE_PANE_MIN_WIDTH {
+        return (area, None);
+    }
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(area);
+    (columns[0], Some(columns[1]))
+}
 
-    let mode = if app.execute { "EXECUTE" } else { "DRY RUN" };
+/// Identity, first-sector summary, and partitions of the highlighted device.
+fn draw_device_details(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let unknown = |value: &str| {
+        if value.is_empty() {
+            "(unknown)".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let mut lines = Vec::new();
+    if let Some(disk) = app.devices.get(app.selected) {
+        lines.push(Line::from(Span::styled(
+            disk.device_path(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        lines.push(Line::from(format!("Vendor : {}", unknown(&disk.vendor))));
+        lines.push(Line::from(format!("Model  : {}", unknown(&disk.model))));
+        lines.push(Line::from(format!(
+            "Serial : {}",
+            unknown(disk.serial.as_deref().unwrap_or_default())
+        )));
+        lines.push(Line::from(format!("Size   : {}", unknown(&disk.size))));
+        lines.push(Line::from(format!(
+            "Media  : {}",
+            if disk.removable { "removable" } else { "internal" }
+        )));
 
-    let text = Text::from(vec![
-        Line::from("Step 3: Confirm"),
-        Line::from(format!("Image : {image}")),
-        Line::from(format!("Device: {device}")),
-        Line::from(format!("Mode  : {mode}")),
-        Line::from(format!("ISO   : {}", iso_info_line(app))),
-        Line::from("Press 'f' to flash, 'b' to go back."),
-    ]);
+        let details = app
+            .device_details
+            .as_ref()
+            .filter(|details| details.device == disk.device_path());
+        if let Some(details) = details {
+            lines.push(Line::from(format!("Start  : {}", details.first_sector)));
+            lines.push(Line::from(""));
+            match &details.partitions {
+                Ok(partitions) if partitions.is_empty() => {
+                    lines.push(Line::from("No partitions."));
+                }
+                Ok(partitions) => {
+                    lines.push(Line::from("Partitions:"));
+                    for partition in partitions {
+                        lines.push(Line::from(format!("  {}", partition.describe())));
+                        if let Some(mountpoint) = &partition.mountpoint {
+                            lines.push(Line::from(Span::styled(
+                                format!("    mounted at {mountpoint}"),
+                                app.theme.warning,
+                            )));
+                        }
+                    }
+                }
+                Err(err) => lines.push(Line::from(Span::styled(
+                    format!("Partitions unavailable: {err}"),
+                    app.theme.error,
+                ))),
+            }
+        }
+    }
 
-    let block = Block::default().borders(Borders::ALL).title("Confirm");
-    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false });
     frame.render_widget(paragraph, area);
 }
 
-fn draw_convert_iso_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+/// Render a bordered list with the selection kept centered, plus a scrollbar
+/// on the right border when the rows don't all fit.
+fn render_scrolling_list(
+    frame: &mut ratatui::Frame,
+    list: List,
+    area: ratatui::layout::Rect,
+    selected: Option<usize>,
+    rows: usize,
+) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let mut state = ratatui::widgets::ListState::default();
+    if let Some(selected) = selected {
+        let max_offset = rows.saturating_sub(visible);
+        let offset = selected.saturating_sub(visible / 2).min(max_offset);
+        state = state.with_offset(offset).with_selected(Some(selected));
+    }
+    frame.render_stateful_widget(list, area, &mut state);
+
+    if rows > visible {
+        let mut scrollbar_state =
+            ScrollbarState::new(rows).position(selected.unwrap_or_default());
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None);
+        frame.render_stateful_widget(
+            scrollbar,
+            area.inner(Margin {
+                vertical: 1,
+                horizontal: 0,
+            }),
+            &mut scrollbar_state,
+        );
+    }
+}
+
+fn draw_confirm_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    input: &str,
+    area: ratatui::layout::Rect,
+) {
     let image = app.image_input.trim();
-    let text = Text::from(vec![
-        Line::from(Span::styled(
-            "Non-hybrid ISO detected",
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+
+    let internal = app.targets_internal_disk();
+
+    let mut lines = vec![Line::from(format!("Image : {image}"))];
+    if app.selected_devices.is_empty() {
+        lines.push(Line::from("Device: <none>"));
+    }
+    for disk in &app.selected_devices {
+        let stable_id = disk
+            .by_id
+            .clone()
+            .unwrap_or_else(|| "(no stable id; using kernel name)".to_string());
+        if disk.removable {
+            lines.push(Line::from(format!("Device: {}", disk.device_path())));
+        } else {
+            lines.push(Line::from(Span::styled(
+                format!("Device: {} (internal disk)", disk.device_path()),
+                app.theme.warning.add_modifier(Modifier::BOLD),
+            )));
+        }
+        lines.push(Line::from(format!("ID    : {stable_id}")));
+    }
+    lines.push(if app.execute {
+        Line::from(Span::styled(
+            "Mode  : EXECUTE - the device will be overwritten (Ctrl+X for a dry run)",
+            app.theme.error.add_modifier(Modifier::BOLD),
+        ))
+    } else {
+        Line::from(Span::styled(
+            "Mode  : DRY RUN - nothing will be written (x to execute)",
+            app.theme.success,
+        ))
+    });
+    lines.push(Line::from(format!("ISO   : {}", iso_info_line(app))));
+    let (size_key, verify_key) = if app.execute {
+        ("Ctrl+S", "Ctrl+V")
+    } else {
+        ("s", "v")
+    };
+    lines.push(Line::from(format!(
+        "Write : {} blocks ({size_key}), verification {} ({verify_key})",
+        crate::format_size(app.block_size.unwrap_or(crate::flash::DEFAULT_BLOCK_SIZE)),
+        if app.require_verification {
+            "required"
+        } else {
+            "best effort"
+        }
+    )));
+    if let Some(journal) = &app.interrupted {
+        let resume_key = if app.execute { "Ctrl+E" } else { "r" };
+        let written = format!(
+            "an earlier write of this image stopped at {:.0}% ({})",
+            journal.fraction() * 100.0,
+            crate::format_size(journal.offset)
+        );
+        lines.push(Line::from(Span::styled(
+            if app.resume {
+                format!("Resume: {written}; continuing there ({resume_key} to start over)")
+            } else {
+                format!("Resume: {written}; starting over ({resume_key} to resume)")
+            },
+            app.theme.warning,
+        )));
+    }
+    if let Some(fstype) = &app.stage_offer {
+        let stage_key = if app.execute { "Ctrl+K" } else { "m" };
+        lines.push(Line::from(if app.stage {
+            format!("Stage : image on {fstype}; copied into RAM first ({stage_key} to read it directly)")
+        } else {
+            format!("Stage : image on {fstype}; read directly ({stage_key} to copy it into RAM first)")
+        }));
+    }
+    lines.push(Line::from(""));
+    if app.target_contents.is_empty() {
+        lines.push(Line::from("Current contents: no partitions found."));
+    } else {
+        lines.push(Line::from("Current contents:"));
+        for content in &app.target_contents {
+            lines.push(Line::from(Span::styled(
+                format!("  {}", content.describe()),
+                app.theme.warning,
+            )));
+        }
+    }
+    lines.push(Line::from(Span::styled(
+        "Everything currently on the target will be permanently lost.",
+        app.theme.error.add_modifier(Modifier::BOLD),
+    )));
+    if !app.target_holders.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Device is open in other programs; close them before flashing:",
+            app.theme.error.add_modifier(Modifier::BOLD),
+        )));
+        for holder in &app.target_holders {
+            lines.push(Line::from(Span::styled(
+                format!("  {}", holder.describe()),
+                app.theme.warning,
+            )));
+        }
+    }
+    lines.push(Line::from(""));
+    if app.execute {
+        let phrase = Line::from(format!(
+            "Type '{}' and press Enter to flash, Esc to go back:",
+            app.confirm_phrase()
+        ));
+        lines.push(if internal {
+            phrase.style(app.theme.warning.add_modifier(Modifier::BOLD))
+        } else {
+            phrase
+        });
+        lines.push(Line::from(Span::styled(
+            format!("> {input}"),
+            app.theme.warning,
+        )));
+    } else {
+        lines.push(Line::from(
+            "Press 'f' to flash, 'x' to switch to execute mode, 'b' to go back.",
+        ));
+    }
+    let text = Text::from(lines);
+
+    let area = if app.target_warnings.is_empty() {
+        area
+    } else {
+        let height = (app.target_warnings.len() as u16 + 2).min(area.height / 2);
+        let sections = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(height), Constraint::Min(0)])
+            .split(area);
+        draw_target_warnings(frame, app, sections[0]);
+        sections[1]
+    };
+    // Internal disks get the whole screen in warning colors, not just a line.
+    let block = if internal {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(app.theme.warning)
+            .title(Span::styled(
+                "Confirm - INTERNAL DISK",
+                app.theme.warning.add_modifier(Modifier::BOLD),
+            ))
+    } else {
+        Block::default().borders(Borders::ALL).title("Confirm")
+    };
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+/// Highlighted list of everything that deserves a second look before flashing.
+fn draw_target_warnings(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let lines: Vec<Line> = app
+        .target_warnings
+        .iter()
+        .map(|warning| Line::from(Span::styled(format!("! {warning}"), app.theme.warning)))
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(app.theme.warning)
+        .title(Span::styled(
+            format!("Warnings ({})", app.target_warnings.len()),
+            app.theme.warning.add_modifier(Modifier::BOLD),
+        ));
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block), area);
+}
+
+fn draw_convert_iso_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let image = app.image_input.trim();
+    let text = Text::from(vec![
+        Line::from(Span::styled(
+            "Non-hybrid ISO detected",
+            app.theme.warning.add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
         Line::from(format!("Image: {image}")),
@@ -548,7 +2010,7 @@ fn draw_convert_iso_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::l
         Line::from(""),
         Line::from(Span::styled(
             "WARNING: This modifies the ISO file in-place.",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            app.theme.error.add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
         Line::from("Press 'y' to convert, 'n' to go back."),
@@ -560,51 +2022,62 @@ fn draw_convert_iso_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::l
     frame.render_widget(paragraph, area);
 }
 
-fn draw_confirm_wipe_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
-    let device = app
-        .selected_device
-        .as_ref()
-        .map(|d| d.device_path())
-        .unwrap_or_else(|| "<none>".to_string());
+fn draw_confirm_wipe_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    info: &DevicePartitionInfo,
+    area: ratatui::layout::Rect,
+) {
+    let device = if app.selected_devices.is_empty() {
+        "<none>".to_string()
+    } else {
+        app.selected_devices
+            .iter()
+            .map(|d| d.device_path())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
 
+    let internal = app.targets_internal_disk();
+    let headline = if internal {
+        "WARNING: Target is an INTERNAL (non-removable) disk!"
+    } else {
+        "WARNING: Device has existing partitions!"
+    };
     let mut lines = vec![
         Line::from(Span::styled(
-            "WARNING: Device has existing partitions!",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            headline,
+            app.theme.error.add_modifier(Modifier::BOLD),
         )),
         Line::from(""),
         Line::from(format!("Device: {device}")),
         Line::from(""),
-        Line::from("The following partitions were found:"),
     ];
+    if info.has_partitions {
+        lines.push(Line::from("The following partitions were found:"));
+    } else {
+        lines.push(Line::from("No partitions were found."));
+    }
 
-    if let Some(info) = &app.partition_info {
-        for detail in &info.partition_details {
-            lines.push(Line::from(Span::styled(
-                format!("  {detail}"),
-                Style::default().fg(Color::Yellow),
-            )));
-        }
+    for detail in &info.partition_details {
+        lines.push(Line::from(Span::styled(
+            format!("  {detail}"),
+            app.theme.warning,
+        )));
+    }
 
-        if info.has_mounted {
-            lines.push(Line::from(""));
-            lines.push(Line::from(Span::styled(
-                "Mounted at:",
-                Style::default().fg(Color::Red),
-            )));
-            for mp in &info.mounted_paths {
-                lines.push(Line::from(Span::styled(
-                    format!("  {mp}"),
-                    Style::default().fg(Color::Red),
-                )));
-            }
+    if info.has_mounted {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Mounted at:", app.theme.error)));
+        for mp in &info.mounted_paths {
+            lines.push(Line::from(Span::styled(format!("  {mp}"), app.theme.error)));
         }
     }
 
     lines.push(Line::from(""));
     lines.push(Line::from(Span::styled(
         "ALL DATA ON THIS DEVICE WILL BE DESTROYED.",
-        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        app.theme.error.add_modifier(Modifier::BOLD),
     )));
     lines.push(Line::from(""));
     lines.push(Line::from("Press 'y' to wipe and flash, 'n' to go back."));
@@ -617,28 +2090,238 @@ fn draw_confirm_wipe_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::
     frame.render_widget(paragraph, area);
 }
 
-fn draw_flashing_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
-    let (percent, label) = if let Some(total) = app.flash_total {
-        let percent = if total == 0 {
-            0
+/// The cached images with their size, age, and digest, and the highlighted
+/// one's details beside them on wide terminals.
+fn draw_cache_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    view: &CacheView,
+    area: ratatui::layout::Rect,
+) {
+    let mut items: Vec<ListItem> = view
+        .images
+        .iter()
+        .map(|image| {
+            let digest = match &image.sha256 {
+                Some(hex) => hex.get(..12).unwrap_or(hex).to_string(),
+                None => "no digest".to_string(),
+            };
+            let mut spans = vec![Span::raw(format!(
+                "{}  {}  {}  ",
+                image.name(),
+                crate::format_size(image.size),
+                crate::format_age(image.used, now())
+            ))];
+            let verifying = view
+                .verifying
+                .as_ref()
+                .is_some_and(|(path, _)| *path == image.path);
+            if verifying {
+                spans.push(Span::raw(format!("{}  ", with_spinner("verifying", true))));
+            } else {
+                match view.checked.get(&image.path) {
+                    Some(Ok(())) => spans.push(Span::styled("verified  ", app.theme.success)),
+                    Some(Err(_)) => spans.push(Span::styled("FAILED  ", app.theme.error)),
+                    None => {}
+                }
+            }
+            spans.push(Span::styled(digest, app.theme.muted));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    if items.is_empty() {
+        items.push(ListItem::new(Line::from(Span::styled(
+            "No downloaded images yet.",
+            app.theme.muted,
+        ))));
+    }
+
+    let total = crate::format_size(view.total_size());
+    let title = match app.config.cache_limit() {
+        Some(limit) => format!(
+            "Downloads ({} images, {total} of {})",
+            view.images.len(),
+            crate::format_size(limit)
+        ),
+        None => format!("Downloads ({} images, {total})", view.images.len()),
+    };
+    let items_len = items.len();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(app.theme.highlight)
+        .highlight_symbol("> ");
+    let selected = (!view.images.is_empty()).then_some(view.selected);
+
+    let (list_area, details_area) = split_side_pane(area);
+    render_scrolling_list(frame, list, list_area, selected, items_len);
+    let Some(details_area) = details_area else {
+        return;
+    };
+    let mut lines = Vec::new();
+    if let Some(image) = view.selected_image() {
+        lines.push(Line::from(Span::styled(
+            image.name(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        lines.push(Line::from(format!("Path   : {}", image.path.display())));
+        lines.push(Line::from(format!(
+            "Size   : {}",
+            crate::format_size(image.size)
+        )));
+        lines.push(Line::from(format!(
+            "Used   : {}",
+            crate::format_age(image.used, now())
+        )));
+        lines.push(Line::from(format!(
+            "SHA-256: {}",
+            image
+                .sha256
+                .as_deref()
+                .unwrap_or("not recorded (v records it)")
+        )));
+        match view.checked.get(&image.path) {
+            Some(Ok(())) => lines.push(Line::from(Span::styled(
+                "Verified: matches its digest",
+                app.theme.success,
+            ))),
+            Some(Err(err)) => lines.push(Line::from(Span::styled(
+                format!("Verify failed: {err}"),
+                app.theme.error,
+            ))),
+            None => {}
+        }
+    }
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, details_area);
+}
+
+fn draw_download_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    run: &DownloadRun,
+    area: ratatui::layout::Rect,
+) {
+    let paused = run.control.is_paused();
+    let status = if run.control.is_cancelled() {
+        "Cancelling..."
+    } else if paused {
+        "Paused"
+    } else {
+        run.phase.as_str()
+    };
+    let received = match run.total {
+        Some(total) => crate::format_progress(run.done, total, app.exact_bytes),
+        None => crate::format_size(run.done),
+    };
+    let speed = match run.rate {
+        _ if paused => "-".to_string(),
+        Some(rate) => format!("{}/s", crate::format_size(rate as u64)),
+        None => "(measuring)".to_string(),
+    };
+    let mut lines = vec![
+        Line::from(format!("Downloading {}", run.name)),
+        Line::from(with_spinner(status, !paused)),
+        Line::from(""),
+        Line::from(format!("Received : {received}")),
+        Line::from(format!("Speed    : {speed}")),
+    ];
+    if let Some(remaining) = run.remaining().filter(|_| !paused) {
+        lines.push(Line::from(format!(
+            "Remaining: {}",
+            crate::format_duration(remaining)
+        )));
+    }
+    lines.push(Line::from(Span::styled(
+        format!("From     : {}", run.url),
+        app.theme.muted,
+    )));
+
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16 + 3),
+            Constraint::Min(3),
+        ])
+        .split(area);
+    let block = Block::default().borders(Borders::ALL).title("Download");
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, sections[0]);
+
+    let percent = run
+        .total
+        .and_then(|total| run.done.saturating_mul(100).checked_div(total))
+        .map_or(0, |p| (p as u16).min(100));
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title("Progress"))
+        .gauge_style(if paused {
+            app.theme.muted
         } else {
-            (((app.flash_done.saturating_mul(100)) / total) as u16).min(100)
-        };
-        let label = format!("{} / {} bytes", app.flash_done, total);
+            app.theme.progress
+        })
+        .label(received)
+        .percent(percent);
+    frame.render_widget(gauge, sections[1]);
+}
+
+fn draw_flashing_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    run: &FlashRun,
+    area: ratatui::layout::Rect,
+) {
+    let (percent, label) = if let Some(total) = run.total {
+        let percent = run
+            .done
+            .saturating_mul(100)
+            .checked_div(total)
+            .map(|p| (p as u16).min(100))
+            .unwrap_or(0);
+        let label = crate::format_progress(run.done, total, app.exact_bytes);
         (percent, label)
     } else {
         (0, "Working...".to_string())
     };
 
+    let pending = || "(waiting)".to_string();
+    let mut lines = vec![
+        Line::from("Flashing in progress"),
+        Line::from(with_spinner(&run.progress, run.indeterminate())),
+        Line::from(""),
+    ];
+    if let Some(total) = run.total {
+        lines.push(Line::from(format!(
+            "Written  : {}",
+            crate::format_progress(run.done, total, app.exact_bytes)
+        )));
+    }
+    if let Some((done, total)) = run.verify {
+        lines.push(Line::from(format!(
+            "Verified : {}",
+            crate::format_progress(done, total, app.exact_bytes)
+        )));
+    }
+    lines.extend([
+        Line::from(format!("Backend  : {}", app.config.backend.name())),
+        Line::from(format!(
+            "Elevation: {}",
+            run.elevation.clone().unwrap_or_else(pending)
+        )),
+        Line::from(Span::styled(
+            format!("Command  : {}", run.command.clone().unwrap_or_else(pending)),
+            app.theme.muted,
+        )),
+    ]);
+
     let sections = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(5), Constraint::Min(3)])
+        .constraints([Constraint::Length(lines.len() as u16 + 3), Constraint::Min(3)])
         .split(area);
 
-    let header = Text::from(vec![
-        Line::from("Flashing in progress"),
-        Line::from(app.flash_progress.as_str()),
-    ]);
+    let header = Text::from(lines);
 
     let block = Block::default().borders(Borders::ALL).title("Flashing");
     let paragraph = Paragraph::new(header)
@@ -646,35 +2329,240 @@ fn draw_flashing_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layo
         .wrap(Wrap { trim: false });
     frame.render_widget(paragraph, sections[0]);
 
+    if run.stage.is_some() {
+        draw_flash_phases(frame, app, run, sections[1]);
+        return;
+    }
     let gauge = Gauge::default()
         .block(Block::default().borders(Borders::ALL).title("Progress"))
-        .gauge_style(Style::default().fg(Color::Green))
+        .gauge_style(app.theme.progress)
         .label(label)
         .percent(percent);
     frame.render_widget(gauge, sections[1]);
 }
 
-fn draw_result_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
-    let result = app.flash_result.as_ref();
-    let (title, style, message) = match result {
-        Some(result) if result.ok => (
-            "Success",
-            Style::default().fg(Color::Green),
-            result.message.as_str(),
-        ),
-        Some(result) => (
-            "Failed",
-            Style::default().fg(Color::Red),
-            result.message.as_str(),
-        ),
-        None => ("Result", Style::default().fg(Color::Gray), "No result."),
+/// One gauge per flash phase, side by side: finished phases full, the running
+/// one with its own percentage, later ones empty.
+fn draw_flash_phases(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    run: &FlashRun,
+    area: ratatui::layout::Rect,
+) {
+    let phases = shown_phases(run);
+    let segments = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Ratio(1, phases.len() as u32);
+            phases.len()
+        ])
+        .split(area);
+    for (phase, segment) in phases.into_iter().zip(segments.iter()) {
+        let current = run.stage == Some(phase);
+        let percent = phase_percent(run, phase);
+        let label = match percent {
+            Some(percent) => format!("{percent}%"),
+            None => with_spinner("...", true),
+        };
+        let (style, title_style) = if current {
+            (
+                app.theme.progress,
+                Style::default().add_modifier(Modifier::BOLD),
+            )
+        } else if percent == Some(100) {
+            (app.theme.success, Style::default())
+        } else {
+            (app.theme.muted, app.theme.muted)
+        };
+        let gauge = Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled(phase.name(), title_style)),
+            )
+            .gauge_style(style)
+            .label(label)
+            .percent(percent.unwrap_or(0));
+        frame.render_widget(gauge, *segment);
+    }
+}
+
+/// The phases of `run`, leaving out staging unless the image is being staged.
+fn shown_phases(run: &FlashRun) -> Vec<FlashPhase> {
+    FlashPhase::ALL
+        .into_iter()
+        .filter(|phase| *phase != FlashPhase::Stage || run.staged.is_some())
+        .collect()
+}
+
+/// Completion of a flash phase in percent, or `None` while the current phase
+/// has no measurable progress (sync and post-flash).
+fn phase_percent(run: &FlashRun, phase: FlashPhase) -> Option<u16> {
+    let percent_of = |done: u64, total: u64| {
+        done.saturating_mul(100)
+            .checked_div(total)
+            .map(|p| p.min(100) as u16)
     };
+    let current = run.stage?;
+    match phase.cmp(&current) {
+        std::cmp::Ordering::Less => Some(100),
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Equal => match phase {
+            FlashPhase::Stage => run.staged.and_then(|(done, total)| percent_of(done, total)),
+            FlashPhase::Write => run.total.and_then(|total| percent_of(run.done, total)),
+            FlashPhase::Verify => run.verify.and_then(|(done, total)| percent_of(done, total)),
+            FlashPhase::Sync | FlashPhase::PostFlash => None,
+        },
+    }
+}
 
-    let text = Text::from(vec![
-        Line::from(Span::styled(title, style.add_modifier(Modifier::BOLD))),
-        Line::from(message),
-        Line::from("Press 'r' to start over, 'q' to quit."),
-    ]);
+/// Full-screen flashing view: large percentage, rate, and current phase only.
+fn draw_flashing_zoomed(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    run: &FlashRun,
+    area: ratatui::layout::Rect,
+) {
+    // Once phases are known, show the running phase's own percentage.
+    let percent = match run.stage {
+        Some(phase) => phase_percent(run, phase).map(u64::from),
+        None => run
+            .total
+            .and_then(|total| run.done.saturating_mul(100).checked_div(total))
+            .map(|p| p.min(100)),
+    };
+
+    let mut lines = vec![Line::from("")];
+    let big = percent.map_or_else(|| "--".to_string(), |p| format!("{p}%"));
+    for row in big_text(&big) {
+        lines.push(Line::from(Span::styled(
+            row,
+            app.theme.success.add_modifier(Modifier::BOLD),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        run.rate.clone().unwrap_or_else(|| "-- MB/s".to_string()),
+        Style::default().add_modifier(Modifier::BOLD),
+    )));
+    if let Some(total) = run.total {
+        lines.push(Line::from(crate::format_progress(
+            run.done,
+            total,
+            app.exact_bytes,
+        )));
+    }
+    lines.push(Line::from(with_spinner(&run.phase, run.indeterminate())));
+    if let Some(current) = run.stage {
+        let mut spans = Vec::new();
+        for phase in shown_phases(run) {
+            if !spans.is_empty() {
+                spans.push(Span::styled(" > ", app.theme.muted));
+            }
+            spans.push(if phase == current {
+                Span::styled(phase.name(), Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Span::styled(phase.name(), app.theme.muted)
+            });
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "z=normal view  u=units",
+        app.theme.muted,
+    )));
+
+    let paragraph = Paragraph::new(Text::from(lines))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Flashing"))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+/// Render text as five rows of block glyphs. Only digits, '%', and '-' are supported;
+/// other characters are drawn as blanks.
+fn big_text(text: &str) -> [String; 5] {
+    const GLYPHS: [(char, [&str; 5]); 12] = [
+        ('0', ["███", "█ █", "█ █", "█ █", "███"]),
+        ('1', [" █ ", "██ ", " █ ", " █ ", "███"]),
+        ('2', ["███", "  █", "███", "█  ", "███"]),
+        ('3', ["███", "  █", "███", "  █", "███"]),
+        ('4', ["█ █", "█ █", "███", "  █", "  █"]),
+        ('5', ["███", "█  ", "███", "  █", "███"]),
+        ('6', ["███", "█  ", "███", "█ █", "███"]),
+        ('7', ["███", "  █", "  █", "  █", "  █"]),
+        ('8', ["███", "█ █", "███", "█ █", "███"]),
+        ('9', ["███", "█ █", "███", "  █", "███"]),
+        ('%', ["█ █", "  █", " █ ", "█  ", "█ █"]),
+        ('-', ["   ", "   ", "███", "   ", "   "]),
+    ];
+    let mut rows: [String; 5] = Default::default();
+    for ch in text.chars() {
+        let glyph = GLYPHS
+            .iter()
+            .find(|(c, _)| *c == ch)
+            .map(|(_, rows)| *rows)
+            .unwrap_or(["   "; 5]);
+        for (row, part) in rows.iter_mut().zip(glyph) {
+            if !row.is_empty() {
+                row.push(' ');
+            }
+            row.push_str(part);
+        }
+    }
+    rows
+}
+
+fn draw_result_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    outcome: &Outcome,
+    area: ratatui::layout::Rect,
+) {
+    let result = &outcome.result;
+    let (title, style) = if result.ok {
+        ("Success", app.theme.success)
+    } else {
+        ("Failed", app.theme.error)
+    };
+
+    let mut lines = vec![Line::from(Span::styled(
+        title,
+        style.add_modifier(Modifier::BOLD),
+    ))];
+    if let Some(hint) = result.hint() {
+        lines.push(Line::from(Span::styled(hint, app.theme.muted)));
+    }
+    // Real flashes get the full report (which repeats the message); dry runs
+    // only have the message.
+    match &outcome.report {
+        Some(report) => {
+            lines.push(Line::from(""));
+            lines.extend(report.lines().into_iter().map(Line::from));
+            lines.push(Line::from(""));
+            let cloud = app.execute
+                && result.ok
+                && crate::seed::looks_like_cloud_image(&report.image.to_string_lossy());
+            if cloud {
+                lines.push(Line::from(Span::styled(
+                    "This looks like a cloud image: press 'i' to add a cloud-init seed so it boots configured.",
+                    app.theme.warning,
+                )));
+            }
+            lines.push(Line::from(
+                "Press 's' to save this report, 'r' to start over, 'q' to quit.",
+            ));
+        }
+        None => {
+            lines.push(Line::from(result.message.as_str()));
+            if let Some(timing) = result.timing_summary() {
+                lines.push(Line::from(timing));
+            }
+            lines.push(Line::from("Press 'r' to start over, 'q' to quit."));
+        }
+    }
+    let text = Text::from(lines);
     let block = Block::default().borders(Borders::ALL).title("Result");
     let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: false });
     frame.render_widget(paragraph, area);
@@ -684,37 +2572,728 @@ fn draw_error_step(frame: &mut ratatui::Frame, app: &App, area: ratatui::layout:
     let text = Text::from(vec![
         Line::from(Span::styled(
             "Error",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            app.theme.error.add_modifier(Modifier::BOLD),
         )),
         Line::from(app.status.as_str()),
-        Line::from("Press 'r' to start over, 'q' to quit."),
+        Line::from(
+            "Press 'b' to go back, 't' to retry, 'd' to rescan devices, 'r' to start over, 'q' to quit.",
+        ),
     ]);
     let block = Block::default().borders(Borders::ALL).title("Error");
     let paragraph = Paragraph::new(text).block(block);
     frame.render_widget(paragraph, area);
 }
 
-fn status_line(app: &App) -> Line<'static> {
-    let keys = match app.step {
-        Step::Image => "Up/Down/PgUp/PgDn=nav  Enter=open/select  Tab=filter  Ctrl+H=hidden  Ctrl+U=clear  q=quit",
-        Step::Device => "Up/Down=select  Enter=next  r=rescan  a=all  b=back  q=quit",
-        Step::Confirm => "f=flash  b=back  q=quit",
-        Step::ConvertIso => "y=convert  n=cancel  q=quit",
-        Step::ConfirmWipe => "y=confirm wipe  n=cancel  q=quit",
-        Step::Flashing => "Flashing... please wait",
-        Step::Result | Step::Error => "r=restart  q=quit",
+/// Maximum number of bad block ranges listed after a surface scan.
+const SCAN_LOCATIONS_SHOWN: usize = 8;
+
+fn draw_benchmark_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    tests: &DeviceTests,
+    area: ratatui::layout::Rect,
+) {
+    let device = tests.disk.device_path();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(area);
+
+    let mut lines = vec![Line::from(format!("Device: {device}")), Line::from("")];
+
+    if tests.running.is_some() {
+        lines.push(Line::from(with_spinner(
+            "Test in progress",
+            tests.fraction.is_none(),
+        )));
+        lines.push(Line::from(tests.progress.as_str()));
+    } else {
+        match &tests.outcome {
+            Some(TestOutcome::Speed(Ok(result))) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Result: {}", result.summary()),
+                    app.theme.success.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from(format!(
+                    "Transferred {} MiB per pass using direct I/O.",
+                    result.bytes / (1024 * 1024)
+                )));
+            }
+            Some(TestOutcome::Speed(Err(err))) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Benchmark failed: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+            }
+            Some(TestOutcome::Capacity(Ok(report))) => {
+                let style = if report.genuine() {
+                    app.theme.success
+                } else {
+                    app.theme.error
+                };
+                lines.push(Line::from(Span::styled(
+                    report.summary(),
+                    style.add_modifier(Modifier::BOLD),
+                )));
+            }
+            Some(TestOutcome::Capacity(Err(err))) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Capacity test failed: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+            }
+            Some(TestOutcome::Scan(Ok(report))) => {
+                let style = if report.bad_blocks.is_empty() {
+                    app.theme.success
+                } else {
+                    app.theme.error
+                };
+                lines.push(Line::from(Span::styled(
+                    report.summary(),
+                    style.add_modifier(Modifier::BOLD),
+                )));
+                for location in report.locations(SCAN_LOCATIONS_SHOWN) {
+                    lines.push(Line::from(format!("  {location}")));
+                }
+            }
+            Some(TestOutcome::Scan(Err(err))) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Surface scan failed: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+            }
+            None => {
+                lines.push(Line::from(
+                    "Measures sequential throughput over the first 64 MiB of the device.",
+                ));
+                lines.push(Line::from(
+                    "Unusually slow results can indicate dying or counterfeit media.",
+                ));
+                lines.push(Line::from(
+                    "The capacity test checks that the full advertised size really stores data.",
+                ));
+                lines.push(Line::from(
+                    "The surface scan writes and verifies every block; it can take hours.",
+                ));
+            }
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "The write, capacity, and surface tests overwrite data on the device (partition table included).",
+            app.theme.warning,
+        )));
+        lines.push(Line::from(
+            "Press 'r' for a read-only test, 'w' for a write+read test, 'c' for a capacity test, 's' for a surface scan, 'b' to go back.",
+        ));
+    }
+
+    let block = Block::default().borders(Borders::ALL).title("Benchmark");
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, chunks[0]);
+
+    if let Some((done, total)) = tests.fraction {
+        let ratio = if total == 0 {
+            0.0
+        } else {
+            (done as f64 / total as f64).min(1.0)
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title("Progress"))
+            .gauge_style(app.theme.progress)
+            .ratio(ratio)
+            .label(format!("{:.1}%", ratio * 100.0));
+        frame.render_widget(gauge, chunks[1]);
+    }
+}
+
+fn draw_wipe_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    job: &DeviceJob,
+    area: ratatui::layout::Rect,
+) {
+    let device = job.disk.device_path();
+
+    let mut lines = vec![Line::from(format!("Device: {device}")), Line::from("")];
+
+    if job.running() {
+        lines.push(Line::from(with_spinner("Wipe in progress", true)));
+        lines.push(Line::from(job.progress.as_str()));
+    } else {
+        match &job.result {
+            Some(Ok(())) => {
+                let message = if app.execute {
+                    "Wipe completed. The device is blank and ready for reuse."
+                } else {
+                    job.progress.as_str()
+                };
+                lines.push(Line::from(Span::styled(
+                    message,
+                    app.theme.success.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press 'b' to return to the device list."));
+            }
+            Some(Err(err)) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Wipe failed: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press 'b' to return to the device list."));
+            }
+            None => {
+                lines.push(Line::from(
+                    "Removes all filesystem/partition signatures and zeroes the first and last MiB.",
+                ));
+                lines.push(Line::from(Span::styled(
+                    "ALL DATA ON THIS DEVICE WILL BE DESTROYED.",
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from(""));
+                lines.push(Line::from("Press 'y' to wipe, 'n' to go back."));
+            }
+        }
+    }
+
+    let block = Block::default().borders(Borders::ALL).title("Quick Wipe");
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_format_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    format: &FormatJob,
+    area: ratatui::layout::Rect,
+) {
+    let device = format.job.disk.device_path();
+
+    let mut lines = vec![Line::from(format!("Device: {device}")), Line::from("")];
+
+    if format.job.running() {
+        lines.push(Line::from(with_spinner("Formatting in progress", true)));
+        lines.push(Line::from(format.job.progress.as_str()));
+    } else {
+        match &format.job.result {
+            Some(Ok(())) => {
+                let message = if app.execute {
+                    "Format completed."
+                } else {
+                    format.job.progress.as_str()
+                };
+                lines.push(Line::from(Span::styled(
+                    message,
+                    app.theme.success.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press Enter to return to the device list."));
+            }
+            Some(Err(err)) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Format failed: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press Enter to return to the device list."));
+            }
+            None => {
+                let choices = crate::format::Filesystem::ALL
+                    .iter()
+                    .map(|fs| {
+                        if *fs == format.fs {
+                            format!("[{}]", fs.name())
+                        } else {
+                            format!(" {} ", fs.name())
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                lines.push(Line::from(format!("Filesystem: {choices}")));
+                lines.push(Line::from(Span::styled(
+                    format!("Label     : {}", format.label),
+                    app.theme.warning,
+                )));
+                let normalized = crate::format::normalize_label(format.fs, &format.label);
+                if normalized != format.label {
+                    lines.push(Line::from(format!("Applied as: {normalized}")));
+                }
+                lines.push(Line::from(""));
+                lines.push(Line::from(Span::styled(
+                    "ALL DATA ON THIS DEVICE WILL BE DESTROYED.",
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from(
+                    "Type a label, Tab to change filesystem, Enter to format, Esc to go back.",
+                ));
+            }
+        }
+    }
+
+    let block = Block::default().borders(Borders::ALL).title("Format Device");
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_seed_step(
+    frame: &mut ratatui::Frame,
+    app: &App,
+    job: &SeedJob,
+    area: ratatui::layout::Rect,
+) {
+    let devices: Vec<String> = app.selected_devices.iter().map(Disk::device_path).collect();
+    let mut lines = vec![Line::from(format!("Devices: {}", devices.join(", ")))];
+
+    if !job.editing() {
+        lines.push(Line::from(""));
+        match &job.result {
+            None => {
+                lines.push(Line::from(with_spinner(
+                    "Writing the cloud-init seed",
+                    true,
+                )));
+                lines.push(Line::from(job.progress.as_str()));
+            }
+            Some(Ok(())) => {
+                let message = if app.execute {
+                    "Cloud-init seed written. The stick boots into the configured system."
+                } else {
+                    job.progress.as_str()
+                };
+                lines.push(Line::from(Span::styled(
+                    message,
+                    app.theme.success.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press Enter to return to the result."));
+            }
+            Some(Err(err)) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Seed not written: {err}"),
+                    app.theme.error.add_modifier(Modifier::BOLD),
+                )));
+                lines.push(Line::from("Press Enter to return to the result."));
+            }
+        }
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Cloud-init Seed");
+        let paragraph = Paragraph::new(Text::from(lines))
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let source = match &job.source {
+        Some(path) => format!("loaded from {}", path.display()),
+        None => "template".to_string(),
+    };
+    lines.push(Line::from(format!("user-data: {source}")));
+    let mut extra = vec![if job.seed.meta_data.is_some() {
+        "meta-data: from files"
+    } else {
+        "meta-data: generated (new instance id)"
+    }];
+    if job.seed.network_config.is_some() {
+        extra.push("network-config: from files");
+    }
+    lines.push(Line::from(Span::styled(extra.join(", "), app.theme.muted)));
+    lines.push(Line::from(format!(
+        "Written to the {} partition, added after the image's own if missing.",
+        crate::seed::LABEL
+    )));
+
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16 + 2),
+            Constraint::Min(5),
+        ])
+        .split(area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Cloud-init Seed");
+    let paragraph = Paragraph::new(Text::from(lines))
+        .block(block)
+        .wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, sections[0]);
+
+    // Scrolled so the cursor's line is always in view; long lines scroll
+    // sideways around the cursor like the path input.
+    let editor = sections[1];
+    let height = editor.height.saturating_sub(2) as usize;
+    let width = editor.width.saturating_sub(2) as usize;
+    let text = &job.seed.user_data;
+    let (row, offset) = lineedit::line_and_offset(text, job.cursor);
+    let first = row.saturating_sub(height.saturating_sub(1));
+    let shown: Vec<Line> = text
+        .split('\n')
+        .enumerate()
+        .skip(first)
+        .take(height)
+        .map(|(index, line)| {
+            if index != row {
+                return Line::from(line.to_string());
+            }
+            let (before, at, after) = lineedit::split_visible(line, offset, width);
+            Line::from(vec![
+                Span::styled(before.to_string(), app.theme.warning),
+                Span::styled(
+                    at.to_string(),
+                    app.theme.warning.add_modifier(Modifier::REVERSED),
+                ),
+                Span::styled(after.to_string(), app.theme.warning),
+            ])
+        })
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("user-data (line {})", row + 1));
+    frame.render_widget(Paragraph::new(Text::from(shown)).block(block), editor);
+}
+
+/// One entry of the key hint footer: "keys=action", or plain text when
+/// `keys` is empty. Disabled hints are drawn dimmed.
+struct Hint {
+    keys: &'static str,
+    action: &'static str,
+    enabled: bool,
+}
+
+impl Hint {
+    fn new(keys: &'static str, action: &'static str) -> Self {
+        Self {
+            keys,
+            action,
+            enabled: true,
+        }
+    }
+
+    /// A plain-text hint, e.g. "Flashing... please wait".
+    fn note(text: &'static str) -> Self {
+        Self::new("", text)
+    }
+
+    /// Dim the hint unless `enabled`.
+    fn when(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    fn width(&self) -> usize {
+        let action = Span::raw(self.action).width();
+        if self.keys.is_empty() {
+            action
+        } else {
+            Span::raw(self.keys).width() + 1 + action
+        }
+    }
+
+    fn spans(&self, theme: &crate::theme::Theme) -> Vec<Span<'static>> {
+        let style = if self.enabled {
+            Style::default()
+        } else {
+            theme.muted.add_modifier(Modifier::DIM)
+        };
+        if self.keys.is_empty() {
+            return vec![Span::styled(self.action, style)];
+        }
+        let key_style = if self.enabled {
+            style.add_modifier(Modifier::BOLD)
+        } else {
+            style
+        };
+        vec![
+            Span::styled(self.keys, key_style),
+            Span::styled(format!("={}", self.action), style),
+        ]
+    }
+}
+
+/// Gap between hints in the footer.
+const HINT_GAP: &str = "  ";
+
+/// Key hints for the current step and state, most important first.
+fn key_hints(app: &App) -> Vec<Hint> {
+    if app.modal.is_some() {
+        return vec![Hint::note("Answer the dialog"), Hint::new("Esc", "cancel")];
+    }
+    if app.log_open {
+        return vec![
+            Hint::new("Up/Down/PgUp/PgDn/Home/End", "scroll"),
+            Hint::new("l/Esc", "close log"),
+        ];
+    }
+    if app.history.is_some() {
+        return vec![
+            Hint::new("Up/Down/PgUp/PgDn/Home/End", "scroll"),
+            Hint::new("h/Esc", "close history"),
+        ];
+    }
+    let any_device = !app.visible_devices().is_empty();
+    match &app.step {
+        Step::Image => {
+            let typed = !app.image_input.is_empty();
+            let url = download::is_url(&app.image_input);
+            vec![
+                Hint::new("Up/Down/PgUp/PgDn", "nav"),
+                Hint::new("Enter", if url { "download" } else { "open/select" })
+                    .when(typed || !app.entries.is_empty()),
+                Hint::new("Tab", "complete/filter"),
+                Hint::new(".", "hidden").when(!typed),
+                Hint::new("Ctrl+S", "sort"),
+                Hint::new("Ctrl+B", "bookmark"),
+                Hint::new("1-9", "jump").when(!typed && !app.config.bookmarks.is_empty()),
+                Hint::new("g", "go to dir").when(!typed),
+                Hint::new("c", "catalog").when(!typed),
+                Hint::new("d", "downloads").when(!typed),
+                Hint::new("Ctrl+V", "paste"),
+                Hint::new("Ctrl+U", "clear").when(typed),
+                Hint::new("q", "quit"),
+            ]
+        }
+        Step::Device if app.device_filter_editing => vec![
+            Hint::note("Type to filter by name, model, or serial"),
+            Hint::new("Enter", "keep"),
+            Hint::new("Esc", "clear"),
+        ],
+        Step::Device => vec![
+            Hint::new("Up/Down", "select").when(any_device),
+            Hint::new("Space", "mark").when(any_device),
+            Hint::new("Enter", "next").when(any_device),
+            Hint::new("/", "filter"),
+            Hint::new("r", "rescan"),
+            Hint::new("a", "all"),
+            Hint::new("t", "benchmark").when(any_device),
+            Hint::new("w", "wipe").when(any_device),
+            Hint::new("m", "format").when(any_device),
+            Hint::new("l", "log"),
+            Hint::new("b", "back"),
+            Hint::new("q", "quit"),
+        ],
+        Step::Confirm(input) if app.execute => {
+            let typed = input.trim() == app.confirm_phrase();
+            let mut hints = vec![
+                Hint::note(if app.targets_internal_disk() {
+                    "Type ERASE and the device name"
+                } else {
+                    "Type device name"
+                }),
+                Hint::new("Enter", "flash").when(typed),
+                Hint::new("Ctrl+X", "dry run"),
+                Hint::new("Ctrl+S", "block size"),
+                Hint::new("Ctrl+V", "verify"),
+                Hint::new("Esc", "back"),
+            ];
+            if app.stage_offer.is_some() {
+                hints.insert(5, Hint::new("Ctrl+K", "stage"));
+            }
+            if app.interrupted.is_some() {
+                hints.insert(5, Hint::new("Ctrl+E", "resume"));
+            }
+            hints
+        }
+        Step::Confirm(_) => {
+            let mut hints = vec![
+                Hint::new("f", "flash").when(!app.selected_devices.is_empty()),
+                Hint::new("x", "execute"),
+                Hint::new("s", "block size"),
+                Hint::new("v", "verify"),
+                Hint::new("b", "back"),
+                Hint::new("q", "quit"),
+            ];
+            if app.stage_offer.is_some() {
+                hints.insert(4, Hint::new("m", "stage"));
+            }
+            if app.interrupted.is_some() {
+                hints.insert(4, Hint::new("r", "resume"));
+            }
+            hints
+        }
+        Step::ConvertIso => vec![
+            Hint::new("y", "convert"),
+            Hint::new("n", "cancel"),
+            Hint::new("q", "quit"),
+        ],
+        Step::ConfirmWipe(_) => vec![
+            Hint::new("y", "confirm wipe"),
+            Hint::new("n", "cancel"),
+            Hint::new("q", "quit"),
+        ],
+        Step::Download(run) => vec![
+            Hint::new(
+                "p",
+                if run.control.is_paused() {
+                    "resume"
+                } else {
+                    "pause"
+                },
+            ),
+            Hint::new("c", "cancel"),
+            Hint::new("u", "units"),
+            Hint::new("l", "log"),
+        ],
+        Step::Cache(view) if view.verifying.is_some() => {
+            vec![
+                Hint::note("Verifying... please wait"),
+                Hint::new("l", "log"),
+            ]
+        }
+        Step::Cache(view) => {
+            let any = !view.images.is_empty();
+            vec![
+                Hint::new("Up/Down", "select").when(any),
+                Hint::new("Enter", "flash").when(any),
+                Hint::new("v", "verify").when(any),
+                Hint::new("d", "delete").when(any),
+                Hint::new("r", "reload"),
+                Hint::new("b", "back"),
+                Hint::new("q", "quit"),
+            ]
+        }
+        Step::Flashing(_) => vec![
+            Hint::note("Flashing... please wait"),
+            Hint::new("z", "zoom"),
+            Hint::new("u", "units"),
+            Hint::new("l", "log"),
+        ],
+        Step::Benchmark(tests) if tests.running.is_some() => {
+            vec![Hint::note("Benchmarking... please wait")]
+        }
+        Step::Benchmark(_) => vec![
+            Hint::new("r", "read"),
+            Hint::new("w", "write").when(app.execute),
+            Hint::new("c", "capacity").when(app.execute),
+            Hint::new("s", "surface scan").when(app.execute),
+            Hint::new("b", "back"),
+            Hint::new("q", "quit"),
+        ],
+        Step::Wipe(job) if job.running() => vec![Hint::note("Wiping... please wait")],
+        Step::Wipe(job) if job.result.is_some() => {
+            vec![Hint::new("b", "back"), Hint::new("q", "quit")]
+        }
+        Step::Wipe(_) => vec![
+            Hint::new("y", "wipe"),
+            Hint::new("n", "cancel"),
+            Hint::new("q", "quit"),
+        ],
+        Step::Format(format) if format.job.running() => {
+            vec![Hint::note("Formatting... please wait")]
+        }
+        Step::Format(format) if format.job.result.is_some() => {
+            vec![Hint::new("Enter", "back"), Hint::new("q", "quit")]
+        }
+        Step::Format(_) => vec![
+            Hint::new("Tab", "filesystem"),
+            Hint::new("Enter", "format"),
+            Hint::new("Esc", "back"),
+        ],
+        Step::Seed(job) if job.task.is_some() => {
+            vec![Hint::note("Writing seed... please wait")]
+        }
+        Step::Seed(job) if job.result.is_some() => {
+            vec![Hint::new("Enter", "back"), Hint::new("q", "quit")]
+        }
+        Step::Seed(_) => vec![
+            Hint::new("Ctrl+S", "write"),
+            Hint::new("Ctrl+F", "load files"),
+            Hint::new("Ctrl+V", "paste"),
+            Hint::new("Tab", "indent"),
+            Hint::new("Esc", "back"),
+        ],
+        Step::Result(outcome) => {
+            let flashed = app.execute && outcome.result.ok;
+            vec![
+                Hint::new("o", "mount").when(flashed),
+                Hint::new("e", "eject").when(flashed),
+                Hint::new("i", "cloud-init").when(flashed),
+                Hint::new("s", "save report").when(outcome.report.is_some()),
+                Hint::new("r", "restart"),
+                Hint::new("l", "log"),
+                Hint::new("q", "quit"),
+            ]
+        }
+        Step::Error(_) => vec![
+            Hint::new("b", "back"),
+            Hint::new("t", "retry"),
+            Hint::new("d", "rescan devices"),
+            Hint::new("r", "restart"),
+            Hint::new("l", "log"),
+            Hint::new("q", "quit"),
+        ],
+    }
+}
+
+/// Split hints into pages that fit in `width` columns, leaving room on each
+/// page for the "more" indicator when more than one page is needed. Returns
+/// the number of hints on each page; a hint too wide for any page gets one
+/// to itself (and is cut off when drawn).
+fn hint_pages(widths: &[usize], width: usize) -> Vec<usize> {
+    let fits = |widths: &[usize], width: usize| {
+        widths.iter().sum::<usize>() + HINT_GAP.len() * widths.len().saturating_sub(1) <= width
     };
+    if fits(widths, width) {
+        return vec![widths.len()];
+    }
+    let width = width.saturating_sub(HINT_MORE_WIDTH);
+    let mut pages = Vec::new();
+    let mut start = 0;
+    while start < widths.len() {
+        let mut end = start + 1;
+        while end < widths.len() && fits(&widths[start..=end], width) {
+            end += 1;
+        }
+        pages.push(end - start);
+        start = end;
+    }
+    pages
+}
+
+/// Columns reserved for the paging indicator, e.g. "  ?=more 1/3".
+const HINT_MORE_WIDTH: usize = 14;
+
+/// The footer: one page of key hints and, below them or after them when
+/// there is only one line, the status message.
+fn footer_text(app: &App, area: ratatui::layout::Rect) -> Text<'static> {
+    let width = area.width.saturating_sub(2) as usize;
+    let hints = key_hints(app);
+    let widths: Vec<usize> = hints.iter().map(Hint::width).collect();
+    let pages = hint_pages(&widths, width);
+    let page = match app.hint_page {
+        (step, page) if step == std::mem::discriminant(&app.step) => page % pages.len(),
+        _ => 0,
+    };
+    let start: usize = pages[..page].iter().sum();
 
-    let mut spans = vec![Span::raw(keys)];
-    if !app.status.is_empty() {
-        spans.push(Span::raw("  |  "));
+    let mut spans = Vec::new();
+    for hint in &hints[start..start + pages[page]] {
+        if !spans.is_empty() {
+            spans.push(Span::raw(HINT_GAP));
+        }
+        spans.extend(hint.spans(&app.theme));
+    }
+    if pages.len() > 1 {
+        let key = if in_text_field(app) { "F1" } else { "?" };
+        spans.push(Span::raw(HINT_GAP));
         spans.push(Span::styled(
-            app.status.clone(),
-            Style::default().fg(Color::Red),
+            format!("{key}=more {}/{}", page + 1, pages.len()),
+            app.theme.highlight,
         ));
     }
 
-    Line::from(spans)
+    let status =
+        (!app.status.is_empty()).then(|| Span::styled(app.status.clone(), app.theme.error));
+    match status {
+        Some(status) if area.height > 3 => Text::from(vec![Line::from(spans), Line::from(status)]),
+        Some(status) => {
+            spans.push(Span::raw("  |  "));
+            spans.push(status);
+            Text::from(Line::from(spans))
+        }
+        None => Text::from(Line::from(spans)),
+    }
 }
 
 fn iso_info_line(app: &App) -> String {
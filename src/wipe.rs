@@ -0,0 +1,130 @@
+//! Quick wipe of a device's boot records and filesystem signatures.
+//!
+//! Sticks that previously held a hybrid ISO often carry leftover MBR/GPT
+//! structures (including the backup GPT at the end of the disk) that confuse
+//! desktop tools. A quick wipe removes all signatures with `wipefs` and zeroes
+//! the first and last MiB, leaving a blank device ready for reuse.
+
+use anyhow::{Context, Result};
+use std::process::Stdio;
+use std::sync::mpsc;
+
+use crate::device::DevicePath;
+use crate::flash::{
+    acquire_elevator, elevated_command, ensure_device_safe, list_partitions, unmount_partitions,
+};
+use crate::platform;
+
+/// Number of 512-byte sectors in one MiB.
+const SECTORS_PER_MIB: u64 = 2048;
+
+/// Wipe signatures and zero the first and last MiB of a device.
+///
+/// Mounted partitions are unmounted first; a device holding the root
+/// filesystem is refused.
+///
+/// # Arguments
+///
+/// * `device` - Device path (e.g., "/dev/sdb") or stable by-id link
+/// * `progress` - Channel to send progress messages to
+///
+/// # Errors
+///
+/// Returns an error if the device is invalid or unsafe, elevation fails,
+/// or `wipefs`/`dd` fail.
+pub fn quick_wipe(device: &str, progress: mpsc::Sender<String>) -> Result<()> {
+    let device_path = DevicePath::resolve(device)?;
+    let dev = device_path.as_str();
+    ensure_device_safe(dev, true)?;
+    let sectors = device_sectors(dev)?;
+    let elevator = acquire_elevator(&progress)?;
+
+    let partitions = list_partitions(dev)?;
+    if !partitions.is_empty() {
+        let _ = progress.send(format!("Unmounting {} partition(s)...", partitions.len()));
+        unmount_partitions(&partitions, elevator);
+    }
+
+    let _ = progress.send(format!("Removing filesystem signatures from {dev}..."));
+    let platform = platform::current();
+    run_quiet(&mut platform.wipe_signatures(dev, elevator), "wipefs")?;
+
+    let _ = progress.send("Zeroing the first MiB...".to_string());
+    run_quiet(
+        elevated_command("dd", elevator)
+            .arg("if=/dev/zero")
+            .arg(format!("of={dev}"))
+            .arg("bs=512")
+            .arg(format!("count={}", SECTORS_PER_MIB.min(sectors)))
+            .arg("oflag=sync")
+            .arg("status=none"),
+        "dd (start of device)",
+    )?;
+
+    if sectors > SECTORS_PER_MIB {
+        let _ = progress.send("Zeroing the last MiB...".to_string());
+        run_quiet(
+            elevated_command("dd", elevator)
+                .arg("if=/dev/zero")
+                .arg(format!("of={dev}"))
+                .arg("bs=512")
+                .arg(format!("seek={}", tail_offset(sectors)))
+                .arg(format!("count={SECTORS_PER_MIB}"))
+                .arg("oflag=sync")
+                .arg("status=none"),
+            "dd (end of device)",
+        )?;
+    }
+
+    let _ = platform
+        .reread_partitions(dev, elevator)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    let _ = progress.send(format!("{dev} wiped."));
+    Ok(())
+}
+
+/// Read a device's size in 512-byte sectors from sysfs (no privileges needed).
+pub(crate) fn device_sectors(device: &str) -> Result<u64> {
+    let platform = platform::current();
+    platform
+        .block_attribute(platform.device_name(device), "size")
+        .ok_or_else(|| anyhow::anyhow!("read the size of {device}"))?
+        .parse()
+        .with_context(|| format!("parse the size of {device}"))
+}
+
+/// First sector of the last MiB of a device with `sectors` sectors.
+fn tail_offset(sectors: u64) -> u64 {
+    sectors.saturating_sub(SECTORS_PER_MIB)
+}
+
+/// Run a command to completion, capturing its output so it can't disturb the TUI.
+pub(crate) fn run_quiet(cmd: &mut std::process::Command, what: &str) -> Result<()> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("run {what}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            return Err(anyhow::anyhow!("{what} failed"));
+        }
+        return Err(anyhow::anyhow!("{what} failed: {stderr}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_offset_points_at_last_mib() {
+        // 8 GiB stick: 16_777_216 sectors.
+        assert_eq!(tail_offset(16_777_216), 16_777_216 - 2048);
+        assert_eq!(tail_offset(100), 0);
+    }
+}